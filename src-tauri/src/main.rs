@@ -1,23 +1,33 @@
 //! Terminal Plugin CLI 入口点
 //!
 //! 该程序作为独立进程运行，通过 stdin/stdout 与前端进行 JSON-RPC 通信。
+//! 指定 `--socket <path>` 时改为监听 Unix 域套接字，前端重新连接后可以继续使用原有会话。
+//! 指定 `--tcp <addr>` 时改为监听 TCP 地址（只给端口时绑定 127.0.0.1），认证令牌从环境变量
+//! `TERMINAL_PLUGIN_AUTH_TOKEN` 读取，绑定到非回环地址时必须设置。
 //! 主要功能：
 //! - 本地 PTY 终端会话管理
 //! - SSH 远程连接管理
 //! - JSON-RPC 2.0 协议通信
 
-mod rpc;
-mod pty;
-mod ssh;
-mod shell;
-mod utils;
-
 use tracing_subscriber::{fmt, prelude::*, EnvFilter};
 
-use crate::rpc::server::RpcServer;
+use terminal_plugin::rpc::server::{RpcServer, RpcServerConfig};
+use terminal_plugin::utils::AUTH_TOKEN_ENV;
+
+fn main() -> anyhow::Result<()> {
+    // 读取后立即从环境中移除，子进程不会看到令牌。修改环境必须在启动其他线程之前完成，
+    // 所以在创建 tokio 运行时之前处理
+    let auth_token = std::env::var(AUTH_TOKEN_ENV).ok().filter(|token| !token.is_empty());
+    std::env::remove_var(AUTH_TOKEN_ENV);
+
+    tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()?
+        .block_on(run(auth_token))
+}
 
-#[tokio::main]
-async fn main() -> anyhow::Result<()> {
+/// 初始化日志并运行 RPC 服务器
+async fn run(auth_token: Option<String>) -> anyhow::Result<()> {
     // 初始化日志系统，输出到 stderr 避免干扰 JSON-RPC 通信
     tracing_subscriber::registry()
         .with(fmt::layer().with_writer(std::io::stderr))
@@ -26,9 +36,38 @@ async fn main() -> anyhow::Result<()> {
 
     tracing::info!("Terminal Plugin 启动");
 
-    // 创建并运行 RPC 服务器
-    let server = RpcServer::new();
-    server.run().await?;
+    // 创建并运行 RPC 服务器，`--socket <path>` 时改为监听 Unix 域套接字，`--tcp <addr>` 时监听 TCP
+    let server = RpcServer::with_config(RpcServerConfig {
+        auth_token,
+        ..Default::default()
+    });
+    if let Some(addr) = arg_value("--tcp") {
+        let addr = if addr.parse::<u16>().is_ok() {
+            format!("127.0.0.1:{}", addr)
+        } else {
+            addr
+        };
+        server.run_on_tcp(addr).await?;
+        return Ok(());
+    }
+    match arg_value("--socket") {
+        #[cfg(unix)]
+        Some(path) => server.run_on_socket(path).await?,
+        #[cfg(not(unix))]
+        Some(_) => anyhow::bail!("当前平台不支持 --socket"),
+        None => server.run().await?,
+    }
 
     Ok(())
 }
+
+/// 读取命令行中指定选项的值，如 `--socket <path>`
+fn arg_value(name: &str) -> Option<String> {
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == name {
+            return args.next();
+        }
+    }
+    None
+}
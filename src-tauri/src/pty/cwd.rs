@@ -0,0 +1,190 @@
+//! 工作目录轮询（仅 Linux）
+//!
+//! 很多 shell 的默认配置不会发出 OSC 7，此时前端无法得知工作目录。
+//! 轮询器定期读取 `/proc/<pid>/cwd`，在目录变化时更新会话信息并发送 `session.cwd` 通知。
+//!
+//! 轮询有一定开销，因此默认关闭，需要在创建会话时通过 `track_cwd` 启用。
+
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{mpsc, RwLock};
+use tokio::task::JoinHandle;
+
+use crate::rpc::server::NotificationSender;
+use crate::rpc::types::SessionInfo;
+
+/// 默认轮询间隔
+pub const DEFAULT_CWD_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// 工作目录轮询器句柄
+pub struct CwdPollerHandle {
+    /// 停止信号发送器
+    stop_tx: mpsc::Sender<()>,
+    /// 任务句柄
+    task_handle: JoinHandle<()>,
+}
+
+impl CwdPollerHandle {
+    /// 停止轮询器
+    pub async fn stop(self) {
+        let _ = self.stop_tx.send(()).await;
+        let _ = self.task_handle.await;
+    }
+
+    /// 检查任务是否已完成
+    pub fn is_finished(&self) -> bool {
+        self.task_handle.is_finished()
+    }
+}
+
+/// 读取进程的当前工作目录
+pub fn read_process_cwd(pid: u32) -> Option<String> {
+    let path: PathBuf = std::fs::read_link(format!("/proc/{}/cwd", pid)).ok()?;
+    Some(path.to_string_lossy().into_owned())
+}
+
+/// 启动工作目录轮询器
+///
+/// 首次轮询立即执行，之后每隔 `interval` 检查一次。进程退出后轮询器自动结束。
+pub fn start_cwd_poller(
+    session_id: String,
+    pid: u32,
+    session_info: Arc<RwLock<SessionInfo>>,
+    notification_sender: NotificationSender,
+    interval: Duration,
+) -> CwdPollerHandle {
+    let (stop_tx, mut stop_rx) = mpsc::channel::<()>(1);
+
+    let task_handle = tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+        loop {
+            tokio::select! {
+                _ = stop_rx.recv() => {
+                    tracing::debug!("工作目录轮询器收到停止信号: {}", session_id);
+                    break;
+                }
+                _ = ticker.tick() => {
+                    let Some(cwd) = read_process_cwd(pid) else {
+                        tracing::debug!("无法读取进程工作目录，停止轮询: {} (pid {})", session_id, pid);
+                        break;
+                    };
+
+                    // OSC 7 也可能已经更新了工作目录，只在与当前记录不同时通知
+                    {
+                        let mut info = session_info.write().await;
+                        if info.cwd.as_deref() == Some(cwd.as_str()) {
+                            continue;
+                        }
+                        info.cwd = Some(cwd.clone());
+                    }
+
+                    tracing::debug!("轮询到工作目录变更: {} -> {}", session_id, cwd);
+                    if let Err(e) = notification_sender.send_cwd(&session_id, &cwd, None) {
+                        tracing::error!("发送工作目录通知失败: {}", e);
+                    }
+                }
+            }
+        }
+    });
+
+    CwdPollerHandle {
+        stop_tx,
+        task_handle,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rpc::types::{ConnectionType, SessionStatus};
+    use tokio::sync::mpsc as tokio_mpsc;
+
+    fn test_session_info() -> Arc<RwLock<SessionInfo>> {
+        Arc::new(RwLock::new(SessionInfo {
+            id: "test-session".to_string(),
+            connection_type: ConnectionType::Local {
+                shell_path: None,
+                cwd: None,
+                env: None,
+            },
+            status: SessionStatus::Running,
+            title: None,
+            name: None,
+            cwd: None,
+            exit_code: None,
+            created_at: 0,
+            modes: Default::default(),
+            ssh: None,
+            exit_reason: None,
+            ended_at: None,
+            duration_secs: None,
+            pid: None,
+            resolved_addr: None,
+        }))
+    }
+
+    #[test]
+    fn test_read_own_cwd() {
+        let expected = std::env::current_dir().unwrap();
+        let cwd = read_process_cwd(std::process::id()).unwrap();
+        assert_eq!(PathBuf::from(cwd), expected);
+    }
+
+    #[test]
+    fn test_read_missing_process_cwd() {
+        // PID 超出 pid_max 上限，不可能存在
+        assert!(read_process_cwd(u32::MAX).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_poller_reports_cwd_once() {
+        let dir = std::env::temp_dir().canonicalize().unwrap();
+        let mut child = std::process::Command::new("sleep")
+            .arg("5")
+            .current_dir(&dir)
+            .spawn()
+            .unwrap();
+
+        let (tx, mut rx) = tokio_mpsc::unbounded_channel();
+        let info = test_session_info();
+        let handle = start_cwd_poller(
+            "test-session".to_string(),
+            child.id(),
+            info.clone(),
+            NotificationSender::new_for_test(tx),
+            Duration::from_millis(10),
+        );
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        handle.stop().await;
+        let _ = child.kill();
+        let _ = child.wait();
+
+        let expected = dir.to_string_lossy().into_owned();
+        assert_eq!(info.read().await.cwd.as_deref(), Some(expected.as_str()));
+
+        // 目录未变化时只通知一次
+        let notification = rx.try_recv().unwrap();
+        assert_eq!(notification.method, "session.cwd");
+        assert_eq!(notification.params.unwrap()["cwd"], expected);
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_poller_stops_when_process_exits() {
+        let (tx, _rx) = tokio_mpsc::unbounded_channel();
+        let handle = start_cwd_poller(
+            "test-session".to_string(),
+            u32::MAX,
+            test_session_info(),
+            NotificationSender::new_for_test(tx),
+            Duration::from_millis(10),
+        );
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(handle.is_finished());
+    }
+}
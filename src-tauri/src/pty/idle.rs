@@ -0,0 +1,115 @@
+//! 空闲超时
+//!
+//! 用于共享终端等场景：超过指定时间没有输入时终止会话的进程。监控器定期检查会话的
+//! 字节计数器，输入字节数变化时重置计时，`reset_on_output` 为 true 时输出也重置计时。
+//!
+//! 空闲时长按会话的时间来源（[`Clock`](crate::utils::time::Clock)）计算，测试中可以用假时钟直接越过超时。
+//!
+//! 超时后在会话信息中记录结束原因 `idle_timeout` 并终止进程，输出读取器读完剩余输出后
+//! 发送带原因的 `done` 状态通知。
+
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{mpsc, Mutex, RwLock};
+use tokio::task::JoinHandle;
+
+use crate::rpc::types::SessionInfo;
+use crate::utils::time::SharedClock;
+
+use super::local::LocalPty;
+use super::stats::ByteCounters;
+
+/// 空闲超时的结束原因
+pub const IDLE_TIMEOUT_REASON: &str = "idle_timeout";
+
+/// 最长检查间隔，超时较短时按超时的四分之一检查
+const MAX_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// 空闲监控器句柄
+pub struct IdleMonitorHandle {
+    /// 停止信号发送器
+    stop_tx: mpsc::Sender<()>,
+    /// 任务句柄
+    task_handle: JoinHandle<()>,
+}
+
+impl IdleMonitorHandle {
+    /// 停止监控器
+    pub async fn stop(self) {
+        let _ = self.stop_tx.send(()).await;
+        let _ = self.task_handle.await;
+    }
+
+    /// 检查任务是否已完成
+    pub fn is_finished(&self) -> bool {
+        self.task_handle.is_finished()
+    }
+}
+
+/// 启动空闲监控器
+///
+/// 进程自行退出或超时终止进程后监控器结束。活动在下一次检查时才被发现，
+/// 实际的空闲时间可能比 `timeout` 最多长一个检查间隔。
+pub fn start_idle_monitor(
+    session_id: String,
+    pty: Arc<Mutex<LocalPty>>,
+    counters: Arc<ByteCounters>,
+    session_info: Arc<RwLock<SessionInfo>>,
+    clock: SharedClock,
+    timeout: Duration,
+    reset_on_output: bool,
+) -> IdleMonitorHandle {
+    let (stop_tx, mut stop_rx) = mpsc::channel::<()>(1);
+    let interval = (timeout / 4).min(MAX_CHECK_INTERVAL);
+
+    let activity = move |counters: &ByteCounters| {
+        let output = if reset_on_output { counters.bytes_out() } else { 0 };
+        (counters.bytes_in(), output)
+    };
+    // 在启动任务前记录起点，计时从调用时开始
+    let mut last_activity = activity(&counters);
+    let mut idle_since = clock.monotonic();
+
+    let task_handle = tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+        loop {
+            tokio::select! {
+                _ = stop_rx.recv() => {
+                    tracing::debug!("空闲监控器收到停止信号: {}", session_id);
+                    break;
+                }
+                _ = ticker.tick() => {
+                    let current = activity(&counters);
+                    if current != last_activity {
+                        last_activity = current;
+                        idle_since = clock.monotonic();
+                        continue;
+                    }
+
+                    let mut pty = pty.lock().await;
+                    if matches!(pty.try_wait(), Ok(Some(_))) {
+                        tracing::debug!("进程已退出，停止空闲监控: {}", session_id);
+                        break;
+                    }
+                    if clock.monotonic().saturating_sub(idle_since) < timeout {
+                        continue;
+                    }
+
+                    tracing::info!("会话空闲超过 {:?}，终止进程: {}", timeout, session_id);
+                    session_info.write().await.exit_reason = Some(IDLE_TIMEOUT_REASON.to_string());
+                    if let Err(e) = pty.kill() {
+                        tracing::warn!("空闲超时终止进程失败: {} - {}", session_id, e);
+                    }
+                    break;
+                }
+            }
+        }
+    });
+
+    IdleMonitorHandle {
+        stop_tx,
+        task_handle,
+    }
+}
@@ -1,23 +1,316 @@
 //! 本地 PTY 实现
 //!
 //! 使用 portable-pty 创建和管理本地伪终端。
+//!
+//! ## 进程树终止
+//!
+//! 仅终止 shell 进程会留下它启动的后台进程，因此 `kill` 会终止整个进程树:
+//! - Unix: shell 是会话首进程（进程组 ID 等于其 PID），向整个进程组发送信号；
+//!   在 Linux 上还会终止同一会话中被作业控制放入其他进程组的进程
+//! - Windows: 子进程在创建后被加入 Job Object，终止时结束整个 Job
+//!
+//! ## 会话和控制终端
+//!
+//! 以守护进程运行时，插件本身可能没有控制终端，也可能带着被修改过的信号处理方式。
+//! Unix 上子进程在 exec 之前会恢复 SIGINT、SIGHUP 等信号的默认处理，调用 `setsid`
+//! 成为新会话的首进程，并把 PTY slave 设为控制终端（`TIOCSCTTY`），因此不会抢占
+//! 插件的控制终端，也不会收到发给插件进程组的信号。启动后会检查子进程确实是会话首进程。
+//!
+//! ## 环境变量
+//!
+//! 子进程不会继承插件的全部环境变量，默认只继承 `PATH`、`HOME`、locale 等基础变量
+//! （见 [`BASE_INHERITED_ENV`]），其他变量需要通过 `inherit_env` 白名单按名称指定，
+//! 如 `DISPLAY`、`SSH_AUTH_SOCK`。插件环境中不存在的名称被忽略；请求中显式给出的
+//! `env` 优先于继承的值。
+//!
+//! ## 登录 shell
+//!
+//! shell 根据 argv[0] 是否以 `-` 开头（如 `-bash`）判断自己是否为登录 shell，
+//! 登录 shell 会读取 `/etc/profile`、`~/.profile` 等配置文件。Unix 上可以用
+//! [`LocalPty::with_login_shell`] 以这种方式启动 shell，Windows 上忽略该选项。
 
 use portable_pty::{native_pty_system, CommandBuilder, MasterPty, PtySize};
 use std::collections::HashMap;
+use std::ffi::{OsStr, OsString};
 use std::io::{Read, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
 use crate::rpc::types::TermSize;
 use crate::shell::detect::detect_default_shell;
+#[cfg(windows)]
+use crate::shell::detect::shell_args;
 use crate::utils::error::TerminalError;
+use crate::utils::AUTH_TOKEN_ENV;
+use crate::utils::path::{expand_path, resolve_program_in};
+
+/// 自检时启动的程序，不需要参数并且会立即正常退出
+#[cfg(unix)]
+const SELF_TEST_PROGRAM: &str = "true";
+#[cfg(windows)]
+const SELF_TEST_PROGRAM: &str = "hostname";
+
+/// 默认从插件环境继承的变量
+#[cfg(unix)]
+pub const BASE_INHERITED_ENV: &[&str] = &[
+    "HOME", "USER", "LOGNAME", "PATH", "SHELL", "LANG", "LANGUAGE", "TZ", "TMPDIR",
+];
+/// 默认从插件环境继承的变量（名称不区分大小写）
+#[cfg(windows)]
+pub const BASE_INHERITED_ENV: &[&str] = &[
+    "PATH", "PATHEXT", "SystemRoot", "SystemDrive", "windir", "ComSpec", "USERPROFILE",
+    "USERNAME", "USERDOMAIN", "HOMEDRIVE", "HOMEPATH", "APPDATA", "LOCALAPPDATA",
+    "ProgramData", "ProgramFiles", "ProgramFiles(x86)", "ProgramW6432", "CommonProgramFiles",
+    "TEMP", "TMP", "OS", "PROCESSOR_ARCHITECTURE", "NUMBER_OF_PROCESSORS", "PSModulePath",
+];
+
+/// 以这些前缀开头的变量（locale 分类设置）也默认继承
+const BASE_INHERITED_ENV_PREFIXES: &[&str] = &["LC_"];
+
+/// 强制彩色输出时设置的变量
+pub const FORCE_COLOR_ENV: &[(&str, &str)] = &[
+    ("FORCE_COLOR", "1"),
+    ("CLICOLOR_FORCE", "1"),
+    ("COLORTERM", "truecolor"),
+];
+
+/// 未指定 locale 时回退使用的 UTF-8 locale
+pub const DEFAULT_UTF8_LOCALE: &str = "en_US.UTF-8";
+
+/// 指定 locale 时设置的变量
+pub const LOCALE_ENV: &[&str] = &["LANG", "LC_ALL"];
+
+/// 在自定义环境变量中把 [`LOCALE_ENV`] 设置为 `locale`，已经给出的变量保持不变
+pub fn with_locale(env: Option<HashMap<String, String>>, locale: &str) -> HashMap<String, String> {
+    let mut env = env.unwrap_or_default();
+    for key in LOCALE_ENV {
+        env.entry(key.to_string()).or_insert_with(|| locale.to_string());
+    }
+    env
+}
+
+/// 插件环境中生效的 locale：依次查找 `LC_ALL` 和 `LANG`，忽略空值
+pub fn inherited_locale() -> Option<String> {
+    ["LC_ALL", "LANG"]
+        .iter()
+        .filter_map(|name| std::env::var(name).ok())
+        .find(|value| !value.is_empty())
+}
+
+/// 选择子进程使用的 locale，返回 `None` 时继承插件的设置
+///
+/// 指定了 `locale` 时使用它；否则 `fallback_utf8` 为 true 且 `inherited` 缺失或为
+/// `C`/`POSIX` 时使用 [`DEFAULT_UTF8_LOCALE`]。
+pub fn resolve_locale(locale: Option<&str>, fallback_utf8: bool, inherited: Option<&str>) -> Option<String> {
+    if let Some(locale) = locale {
+        return Some(locale.to_string());
+    }
+    let unusable = inherited.is_none_or(|value| matches!(value, "C" | "POSIX"));
+    (fallback_utf8 && unusable).then(|| DEFAULT_UTF8_LOCALE.to_string())
+}
+
+/// 在自定义环境变量中加入 [`FORCE_COLOR_ENV`]，已经给出的变量保持不变
+pub fn with_force_color(env: Option<HashMap<String, String>>) -> HashMap<String, String> {
+    let mut env = env.unwrap_or_default();
+    for (key, value) in FORCE_COLOR_ENV {
+        env.entry(key.to_string()).or_insert_with(|| value.to_string());
+    }
+    env
+}
+
+/// 等待 PTY 可写时检查输入是否已关闭的间隔
+#[cfg(unix)]
+const WRITE_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// PTY master 的 writer
+///
+/// 子进程不读取输入、PTY 缓冲区已满时，阻塞写入会一直等待，即使子进程随后退出也不会被唤醒。
+/// Unix 上持有 master 的一个副本描述符，写入前先等待可写，再以非阻塞方式写入；
+/// portable-pty 的 writer 释放时会写入换行和 EOF，同样以非阻塞方式释放。
+struct MasterWriter {
+    /// 释放前总是 `Some`
+    writer: Option<Box<dyn Write + Send>>,
+    #[cfg(unix)]
+    fd: Option<std::os::fd::OwnedFd>,
+}
+
+impl MasterWriter {
+    fn new(writer: Box<dyn Write + Send>, master: &dyn MasterPty) -> Self {
+        #[cfg(windows)]
+        let _ = master;
+        Self {
+            writer: Some(writer),
+            #[cfg(unix)]
+            fd: master.as_raw_fd().and_then(unix::dup_fd),
+        }
+    }
+
+    fn writer(&mut self) -> &mut dyn Write {
+        self.writer.as_mut().expect("writer 已释放").as_mut()
+    }
+
+    /// 写入一个分块，`closed` 被设置后停止等待
+    #[cfg(unix)]
+    fn write_chunk(&mut self, mut chunk: &[u8], closed: &AtomicBool) -> Result<(), TerminalError> {
+        use std::os::fd::AsRawFd;
+
+        let Some(fd) = self.fd.as_ref().map(|fd| fd.as_raw_fd()) else {
+            self.writer().write_all(chunk)?;
+            return Ok(());
+        };
+
+        while !chunk.is_empty() {
+            if closed.load(Ordering::SeqCst) {
+                return Err(TerminalError::SessionClosed("PTY 输入已关闭".to_string()));
+            }
+            // 定期醒来检查输入是否已关闭
+            if !unix::wait_writable(fd, WRITE_POLL_INTERVAL)? {
+                return Err(std::io::Error::from_raw_os_error(libc::EIO).into());
+            }
+            let writer = self.writer();
+            match unix::with_nonblocking(fd, || writer.write(chunk)) {
+                Ok(n) => chunk = &chunk[n..],
+                Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {
+                    std::thread::sleep(Duration::from_millis(10));
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+        Ok(())
+    }
+
+    /// 写入一个分块
+    #[cfg(windows)]
+    fn write_chunk(&mut self, chunk: &[u8], _closed: &AtomicBool) -> Result<(), TerminalError> {
+        self.writer().write_all(chunk)?;
+        Ok(())
+    }
+}
+
+impl Drop for MasterWriter {
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        if let (Some(fd), Some(writer)) = (&self.fd, self.writer.take()) {
+            use std::os::fd::AsRawFd;
+
+            let fd = fd.as_raw_fd();
+            // 缓冲区已满时 EOF 可能丢失，此时子进程本来也读不到它
+            let _ = unix::wait_writable(fd, WRITE_POLL_INTERVAL);
+            let _ = unix::with_nonblocking(fd, || {
+                drop(writer);
+                Ok(())
+            });
+        }
+    }
+}
+
+/// PTY 输入端
+///
+/// 与 [`LocalPty`] 共享同一个 writer，可以在阻塞线程中写入，写入期间不需要持有
+/// `LocalPty` 的锁，调整大小和终止进程不会被大块写入阻塞。
+#[derive(Clone)]
+pub struct PtyWriter {
+    /// PTY writer，关闭输入后为 `None`
+    inner: Arc<Mutex<Option<MasterWriter>>>,
+    /// 输入已关闭或正在关闭，进行中的分块写入在当前分块之后停止
+    closed: Arc<AtomicBool>,
+}
+
+impl PtyWriter {
+    fn new(writer: Box<dyn Write + Send>, master: &dyn MasterPty) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(Some(MasterWriter::new(writer, master)))),
+            closed: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// 写入并刷新
+    pub fn write(&self, data: &[u8]) -> Result<(), TerminalError> {
+        self.write_chunks(data, data.len(), |_| {})
+    }
+
+    /// 按 `chunk_size` 分块写入，每块写入并刷新后以写入的字节数调用 `on_chunk`
+    ///
+    /// 整个写入期间持有 writer，并发的写入不会交错。输入关闭后剩余的数据不再写入，
+    /// 返回 `SessionClosed`；子进程退出后返回 IO 错误。会阻塞当前线程，异步上下文中应在
+    /// `spawn_blocking` 中调用。
+    pub fn write_chunks(
+        &self,
+        data: &[u8],
+        chunk_size: usize,
+        mut on_chunk: impl FnMut(usize),
+    ) -> Result<(), TerminalError> {
+        let mut inner = self.lock();
+        let mut remaining = data;
+        loop {
+            let writer = inner
+                .as_mut()
+                .filter(|_| !self.is_closed())
+                .ok_or_else(|| TerminalError::SessionClosed("PTY 输入已关闭".to_string()))?;
+            let (chunk, rest) = remaining.split_at(remaining.len().min(chunk_size.max(1)));
+            writer.write_chunk(chunk, &self.closed)?;
+            writer.writer().flush()?;
+            on_chunk(chunk.len());
+            if rest.is_empty() {
+                return Ok(());
+            }
+            remaining = rest;
+        }
+    }
+
+    /// 刷新输入，输入已关闭时返回 `SessionClosed`
+    pub fn flush(&self) -> Result<(), TerminalError> {
+        let mut inner = self.lock();
+        let writer = inner
+            .as_mut()
+            .ok_or_else(|| TerminalError::SessionClosed("PTY 输入已关闭".to_string()))?;
+        writer.writer().flush()?;
+        Ok(())
+    }
+
+    /// 标记输入已关闭，不等待进行中的写入
+    ///
+    /// 进行中的分块写入在当前分块之后停止，之后的写入返回 `SessionClosed`。
+    pub fn cancel(&self) {
+        self.closed.store(true, Ordering::SeqCst);
+    }
+
+    /// 关闭输入并释放 writer，等待进行中的写入停止
+    pub fn close(&self) {
+        self.cancel();
+        *self.lock() = None;
+    }
+
+    /// 输入是否已关闭
+    pub fn is_closed(&self) -> bool {
+        self.closed.load(Ordering::SeqCst)
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Option<MasterWriter>> {
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
 
 /// 本地 PTY 实例
 pub struct LocalPty {
     /// PTY master
     master: Box<dyn MasterPty + Send>,
-    /// PTY writer
-    writer: Box<dyn Write + Send>,
+    /// PTY 输入端
+    writer: PtyWriter,
     /// 子进程
     child: Box<dyn portable_pty::Child + Send + Sync>,
+    /// 启动子进程时使用的完整环境变量（继承的环境、TERM 和自定义变量合并后）
+    env: HashMap<String, String>,
+    /// 实际启动的 shell 路径
+    shell_path: PathBuf,
+    /// 展开后的工作目录，未指定时为 `None`
+    cwd: Option<String>,
+    /// 子进程所在的 Job Object
+    #[cfg(windows)]
+    job: Option<windows::JobObject>,
 }
 
 impl LocalPty {
@@ -28,31 +321,71 @@ impl LocalPty {
         env: Option<HashMap<String, String>>,
         term_size: TermSize,
     ) -> Result<Self, TerminalError> {
-        // 获取 PTY 系统
-        let pty_system = native_pty_system();
+        Self::with_login_shell(shell_path, cwd, env, term_size, false)
+    }
 
-        // 配置 PTY 大小
-        let size = PtySize {
-            rows: term_size.rows,
-            cols: term_size.cols,
-            pixel_width: 0,
-            pixel_height: 0,
+    /// 创建新的本地 PTY，`login_shell` 为 true 时以登录 shell 启动
+    ///
+    /// 只在 Unix 上生效，argv[0] 被设置为 `-<basename>`。
+    pub fn with_login_shell(
+        shell_path: Option<String>,
+        cwd: Option<String>,
+        env: Option<HashMap<String, String>>,
+        term_size: TermSize,
+        login_shell: bool,
+    ) -> Result<Self, TerminalError> {
+        Self::with_inherited_env(shell_path, cwd, env, term_size, login_shell, &[])
+    }
+
+    /// 创建新的本地 PTY，除基础变量外还从插件环境继承 `inherit_env` 中列出的变量
+    pub fn with_inherited_env(
+        shell_path: Option<String>,
+        cwd: Option<String>,
+        env: Option<HashMap<String, String>>,
+        term_size: TermSize,
+        login_shell: bool,
+        inherit_env: &[String],
+    ) -> Result<Self, TerminalError> {
+        // 在创建 PTY 之前检查工作目录和 shell，避免启动失败时只得到含糊的错误
+        let cwd = cwd.as_deref().map(resolve_cwd).transpose()?;
+        let shell = match shell_path {
+            Some(path) => expand_path(&path),
+            None => {
+                let shell = detect_default_shell();
+                tracing::info!("未指定 shell，使用默认 shell: {}", shell);
+                shell
+            }
         };
+        // 自定义环境变量中的 PATH 优先，与子进程实际使用的 PATH 一致
+        let path_var = env
+            .as_ref()
+            .and_then(|vars| vars.get("PATH"))
+            .map(OsString::from)
+            .or_else(|| std::env::var_os("PATH"));
+        let shell = resolve_shell(&shell, path_var.as_deref())?;
+
+        // 获取 PTY 系统
+        let pty_system = native_pty_system();
 
         // 创建 PTY pair
         let pair = pty_system
-            .openpty(size)
-            .map_err(|e| TerminalError::PtyCreationFailed(e.to_string()))?;
+            .openpty(pty_size(&term_size))
+            .map_err(openpty_error)?;
 
         // 构建命令
-        let shell = shell_path.unwrap_or_else(detect_default_shell);
-        let mut cmd = CommandBuilder::new(&shell);
+        let mut cmd = shell_command(&shell, login_shell);
 
         // 设置工作目录
-        if let Some(dir) = cwd {
+        if let Some(dir) = &cwd {
             cmd.cwd(dir);
         }
 
+        // 只继承基础变量和白名单中的变量
+        cmd.env_clear();
+        for (key, value) in inherited_env(inherit_env) {
+            cmd.env(key, value);
+        }
+
         // 设置 TERM 环境变量
         cmd.env("TERM", "xterm-256color");
 
@@ -63,74 +396,564 @@ impl LocalPty {
             }
         }
 
+        // 不查询 TTY 的程序从 COLUMNS/LINES 读取窗口大小，与 PTY 的初始大小保持一致。
+        // 之后调整大小时无法修改子进程的环境，由 shell 自己更新（如 bash 的 checkwinsize）
+        cmd.env("COLUMNS", term_size.cols.to_string());
+        cmd.env("LINES", term_size.rows.to_string());
+
+        // 登录 shell 由 SHELL 决定启动的程序，不能被自定义环境变量覆盖
+        if cmd.is_default_prog() {
+            cmd.env("SHELL", &shell);
+        }
+
+        // 子进程成为新会话的首进程，PTY slave 作为控制终端
+        cmd.set_controlling_tty(true);
+
+        // 记录合并后的环境变量
+        let resolved_env = cmd
+            .iter_full_env_as_str()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect();
+
         // 启动子进程
         let child = pair
             .slave
             .spawn_command(cmd)
             .map_err(|e| TerminalError::PtyCreationFailed(e.to_string()))?;
 
+        #[cfg(unix)]
+        if let Some(pid) = child.process_id() {
+            if !unix::is_session_leader(pid) {
+                let mut child = child;
+                let _ = child.kill();
+                return Err(TerminalError::PtyCreationFailed(format!(
+                    "子进程没有成为新会话的首进程: {}",
+                    pid
+                )));
+            }
+        }
+
         // 获取 writer
         let writer = pair
             .master
             .take_writer()
             .map_err(|e| TerminalError::PtyCreationFailed(e.to_string()))?;
+        let writer = PtyWriter::new(writer, pair.master.as_ref());
+
+        // 将子进程加入 Job Object，失败时仍可退回到只终止子进程
+        #[cfg(windows)]
+        let job = windows::JobObject::for_child(child.as_ref());
 
         Ok(Self {
             master: pair.master,
             writer,
             child,
+            env: resolved_env,
+            shell_path: shell,
+            cwd,
+            #[cfg(windows)]
+            job,
         })
     }
 
     /// 获取 PTY reader
+    ///
+    /// Unix 上与 writer 共享同一个打开的文件，写入期间的读取可能返回 `WouldBlock`，应稍后重试。
     pub fn try_clone_reader(&self) -> Result<Box<dyn Read + Send>, TerminalError> {
         self.master
             .try_clone_reader()
-            .map_err(|e| TerminalError::IoError(std::io::Error::new(std::io::ErrorKind::Other, e.to_string())))
+            .map_err(|e| TerminalError::IoError(std::io::Error::other(e.to_string())))
     }
 
     /// 写入数据到 PTY
     pub fn write(&mut self, data: &[u8]) -> Result<(), TerminalError> {
-        self.writer.write_all(data)?;
-        self.writer.flush()?;
-        Ok(())
+        self.writer.write(data)
+    }
+
+    /// 刷新 PTY 输入，输入已关闭时返回 `SessionClosed`
+    pub fn flush(&mut self) -> Result<(), TerminalError> {
+        self.writer.flush()
+    }
+
+    /// 与 PTY 共享的输入端
+    pub fn writer(&self) -> PtyWriter {
+        self.writer.clone()
+    }
+
+    /// 关闭 PTY 输入，子进程读到 EOF
+    ///
+    /// Unix 上 portable-pty 关闭 writer 时写入换行和 VEOF 字符（通常是 Ctrl-D），
+    /// 由终端行规程转换为 EOF。之后的写入返回 `SessionClosed`，重复关闭没有效果。
+    pub fn close_input(&mut self) {
+        self.writer.close();
+    }
+
+    /// 输入是否已关闭
+    pub fn is_input_closed(&self) -> bool {
+        self.writer.is_closed()
     }
 
     /// 调整 PTY 大小
     pub fn resize(&self, term_size: TermSize) -> Result<(), TerminalError> {
-        let size = PtySize {
-            rows: term_size.rows,
-            cols: term_size.cols,
-            pixel_width: 0,
-            pixel_height: 0,
-        };
         self.master
-            .resize(size)
-            .map_err(|e| TerminalError::IoError(std::io::Error::new(std::io::ErrorKind::Other, e.to_string())))
+            .resize(pty_size(&term_size))
+            .map_err(|e| TerminalError::IoError(std::io::Error::other(e.to_string())))
     }
 
     /// 检查子进程是否已退出
     pub fn try_wait(&mut self) -> Result<Option<portable_pty::ExitStatus>, TerminalError> {
         self.child
             .try_wait()
-            .map_err(|e| TerminalError::IoError(std::io::Error::new(std::io::ErrorKind::Other, e.to_string())))
+            .map_err(|e| TerminalError::IoError(std::io::Error::other(e.to_string())))
     }
 
     /// 等待子进程退出
     pub fn wait(&mut self) -> Result<portable_pty::ExitStatus, TerminalError> {
         self.child
             .wait()
-            .map_err(|e| TerminalError::IoError(std::io::Error::new(std::io::ErrorKind::Other, e.to_string())))
+            .map_err(|e| TerminalError::IoError(std::io::Error::other(e.to_string())))
+    }
+
+    /// 获取启动子进程时使用的完整环境变量
+    pub fn env(&self) -> &HashMap<String, String> {
+        &self.env
+    }
+
+    /// 获取实际启动的 shell 路径
+    pub fn shell_path(&self) -> &std::path::Path {
+        &self.shell_path
+    }
+
+    /// 获取展开后的工作目录
+    pub fn cwd(&self) -> Option<&str> {
+        self.cwd.as_deref()
     }
 
-    /// 终止子进程
+    /// 获取子进程 PID
+    pub fn process_id(&self) -> Option<u32> {
+        self.child.process_id()
+    }
+
+    /// 向终端的前台进程组发送信号，查询不到前台进程组时发给 shell 的进程组
+    ///
+    /// 与在终端中按 Ctrl+C 一样作用于正在运行的命令，而不是 shell 本身。
+    #[cfg(unix)]
+    pub fn signal(&self, signal: libc::c_int) -> Result<(), TerminalError> {
+        let pgid = self
+            .master
+            .process_group_leader()
+            .and_then(|pgid| u32::try_from(pgid).ok())
+            .or_else(|| self.child.process_id())
+            .ok_or_else(|| TerminalError::SessionClosed("找不到接收信号的进程".to_string()))?;
+        unix::signal_group(pgid, signal);
+        Ok(())
+    }
+
+    /// 终止子进程及其启动的所有进程
     pub fn kill(&mut self) -> Result<(), TerminalError> {
-        self.child
-            .kill()
-            .map_err(|e| TerminalError::IoError(std::io::Error::new(std::io::ErrorKind::Other, e.to_string())))
+        #[cfg(unix)]
+        let pid = self.child.process_id();
+
+        // 先发送 SIGHUP，让 shell 和前台进程有机会正常退出
+        #[cfg(unix)]
+        if let Some(pid) = pid {
+            unix::signal_group(pid, libc::SIGHUP);
+        }
+
+        #[cfg(windows)]
+        if let Some(job) = &self.job {
+            job.terminate();
+        }
+
+        // 子进程已经退出时无需再终止，只清理残留进程
+        let result = if matches!(self.child.try_wait(), Ok(Some(_))) {
+            Ok(())
+        } else {
+            self.child
+                .kill()
+                .map_err(|e| TerminalError::IoError(std::io::Error::other(e.to_string())))
+        };
+
+        // shell 退出后强制终止残留的进程
+        #[cfg(unix)]
+        if let Some(pid) = pid {
+            unix::kill_session(pid);
+        }
+
+        result
+    }
+
+    /// 检查当前环境能否创建 PTY
+    ///
+    /// 在 PTY 中启动一个立即退出的程序并等待它退出。超过 `timeout` 或等待出错时终止该程序，
+    /// 返回前 PTY 总是被关闭。
+    pub fn self_test(timeout: Duration) -> Result<(), TerminalError> {
+        let mut pty = Self::new(
+            Some(SELF_TEST_PROGRAM.to_string()),
+            None,
+            None,
+            TermSize::default(),
+        )?;
+
+        let deadline = Instant::now() + timeout;
+        let result = loop {
+            match pty.try_wait() {
+                Ok(Some(status)) if status.success() => break Ok(()),
+                Ok(Some(status)) => {
+                    break Err(TerminalError::PtyCreationFailed(format!(
+                        "自检程序异常退出 (code={})",
+                        status.exit_code()
+                    )))
+                }
+                Ok(None) if Instant::now() < deadline => std::thread::sleep(Duration::from_millis(10)),
+                Ok(None) => {
+                    break Err(TerminalError::PtyCreationFailed(format!(
+                        "自检程序在 {:?} 内没有退出",
+                        timeout
+                    )))
+                }
+                Err(e) => break Err(e),
+            }
+        };
+
+        if result.is_err() {
+            let _ = pty.kill();
+        }
+        result
+    }
+}
+
+/// 解析信号名称，如 `INT`、`SIGINT`（不区分大小写）
+///
+/// 未知的名称和 Windows 上返回 `InvalidRequest`。
+pub fn parse_signal(name: &str) -> Result<i32, TerminalError> {
+    #[cfg(unix)]
+    {
+        let upper = name.to_ascii_uppercase();
+        let signal = match upper.strip_prefix("SIG").unwrap_or(&upper) {
+            "HUP" => libc::SIGHUP,
+            "INT" => libc::SIGINT,
+            "QUIT" => libc::SIGQUIT,
+            "KILL" => libc::SIGKILL,
+            "TERM" => libc::SIGTERM,
+            "USR1" => libc::SIGUSR1,
+            "USR2" => libc::SIGUSR2,
+            "CONT" => libc::SIGCONT,
+            "STOP" => libc::SIGSTOP,
+            "TSTP" => libc::SIGTSTP,
+            "WINCH" => libc::SIGWINCH,
+            _ => return Err(TerminalError::InvalidRequest(format!("未知的信号: {}", name))),
+        };
+        Ok(signal)
+    }
+
+    #[cfg(windows)]
+    {
+        Err(TerminalError::InvalidRequest(format!("当前平台不支持发送信号: {}", name)))
+    }
+}
+
+/// Unix 进程组终止
+#[cfg(unix)]
+mod unix {
+    use std::os::fd::{FromRawFd, OwnedFd, RawFd};
+    use std::time::Duration;
+
+    /// 等待 PTY master 可写，另一端已关闭时返回 false
+    ///
+    /// 超时也返回 true，由调用方检查是否继续写入。
+    pub fn wait_writable(fd: RawFd, timeout: Duration) -> std::io::Result<bool> {
+        let mut pollfd = libc::pollfd {
+            fd,
+            events: libc::POLLOUT,
+            revents: 0,
+        };
+        let timeout = libc::c_int::try_from(timeout.as_millis()).unwrap_or(libc::c_int::MAX);
+        // SAFETY: pollfd 在调用期间有效，数量与传入的 1 一致
+        let ready = unsafe { libc::poll(&mut pollfd, 1, timeout) };
+        if ready < 0 {
+            let err = std::io::Error::last_os_error();
+            return if err.kind() == std::io::ErrorKind::Interrupted {
+                Ok(true)
+            } else {
+                Err(err)
+            };
+        }
+        Ok(pollfd.revents & (libc::POLLHUP | libc::POLLERR | libc::POLLNVAL) == 0)
+    }
+
+    /// 在 `O_NONBLOCK` 下执行 `f`，返回前恢复原来的标志
+    ///
+    /// 读取线程与 writer 共享同一个打开的文件，期间的读取可能返回 `WouldBlock`，
+    /// 读取线程会稍后重试。
+    pub fn with_nonblocking<T>(
+        fd: RawFd,
+        f: impl FnOnce() -> std::io::Result<T>,
+    ) -> std::io::Result<T> {
+        // SAFETY: fcntl 只读取和设置文件状态标志，不涉及内存访问
+        let flags = unsafe { libc::fcntl(fd, libc::F_GETFL) };
+        if flags < 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        // SAFETY: 同上
+        if unsafe { libc::fcntl(fd, libc::F_SETFL, flags | libc::O_NONBLOCK) } < 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        let result = f();
+        // SAFETY: 同上
+        unsafe {
+            libc::fcntl(fd, libc::F_SETFL, flags);
+        }
+        result
+    }
+
+    /// 复制文件描述符，失败时返回 `None`
+    pub fn dup_fd(fd: RawFd) -> Option<OwnedFd> {
+        // SAFETY: fcntl 只复制描述符，不涉及内存访问
+        let dup = unsafe { libc::fcntl(fd, libc::F_DUPFD_CLOEXEC, 0) };
+        if dup < 0 {
+            return None;
+        }
+        // SAFETY: dup 是刚创建的描述符，没有其他所有者
+        Some(unsafe { OwnedFd::from_raw_fd(dup) })
+    }
+    /// 检查进程是否为会话首进程（会话 ID 等于其 PID）
+    ///
+    /// 进程已经退出时无法检查，视为满足。
+    pub fn is_session_leader(pid: u32) -> bool {
+        let Ok(pid) = libc::pid_t::try_from(pid) else {
+            return true;
+        };
+        // SAFETY: getsid 只查询进程信息，不涉及内存访问
+        let sid = unsafe { libc::getsid(pid) };
+        sid == -1 || sid == pid
+    }
+
+    /// 向进程组发送信号
+    ///
+    /// 进程组不存在时忽略错误。
+    pub fn signal_group(pgid: u32, signal: libc::c_int) {
+        let Ok(pgid) = libc::pid_t::try_from(pgid) else {
+            return;
+        };
+        if pgid <= 1 {
+            return;
+        }
+        // SAFETY: killpg 只发送信号，不涉及内存访问
+        unsafe {
+            libc::killpg(pgid, signal);
+        }
+    }
+
+    /// 强制终止会话中的所有进程
+    ///
+    /// 交互式 shell 会把后台作业放入独立的进程组，仅终止 shell 的进程组无法覆盖它们。
+    pub fn kill_session(sid: u32) {
+        #[cfg(target_os = "linux")]
+        for pid in session_members(sid) {
+            // SAFETY: kill 只发送信号，不涉及内存访问
+            unsafe {
+                libc::kill(pid, libc::SIGKILL);
+            }
+        }
+
+        signal_group(sid, libc::SIGKILL);
+    }
+
+    /// 列出属于指定会话的进程
+    #[cfg(target_os = "linux")]
+    fn session_members(sid: u32) -> Vec<libc::pid_t> {
+        let Ok(entries) = std::fs::read_dir("/proc") else {
+            return Vec::new();
+        };
+
+        entries
+            .flatten()
+            .filter_map(|entry| entry.file_name().to_str()?.parse::<libc::pid_t>().ok())
+            .filter(|&pid| pid > 1 && process_session(pid) == Some(sid))
+            .collect()
+    }
+
+    /// 读取进程所属的会话 ID
+    ///
+    /// `/proc/<pid>/stat` 的格式为 `pid (comm) state ppid pgrp session ...`，
+    /// comm 中可能包含空格和括号，因此从最后一个 `)` 之后开始解析。
+    #[cfg(target_os = "linux")]
+    fn process_session(pid: libc::pid_t) -> Option<u32> {
+        let stat = std::fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+        let rest = &stat[stat.rfind(')')? + 1..];
+        rest.split_whitespace().nth(3)?.parse().ok()
+    }
+}
+
+/// Windows Job Object
+#[cfg(windows)]
+mod windows {
+    use std::os::windows::io::RawHandle;
+    use std::ptr;
+    use winapi::um::handleapi::CloseHandle;
+    use winapi::um::jobapi2::{AssignProcessToJobObject, CreateJobObjectW, TerminateJobObject};
+    use winapi::um::winnt::HANDLE;
+
+    /// Job Object 句柄
+    ///
+    /// 子进程之后创建的进程会自动加入同一个 Job，终止 Job 即可结束整个进程树。
+    pub struct JobObject(HANDLE);
+
+    // SAFETY: Job Object 句柄可以在线程间安全使用
+    unsafe impl Send for JobObject {}
+    unsafe impl Sync for JobObject {}
+
+    impl JobObject {
+        /// 创建 Job Object 并加入子进程
+        pub fn for_child(child: &(dyn portable_pty::Child + Send + Sync)) -> Option<Self> {
+            let process: RawHandle = child.as_raw_handle()?;
+
+            // SAFETY: 参数均为合法的空指针或有效句柄，失败时返回空句柄
+            let handle = unsafe { CreateJobObjectW(ptr::null_mut(), ptr::null()) };
+            if handle.is_null() {
+                tracing::warn!("创建 Job Object 失败");
+                return None;
+            }
+            let job = Self(handle);
+
+            // SAFETY: job 和 process 都是有效句柄
+            if unsafe { AssignProcessToJobObject(job.0, process as HANDLE) } == 0 {
+                tracing::warn!("将子进程加入 Job Object 失败");
+                return None;
+            }
+
+            Some(job)
+        }
+
+        /// 终止 Job 中的所有进程
+        pub fn terminate(&self) {
+            // SAFETY: 句柄在 JobObject 生命周期内有效
+            unsafe {
+                TerminateJobObject(self.0, 1);
+            }
+        }
+    }
+
+    impl Drop for JobObject {
+        fn drop(&mut self) {
+            // SAFETY: 句柄只在这里关闭一次
+            unsafe {
+                CloseHandle(self.0);
+            }
+        }
+    }
+}
+
+/// 解析 shell 的完整路径
+///
+/// 只给出名称时在 PATH 中查找，找不到时返回明确的错误而不是启动失败。
+fn resolve_shell(shell: &str, path_var: Option<&OsStr>) -> Result<PathBuf, TerminalError> {
+    resolve_program_in(shell, path_var).ok_or_else(|| {
+        if std::path::Path::new(shell).components().count() > 1 {
+            TerminalError::PtyCreationFailed(format!("shell 不存在或不可执行: {}", shell))
+        } else {
+            TerminalError::PtyCreationFailed(format!("在 PATH 中找不到 shell: {}", shell))
+        }
+    })
+}
+
+/// 转换打开 PTY 失败的错误，环境不支持伪终端时返回 `PtyUnsupported`
+///
+/// 只根据错误链中的 IO 错误分类，错误链中没有带错误码的 IO 错误时返回 `PtyCreationFailed`。
+fn openpty_error(err: anyhow::Error) -> TerminalError {
+    let errno = err
+        .chain()
+        .find_map(|cause| cause.downcast_ref::<std::io::Error>()?.raw_os_error());
+    match errno {
+        Some(errno) => TerminalError::pty_open_failed(&std::io::Error::from_raw_os_error(errno)),
+        None => TerminalError::PtyCreationFailed(err.to_string()),
+    }
+}
+
+/// 转换为 portable-pty 的窗口大小，包括像素尺寸
+fn pty_size(term_size: &TermSize) -> PtySize {
+    PtySize {
+        rows: term_size.rows,
+        cols: term_size.cols,
+        pixel_width: term_size.pixel_width,
+        pixel_height: term_size.pixel_height,
+    }
+}
+
+/// 从插件环境中选出子进程继承的变量：基础变量和 `allowlist` 中的名称
+///
+/// [`AUTH_TOKEN_ENV`] 即使在 `allowlist` 中也不继承。
+pub(crate) fn inherited_env(allowlist: &[String]) -> Vec<(OsString, OsString)> {
+    let listed = |name: &str| {
+        BASE_INHERITED_ENV
+            .iter()
+            .copied()
+            .chain(allowlist.iter().map(String::as_str))
+            .any(|allowed| env_name_eq(allowed, name))
+    };
+    std::env::vars_os()
+        .filter(|(key, _)| {
+            key.to_str().is_some_and(|name| {
+                !env_name_eq(name, AUTH_TOKEN_ENV)
+                    && (listed(name)
+                        || BASE_INHERITED_ENV_PREFIXES
+                            .iter()
+                            .any(|prefix| name.starts_with(prefix)))
+            })
+        })
+        .collect()
+}
+
+/// 比较环境变量名，Windows 上不区分大小写
+fn env_name_eq(a: &str, b: &str) -> bool {
+    if cfg!(windows) {
+        a.eq_ignore_ascii_case(b)
+    } else {
+        a == b
     }
 }
 
+/// 构建启动 shell 的命令
+///
+/// portable-pty 只在启动默认程序时把 argv[0] 设置为 `-<basename>`，启动的程序取自
+/// SHELL 环境变量，因此登录 shell 使用默认程序，并由调用方把 SHELL 设置为解析后的路径。
+/// portable-pty 只接受 UTF-8 的 SHELL，其他路径退回到普通启动。
+#[cfg(unix)]
+fn shell_command(shell: &std::path::Path, login_shell: bool) -> CommandBuilder {
+    if !login_shell {
+        return CommandBuilder::new(shell);
+    }
+    if shell.to_str().is_none() {
+        tracing::warn!("shell 路径不是 UTF-8，不能以登录 shell 启动: {}", shell.display());
+        return CommandBuilder::new(shell);
+    }
+    CommandBuilder::new_default_prog()
+}
+
+/// 构建启动 shell 的命令
+///
+/// Windows 没有登录 shell 的约定，忽略 `login_shell`。PowerShell 附加 [`shell_args`] 中的参数。
+#[cfg(windows)]
+fn shell_command(shell: &std::path::Path, _login_shell: bool) -> CommandBuilder {
+    let mut cmd = CommandBuilder::new(shell);
+    cmd.args(shell_args(&shell.to_string_lossy()));
+    cmd
+}
+
+/// 展开工作目录中的 `~` 和环境变量，并检查它是否为已存在的目录
+pub(super) fn resolve_cwd(cwd: &str) -> Result<String, TerminalError> {
+    let dir = expand_path(cwd);
+    let path = std::path::Path::new(&dir);
+    if !path.exists() {
+        return Err(TerminalError::InvalidRequest(format!("工作目录不存在: {}", dir)));
+    }
+    if !path.is_dir() {
+        return Err(TerminalError::InvalidRequest(format!("工作目录不是目录: {}", dir)));
+    }
+    Ok(dir)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -169,6 +992,334 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_nonexistent_cwd_rejected() {
+        let dir = std::env::temp_dir().join(format!("missing-{}", uuid::Uuid::new_v4()));
+        let dir = dir.to_string_lossy().to_string();
+
+        match LocalPty::new(None, Some(dir.clone()), None, TermSize::default()) {
+            Err(TerminalError::InvalidRequest(message)) => assert!(message.contains(&dir)),
+            Err(e) => panic!("unexpected error: {}", e),
+            Ok(_) => panic!("nonexistent cwd should be rejected"),
+        }
+    }
+
+    #[test]
+    fn test_resolve_cwd() {
+        let temp = std::env::temp_dir().to_string_lossy().to_string();
+        assert_eq!(resolve_cwd(&temp).unwrap(), temp);
+
+        // 文件不能作为工作目录
+        let file = std::env::temp_dir().join(format!("cwd-file-{}", uuid::Uuid::new_v4()));
+        std::fs::write(&file, b"").unwrap();
+        let result = resolve_cwd(&file.to_string_lossy());
+        std::fs::remove_file(&file).unwrap();
+        assert!(matches!(result, Err(TerminalError::InvalidRequest(_))));
+
+        if let Some(home) = dirs::home_dir() {
+            if home.join(".").is_dir() {
+                assert_eq!(resolve_cwd("~/.").unwrap(), format!("{}/.", home.to_string_lossy()));
+            }
+        }
+    }
+
+    #[test]
+    fn test_missing_shell_rejected() {
+        match LocalPty::new(
+            Some("terminal-plugin-no-such-shell".to_string()),
+            None,
+            None,
+            TermSize::default(),
+        ) {
+            Err(TerminalError::PtyCreationFailed(message)) => {
+                assert!(message.contains("terminal-plugin-no-such-shell"));
+                assert!(message.contains("PATH"));
+            }
+            Err(e) => panic!("unexpected error: {}", e),
+            Ok(_) => panic!("missing shell should be rejected"),
+        }
+    }
+
+    /// 读取进程的 argv[0]，等待子进程完成 exec
+    #[cfg(target_os = "linux")]
+    fn process_argv0(pid: u32, expected: &str) -> Option<String> {
+        let mut argv0 = None;
+        for _ in 0..100 {
+            let cmdline = std::fs::read(format!("/proc/{}/cmdline", pid)).ok()?;
+            argv0 = cmdline
+                .split(|&b| b == 0)
+                .next()
+                .map(|arg| String::from_utf8_lossy(arg).into_owned());
+            if argv0.as_deref() == Some(expected) {
+                break;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(20));
+        }
+        argv0
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_login_shell_prefixes_argv0() {
+        let mut login = match LocalPty::with_login_shell(
+            Some("/bin/sh".to_string()),
+            None,
+            None,
+            TermSize::default(),
+            true,
+        ) {
+            Ok(pty) => pty,
+            Err(e) => {
+                println!("PTY creation failed (may be expected in CI): {}", e);
+                return;
+            }
+        };
+        let mut normal =
+            LocalPty::new(Some("/bin/sh".to_string()), None, None, TermSize::default()).unwrap();
+
+        let login_pid = login.process_id().expect("shell pid");
+        let normal_pid = normal.process_id().expect("shell pid");
+        assert_eq!(process_argv0(login_pid, "-sh").as_deref(), Some("-sh"));
+        assert_eq!(process_argv0(normal_pid, "/bin/sh").as_deref(), Some("/bin/sh"));
+        assert_eq!(login.env().get("SHELL").map(String::as_str), Some("/bin/sh"));
+        assert_eq!(login.shell_path(), std::path::Path::new("/bin/sh"));
+
+        let _ = login.kill();
+        let _ = normal.kill();
+    }
+
+    #[test]
+    fn test_self_test() {
+        if let Err(e) = LocalPty::self_test(Duration::from_secs(5)) {
+            println!("PTY creation failed (may be expected in CI): {}", e);
+        }
+    }
+
+    #[test]
+    fn test_resolve_cwd_expands_env_vars() {
+        std::env::set_var("TERMINAL_PLUGIN_CWD_TEST", std::env::temp_dir());
+        let expected = std::env::temp_dir().to_string_lossy().to_string();
+        assert_eq!(resolve_cwd("$TERMINAL_PLUGIN_CWD_TEST").unwrap(), expected);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_shell_path_tilde_expanded() {
+        let Some(home) = dirs::home_dir() else {
+            return;
+        };
+        match LocalPty::new(Some("/bin/sh".to_string()), None, None, TermSize::default()) {
+            Ok(mut pty) => {
+                let _ = pty.kill();
+            }
+            Err(e) => {
+                println!("PTY creation failed (may be expected in CI): {}", e);
+                return;
+            }
+        }
+
+        // 在主目录下放一个指向 /bin/sh 的链接，通过 ~/ 路径启动
+        let name = format!(".terminal-plugin-shell-{}", uuid::Uuid::new_v4());
+        let link = home.join(&name);
+        if std::os::unix::fs::symlink("/bin/sh", &link).is_err() {
+            return;
+        }
+
+        let result = LocalPty::new(Some(format!("~/{}", name)), None, None, TermSize::default());
+        let _ = std::fs::remove_file(&link);
+        let _ = result.expect("~/ shell path should be expanded").kill();
+    }
+
+    #[test]
+    fn test_resolved_env_includes_defaults_and_custom_vars() {
+        let mut env = HashMap::new();
+        env.insert("TEST_RESOLVED_VAR".to_string(), "resolved".to_string());
+
+        match LocalPty::new(None, None, Some(env), TermSize::default()) {
+            Ok(mut pty) => {
+                let resolved = pty.env();
+                assert_eq!(resolved.get("TERM").map(String::as_str), Some("xterm-256color"));
+                assert_eq!(
+                    resolved.get("TEST_RESOLVED_VAR").map(String::as_str),
+                    Some("resolved")
+                );
+                // 继承自当前进程的环境变量
+                if let Ok(path) = std::env::var("PATH") {
+                    assert_eq!(resolved.get("PATH"), Some(&path));
+                }
+                let _ = pty.kill();
+            }
+            Err(e) => {
+                println!("PTY creation failed (may be expected in CI): {}", e);
+            }
+        }
+    }
+
+    #[test]
+    fn test_resolve_locale() {
+        assert_eq!(resolve_locale(Some("zh_CN.UTF-8"), false, Some("C")), Some("zh_CN.UTF-8".to_string()));
+        assert_eq!(resolve_locale(None, false, None), None);
+        assert_eq!(resolve_locale(None, true, Some("de_DE.UTF-8")), None);
+        assert_eq!(resolve_locale(None, true, Some("POSIX")), Some(DEFAULT_UTF8_LOCALE.to_string()));
+        assert_eq!(resolve_locale(None, true, None), Some(DEFAULT_UTF8_LOCALE.to_string()));
+
+        let env = HashMap::from([("LC_ALL".to_string(), "C".to_string())]);
+        let env = with_locale(Some(env), "ja_JP.UTF-8");
+        assert_eq!(env.get("LANG").map(String::as_str), Some("ja_JP.UTF-8"));
+        assert_eq!(env.get("LC_ALL").map(String::as_str), Some("C"));
+    }
+
+    #[test]
+    fn test_inherit_env_allowlist() {
+        std::env::set_var("TERMINAL_PLUGIN_INHERITED", "passed");
+        std::env::set_var("TERMINAL_PLUGIN_NOT_INHERITED", "hidden");
+        std::env::set_var("TERMINAL_PLUGIN_OVERRIDDEN", "parent");
+        let mut env = HashMap::new();
+        env.insert("TERMINAL_PLUGIN_OVERRIDDEN".to_string(), "explicit".to_string());
+        let allowlist = vec![
+            "TERMINAL_PLUGIN_INHERITED".to_string(),
+            "TERMINAL_PLUGIN_OVERRIDDEN".to_string(),
+            "TERMINAL_PLUGIN_MISSING".to_string(),
+        ];
+
+        match LocalPty::with_inherited_env(None, None, Some(env), TermSize::default(), false, &allowlist) {
+            Ok(mut pty) => {
+                let resolved = pty.env();
+                assert_eq!(
+                    resolved.get("TERMINAL_PLUGIN_INHERITED").map(String::as_str),
+                    Some("passed")
+                );
+                assert!(!resolved.contains_key("TERMINAL_PLUGIN_NOT_INHERITED"));
+                assert!(!resolved.contains_key("TERMINAL_PLUGIN_MISSING"));
+                // 显式给出的值优先
+                assert_eq!(
+                    resolved.get("TERMINAL_PLUGIN_OVERRIDDEN").map(String::as_str),
+                    Some("explicit")
+                );
+                let _ = pty.kill();
+            }
+            Err(e) => {
+                println!("PTY creation failed (may be expected in CI): {}", e);
+            }
+        }
+
+        // 没有白名单时只继承基础变量
+        let inherited = inherited_env(&[]);
+        assert!(inherited.iter().all(|(key, _)| key != "TERMINAL_PLUGIN_INHERITED"));
+
+        // 认证令牌即使在白名单中也不继承
+        std::env::set_var(AUTH_TOKEN_ENV, "secret");
+        let inherited = inherited_env(&[AUTH_TOKEN_ENV.to_string()]);
+        assert!(inherited.iter().all(|(key, _)| key != AUTH_TOKEN_ENV));
+    }
+
+    #[test]
+    fn test_window_size_env() {
+        let mut env = HashMap::new();
+        env.insert("COLUMNS".to_string(), "1".to_string());
+        let term_size = TermSize {
+            rows: 33,
+            cols: 101,
+            ..Default::default()
+        };
+
+        let mut pty = match LocalPty::new(Some("/bin/sh".to_string()), None, Some(env), term_size) {
+            Ok(pty) => pty,
+            Err(e) => {
+                println!("PTY creation failed (may be expected in CI): {}", e);
+                return;
+            }
+        };
+
+        // 自定义的值被 PTY 大小覆盖
+        assert_eq!(pty.env().get("COLUMNS").map(String::as_str), Some("101"));
+        assert_eq!(pty.env().get("LINES").map(String::as_str), Some("33"));
+
+        // 子进程实际收到的环境变量
+        #[cfg(target_os = "linux")]
+        {
+            // fork 之后、exec 之前读到的是父进程的环境，等待 exec 完成
+            let pid = pty.process_id().expect("shell pid");
+            let mut vars = Vec::new();
+            for _ in 0..100 {
+                let environ = std::fs::read(format!("/proc/{}/environ", pid)).unwrap_or_default();
+                vars = environ.split(|&b| b == 0).map(<[u8]>::to_vec).collect();
+                if vars.iter().any(|var| var == b"COLUMNS=101") {
+                    break;
+                }
+                std::thread::sleep(std::time::Duration::from_millis(20));
+            }
+            assert!(vars.iter().any(|var| var == b"COLUMNS=101"));
+            assert!(vars.iter().any(|var| var == b"LINES=33"));
+        }
+
+        let _ = pty.kill();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_openpty_error_classification() {
+        let unsupported = anyhow::Error::new(std::io::Error::from_raw_os_error(libc::ENXIO))
+            .context("failed to openpty");
+        assert!(matches!(openpty_error(unsupported), TerminalError::PtyUnsupported(_)));
+
+        let chained = anyhow::Error::new(std::io::Error::from_raw_os_error(libc::ENOENT));
+        assert!(matches!(openpty_error(chained), TerminalError::PtyUnsupported(_)));
+
+        let exhausted = anyhow::Error::new(std::io::Error::from_raw_os_error(libc::EMFILE))
+            .context("failed to openpty");
+        assert!(matches!(openpty_error(exhausted), TerminalError::PtyCreationFailed(_)));
+
+        // 只格式化进消息的错误码不参与分类
+        let formatted = anyhow::anyhow!(
+            "failed to openpty: {:?}",
+            std::io::Error::from_raw_os_error(libc::ENXIO)
+        );
+        assert!(matches!(openpty_error(formatted), TerminalError::PtyCreationFailed(_)));
+
+        let other = anyhow::anyhow!("something else");
+        match openpty_error(other) {
+            TerminalError::PtyCreationFailed(message) => assert_eq!(message, "something else"),
+            e => panic!("unexpected error: {:?}", e),
+        }
+    }
+
+    #[test]
+    fn test_pixel_size_reaches_pty() {
+        let term_size = TermSize {
+            rows: 30,
+            cols: 100,
+            pixel_width: 1000,
+            pixel_height: 600,
+        };
+        let mut pty = match LocalPty::new(Some("/bin/sh".to_string()), None, None, term_size) {
+            Ok(pty) => pty,
+            Err(e) => {
+                println!("PTY creation failed (may be expected in CI): {}", e);
+                return;
+            }
+        };
+
+        #[cfg(unix)]
+        {
+            let size = pty.master.get_size().unwrap();
+            assert_eq!((size.pixel_width, size.pixel_height), (1000, 600));
+
+            pty.resize(TermSize {
+                rows: 40,
+                cols: 120,
+                pixel_width: 1200,
+                pixel_height: 800,
+            })
+            .unwrap();
+            let size = pty.master.get_size().unwrap();
+            assert_eq!((size.rows, size.cols), (40, 120));
+            assert_eq!((size.pixel_width, size.pixel_height), (1200, 800));
+        }
+
+        let _ = pty.kill();
+    }
+
     #[test]
     fn test_create_local_pty_with_env() {
         let mut env = HashMap::new();
@@ -184,6 +1335,140 @@ mod tests {
             }
         }
     }
+
+    /// 检查进程是否仍在运行（僵尸进程视为已退出）
+    #[cfg(unix)]
+    fn process_alive(pid: libc::pid_t) -> bool {
+        // SAFETY: 信号 0 只检查进程是否存在
+        if unsafe { libc::kill(pid, 0) } != 0 {
+            return false;
+        }
+        match std::fs::read_to_string(format!("/proc/{}/stat", pid)) {
+            Ok(stat) => stat
+                .rfind(')')
+                .and_then(|i| stat[i + 1..].split_whitespace().next())
+                .is_some_and(|state| state != "Z"),
+            Err(_) => true,
+        }
+    }
+
+    /// 等待进程退出
+    #[cfg(unix)]
+    fn wait_for_exit(pid: libc::pid_t) -> bool {
+        for _ in 0..100 {
+            if !process_alive(pid) {
+                return true;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(20));
+        }
+        false
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_child_runs_in_new_session() {
+        let mut pty = match LocalPty::new(
+            Some("/bin/sh".to_string()),
+            None,
+            None,
+            TermSize::default(),
+        ) {
+            Ok(pty) => pty,
+            Err(e) => {
+                println!("PTY creation failed (may be expected in CI): {}", e);
+                return;
+            }
+        };
+
+        let shell_pid = pty.process_id().expect("shell pid") as libc::pid_t;
+        // SAFETY: getpgid/getsid 只查询进程信息
+        let (child_pgid, parent_pgid, child_sid, parent_sid) = unsafe {
+            (
+                libc::getpgid(shell_pid),
+                libc::getpgid(0),
+                libc::getsid(shell_pid),
+                libc::getsid(0),
+            )
+        };
+        assert_eq!(child_pgid, shell_pid, "shell 应是自己进程组的组长");
+        assert_ne!(child_pgid, parent_pgid);
+        assert_eq!(child_sid, shell_pid, "shell 应是新会话的首进程");
+        assert_ne!(child_sid, parent_sid);
+
+        pty.kill().unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_kill_terminates_background_children() {
+        let mut pty = match LocalPty::new(
+            Some("/bin/sh".to_string()),
+            None,
+            None,
+            TermSize::default(),
+        ) {
+            Ok(pty) => pty,
+            Err(e) => {
+                println!("PTY creation failed (may be expected in CI): {}", e);
+                return;
+            }
+        };
+
+        let shell_pid = pty.process_id().expect("shell pid") as libc::pid_t;
+        let mut reader = pty.try_clone_reader().unwrap();
+
+        // 读取线程在 PTY 关闭后自然结束
+        let (tx, rx) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            let mut buf = [0u8; 1024];
+            loop {
+                match reader.read(&mut buf) {
+                    Ok(n) if n > 0 && tx.send(buf[..n].to_vec()).is_ok() => {}
+                    // 写入期间的读取可能返回 WouldBlock
+                    Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {
+                        std::thread::sleep(std::time::Duration::from_millis(10));
+                    }
+                    _ => break,
+                }
+            }
+        });
+
+        pty.write(b"sleep 1000 & echo \"SLEEP_PID=$!\"\n").unwrap();
+
+        // 命令回显中也包含 SLEEP_PID=，只接受后面跟数字的那一次
+        let mut output = String::new();
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(5);
+        let sleep_pid = loop {
+            let found = output.match_indices("SLEEP_PID=").find_map(|(i, m)| {
+                let digits: String = output[i + m.len()..]
+                    .chars()
+                    .take_while(|c| c.is_ascii_digit())
+                    .collect();
+                let terminated = output[i + m.len() + digits.len()..]
+                    .starts_with(['\r', '\n']);
+                if terminated {
+                    digits.parse::<libc::pid_t>().ok()
+                } else {
+                    None
+                }
+            });
+            if let Some(pid) = found {
+                break pid;
+            }
+            let remaining = deadline.saturating_duration_since(std::time::Instant::now());
+            match rx.recv_timeout(remaining) {
+                Ok(chunk) => output.push_str(&String::from_utf8_lossy(&chunk)),
+                Err(_) => panic!("未读取到 sleep 的 PID，输出: {:?}", output),
+            }
+        };
+
+        assert!(process_alive(sleep_pid));
+
+        pty.kill().unwrap();
+
+        assert!(wait_for_exit(shell_pid), "shell 进程未退出");
+        assert!(wait_for_exit(sleep_pid), "后台 sleep 进程未退出");
+    }
 }
 
 
@@ -3,12 +3,28 @@
 //! 管理多个 PTY 会话的创建、输入、调整大小和关闭。
 
 use std::collections::HashMap;
+use std::sync::Arc;
 
 use crate::rpc::server::NotificationSender;
-use crate::rpc::types::{ConnectionType, CreateSessionRequest, SessionInfo, SessionStatus, TermSize};
+use crate::rpc::types::{
+    BatchOperation, BatchOperationResult, BatchRequest, BatchResponse, ClearMode, CloseFailure, CloseWhereRequest, CloseWhereResponse, ConnectionType,
+    CreateSessionRequest, SyncSessionResponse, JsonRpcError, QuerySessionsRequest, ServerStats, SessionInfo, SessionStats, SessionStatus,
+    TermSize, WriteRawRequest,
+};
+use crate::shell::detect::{default_clear_mode, detect_default_shell};
+#[cfg(windows)]
+use crate::shell::detect::detect_powershell;
+use crate::shell::keys;
+use crate::utils::codec;
 use crate::utils::error::TerminalError;
+use crate::utils::time::{system_clock, SharedClock};
 
-use super::session::PtySession;
+use super::local::{inherited_locale, parse_signal, resolve_locale, with_force_color, with_locale};
+use super::session::{BatchStep, PtySession};
+use super::stats::ByteCounters;
+
+/// 客户端指定的会话 ID 的最大长度
+pub const MAX_SESSION_ID_LEN: usize = 128;
 
 /// PTY 管理器
 pub struct PtyManager {
@@ -16,6 +32,12 @@ pub struct PtyManager {
     sessions: HashMap<String, PtySession>,
     /// 通知发送器（可选，用于发送输出通知）
     notification_sender: Option<NotificationSender>,
+    /// 所有会话的字节总数（会话计数器的上级）
+    totals: Arc<ByteCounters>,
+    /// 成功创建的会话总数
+    sessions_created: u64,
+    /// 会话使用的时间来源
+    clock: SharedClock,
 }
 
 impl PtyManager {
@@ -24,6 +46,9 @@ impl PtyManager {
         Self {
             sessions: HashMap::new(),
             notification_sender: None,
+            totals: Arc::new(ByteCounters::new()),
+            sessions_created: 0,
+            clock: system_clock(),
         }
     }
 
@@ -32,6 +57,9 @@ impl PtyManager {
         Self {
             sessions: HashMap::new(),
             notification_sender: Some(notification_sender),
+            totals: Arc::new(ByteCounters::new()),
+            sessions_created: 0,
+            clock: system_clock(),
         }
     }
 
@@ -40,63 +68,327 @@ impl PtyManager {
         self.notification_sender = Some(sender);
     }
 
+    /// 设置时间来源，只影响之后创建的会话
+    pub fn set_clock(&mut self, clock: SharedClock) {
+        self.clock = clock;
+    }
+
     /// 创建新会话
+    ///
+    /// 使用请求中指定的会话 ID，未指定时生成 UUID，见 [`resolve_session_id`](Self::resolve_session_id)。
     pub async fn create_session(
         &mut self,
         request: CreateSessionRequest,
     ) -> Result<String, TerminalError> {
-        // 生成唯一会话 ID
-        let session_id = uuid::Uuid::new_v4().to_string();
+        let session_id = self.resolve_session_id(&request)?;
+        self.create_session_with_id(session_id, request).await
+    }
+
+    /// 确定新会话的 ID
+    ///
+    /// 请求中指定的 ID 格式无效时返回 `InvalidRequest`；已存在时按 `generate_id_on_conflict`
+    /// 改为生成 UUID 或返回 `InvalidRequest`。未指定时生成 UUID。
+    pub fn resolve_session_id(&self, request: &CreateSessionRequest) -> Result<String, TerminalError> {
+        let Some(session_id) = &request.session_id else {
+            return Ok(uuid::Uuid::new_v4().to_string());
+        };
+
+        validate_session_id(session_id)?;
+        if !self.sessions.contains_key(session_id) {
+            return Ok(session_id.clone());
+        }
+        if request.generate_id_on_conflict {
+            let generated = uuid::Uuid::new_v4().to_string();
+            tracing::debug!("会话 ID 已存在，改用生成的 ID: {} -> {}", session_id, generated);
+            return Ok(generated);
+        }
+        Err(TerminalError::InvalidRequest(format!("会话 ID 已存在: {}", session_id)))
+    }
+
+    /// 使用指定 ID 创建新会话
+    ///
+    /// 创建失败时除了返回错误，还会发送 `error` 状态通知，
+    /// 使前端可以统一通过 `session.status` 跟踪会话生命周期。
+    ///
+    /// 请求指定了 `timeout_ms` 时，超时后取消创建并返回 `ConnectionTimeout`，
+    /// 已经启动的进程会被终止。
+    pub async fn create_session_with_id(
+        &mut self,
+        session_id: String,
+        request: CreateSessionRequest,
+    ) -> Result<String, TerminalError> {
+        let result = self.build_session(&session_id, &request).await;
+
+        match result {
+            Ok(session) => {
+                self.sessions.insert(session_id.clone(), session);
+                self.sessions_created += 1;
+                tracing::info!("创建会话: {}", session_id);
+                Ok(session_id)
+            }
+            Err(e) => {
+                tracing::warn!("创建会话失败: {}: {}", session_id, e);
+                if let Some(sender) = &self.notification_sender {
+                    if let Err(send_err) = sender.send_error_status(&session_id, &e) {
+                        tracing::error!("发送错误状态通知失败: {}", send_err);
+                    }
+                }
+                Err(e)
+            }
+        }
+    }
+
+    /// 根据请求构建会话并启动后台任务
+    ///
+    /// `timeout_ms` 同时限制启动进程和之后的初始化。进程启动前超时直接放弃；
+    /// 启动后超时会关闭会话，子进程和读取线程不会遗留。
+    async fn build_session(
+        &self,
+        session_id: &str,
+        request: &CreateSessionRequest,
+    ) -> Result<PtySession, TerminalError> {
+        let timeout_ms = request.timeout_ms.filter(|&ms| ms > 0);
+        let deadline = timeout_ms
+            .map(|ms| tokio::time::Instant::now() + std::time::Duration::from_millis(ms));
+        let timed_out = || {
+            TerminalError::ConnectionTimeout(format!(
+                "创建会话超过 {} 毫秒未完成",
+                timeout_ms.unwrap_or_default()
+            ))
+        };
+
+        let spawn = self.spawn_session(session_id, request);
+        let (mut session, initial_input) = match deadline {
+            Some(deadline) => tokio::time::timeout_at(deadline, spawn)
+                .await
+                .map_err(|_| timed_out())??,
+            None => spawn.await?,
+        };
+
+        let setup = self.setup_session(&mut session, request, initial_input);
+        let completed = match deadline {
+            Some(deadline) => tokio::time::timeout_at(deadline, setup).await.is_ok(),
+            None => {
+                setup.await;
+                true
+            }
+        };
+        if !completed {
+            if let Err(e) = session.close().await {
+                tracing::warn!("关闭超时的会话失败: {}: {}", session_id, e);
+            }
+            return Err(timed_out());
+        }
+
+        Ok(session)
+    }
+
+    /// 根据连接类型创建会话，返回会话和解码后的初始输入
+    async fn spawn_session(
+        &self,
+        session_id: &str,
+        request: &CreateSessionRequest,
+    ) -> Result<(PtySession, Option<Vec<u8>>), TerminalError> {
+        // 先解码初始输入，数据无效时不创建会话
+        let initial_input = request
+            .initial_input
+            .as_deref()
+            .map(codec::decode_input)
+            .transpose()?;
 
         // 根据连接类型创建会话
-        let mut session = match &request.connection {
+        let session = match &request.connection {
             ConnectionType::Local { shell_path, cwd, env } => {
+                let env = child_env(request, env.clone());
+
+                // 未指定工作目录时尝试从其他会话继承
+                let cwd = match (cwd, &request.inherit_cwd_from) {
+                    (None, Some(source_id)) => self.inherited_cwd(source_id).await,
+                    _ => cwd.clone(),
+                };
+
+                let shell_path = match shell_path {
+                    #[cfg(windows)]
+                    None if request.prefer_powershell => detect_powershell(),
+                    _ => shell_path.clone(),
+                };
+
                 // 创建本地 PTY 会话
                 PtySession::new_local(
-                    session_id.clone(),
-                    shell_path.clone(),
-                    cwd.clone(),
-                    env.clone(),
-                    request.term_size,
+                    session_id.to_string(),
+                    shell_path,
+                    cwd,
+                    env,
+                    request.term_size.clone(),
+                    request.login_shell,
+                    &request.inherit_env,
+                    self.clock.clone(),
                 )?
             }
+            ConnectionType::LocalProcess { command, cwd, env } => {
+                let env = child_env(request, env.clone());
+                PtySession::new_local_process(
+                    session_id.to_string(),
+                    command.clone(),
+                    cwd.clone(),
+                    env,
+                    &request.inherit_env,
+                    self.clock.clone(),
+                )
+                .await?
+            }
             ConnectionType::Ssh { .. } => {
                 // SSH 会话暂时只创建占位符，实际实现在 SSH 模块
-                let mut session = PtySession::new(session_id.clone(), request.connection.clone());
-                session.set_status(SessionStatus::Connecting);
+                let session = PtySession::new(
+                    session_id.to_string(),
+                    request.connection.clone(),
+                    self.clock.clone(),
+                );
+                session.set_status(SessionStatus::Connecting).await;
                 session
             }
         };
 
+        Ok((session, initial_input))
+    }
+
+    /// 应用会话选项并启动后台任务，最后写入初始输入
+    async fn setup_session(
+        &self,
+        session: &mut PtySession,
+        request: &CreateSessionRequest,
+        initial_input: Option<Vec<u8>>,
+    ) {
+        session.set_counters(Arc::new(ByteCounters::with_parent(self.totals.clone())));
+        if let Some(buffer_size) = request.read_buffer_size {
+            session.set_read_buffer_size(buffer_size);
+        }
+        if let Some(chunk_size) = request.write_chunk_size {
+            session.set_write_chunk_size(chunk_size);
+        }
+        if let Some(rate) = request.rate_limit_bytes_per_sec {
+            session.set_rate_limit(rate);
+        }
+        session.set_sanitize_clipboard(request.sanitize_clipboard);
+        session.set_strip_osc_from_output(request.strip_osc_from_output);
+        session.set_allow_inject(request.allow_inject);
+        session.set_read_only(request.read_only);
+
         // 如果有通知发送器且是本地会话，启动输出读取器
         if let Some(sender) = &self.notification_sender {
-            if matches!(request.connection, ConnectionType::Local { .. }) {
+            if matches!(
+                request.connection,
+                ConnectionType::Local { .. } | ConnectionType::LocalProcess { .. }
+            ) {
                 if let Err(e) = session.start_output_reader(sender.clone()).await {
                     tracing::warn!("启动输出读取器失败: {}", e);
                 }
             }
         }
 
-        // 存储会话
-        self.sessions.insert(session_id.clone(), session);
+        // 按需启动空闲监控
+        if let Some(secs) = request.idle_timeout_secs.filter(|&secs| secs > 0) {
+            if let Err(e) = session.start_idle_monitor(
+                std::time::Duration::from_secs(secs),
+                request.idle_reset_on_output,
+            ) {
+                tracing::warn!("启动空闲监控器失败: {}", e);
+            }
+        }
+
+        // 按需启动工作目录轮询
+        if request.track_cwd && matches!(request.connection, ConnectionType::Local { .. }) {
+            self.start_cwd_tracking(session).await;
+        }
+
+        // 输出读取器启动后再写入初始输入，回显和输出都会被发送
+        if let Some(data) = initial_input {
+            self.write_initial_input(session, &data).await;
+        }
+    }
 
-        tracing::info!("创建会话: {}", session_id);
-        Ok(session_id)
+    /// 会话处于 `running` 状态时写入一次初始输入
+    ///
+    /// 写入失败（如进程已退出）只记录日志，不影响会话创建。SSH 占位会话还没有通道，
+    /// 初始输入由 [`SshSession::set_initial_input`](crate::ssh::session::SshSession::set_initial_input)
+    /// 在 shell 打开后写入。
+    async fn write_initial_input(&self, session: &PtySession, data: &[u8]) {
+        if session.info().await.status != SessionStatus::Running {
+            tracing::debug!("会话未处于运行状态，跳过初始输入: {}", session.id());
+            return;
+        }
+        match session.write(data).await {
+            Ok(()) => tracing::debug!("写入初始输入到会话 {}: {} bytes", session.id(), data.len()),
+            Err(e) => tracing::warn!("写入初始输入失败: {} - {}", session.id(), e),
+        }
     }
 
-    /// 发送输入到会话
-    pub async fn send_input(&mut self, session_id: &str, data: &str) -> Result<(), TerminalError> {
+    /// 获取可继承的工作目录
+    ///
+    /// 源会话不存在或工作目录未知时返回 `None`，使用默认工作目录。
+    async fn inherited_cwd(&self, source_id: &str) -> Option<String> {
+        let Some(source) = self.sessions.get(source_id) else {
+            tracing::debug!("继承工作目录的源会话不存在: {}", source_id);
+            return None;
+        };
+
+        let cwd = source.info().await.cwd;
+        if cwd.is_none() {
+            tracing::debug!("源会话的工作目录未知: {}", source_id);
+        }
+        cwd
+    }
+
+    /// 启动工作目录轮询
+    #[cfg(target_os = "linux")]
+    async fn start_cwd_tracking(&self, session: &mut PtySession) {
+        let Some(sender) = &self.notification_sender else {
+            return;
+        };
+        if let Err(e) = session
+            .start_cwd_poller(sender.clone(), super::cwd::DEFAULT_CWD_POLL_INTERVAL)
+            .await
+        {
+            tracing::warn!("启动工作目录轮询器失败: {}", e);
+        }
+    }
+
+    /// 启动工作目录轮询（当前平台不支持）
+    #[cfg(not(target_os = "linux"))]
+    async fn start_cwd_tracking(&self, _session: &mut PtySession) {
+        tracing::warn!("当前平台不支持工作目录轮询");
+    }
+
+    /// 获取接受输入的会话，会话不存在时返回 `SessionNotFound`，只读时返回 `InvalidRequest`
+    fn writable_session(&self, session_id: &str) -> Result<&PtySession, TerminalError> {
         let session = self
             .sessions
             .get(session_id)
             .ok_or_else(|| TerminalError::SessionNotFound(session_id.to_string()))?;
+        session.ensure_writable()?;
+        Ok(session)
+    }
+
+    /// 设置会话是否只读
+    ///
+    /// 只读会话拒绝输入、EOF、清屏和调整大小，输出照常发送。
+    pub fn set_read_only(&mut self, session_id: &str, read_only: bool) -> Result<(), TerminalError> {
+        let session = self
+            .sessions
+            .get_mut(session_id)
+            .ok_or_else(|| TerminalError::SessionNotFound(session_id.to_string()))?;
+
+        session.set_read_only(read_only);
+        tracing::debug!("设置会话 {} 只读: {}", session_id, read_only);
+        Ok(())
+    }
+
+    /// 发送输入到会话
+    pub async fn send_input(&mut self, session_id: &str, data: &str) -> Result<(), TerminalError> {
+        let session = self.writable_session(session_id)?;
 
         // 解码 base64 数据
-        let decoded = base64::Engine::decode(
-            &base64::engine::general_purpose::STANDARD,
-            data,
-        )
-        .map_err(|e| TerminalError::InvalidRequest(format!("Invalid base64 data: {}", e)))?;
+        let decoded = codec::decode_input(data)?;
 
         // 写入 PTY
         session.write(&decoded).await?;
@@ -105,16 +397,85 @@ impl PtyManager {
         Ok(())
     }
 
+    /// 向会话发送按键或十六进制编码的原始字节
+    ///
+    /// 未指定或同时指定 `key` 和 `hex`、按键名称未知或十六进制无效时返回 `InvalidRequest`。
+    pub async fn write_raw(&mut self, request: &WriteRawRequest) -> Result<(), TerminalError> {
+        let data = match (&request.key, &request.hex) {
+            (Some(key), None) => keys::key_sequence(key).map(<[u8]>::to_vec).ok_or_else(|| {
+                let available: Vec<&str> = keys::key_names().collect();
+                TerminalError::InvalidRequest(format!(
+                    "未知的按键: {}（可用: {}）",
+                    key,
+                    available.join(", ")
+                ))
+            })?,
+            (None, Some(hex)) => codec::decode_hex(hex)?,
+            _ => {
+                return Err(TerminalError::InvalidRequest(
+                    "key 和 hex 必须且只能指定一个".to_string(),
+                ))
+            }
+        };
+
+        let session = self.writable_session(&request.session_id)?;
+        session.write(&data).await?;
+
+        tracing::debug!("发送原始输入到会话 {}: {} bytes", request.session_id, data.len());
+        Ok(())
+    }
+
+    /// 向会话发送 EOF（关闭输入）
+    ///
+    /// 等待输入的命令（如 `cat`）读到 EOF 后结束，之后的输入返回 `SessionClosed`。
+    pub async fn eof_session(&mut self, session_id: &str) -> Result<(), TerminalError> {
+        let session = self.writable_session(session_id)?;
+
+        session.close_input().await?;
+
+        tracing::debug!("关闭会话输入: {}", session_id);
+        Ok(())
+    }
+
+    /// 等待会话之前的输入写入完成并刷新，返回累计写入的输入字节数
+    ///
+    /// 用作脚本化流程中的屏障：返回时之前发送的输入都已交给 PTY。
+    pub async fn sync_session(&self, session_id: &str) -> Result<SyncSessionResponse, TerminalError> {
+        let session = self
+            .sessions
+            .get(session_id)
+            .ok_or_else(|| TerminalError::SessionNotFound(session_id.to_string()))?;
+
+        let bytes_in = session.sync().await?;
+        tracing::debug!("同步会话输入: {} ({} bytes)", session_id, bytes_in);
+        Ok(SyncSessionResponse { bytes_in })
+    }
+
+    /// 向会话注入输出
+    ///
+    /// 数据作为 `terminal.output` 通知发送，不写入子进程的输入，需要创建会话时设置
+    /// `allow_inject`。
+    pub fn inject_output(&self, session_id: &str, data: &str) -> Result<(), TerminalError> {
+        let session = self
+            .sessions
+            .get(session_id)
+            .ok_or_else(|| TerminalError::SessionNotFound(session_id.to_string()))?;
+
+        let decoded = codec::decode_input(data)?;
+        let len = decoded.len();
+        session.inject_output(decoded)?;
+
+        tracing::debug!("注入输出到会话 {}: {} bytes", session_id, len);
+        Ok(())
+    }
+
     /// 调整会话大小
     pub async fn resize_session(
         &mut self,
         session_id: &str,
         term_size: TermSize,
     ) -> Result<(), TerminalError> {
-        let session = self
-            .sessions
-            .get(session_id)
-            .ok_or_else(|| TerminalError::SessionNotFound(session_id.to_string()))?;
+        let session = self.writable_session(session_id)?;
 
         // 调整 PTY 大小
         session.resize(term_size.clone()).await?;
@@ -128,6 +489,119 @@ impl PtyManager {
         Ok(())
     }
 
+    /// 按顺序对会话执行一组操作（`session.batch`）
+    ///
+    /// 先校验所有操作，输入无法解码或信号未知时不执行任何操作并返回 `InvalidRequest`。
+    /// 执行时某项失败则停止，失败原因记录在响应中。
+    pub async fn batch(&mut self, request: &BatchRequest) -> Result<BatchResponse, TerminalError> {
+        if request.operations.is_empty() {
+            return Err(TerminalError::InvalidRequest("operations 不能为空".to_string()));
+        }
+        let steps = request
+            .operations
+            .iter()
+            .map(|operation| match operation {
+                BatchOperation::Resize { term_size } => Ok(BatchStep::Resize(term_size.clone())),
+                BatchOperation::Input { data } => codec::decode_input(data).map(BatchStep::Input),
+                BatchOperation::Signal { signal } => parse_signal(signal).map(BatchStep::Signal),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let session = self.writable_session(&request.session_id)?;
+        let results: Vec<BatchOperationResult> = session
+            .apply_batch(&steps)
+            .await
+            .into_iter()
+            .zip(&request.operations)
+            .map(|(result, operation)| BatchOperationResult {
+                op: operation.name().to_string(),
+                ok: result.is_ok(),
+                error: result.err().map(|e| e.to_string()),
+            })
+            .collect();
+        let applied = results.iter().filter(|result| result.ok).count();
+
+        tracing::debug!(
+            "会话 {} 批量操作: {}/{} 项成功",
+            request.session_id,
+            applied,
+            request.operations.len()
+        );
+        Ok(BatchResponse { applied, results })
+    }
+
+    /// 暂停会话输出
+    pub async fn pause_session(&mut self, session_id: &str) -> Result<(), TerminalError> {
+        let session = self
+            .sessions
+            .get(session_id)
+            .ok_or_else(|| TerminalError::SessionNotFound(session_id.to_string()))?;
+
+        session.pause_output()
+    }
+
+    /// 恢复会话输出
+    pub async fn resume_session(&mut self, session_id: &str) -> Result<(), TerminalError> {
+        let session = self
+            .sessions
+            .get(session_id)
+            .ok_or_else(|| TerminalError::SessionNotFound(session_id.to_string()))?;
+
+        session.resume_output()
+    }
+
+    /// 分离会话输出：停止输出读取器，保留子进程
+    pub async fn detach_session(&mut self, session_id: &str) -> Result<(), TerminalError> {
+        let session = self
+            .sessions
+            .get_mut(session_id)
+            .ok_or_else(|| TerminalError::SessionNotFound(session_id.to_string()))?;
+
+        session.detach_output().await
+    }
+
+    /// 重新附加会话输出，使用当前的通知发送器
+    pub async fn attach_session(&mut self, session_id: &str) -> Result<(), TerminalError> {
+        let sender = self.notification_sender.clone().ok_or_else(|| {
+            TerminalError::InvalidRequest("未设置通知发送器，无法附加输出".to_string())
+        })?;
+        let session = self
+            .sessions
+            .get_mut(session_id)
+            .ok_or_else(|| TerminalError::SessionNotFound(session_id.to_string()))?;
+
+        session.attach_output(sender).await
+    }
+
+    /// 清屏
+    ///
+    /// 向会话输入写入清屏序列，未指定方式时根据 shell 类型选择。返回实际使用的方式。
+    pub async fn clear_session(
+        &mut self,
+        session_id: &str,
+        mode: Option<ClearMode>,
+    ) -> Result<ClearMode, TerminalError> {
+        let session = self.writable_session(session_id)?;
+
+        let mode = match mode {
+            Some(mode) => mode,
+            None => match session.info().await.connection_type {
+                ConnectionType::Local { shell_path, .. } => {
+                    default_clear_mode(&shell_path.unwrap_or_else(detect_default_shell))
+                }
+                // 远程 shell 未知，大多数 shell 都支持 Ctrl+L
+                ConnectionType::LocalProcess { .. } | ConnectionType::Ssh { .. } => {
+                    ClearMode::FormFeed
+                }
+            },
+        };
+
+        session.write(mode.as_bytes()).await?;
+
+        tracing::debug!("清屏: {} ({:?})", session_id, mode);
+        Ok(mode)
+    }
+
     /// 关闭会话
     pub async fn close_session(&mut self, session_id: &str) -> Result<(), TerminalError> {
         let mut session = self
@@ -135,24 +609,161 @@ impl PtyManager {
             .remove(session_id)
             .ok_or_else(|| TerminalError::SessionNotFound(session_id.to_string()))?;
 
-        // 停止输出读取器
-        session.stop_output_reader().await;
-
-        // 终止 PTY 进程
-        session.kill().await?;
+        // 终止 PTY 进程并停止输出读取器
+        session.close().await?;
 
         tracing::info!("关闭会话: {}", session_id);
         Ok(())
     }
 
+    /// 关闭所有满足条件的会话
+    ///
+    /// 每个会话都按 [`close_session`](Self::close_session) 的流程关闭，单个会话失败时
+    /// 继续关闭其他会话，失败原因汇总在响应中。没有给出任何条件时返回 `InvalidRequest`。
+    pub async fn close_where(
+        &mut self,
+        filter: &CloseWhereRequest,
+    ) -> Result<CloseWhereResponse, TerminalError> {
+        if filter.is_empty() {
+            return Err(TerminalError::InvalidRequest("至少需要一个过滤条件".to_string()));
+        }
+
+        let mut matched = Vec::new();
+        for (session_id, session) in &self.sessions {
+            if filter.matches(&session.info().await.connection_type) {
+                matched.push(session_id.clone());
+            }
+        }
+        matched.sort();
+
+        let mut response = CloseWhereResponse::default();
+        for session_id in matched {
+            match self.close_session(&session_id).await {
+                Ok(()) => response.closed.push(session_id),
+                Err(e) => {
+                    tracing::warn!("关闭会话失败: {} - {}", session_id, e);
+                    response.errors.push(CloseFailure {
+                        session_id,
+                        error: JsonRpcError::from(e),
+                    });
+                }
+            }
+        }
+
+        tracing::info!(
+            "批量关闭会话: {} 个成功, {} 个失败",
+            response.closed.len(),
+            response.errors.len()
+        );
+        Ok(response)
+    }
+
+    /// 关闭所有会话
+    ///
+    /// 单个会话关闭失败只记录日志，不影响其他会话。
+    pub async fn close_all_sessions(&mut self) {
+        for (session_id, mut session) in self.sessions.drain() {
+            if let Err(e) = session.close().await {
+                tracing::warn!("关闭会话失败: {} - {}", session_id, e);
+            }
+        }
+        tracing::info!("已关闭所有会话");
+    }
+
+    /// 设置会话标题
+    ///
+    /// 更新会话信息中的标题，并发送 `session.title` 通知。
+    pub async fn set_title(&mut self, session_id: &str, title: String) -> Result<(), TerminalError> {
+        let session = self
+            .sessions
+            .get(session_id)
+            .ok_or_else(|| TerminalError::SessionNotFound(session_id.to_string()))?;
+
+        session.set_title(title.clone()).await;
+
+        if let Some(sender) = &self.notification_sender {
+            if let Err(e) = sender.send_title(session_id, &title) {
+                tracing::error!("发送标题通知失败: {}", e);
+            }
+        }
+
+        tracing::debug!("设置会话 {} 标题: {}", session_id, title);
+        Ok(())
+    }
+
+    /// 设置会话显示名称
+    ///
+    /// `None` 或空字符串清除名称。名称变化时发送 `session.renamed` 通知。
+    pub async fn rename_session(
+        &mut self,
+        session_id: &str,
+        name: Option<String>,
+    ) -> Result<(), TerminalError> {
+        let session = self
+            .sessions
+            .get(session_id)
+            .ok_or_else(|| TerminalError::SessionNotFound(session_id.to_string()))?;
+
+        let name = name.filter(|name| !name.is_empty());
+        if session.info().await.name == name {
+            return Ok(());
+        }
+        session.set_name(name.clone()).await;
+
+        if let Some(sender) = &self.notification_sender {
+            if let Err(e) = sender.send_renamed(session_id, name.as_deref()) {
+                tracing::error!("发送重命名通知失败: {}", e);
+            }
+        }
+
+        tracing::debug!("重命名会话 {}: {:?}", session_id, name);
+        Ok(())
+    }
+
     /// 列出所有会话
+    ///
+    /// 按创建时间排序，创建时间相同（同一秒内创建）时按 ID 排序，相同的会话集合每次返回的顺序
+    /// 一致。
     pub async fn list_sessions(&self) -> Vec<SessionInfo> {
-        self.sessions.values().map(|s| s.info().clone()).collect()
+        let mut sessions = Vec::with_capacity(self.sessions.len());
+        for session in self.sessions.values() {
+            sessions.push(session.info().await);
+        }
+        sessions.sort_by(|a, b| a.created_at.cmp(&b.created_at).then_with(|| a.id.cmp(&b.id)));
+        sessions
+    }
+
+    /// 查询满足条件的会话，按 ID 排序
+    pub async fn query_sessions(&self, query: &QuerySessionsRequest) -> Vec<SessionInfo> {
+        let mut sessions = Vec::new();
+        for session in self.sessions.values() {
+            let info = session.info().await;
+            if query.matches(&info) {
+                sessions.push(info);
+            }
+        }
+        sessions.sort_by(|a, b| a.id.cmp(&b.id));
+        sessions
     }
 
     /// 获取会话信息
     pub async fn get_session(&self, session_id: &str) -> Option<SessionInfo> {
-        self.sessions.get(session_id).map(|s| s.info().clone())
+        match self.sessions.get(session_id) {
+            Some(session) => Some(session.info().await),
+            None => None,
+        }
+    }
+
+    /// 获取本地会话启动时使用的完整环境变量
+    pub async fn get_env(&self, session_id: &str) -> Result<HashMap<String, String>, TerminalError> {
+        let session = self
+            .sessions
+            .get(session_id)
+            .ok_or_else(|| TerminalError::SessionNotFound(session_id.to_string()))?;
+
+        session.env().await.ok_or_else(|| {
+            TerminalError::InvalidRequest(format!("只有本地会话支持读取环境变量: {}", session_id))
+        })
     }
 
     /// 获取会话引用
@@ -169,6 +780,80 @@ impl PtyManager {
     pub fn session_count(&self) -> usize {
         self.sessions.len()
     }
+
+    /// 获取统计快照
+    ///
+    /// 总字节数包含已关闭会话的计数，`sessions` 只列出当前打开的会话。
+    pub fn stats(&self) -> ServerStats {
+        let mut sessions: Vec<SessionStats> = self
+            .sessions
+            .iter()
+            .map(|(id, session)| SessionStats {
+                session_id: id.clone(),
+                bytes_in: session.counters().bytes_in(),
+                bytes_out: session.counters().bytes_out(),
+            })
+            .collect();
+        sessions.sort_by(|a, b| a.session_id.cmp(&b.session_id));
+
+        ServerStats {
+            sessions_created: self.sessions_created,
+            sessions_open: self.sessions.len(),
+            bytes_in: self.totals.bytes_in(),
+            bytes_out: self.totals.bytes_out(),
+            notifications_dropped: self
+                .notification_sender
+                .as_ref()
+                .map_or(0, NotificationSender::dropped_count),
+            notifications_oversized: self
+                .notification_sender
+                .as_ref()
+                .map_or(0, NotificationSender::oversized_count),
+            sessions,
+        }
+    }
+}
+
+/// 本地子进程的自定义环境变量：在 `env` 中加入请求指定的 locale 和彩色输出变量，
+/// `env` 中已经给出的变量优先
+fn child_env(
+    request: &CreateSessionRequest,
+    env: Option<HashMap<String, String>>,
+) -> Option<HashMap<String, String>> {
+    let locale = resolve_locale(
+        request.locale.as_deref(),
+        request.fallback_utf8_locale,
+        inherited_locale().as_deref(),
+    );
+    let env = match locale {
+        Some(locale) => Some(with_locale(env, &locale)),
+        None => env,
+    };
+    if request.force_color {
+        Some(with_force_color(env))
+    } else {
+        env
+    }
+}
+
+/// 检查客户端指定的会话 ID：非空、不超过最大长度，只包含字母、数字、`-`、`_` 和 `.`
+fn validate_session_id(session_id: &str) -> Result<(), TerminalError> {
+    if session_id.is_empty() || session_id.len() > MAX_SESSION_ID_LEN {
+        return Err(TerminalError::InvalidRequest(format!(
+            "会话 ID 长度必须在 1 到 {} 之间",
+            MAX_SESSION_ID_LEN
+        )));
+    }
+    if let Some(c) = session_id
+        .chars()
+        .find(|c| !(c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.')))
+    {
+        return Err(TerminalError::InvalidRequest(format!(
+            "会话 ID 包含无效字符 {:?}: {}",
+            c, session_id
+        )));
+    }
+    Ok(())
 }
 
 impl Default for PtyManager {
@@ -180,6 +865,7 @@ impl Default for PtyManager {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::rpc::types::ConnectionKind;
 
     #[tokio::test]
     async fn test_create_session() {
@@ -190,7 +876,7 @@ mod tests {
                 cwd: None,
                 env: None,
             },
-            term_size: TermSize::default(),
+            ..Default::default()
         };
 
         let result = manager.create_session(request).await;
@@ -220,7 +906,7 @@ mod tests {
                     cwd: None,
                     env: None,
                 },
-                term_size: TermSize::default(),
+                ..Default::default()
             };
             match manager.create_session(request).await {
                 Ok(id) => ids.push(id),
@@ -250,7 +936,7 @@ mod tests {
                 cwd: None,
                 env: None,
             },
-            term_size: TermSize::default(),
+            ..Default::default()
         };
 
         match manager.create_session(request).await {
@@ -265,6 +951,1740 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn test_set_title() {
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let mut manager = PtyManager::with_notification_sender(NotificationSender::new_for_test(tx));
+        let request = CreateSessionRequest {
+            connection: ConnectionType::Ssh {
+                host: "test.example.com".to_string(),
+                port: Some(22),
+                user: Some("test".to_string()),
+                identity_file: None,
+                password: None,
+                compression: false,
+                max_retries: 0,
+                retry_delay_ms: 1000,
+                channel_retries: 0,
+                notify_ready: false,
+                env: None,
+            },
+            ..Default::default()
+        };
+        let session_id = manager.create_session(request).await.unwrap();
+
+        manager.set_title(&session_id, "build".to_string()).await.unwrap();
+
+        let info = manager.get_session(&session_id).await.unwrap();
+        assert_eq!(info.title.as_deref(), Some("build"));
+
+        let notification = rx.try_recv().unwrap();
+        assert_eq!(notification.method, "session.title");
+        assert_eq!(notification.params.unwrap()["title"], "build");
+    }
+
+    #[tokio::test]
+    async fn test_create_session_timeout() {
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let mut manager = PtyManager::with_notification_sender(NotificationSender::new_for_test(tx));
+        let source = CreateSessionRequest {
+            session_id: Some("source".to_string()),
+            ..ssh_request("example.com", None)
+        };
+        manager.create_session(source).await.unwrap();
+
+        // 持有源会话信息的写锁，继承工作目录时读取信息会一直等待
+        let info = manager.get_session_ref("source").unwrap().info_ref();
+        let guard = info.write().await;
+
+        let request = CreateSessionRequest {
+            connection: ConnectionType::Local {
+                shell_path: Some("/bin/sh".to_string()),
+                cwd: None,
+                env: None,
+            },
+            inherit_cwd_from: Some("source".to_string()),
+            session_id: Some("slow".to_string()),
+            timeout_ms: Some(50),
+            ..Default::default()
+        };
+        let started = std::time::Instant::now();
+        let err = manager.create_session(request).await.unwrap_err();
+        assert!(matches!(err, TerminalError::ConnectionTimeout(_)), "{:?}", err);
+        assert_eq!(err.rpc_code(), -32022);
+        assert!(started.elapsed() < std::time::Duration::from_secs(5));
+        drop(guard);
+
+        // 超时的会话没有被创建，并发送了错误状态
+        assert!(manager.get_session("slow").await.is_none());
+        let notification = rx.try_recv().unwrap();
+        let params = notification.params.unwrap();
+        assert_eq!(params["session_id"], "slow");
+        assert_eq!(params["status"], "error");
+        assert_eq!(params["error"]["error_type"], "connection_timeout");
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_create_session_timeout_after_spawn_closes_session() {
+        // 进程环境中带有唯一标记，用来确认超时后没有遗留进程
+        let marker = format!("TEST_SPAWN_TIMEOUT={}", uuid::Uuid::new_v4());
+        let (key, value) = marker.split_once('=').unwrap();
+        let has_marker = |marker: &str| {
+            std::fs::read_dir("/proc")
+                .into_iter()
+                .flatten()
+                .flatten()
+                .any(|entry| {
+                    std::fs::read(entry.path().join("environ"))
+                        .map(|environ| environ.split(|&b| b == 0).any(|var| var == marker.as_bytes()))
+                        .unwrap_or(false)
+                })
+        };
+
+        // sleep 不读取输入，初始输入写满 PTY 后一直等待，超时发生在进程启动之后
+        let payload: String = (0..200_000).map(|i| format!("{:05}\n", i)).collect();
+        let mut manager = PtyManager::new();
+        let request = CreateSessionRequest {
+            connection: ConnectionType::Local {
+                shell_path: Some("/bin/sh".to_string()),
+                cwd: None,
+                env: Some(HashMap::from([(key.to_string(), value.to_string())])),
+            },
+            session_id: Some("slow".to_string()),
+            initial_input: Some(codec::encode_output(format!("exec sleep 30\n{}", payload).as_bytes())),
+            timeout_ms: Some(500),
+            ..Default::default()
+        };
+        let err = match manager.create_session(request).await {
+            Err(TerminalError::PtyCreationFailed(e)) => {
+                println!("PTY creation failed (may be expected in CI): {}", e);
+                return;
+            }
+            result => result.unwrap_err(),
+        };
+        assert!(matches!(err, TerminalError::ConnectionTimeout(_)), "{:?}", err);
+        assert!(manager.get_session("slow").await.is_none());
+
+        let mut remaining = true;
+        for _ in 0..100 {
+            remaining = has_marker(&marker);
+            if !remaining {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        }
+        assert!(!remaining, "超时后子进程仍在运行");
+    }
+
+    #[tokio::test]
+    async fn test_locale_env() {
+        let mut manager = PtyManager::new();
+        let request = CreateSessionRequest {
+            connection: ConnectionType::Local {
+                shell_path: Some("/bin/sh".to_string()),
+                cwd: None,
+                env: None,
+            },
+            locale: Some("zh_CN.UTF-8".to_string()),
+            ..Default::default()
+        };
+        let session_id = match manager.create_session(request).await {
+            Ok(id) => id,
+            Err(e) => {
+                println!("PTY creation failed (may be expected in CI): {}", e);
+                return;
+            }
+        };
+
+        let env = manager.get_env(&session_id).await.unwrap();
+        assert_eq!(env.get("LANG").map(String::as_str), Some("zh_CN.UTF-8"));
+        assert_eq!(env.get("LC_ALL").map(String::as_str), Some("zh_CN.UTF-8"));
+
+        manager.close_session(&session_id).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_force_color_env() {
+        let mut manager = PtyManager::new();
+        let mut env = HashMap::new();
+        env.insert("COLORTERM".to_string(), "24bit".to_string());
+        let request = CreateSessionRequest {
+            connection: ConnectionType::Local {
+                shell_path: Some("/bin/sh".to_string()),
+                cwd: None,
+                env: Some(env),
+            },
+            force_color: true,
+            ..Default::default()
+        };
+        let session_id = match manager.create_session(request).await {
+            Ok(id) => id,
+            Err(e) => {
+                println!("PTY creation failed (may be expected in CI): {}", e);
+                return;
+            }
+        };
+
+        let env = manager.get_env(&session_id).await.unwrap();
+        assert_eq!(env.get("FORCE_COLOR").map(String::as_str), Some("1"));
+        assert_eq!(env.get("CLICOLOR_FORCE").map(String::as_str), Some("1"));
+        // 显式给出的值优先
+        assert_eq!(env.get("COLORTERM").map(String::as_str), Some("24bit"));
+
+        manager.close_session(&session_id).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_sync_waits_for_queued_input() {
+        let mut manager = PtyManager::new();
+        let request = CreateSessionRequest {
+            connection: ConnectionType::Local {
+                shell_path: Some("/bin/sh".to_string()),
+                cwd: None,
+                env: None,
+            },
+            ..Default::default()
+        };
+        let session_id = match manager.create_session(request).await {
+            Ok(id) => id,
+            Err(e) => {
+                println!("PTY creation failed (may be expected in CI): {}", e);
+                return;
+            }
+        };
+        assert!(matches!(
+            manager.sync_session("missing").await,
+            Err(TerminalError::SessionNotFound(_))
+        ));
+
+        // 持有 PTY 锁使写入排队，同步排在写入之后，释放锁后应等写入完成才返回
+        let session = manager.get_session_ref(&session_id).unwrap();
+        let pty = session.local_pty().unwrap();
+        let guard = pty.lock().await;
+        let data = b"true\r";
+        let release = async {
+            tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+            drop(guard);
+        };
+        let (written, synced, ()) = tokio::join!(session.write(data), manager.sync_session(&session_id), release);
+        written.unwrap();
+        assert_eq!(synced.unwrap().bytes_in, data.len() as u64);
+
+        manager.close_session(&session_id).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_list_sessions_ordering() {
+        use crate::utils::time::FakeClock;
+
+        let clock = Arc::new(FakeClock::new(1_700_000_000));
+        let mut manager = PtyManager::new();
+        manager.set_clock(clock.clone());
+
+        // 每个会话晚一秒创建，ID 的字典序与创建顺序相反
+        let mut created = Vec::new();
+        for name in ["session-d", "session-c", "session-b"] {
+            let request = CreateSessionRequest {
+                session_id: Some(name.to_string()),
+                ..ssh_request("example.com", None)
+            };
+            created.push(manager.create_session(request).await.unwrap());
+            clock.advance(std::time::Duration::from_secs(1));
+        }
+        // 同一秒内创建的会话按 ID 排序
+        for name in ["session-z", "session-a"] {
+            let request = CreateSessionRequest {
+                session_id: Some(name.to_string()),
+                ..ssh_request("example.com", None)
+            };
+            manager.create_session(request).await.unwrap();
+        }
+        created.extend(["session-a".to_string(), "session-z".to_string()]);
+
+        for _ in 0..5 {
+            let ids: Vec<String> = manager.list_sessions().await.into_iter().map(|info| info.id).collect();
+            assert_eq!(ids, created);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_query_sessions() {
+        let mut manager = PtyManager::new();
+        let project = manager.create_session(ssh_request("example.com", None)).await.unwrap();
+        let nested = manager.create_session(ssh_request("example.com", None)).await.unwrap();
+        let other = manager.create_session(ssh_request("other.com", None)).await.unwrap();
+        let unknown_cwd = manager.create_session(ssh_request("other.com", None)).await.unwrap();
+
+        for (session_id, cwd) in [
+            (&project, "/home/user/project"),
+            (&nested, "/home/user/project/src"),
+            (&other, "/home/user/project-old"),
+        ] {
+            manager
+                .get_session_ref(session_id)
+                .unwrap()
+                .set_cwd(cwd.to_string())
+                .await;
+        }
+        manager
+            .get_session_ref(&other)
+            .unwrap()
+            .set_status(SessionStatus::Running)
+            .await;
+
+        let ids = |sessions: Vec<SessionInfo>| -> Vec<String> {
+            sessions.into_iter().map(|info| info.id).collect()
+        };
+        let sorted = |mut ids: Vec<String>| {
+            ids.sort();
+            ids
+        };
+
+        // 没有条件时返回所有会话
+        let all = manager.query_sessions(&QuerySessionsRequest::default()).await;
+        assert_eq!(ids(all), sorted(vec![project.clone(), nested.clone(), other.clone(), unknown_cwd.clone()]));
+
+        // 工作目录精确匹配，忽略末尾的 `/`
+        let query = QuerySessionsRequest {
+            cwd: Some("/home/user/project/".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(ids(manager.query_sessions(&query).await), vec![project.clone()]);
+
+        // 前缀匹配包含子目录，但不包含同名前缀的其他目录
+        let query = QuerySessionsRequest {
+            cwd: Some("/home/user/project".to_string()),
+            cwd_prefix: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            ids(manager.query_sessions(&query).await),
+            sorted(vec![project.clone(), nested.clone()])
+        );
+
+        let query = QuerySessionsRequest {
+            status: Some(SessionStatus::Connecting),
+            host: Some("other.com".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(ids(manager.query_sessions(&query).await), vec![unknown_cwd.clone()]);
+
+        let query = QuerySessionsRequest {
+            cwd: Some("/home/user".to_string()),
+            cwd_prefix: true,
+            status: Some(SessionStatus::Running),
+            connection_type: Some(ConnectionKind::Ssh),
+            ..Default::default()
+        };
+        assert_eq!(ids(manager.query_sessions(&query).await), vec![other.clone()]);
+
+        let query = QuerySessionsRequest {
+            connection_type: Some(ConnectionKind::Local),
+            ..Default::default()
+        };
+        assert!(manager.query_sessions(&query).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_rename_session() {
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let mut manager = PtyManager::with_notification_sender(NotificationSender::new_for_test(tx));
+        let session_id = manager
+            .create_session(ssh_request("test.example.com", None))
+            .await
+            .unwrap();
+        manager.set_title(&session_id, "top".to_string()).await.unwrap();
+        let _ = rx.try_recv();
+
+        manager
+            .rename_session(&session_id, Some("prod db".to_string()))
+            .await
+            .unwrap();
+        let info = manager.get_session(&session_id).await.unwrap();
+        assert_eq!(info.name.as_deref(), Some("prod db"));
+        assert_eq!(info.title.as_deref(), Some("top"));
+
+        let notification = rx.try_recv().unwrap();
+        assert_eq!(notification.method, "session.renamed");
+        let params = notification.params.unwrap();
+        assert_eq!(params["session_id"], session_id);
+        assert_eq!(params["name"], "prod db");
+
+        // 名称不变时不发送通知
+        manager
+            .rename_session(&session_id, Some("prod db".to_string()))
+            .await
+            .unwrap();
+        assert!(rx.try_recv().is_err());
+
+        // 空字符串清除名称
+        manager.rename_session(&session_id, Some(String::new())).await.unwrap();
+        assert!(manager.get_session(&session_id).await.unwrap().name.is_none());
+        assert!(rx.try_recv().unwrap().params.unwrap()["name"].is_null());
+
+        assert!(matches!(
+            manager.rename_session("missing", None).await,
+            Err(TerminalError::SessionNotFound(_))
+        ));
+    }
+
+    fn ssh_request(host: &str, port: Option<u16>) -> CreateSessionRequest {
+        CreateSessionRequest {
+            connection: ConnectionType::Ssh {
+                host: host.to_string(),
+                port,
+                user: Some("test".to_string()),
+                identity_file: None,
+                password: None,
+                compression: false,
+                max_retries: 0,
+                retry_delay_ms: 1000,
+                channel_retries: 0,
+                notify_ready: false,
+                env: None,
+            },
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_close_where_by_host() {
+        let mut manager = PtyManager::new();
+        let mut stale = vec![
+            manager.create_session(ssh_request("example.com", None)).await.unwrap(),
+            manager.create_session(ssh_request("example.com", Some(22))).await.unwrap(),
+        ];
+        stale.sort();
+        let other_port = manager.create_session(ssh_request("example.com", Some(2222))).await.unwrap();
+        let other_host = manager.create_session(ssh_request("other.com", None)).await.unwrap();
+
+        let filter = CloseWhereRequest {
+            connection_type: Some(ConnectionKind::Ssh),
+            host: Some("example.com".to_string()),
+            port: Some(22),
+            ..Default::default()
+        };
+        let response = manager.close_where(&filter).await.unwrap();
+        assert_eq!(response.closed, stale);
+        assert!(response.errors.is_empty());
+        assert_eq!(manager.session_count(), 2);
+        assert!(manager.get_session_ref(&other_port).is_some());
+        assert!(manager.get_session_ref(&other_host).is_some());
+
+        // 只按主机过滤时不限制端口
+        let filter = CloseWhereRequest {
+            host: Some("example.com".to_string()),
+            ..Default::default()
+        };
+        let response = manager.close_where(&filter).await.unwrap();
+        assert_eq!(response.closed, vec![other_port]);
+        assert_eq!(manager.session_count(), 1);
+
+        // 没有条件时不关闭任何会话
+        assert!(manager.close_where(&CloseWhereRequest::default()).await.is_err());
+        assert_eq!(manager.session_count(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_create_failure_sends_error_status() {
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let mut manager = PtyManager::with_notification_sender(NotificationSender::new_for_test(tx));
+        let request = CreateSessionRequest {
+            connection: ConnectionType::Local {
+                shell_path: Some("/nonexistent/shell".to_string()),
+                cwd: None,
+                env: None,
+            },
+            ..Default::default()
+        };
+
+        let err = manager
+            .create_session_with_id("failed-session".to_string(), request)
+            .await
+            .unwrap_err();
+        assert_eq!(manager.session_count(), 0);
+
+        let notification = rx.try_recv().unwrap();
+        assert_eq!(notification.method, "session.status");
+        let params = notification.params.unwrap();
+        assert_eq!(params["session_id"], "failed-session");
+        assert_eq!(params["status"], "error");
+        assert_eq!(params["error"]["message"], err.to_string());
+        assert_eq!(params["error"]["error_type"], err.error_type());
+    }
+
+    #[tokio::test]
+    async fn test_input_after_kill_returns_session_closed() {
+        let mut manager = PtyManager::new();
+        let request = CreateSessionRequest {
+            connection: ConnectionType::Local {
+                shell_path: None,
+                cwd: None,
+                env: None,
+            },
+            ..Default::default()
+        };
+
+        let session_id = match manager.create_session(request).await {
+            Ok(id) => id,
+            Err(e) => {
+                println!("PTY creation failed (may be expected in CI): {}", e);
+                return;
+            }
+        };
+
+        let session = manager.get_session_ref(&session_id).unwrap();
+        session.kill().await.unwrap();
+        for _ in 0..50 {
+            if matches!(session.try_wait().await, Ok(Some(_))) {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+
+        let data = codec::encode_output(b"echo hi\n");
+        let err = manager.send_input(&session_id, &data).await.unwrap_err();
+        assert!(matches!(err, TerminalError::SessionClosed(_)), "unexpected error: {:?}", err);
+        assert_eq!(crate::rpc::types::JsonRpcError::from(err).code, -32002);
+    }
+
+    #[tokio::test]
+    async fn test_resize_after_exit_returns_session_closed() {
+        let mut manager = PtyManager::new();
+        let request = CreateSessionRequest {
+            connection: ConnectionType::Local {
+                shell_path: None,
+                cwd: None,
+                env: None,
+            },
+            ..Default::default()
+        };
+
+        let session_id = match manager.create_session(request).await {
+            Ok(id) => id,
+            Err(e) => {
+                println!("PTY creation failed (may be expected in CI): {}", e);
+                return;
+            }
+        };
+
+        let session = manager.get_session_ref(&session_id).unwrap();
+        session.kill().await.unwrap();
+        for _ in 0..50 {
+            if matches!(session.try_wait().await, Ok(Some(_))) {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+
+        let err = manager
+            .resize_session(&session_id, TermSize {
+                rows: 40,
+                cols: 120,
+                ..Default::default()
+            })
+            .await
+            .unwrap_err();
+        assert!(matches!(err, TerminalError::SessionClosed(_)), "unexpected error: {:?}", err);
+        assert_eq!(crate::rpc::types::JsonRpcError::from(err).code, -32002);
+    }
+
+    #[tokio::test]
+    async fn test_set_title_nonexistent_session() {
+        let mut manager = PtyManager::new();
+        let result = manager.set_title("nonexistent", "title".to_string()).await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_pause_resume_session() {
+        let (tx, _rx) = tokio::sync::mpsc::unbounded_channel();
+        let mut manager = PtyManager::with_notification_sender(NotificationSender::new_for_test(tx));
+        let request = CreateSessionRequest {
+            connection: ConnectionType::Local {
+                shell_path: None,
+                cwd: None,
+                env: None,
+            },
+            ..Default::default()
+        };
+
+        match manager.create_session(request).await {
+            Ok(session_id) => {
+                manager.pause_session(&session_id).await.unwrap();
+                assert!(manager.get_session_ref(&session_id).unwrap().is_output_paused());
+
+                manager.resume_session(&session_id).await.unwrap();
+                assert!(!manager.get_session_ref(&session_id).unwrap().is_output_paused());
+
+                // 暂停状态下关闭会话不应阻塞
+                manager.pause_session(&session_id).await.unwrap();
+                let _ = manager.close_session(&session_id).await;
+            }
+            Err(e) => {
+                println!("PTY creation failed (may be expected in CI): {}", e);
+            }
+        }
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_detach_attach_preserves_child() {
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let mut manager = PtyManager::with_notification_sender(NotificationSender::new_for_test(tx));
+        let request = CreateSessionRequest {
+            connection: ConnectionType::Local {
+                shell_path: Some("/bin/sh".to_string()),
+                cwd: None,
+                env: None,
+            },
+            ..Default::default()
+        };
+
+        let session_id = match manager.create_session(request).await {
+            Ok(id) => id,
+            Err(e) => {
+                println!("PTY creation failed (may be expected in CI): {}", e);
+                return;
+            }
+        };
+
+        // 已附加时不能重复附加
+        assert!(manager.attach_session(&session_id).await.is_err());
+
+        manager.detach_session(&session_id).await.unwrap();
+        assert!(manager.get_session_ref(&session_id).unwrap().is_output_reader_finished());
+        assert!(manager.detach_session(&session_id).await.is_err());
+        while rx.try_recv().is_ok() {}
+
+        // 分离期间的输出留在 PTY 中
+        let input = codec::encode_output(b"echo $((2222*3))\n");
+        manager.send_input(&session_id, &input).await.unwrap();
+        tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+        assert!(rx.try_recv().is_err(), "Should not receive output while detached");
+        let session = manager.get_session_ref(&session_id).unwrap();
+        assert!(matches!(session.try_wait().await, Ok(None)));
+
+        manager.attach_session(&session_id).await.unwrap();
+
+        let mut output = Vec::new();
+        for _ in 0..100 {
+            while let Ok(notification) = rx.try_recv() {
+                if notification.method == "terminal.output" {
+                    output.extend(
+                        codec::decode_input(notification.params.unwrap()["data"].as_str().unwrap()).unwrap(),
+                    );
+                }
+            }
+            if String::from_utf8_lossy(&output).contains("6666") {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+        assert!(String::from_utf8_lossy(&output).contains("6666"));
+
+        let session = manager.get_session_ref(&session_id).unwrap();
+        assert!(matches!(session.try_wait().await, Ok(None)));
+
+        let _ = manager.close_session(&session_id).await;
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_eof_ends_waiting_command() {
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let mut manager = PtyManager::with_notification_sender(NotificationSender::new_for_test(tx));
+        let request = CreateSessionRequest {
+            connection: ConnectionType::Local {
+                shell_path: Some("/bin/sh".to_string()),
+                cwd: None,
+                env: None,
+            },
+            ..Default::default()
+        };
+
+        let session_id = match manager.create_session(request).await {
+            Ok(id) => id,
+            Err(e) => {
+                println!("PTY creation failed (may be expected in CI): {}", e);
+                return;
+            }
+        };
+
+        // cat 等待输入，读到 EOF 后才会结束
+        let input = codec::encode_output(b"cat; echo cat-$((6*7))-done\n");
+        manager.send_input(&session_id, &input).await.unwrap();
+        tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+        manager.eof_session(&session_id).await.unwrap();
+
+        let mut output = Vec::new();
+        for _ in 0..100 {
+            while let Ok(notification) = rx.try_recv() {
+                if notification.method == "terminal.output" {
+                    output.extend(
+                        codec::decode_input(notification.params.unwrap()["data"].as_str().unwrap()).unwrap(),
+                    );
+                }
+            }
+            if String::from_utf8_lossy(&output).contains("cat-42-done") {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+        assert!(String::from_utf8_lossy(&output).contains("cat-42-done"));
+
+        // EOF 之后不能再输入
+        let result = manager.send_input(&session_id, &input).await;
+        assert!(matches!(result, Err(TerminalError::SessionClosed(_))), "{:?}", result);
+
+        let _ = manager.close_session(&session_id).await;
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_write_raw() {
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let mut manager = PtyManager::with_notification_sender(NotificationSender::new_for_test(tx));
+        let request = CreateSessionRequest {
+            connection: ConnectionType::Local {
+                shell_path: Some("/bin/sh".to_string()),
+                cwd: None,
+                env: None,
+            },
+            ..Default::default()
+        };
+
+        let session_id = match manager.create_session(request).await {
+            Ok(id) => id,
+            Err(e) => {
+                println!("PTY creation failed (may be expected in CI): {}", e);
+                return;
+            }
+        };
+        let raw = |key: Option<&str>, hex: Option<&str>| WriteRawRequest {
+            session_id: session_id.clone(),
+            key: key.map(str::to_string),
+            hex: hex.map(str::to_string),
+        };
+
+        // "echo $((40+2))\r"，只有 shell 执行后才会出现 42
+        manager
+            .write_raw(&raw(None, Some("6563686f2024282834302b3229290d")))
+            .await
+            .unwrap();
+        // cat -v 把收到的方向键显示为 ^[[A
+        manager.write_raw(&raw(None, Some("636174202d760d"))).await.unwrap();
+        tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+        manager.write_raw(&raw(Some("ArrowUp"), None)).await.unwrap();
+        manager.write_raw(&raw(Some("Enter"), None)).await.unwrap();
+
+        let mut output = String::new();
+        for _ in 0..100 {
+            while let Ok(notification) = rx.try_recv() {
+                if notification.method == "terminal.output" {
+                    let data = codec::decode_input(notification.params.unwrap()["data"].as_str().unwrap()).unwrap();
+                    output.push_str(&String::from_utf8_lossy(&data));
+                }
+            }
+            if output.contains("42") && output.contains("^[[A") {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+        assert!(output.contains("42"), "output: {:?}", output);
+        assert!(output.contains("^[[A"), "output: {:?}", output);
+
+        for request in [raw(Some("NoSuchKey"), None), raw(None, None), raw(Some("Tab"), Some("09"))] {
+            assert!(matches!(
+                manager.write_raw(&request).await,
+                Err(TerminalError::InvalidRequest(_))
+            ));
+        }
+
+        let _ = manager.close_session(&session_id).await;
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_inject_output() {
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let mut manager = PtyManager::with_notification_sender(NotificationSender::new_for_test(tx));
+        let request = |allow_inject| CreateSessionRequest {
+            connection: ConnectionType::Local {
+                shell_path: Some("/bin/sh".to_string()),
+                cwd: None,
+                env: None,
+            },
+            allow_inject,
+            ..Default::default()
+        };
+
+        let session_id = match manager.create_session(request(true)).await {
+            Ok(id) => id,
+            Err(e) => {
+                println!("PTY creation failed (may be expected in CI): {}", e);
+                return;
+            }
+        };
+
+        // 注入的内容如果被写入 shell 的输入，会被执行并输出两次
+        let input = codec::encode_output(b"echo injected-marker\r\n");
+        manager.inject_output(&session_id, &input).unwrap();
+
+        let mut output = Vec::new();
+        for _ in 0..20 {
+            while let Ok(notification) = rx.try_recv() {
+                if notification.method == "terminal.output" {
+                    output.extend(
+                        codec::decode_input(notification.params.unwrap()["data"].as_str().unwrap())
+                            .unwrap(),
+                    );
+                }
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+        let text = String::from_utf8_lossy(&output);
+        assert_eq!(text.matches("injected-marker").count(), 1, "output: {:?}", text);
+        assert!(text.contains("echo injected-marker\r\n"));
+
+        // 未允许注入的会话拒绝注入
+        let other_id = manager.create_session(request(false)).await.unwrap();
+        assert!(matches!(
+            manager.inject_output(&other_id, &input),
+            Err(TerminalError::InvalidRequest(_))
+        ));
+
+        let _ = manager.close_session(&session_id).await;
+        let _ = manager.close_session(&other_id).await;
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_large_write_delivered_in_order() {
+        let mut manager = PtyManager::new();
+        let request = CreateSessionRequest {
+            connection: ConnectionType::Local {
+                shell_path: Some("/bin/sh".to_string()),
+                cwd: None,
+                env: None,
+            },
+            // 小于下限，被限制为 512
+            write_chunk_size: Some(100),
+            ..Default::default()
+        };
+        let session_id = match manager.create_session(request).await {
+            Ok(id) => id,
+            Err(e) => {
+                println!("PTY creation failed (may be expected in CI): {}", e);
+                return;
+            }
+        };
+        let session = manager.get_session_ref(&session_id).unwrap();
+        assert_eq!(session.write_chunk_size(), 512);
+
+        let path = std::env::temp_dir().join(format!("pty-write-chunks-{}", uuid::Uuid::new_v4()));
+        session
+            .write(format!("stty -echo; cat > '{}'\n", path.display()).as_bytes())
+            .await
+            .unwrap();
+        tokio::time::sleep(std::time::Duration::from_millis(300)).await;
+
+        // 每行较短，规范模式的行长度限制不影响结果；最后用 Ctrl+D 结束 cat
+        let payload: String = (0..20_000).map(|i| format!("{:05}\n", i)).collect();
+        session.write(payload.as_bytes()).await.unwrap();
+        session.write(b"\x04").await.unwrap();
+
+        let mut written = String::new();
+        for _ in 0..250 {
+            written = std::fs::read_to_string(&path).unwrap_or_default();
+            if written.len() >= payload.len() {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(written, payload);
+
+        manager.close_session(&session_id).await.unwrap();
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_blocked_write_does_not_block_resize_or_kill() {
+        let mut manager = PtyManager::new();
+        let request = CreateSessionRequest {
+            connection: ConnectionType::Local {
+                shell_path: Some("/bin/sh".to_string()),
+                cwd: None,
+                env: None,
+            },
+            ..Default::default()
+        };
+        let session_id = match manager.create_session(request).await {
+            Ok(id) => id,
+            Err(e) => {
+                println!("PTY creation failed (may be expected in CI): {}", e);
+                return;
+            }
+        };
+        let session = manager.get_session_ref(&session_id).unwrap();
+        session.write(b"exec sleep 30\n").await.unwrap();
+        tokio::time::sleep(std::time::Duration::from_millis(300)).await;
+
+        // sleep 不读取输入，PTY 缓冲区写满后写入一直阻塞
+        let payload: String = (0..200_000).map(|i| format!("{:05}\n", i)).collect();
+        let timeout = std::time::Duration::from_secs(5);
+        let (written, ()) = tokio::join!(session.write(payload.as_bytes()), async {
+            tokio::time::sleep(std::time::Duration::from_millis(300)).await;
+            let size = TermSize {
+                rows: 30,
+                cols: 100,
+                ..Default::default()
+            };
+            tokio::time::timeout(timeout, session.resize(size))
+                .await
+                .expect("resize blocked by pending write")
+                .unwrap();
+            tokio::time::timeout(timeout, session.kill())
+                .await
+                .expect("kill blocked by pending write")
+                .unwrap();
+        });
+        assert!(written.is_err());
+
+        manager.close_session(&session_id).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_custom_read_buffer_size() {
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let mut manager = PtyManager::with_notification_sender(NotificationSender::new_for_test(tx));
+        let request = CreateSessionRequest {
+            connection: ConnectionType::Local {
+                shell_path: Some("/bin/sh".to_string()),
+                cwd: None,
+                env: None,
+            },
+            // 小于下限，被限制为 512
+            read_buffer_size: Some(100),
+            ..Default::default()
+        };
+
+        let session_id = match manager.create_session(request).await {
+            Ok(id) => id,
+            Err(e) => {
+                println!("PTY creation failed (may be expected in CI): {}", e);
+                return;
+            }
+        };
+        let session = manager.get_session_ref(&session_id).unwrap();
+        assert_eq!(session.output_config().buffer_size, 512);
+
+        // 大量输出时每次读取不超过缓冲区大小
+        let input = codec::encode_output(b"printf '%4000s' | tr ' ' x; echo done\n");
+        manager.send_input(&session_id, &input).await.unwrap();
+
+        let mut output = Vec::new();
+        let mut largest = 0;
+        for _ in 0..100 {
+            while let Ok(notification) = rx.try_recv() {
+                if notification.method == "terminal.output" {
+                    let data = codec::decode_input(notification.params.unwrap()["data"].as_str().unwrap()).unwrap();
+                    largest = largest.max(data.len());
+                    output.extend(data);
+                }
+            }
+            if String::from_utf8_lossy(&output).contains("xdone") {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+
+        assert!(String::from_utf8_lossy(&output).contains("xdone"));
+        assert!(largest <= 512, "notification of {} bytes exceeds buffer", largest);
+
+        let _ = manager.close_session(&session_id).await;
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_read_only_session() {
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let mut manager = PtyManager::with_notification_sender(NotificationSender::new_for_test(tx));
+        let request = CreateSessionRequest {
+            connection: ConnectionType::LocalProcess {
+                command: "cat".to_string(),
+                cwd: None,
+                env: None,
+            },
+            read_only: true,
+            ..Default::default()
+        };
+        let session_id = manager.create_session(request).await.unwrap();
+        let input = codec::encode_output(b"hello\n");
+
+        assert!(matches!(
+            manager.send_input(&session_id, &input).await,
+            Err(TerminalError::InvalidRequest(_))
+        ));
+        assert!(matches!(
+            manager.resize_session(&session_id, TermSize {
+                rows: 40,
+                cols: 120,
+                ..Default::default()
+            }).await,
+            Err(TerminalError::InvalidRequest(_))
+        ));
+        assert!(matches!(
+            manager.eof_session(&session_id).await,
+            Err(TerminalError::InvalidRequest(_))
+        ));
+
+        // 清除只读后输入正常写入
+        manager.set_read_only(&session_id, false).unwrap();
+        manager.send_input(&session_id, &input).await.unwrap();
+
+        let mut output = Vec::new();
+        while !output.ends_with(b"hello\n") {
+            let notification = tokio::time::timeout(std::time::Duration::from_secs(5), rx.recv())
+                .await
+                .expect("timed out waiting for output")
+                .unwrap();
+            if notification.method == "terminal.output" {
+                let params = notification.params.unwrap();
+                output.extend(codec::decode_input(params["data"].as_str().unwrap()).unwrap());
+            }
+        }
+
+        assert!(matches!(
+            manager.set_read_only("missing", true),
+            Err(TerminalError::SessionNotFound(_))
+        ));
+        manager.close_session(&session_id).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_local_process_session() {
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let mut manager = PtyManager::with_notification_sender(NotificationSender::new_for_test(tx));
+        let request = CreateSessionRequest {
+            connection: ConnectionType::LocalProcess {
+                command: "echo hi".to_string(),
+                cwd: None,
+                env: None,
+            },
+            ..Default::default()
+        };
+        let session_id = manager.create_session(request).await.unwrap();
+
+        // 没有终端尺寸，调整大小直接成功
+        manager
+            .resize_session(&session_id, TermSize {
+                rows: 40,
+                cols: 120,
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        let mut output = Vec::new();
+        let status = loop {
+            let notification = tokio::time::timeout(std::time::Duration::from_secs(5), rx.recv())
+                .await
+                .expect("timed out waiting for notification")
+                .unwrap();
+            let params = notification.params.unwrap();
+            match notification.method.as_str() {
+                "terminal.output" => {
+                    output.extend(codec::decode_input(params["data"].as_str().unwrap()).unwrap())
+                }
+                "session.status" => break params,
+                _ => {}
+            }
+        };
+        assert_eq!(String::from_utf8_lossy(&output).trim_end(), "hi");
+        assert_eq!(status["status"], "done");
+        assert_eq!(status["exit_code"], 0);
+
+        let info = manager.get_session(&session_id).await.unwrap();
+        assert_eq!(info.status, SessionStatus::Done);
+        assert_eq!(info.exit_code, Some(0));
+        assert_eq!(manager.stats().bytes_out, output.len() as u64);
+
+        manager.close_session(&session_id).await.unwrap();
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_idle_timeout_closes_session() {
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let mut manager = PtyManager::with_notification_sender(NotificationSender::new_for_test(tx));
+        let request = CreateSessionRequest {
+            connection: ConnectionType::Local {
+                shell_path: Some("/bin/sh".to_string()),
+                cwd: None,
+                env: None,
+            },
+            idle_timeout_secs: Some(1),
+            ..Default::default()
+        };
+
+        let started = std::time::Instant::now();
+        let session_id = match manager.create_session(request).await {
+            Ok(id) => id,
+            Err(e) => {
+                println!("PTY creation failed (may be expected in CI): {}", e);
+                return;
+            }
+        };
+
+        // 输入重置计时，之后的输出不重置
+        tokio::time::sleep(std::time::Duration::from_millis(700)).await;
+        let input = codec::encode_output(b"echo still-here\n");
+        manager.send_input(&session_id, &input).await.unwrap();
+
+        let status = loop {
+            let notification = tokio::time::timeout(std::time::Duration::from_secs(10), rx.recv())
+                .await
+                .expect("timed out waiting for idle timeout")
+                .unwrap();
+            if notification.method == "session.status" {
+                break notification.params.unwrap();
+            }
+        };
+        assert!(started.elapsed() >= std::time::Duration::from_millis(1400));
+        assert_eq!(status["status"], "done");
+        assert_eq!(status["reason"], "idle_timeout");
+
+        let info = manager.get_session(&session_id).await.unwrap();
+        assert_eq!(info.exit_reason.as_deref(), Some("idle_timeout"));
+
+        let _ = manager.close_session(&session_id).await;
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_idle_timeout_with_fake_clock() {
+        use crate::utils::time::{Clock, FakeClock};
+
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let mut manager = PtyManager::with_notification_sender(NotificationSender::new_for_test(tx));
+        let clock = Arc::new(FakeClock::new(1_700_000_000));
+        manager.set_clock(clock.clone());
+        let request = CreateSessionRequest {
+            connection: ConnectionType::Local {
+                shell_path: Some("/bin/sh".to_string()),
+                cwd: None,
+                env: None,
+            },
+            idle_timeout_secs: Some(3600),
+            ..Default::default()
+        };
+
+        let session_id = match manager.create_session(request).await {
+            Ok(id) => id,
+            Err(e) => {
+                println!("PTY creation failed (may be expected in CI): {}", e);
+                return;
+            }
+        };
+        let info = manager.get_session(&session_id).await.unwrap();
+        assert_eq!(info.created_at, clock.unix_secs());
+
+        // 没有输入，直接越过一小时的超时，无需真的等待
+        clock.advance(std::time::Duration::from_secs(3601));
+
+        let status = loop {
+            let notification = tokio::time::timeout(std::time::Duration::from_secs(5), rx.recv())
+                .await
+                .expect("timed out waiting for idle timeout")
+                .unwrap();
+            if notification.method == "session.status" {
+                break notification.params.unwrap();
+            }
+        };
+        assert_eq!(status["status"], "done");
+        assert_eq!(status["reason"], "idle_timeout");
+
+        let _ = manager.close_session(&session_id).await;
+    }
+
+    #[tokio::test]
+    async fn test_rate_limit_option() {
+        let mut manager = PtyManager::new();
+        for (rate, expected) in [(Some(2048), Some(2048)), (Some(0), None), (None, None)] {
+            let request = CreateSessionRequest {
+                connection: ConnectionType::Local {
+                    shell_path: None,
+                    cwd: None,
+                    env: None,
+                },
+                rate_limit_bytes_per_sec: rate,
+                ..Default::default()
+            };
+
+            let session_id = match manager.create_session(request).await {
+                Ok(id) => id,
+                Err(e) => {
+                    println!("PTY creation failed (may be expected in CI): {}", e);
+                    return;
+                }
+            };
+            let session = manager.get_session_ref(&session_id).unwrap();
+            assert_eq!(session.output_config().rate_limit_bytes_per_sec, expected);
+            let _ = manager.close_session(&session_id).await;
+        }
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_stats_count_bytes_and_sessions() {
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let mut manager = PtyManager::with_notification_sender(NotificationSender::new_for_test(tx));
+        let request = CreateSessionRequest {
+            connection: ConnectionType::Local {
+                shell_path: Some("/bin/sh".to_string()),
+                cwd: None,
+                env: None,
+            },
+            ..Default::default()
+        };
+
+        let session_id = match manager.create_session(request).await {
+            Ok(id) => id,
+            Err(e) => {
+                println!("PTY creation failed (may be expected in CI): {}", e);
+                return;
+            }
+        };
+
+        let command = "echo $((1111*3))\n";
+        let input = codec::encode_output(command.as_bytes());
+        manager.send_input(&session_id, &input).await.unwrap();
+
+        let mut received = 0;
+        let mut output = Vec::new();
+        for _ in 0..100 {
+            while let Ok(notification) = rx.try_recv() {
+                if notification.method == "terminal.output" {
+                    let data = codec::decode_input(notification.params.unwrap()["data"].as_str().unwrap()).unwrap();
+                    received += data.len() as u64;
+                    output.extend(data);
+                }
+            }
+            if String::from_utf8_lossy(&output).contains("3333") {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+
+        let stats = manager.stats();
+        assert_eq!(stats.sessions_created, 1);
+        assert_eq!(stats.sessions_open, 1);
+        assert_eq!(stats.bytes_in, command.len() as u64);
+        assert!(stats.bytes_out >= received);
+        assert_eq!(stats.sessions.len(), 1);
+        assert_eq!(stats.sessions[0].session_id, session_id);
+        assert_eq!(stats.sessions[0].bytes_in, stats.bytes_in);
+
+        // 关闭后总数保留
+        let _ = manager.close_session(&session_id).await;
+        let closed = manager.stats();
+        assert_eq!(closed.sessions_created, 1);
+        assert_eq!(closed.sessions_open, 0);
+        assert!(closed.sessions.is_empty());
+        assert_eq!(closed.bytes_in, stats.bytes_in);
+        assert!(closed.bytes_out >= stats.bytes_out);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_osc7_updates_session_cwd() {
+        let (tx, _rx) = tokio::sync::mpsc::unbounded_channel();
+        let mut manager = PtyManager::with_notification_sender(NotificationSender::new_for_test(tx));
+        let request = CreateSessionRequest {
+            connection: ConnectionType::Local {
+                shell_path: Some("/bin/sh".to_string()),
+                cwd: None,
+                env: None,
+            },
+            ..Default::default()
+        };
+
+        let session_id = match manager.create_session(request).await {
+            Ok(id) => id,
+            Err(e) => {
+                println!("PTY creation failed (may be expected in CI): {}", e);
+                return;
+            }
+        };
+
+        let input = codec::encode_output(b"printf '\\033]7;file://localhost/tmp/osc7-test\\007'\n");
+        manager.send_input(&session_id, &input).await.unwrap();
+
+        let mut cwd = None;
+        for _ in 0..100 {
+            cwd = manager.get_session(&session_id).await.unwrap().cwd;
+            if cwd.is_some() {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+        assert_eq!(cwd.as_deref(), Some("/tmp/osc7-test"));
+
+        let _ = manager.close_session(&session_id).await;
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_close_delivers_final_output() {
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let mut manager = PtyManager::with_notification_sender(NotificationSender::new_for_test(tx));
+        let request = CreateSessionRequest {
+            connection: ConnectionType::Local {
+                shell_path: Some("/bin/sh".to_string()),
+                cwd: None,
+                env: None,
+            },
+            ..Default::default()
+        };
+
+        let session_id = match manager.create_session(request).await {
+            Ok(id) => id,
+            Err(e) => {
+                println!("PTY creation failed (may be expected in CI): {}", e);
+                return;
+            }
+        };
+
+        // 暂停输出，让命令的输出留在 PTY 中，直到关闭会话时才被读取
+        manager.pause_session(&session_id).await.unwrap();
+        let input = codec::encode_output(b"echo $((1111*9)); exit\n");
+        manager.send_input(&session_id, &input).await.unwrap();
+
+        let session = manager.get_session_ref(&session_id).unwrap();
+        for _ in 0..100 {
+            if matches!(session.try_wait().await, Ok(Some(_))) {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+
+        manager.close_session(&session_id).await.unwrap();
+
+        let mut output = Vec::new();
+        let mut statuses = Vec::new();
+        while let Ok(notification) = rx.try_recv() {
+            let params = notification.params.unwrap();
+            match notification.method.as_str() {
+                "terminal.output" => output.extend(
+                    codec::decode_input(params["data"].as_str().unwrap()).unwrap(),
+                ),
+                "session.status" => statuses.push(params["status"].clone()),
+                _ => {}
+            }
+        }
+
+        assert!(String::from_utf8_lossy(&output).contains("9999"));
+        // 关闭时读到 EOF 不应再报告错误状态
+        assert!(!statuses.contains(&serde_json::json!("error")));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_inherit_cwd_from_session() {
+        let (tx, _rx) = tokio::sync::mpsc::unbounded_channel();
+        let mut manager = PtyManager::with_notification_sender(NotificationSender::new_for_test(tx));
+        let request = CreateSessionRequest {
+            connection: ConnectionType::Local {
+                shell_path: Some("/bin/sh".to_string()),
+                cwd: None,
+                env: None,
+            },
+            ..Default::default()
+        };
+
+        let parent_id = match manager.create_session(request).await {
+            Ok(id) => id,
+            Err(e) => {
+                println!("PTY creation failed (may be expected in CI): {}", e);
+                return;
+            }
+        };
+
+        // 通过 OSC 7 设置父会话的工作目录
+        let target = std::env::temp_dir().canonicalize().unwrap();
+        let target = target.to_string_lossy().into_owned();
+        let input = codec::encode_output(format!("printf '\\033]7;file://localhost{}\\007'\n", target).as_bytes());
+        manager.send_input(&parent_id, &input).await.unwrap();
+
+        for _ in 0..100 {
+            if manager.get_session(&parent_id).await.unwrap().cwd.is_some() {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+        assert_eq!(
+            manager.get_session(&parent_id).await.unwrap().cwd.as_deref(),
+            Some(target.as_str())
+        );
+
+        let request = CreateSessionRequest {
+            connection: ConnectionType::Local {
+                shell_path: Some("/bin/sh".to_string()),
+                cwd: None,
+                env: None,
+            },
+            inherit_cwd_from: Some(parent_id.clone()),
+            ..Default::default()
+        };
+        let child_id = manager.create_session(request).await.unwrap();
+
+        let info = manager.get_session(&child_id).await.unwrap();
+        match info.connection_type {
+            ConnectionType::Local { cwd, .. } => assert_eq!(cwd.as_deref(), Some(target.as_str())),
+            _ => panic!("Expected local connection type"),
+        }
+
+        // 子会话的 shell 实际在继承的目录中启动
+        #[cfg(target_os = "linux")]
+        {
+            let pid = manager.get_session_ref(&child_id).unwrap().process_id().await.unwrap();
+            assert_eq!(super::super::cwd::read_process_cwd(pid).as_deref(), Some(target.as_str()));
+        }
+
+        let _ = manager.close_session(&child_id).await;
+        let _ = manager.close_session(&parent_id).await;
+    }
+
+    #[tokio::test]
+    async fn test_session_info_records_resolved_shell() {
+        let mut manager = PtyManager::new();
+        let request = CreateSessionRequest {
+            connection: ConnectionType::Local {
+                shell_path: None,
+                cwd: None,
+                env: None,
+            },
+            ..Default::default()
+        };
+
+        let session_id = match manager.create_session(request).await {
+            Ok(id) => id,
+            Err(e) => {
+                println!("PTY creation failed (may be expected in CI): {}", e);
+                return;
+            }
+        };
+
+        let info = manager.get_session(&session_id).await.unwrap();
+        match info.connection_type {
+            ConnectionType::Local { shell_path, env, .. } => {
+                let shell_path = shell_path.expect("resolved shell path should be recorded");
+                assert!(std::path::Path::new(&shell_path).is_absolute());
+                assert_eq!(
+                    env.unwrap().get("TERM").map(String::as_str),
+                    Some("xterm-256color")
+                );
+            }
+            _ => panic!("Expected local connection type"),
+        }
+
+        let _ = manager.close_session(&session_id).await;
+    }
+
+    #[tokio::test]
+    async fn test_session_info_reports_pid() {
+        let mut manager = PtyManager::new();
+        let request = CreateSessionRequest {
+            connection: ConnectionType::Local {
+                shell_path: Some("/bin/sh".to_string()),
+                cwd: None,
+                env: None,
+            },
+            ..Default::default()
+        };
+
+        let session_id = match manager.create_session(request).await {
+            Ok(id) => id,
+            Err(e) => {
+                println!("PTY creation failed (may be expected in CI): {}", e);
+                return;
+            }
+        };
+
+        let pid = manager.get_session(&session_id).await.unwrap().pid.expect("本地会话应有 PID");
+        assert!(pid > 1);
+        assert_ne!(pid, std::process::id());
+        #[cfg(target_os = "linux")]
+        assert!(std::path::Path::new(&format!("/proc/{}", pid)).exists());
+
+        // SSH 会话没有本地子进程
+        let ssh_id = manager.create_session(ssh_request("example.com", None)).await.unwrap();
+        assert_eq!(manager.get_session(&ssh_id).await.unwrap().pid, None);
+
+        manager.close_session(&session_id).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_inherit_cwd_from_missing_session_falls_back() {
+        let mut manager = PtyManager::new();
+        let request = CreateSessionRequest {
+            connection: ConnectionType::Local {
+                shell_path: None,
+                cwd: None,
+                env: None,
+            },
+            inherit_cwd_from: Some("missing".to_string()),
+            ..Default::default()
+        };
+
+        match manager.create_session(request).await {
+            Ok(session_id) => {
+                let info = manager.get_session(&session_id).await.unwrap();
+                assert!(matches!(info.connection_type, ConnectionType::Local { cwd: None, .. }));
+                let _ = manager.close_session(&session_id).await;
+            }
+            Err(e) => {
+                println!("PTY creation failed (may be expected in CI): {}", e);
+            }
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_track_cwd_reports_directory_change() {
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let mut manager = PtyManager::with_notification_sender(NotificationSender::new_for_test(tx));
+        let request = CreateSessionRequest {
+            connection: ConnectionType::Local {
+                shell_path: Some("/bin/sh".to_string()),
+                cwd: None,
+                env: None,
+            },
+            track_cwd: true,
+            ..Default::default()
+        };
+
+        let session_id = match manager.create_session(request).await {
+            Ok(id) => id,
+            Err(e) => {
+                println!("PTY creation failed (may be expected in CI): {}", e);
+                return;
+            }
+        };
+
+        let target = std::env::temp_dir().canonicalize().unwrap();
+        let target = target.to_string_lossy().into_owned();
+        let input = codec::encode_output(format!("cd '{}'\n", target).as_bytes());
+        manager.send_input(&session_id, &input).await.unwrap();
+
+        let mut cwd = None;
+        for _ in 0..50 {
+            cwd = manager.get_session(&session_id).await.unwrap().cwd;
+            if cwd.as_deref() == Some(target.as_str()) {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        }
+        assert_eq!(cwd.as_deref(), Some(target.as_str()));
+
+        let _ = manager.close_session(&session_id).await;
+
+        // 应该收到对应的 session.cwd 通知
+        let mut notified = false;
+        while let Ok(notification) = rx.try_recv() {
+            if notification.method == "session.cwd"
+                && notification.params.as_ref().unwrap()["cwd"] == target.as_str()
+            {
+                notified = true;
+            }
+        }
+        assert!(notified, "Should receive cwd notification for {}", target);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_initial_input_written_once() {
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let mut manager = PtyManager::with_notification_sender(NotificationSender::new_for_test(tx));
+        let request = CreateSessionRequest {
+            connection: ConnectionType::Local {
+                shell_path: Some("/bin/sh".to_string()),
+                cwd: None,
+                env: None,
+            },
+            initial_input: Some(codec::encode_output(b"echo initial-$((6 * 7))\r")),
+            ..Default::default()
+        };
+
+        // 无效的初始输入使创建失败，不会留下会话
+        let invalid = CreateSessionRequest {
+            initial_input: Some("not base64!".to_string()),
+            ..request.clone()
+        };
+        assert!(matches!(
+            manager.create_session(invalid).await,
+            Err(TerminalError::InvalidRequest(_))
+        ));
+        assert_eq!(manager.session_count(), 0);
+
+        let session_id = match manager.create_session(request).await {
+            Ok(id) => id,
+            Err(e) => {
+                println!("PTY creation failed (may be expected in CI): {}", e);
+                return;
+            }
+        };
+        assert_eq!(manager.get_session(&session_id).await.unwrap().status, SessionStatus::Running);
+
+        // 回显的是 `$((6 * 7))`，只有执行结果包含 `initial-42`
+        let mut output = String::new();
+        for _ in 0..100 {
+            while let Ok(notification) = rx.try_recv() {
+                if notification.method == "terminal.output" {
+                    let data = codec::decode_input(notification.params.unwrap()["data"].as_str().unwrap()).unwrap();
+                    output.push_str(&String::from_utf8_lossy(&data));
+                }
+            }
+            if output.contains("initial-42") {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+        assert!(output.contains("initial-42"), "output: {:?}", output);
+
+        // 再等一会，确认没有重复写入
+        tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+        while let Ok(notification) = rx.try_recv() {
+            if notification.method == "terminal.output" {
+                let data = codec::decode_input(notification.params.unwrap()["data"].as_str().unwrap()).unwrap();
+                output.push_str(&String::from_utf8_lossy(&data));
+            }
+        }
+        assert_eq!(output.matches("initial-42").count(), 1, "output: {:?}", output);
+
+        manager.close_session(&session_id).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_batch_resize_then_input() {
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let mut manager = PtyManager::with_notification_sender(NotificationSender::new_for_test(tx));
+        let request = CreateSessionRequest {
+            connection: ConnectionType::Local {
+                shell_path: Some("/bin/sh".to_string()),
+                cwd: None,
+                env: None,
+            },
+            ..Default::default()
+        };
+
+        let session_id = match manager.create_session(request).await {
+            Ok(id) => id,
+            Err(e) => {
+                println!("PTY creation failed (may be expected in CI): {}", e);
+                return;
+            }
+        };
+
+        // 无效的操作使整个批次被拒绝，已校验的调整大小也不会执行
+        let invalid = BatchRequest {
+            session_id: session_id.clone(),
+            operations: vec![
+                BatchOperation::Resize {
+                    term_size: TermSize {
+                        rows: 10,
+                        cols: 10,
+                        ..Default::default()
+                    },
+                },
+                BatchOperation::Signal {
+                    signal: "NOPE".to_string(),
+                },
+            ],
+        };
+        assert!(matches!(
+            manager.batch(&invalid).await,
+            Err(TerminalError::InvalidRequest(_))
+        ));
+
+        // 输入执行时终端已经是新的大小
+        let batch = BatchRequest {
+            session_id: session_id.clone(),
+            operations: vec![
+                BatchOperation::Resize {
+                    term_size: TermSize {
+                        rows: 40,
+                        cols: 120,
+                        ..Default::default()
+                    },
+                },
+                BatchOperation::Input {
+                    data: codec::encode_output(b"stty size\r"),
+                },
+            ],
+        };
+        let response = manager.batch(&batch).await.unwrap();
+        assert_eq!(response.applied, 2);
+        assert_eq!(
+            response.results.iter().map(|r| r.op.as_str()).collect::<Vec<_>>(),
+            vec!["resize", "input"]
+        );
+
+        let mut output = String::new();
+        for _ in 0..100 {
+            while let Ok(notification) = rx.try_recv() {
+                if notification.method == "terminal.output" {
+                    let data = codec::decode_input(notification.params.unwrap()["data"].as_str().unwrap()).unwrap();
+                    output.push_str(&String::from_utf8_lossy(&data));
+                }
+            }
+            if output.contains("40 120") {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+        assert!(output.contains("40 120"), "output: {:?}", output);
+
+        // 进程退出后停止执行后续操作
+        let kill = BatchRequest {
+            session_id: session_id.clone(),
+            operations: vec![
+                BatchOperation::Signal {
+                    signal: "SIGKILL".to_string(),
+                },
+                BatchOperation::Input {
+                    data: codec::encode_output(b"echo\r"),
+                },
+                BatchOperation::Input {
+                    data: codec::encode_output(b"echo\r"),
+                },
+            ],
+        };
+        let mut response = manager.batch(&kill).await.unwrap();
+        for _ in 0..50 {
+            if response.results.len() == 1 && !response.results[0].ok {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+            response = manager.batch(&kill).await.unwrap();
+        }
+        assert_eq!(response.applied, 0);
+        assert_eq!(response.results.len(), 1);
+        assert!(response.results[0].error.is_some());
+
+        let _ = manager.close_session(&session_id).await;
+    }
+
+    #[tokio::test]
+    async fn test_pause_nonexistent_session() {
+        let mut manager = PtyManager::new();
+        assert!(manager.pause_session("nonexistent").await.is_err());
+        assert!(manager.resume_session("nonexistent").await.is_err());
+    }
+
     #[tokio::test]
     async fn test_close_nonexistent_session() {
         let mut manager = PtyManager::new();
@@ -309,8 +2729,14 @@ mod proptests {
                             user: Some("test".to_string()),
                             identity_file: None,
                             password: None,
+                            compression: false,
+                            max_retries: 0,
+                            retry_delay_ms: 1000,
+                            channel_retries: 0,
+                            notify_ready: false,
+                            env: None,
                         },
-                        term_size: TermSize::default(),
+                        ..Default::default()
                     };
 
                     match manager.create_session(request).await {
@@ -363,8 +2789,14 @@ mod proptests {
                         user: Some("test".to_string()),
                         identity_file: None,
                         password: None,
+                        compression: false,
+                        max_retries: 0,
+                        retry_delay_ms: 1000,
+                        channel_retries: 0,
+                        notify_ready: false,
+                        env: None,
                     },
-                    term_size: TermSize::default(),
+                    ..Default::default()
                 };
 
                 match manager.create_session(request).await {
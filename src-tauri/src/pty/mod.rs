@@ -2,12 +2,22 @@
 //!
 //! 负责本地伪终端的创建和管理。
 
+#[cfg(target_os = "linux")]
+pub mod cwd;
+pub mod idle;
 pub mod local;
 pub mod manager;
 pub mod output;
+pub mod process;
 pub mod session;
+pub mod stats;
 
 pub use local::LocalPty;
 pub use manager::PtyManager;
-pub use output::{start_output_reader, OutputReaderConfig, OutputReaderHandle};
-pub use session::PtySession;
+pub use output::{
+    start_output_reader, start_output_reader_with_listeners, OutputListenerHandle, OutputListeners,
+    OutputReaderConfig, OutputReaderHandle,
+};
+pub use process::LocalProcess;
+pub use session::{BatchStep, PtySession};
+pub use stats::ByteCounters;
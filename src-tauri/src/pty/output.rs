@@ -1,27 +1,61 @@
 //! PTY 输出读取器
 //!
 //! 异步读取 PTY 输出并通过 JSON-RPC 通知发送到前端。
-//! 支持检测和处理 OSC 序列（如工作目录变更、剪贴板操作）。
+//! 支持检测和处理 OSC 序列（如工作目录变更、剪贴板操作、OSC 133 命令结束），
+//! 以及跟踪 CSI 私有模式（如鼠标报告、备用屏幕）。
 
+use std::collections::{BTreeMap, VecDeque};
 use std::io::Read;
-use std::time::Duration;
-use tokio::sync::mpsc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc as std_mpsc;
+use std::sync::{Arc, Mutex, Weak};
+use std::time::{Duration, Instant};
+use tokio::sync::{mpsc, RwLock};
 use tokio::task::JoinHandle;
 
 use crate::rpc::server::NotificationSender;
-use crate::rpc::types::SessionStatus;
-use crate::shell::osc::{OscHandler, OscSequence};
+use crate::rpc::types::{JsonRpcNotification, SessionInfo, SessionStatus};
+use crate::shell::csi::CsiScanner;
+use crate::shell::osc::{OscDiagnostic, OscFeature, OscHandler, OscSequence};
+use crate::shell::prompt::CommandTracker;
+use crate::pty::stats::ByteCounters;
+use crate::utils::codec;
+use crate::utils::error::TerminalError;
+use crate::utils::time::{system_clock, SharedClock};
 
 /// 输出读取器配置
+#[derive(Debug, Clone)]
 pub struct OutputReaderConfig {
     /// 读取缓冲区大小
+    ///
+    /// 较大的缓冲区减少大量输出时的系统调用次数，较小的缓冲区降低交互时的延迟。
     pub buffer_size: usize,
-    /// 读取超时时间
+    /// 等待 PTY 输出的超时时间
+    ///
+    /// 没有输出时每隔这段时间检查一次停止信号，决定空闲会话停止的响应速度。
     pub read_timeout: Duration,
     /// 是否启用 OSC 处理
     pub enable_osc_processing: bool,
     /// 剪贴板大小限制（字节）
     pub max_clipboard_size: usize,
+    /// 是否去掉剪贴板内容中的控制字符，见 [`sanitize_clipboard`](crate::shell::osc::sanitize_clipboard)
+    pub sanitize_clipboard: bool,
+    /// 是否从输出中去掉 OSC 序列
+    ///
+    /// 为 false 时仍然识别序列并发送标题、工作目录、剪贴板等通知，但输出原样发送，
+    /// 由自带解析器的前端自行处理序列。
+    pub strip_osc_from_output: bool,
+    /// 是否跟踪终端模式（鼠标报告、备用屏幕）
+    pub enable_mode_tracking: bool,
+    /// 单条输出通知的最大原始字节数，超过时拆分为多条通知
+    pub output_chunk_size: usize,
+    /// 输出速率上限（字节/秒），`None` 或 0 表示不限制
+    ///
+    /// 超过上限时读取器暂停读取，PTY 缓冲区写满后子进程的写入随之阻塞。
+    /// 用于保护处理较慢的前端、以可读的速度录制演示或模拟低速链路。
+    pub rate_limit_bytes_per_sec: Option<u64>,
+    /// 记录会话结束时间使用的时间来源
+    pub clock: SharedClock,
 }
 
 impl Default for OutputReaderConfig {
@@ -31,47 +65,251 @@ impl Default for OutputReaderConfig {
             read_timeout: Duration::from_millis(100),
             enable_osc_processing: true,
             max_clipboard_size: 1024 * 1024, // 1MB
+            sanitize_clipboard: true,
+            strip_osc_from_output: true,
+            enable_mode_tracking: true,
+            output_chunk_size: 64 * 1024, // 64KB
+            rate_limit_bytes_per_sec: None,
+            clock: system_clock(),
         }
     }
 }
 
+impl OutputReaderConfig {
+    /// 读取缓冲区的最小值
+    pub const MIN_BUFFER_SIZE: usize = 512;
+    /// 读取缓冲区的最大值
+    pub const MAX_BUFFER_SIZE: usize = 1024 * 1024;
+
+    /// 设置读取缓冲区大小，限制在 [`MIN_BUFFER_SIZE`](Self::MIN_BUFFER_SIZE)
+    /// 到 [`MAX_BUFFER_SIZE`](Self::MAX_BUFFER_SIZE) 之间
+    pub fn with_buffer_size(mut self, buffer_size: usize) -> Self {
+        self.buffer_size = buffer_size.clamp(Self::MIN_BUFFER_SIZE, Self::MAX_BUFFER_SIZE);
+        self
+    }
+
+    /// 按当前配置，读取器是否处理指定功能的 OSC 序列
+    pub fn osc_feature_enabled(&self, feature: OscFeature) -> bool {
+        match feature {
+            OscFeature::Clipboard => self.enable_osc_processing && self.max_clipboard_size > 0,
+            _ => self.enable_osc_processing,
+        }
+    }
+}
+
+/// 附加输出监听器
+///
+/// 除创建会话时的通知发送器外，额外接收同一会话 `terminal.output` 通知的发送器集合，
+/// 用于分屏镜像或记录输出，不需要再创建一个 PTY。克隆后与输出读取器共享同一集合。
+#[derive(Clone, Default)]
+pub struct OutputListeners {
+    inner: Arc<Mutex<ListenerRegistry>>,
+}
+
+#[derive(Default)]
+struct ListenerRegistry {
+    next_id: u64,
+    senders: BTreeMap<u64, NotificationSender>,
+}
+
+impl OutputListeners {
+    /// 创建空的监听器集合
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 注册监听器，返回的句柄被丢弃时自动移除
+    pub fn add(&self, sender: NotificationSender) -> OutputListenerHandle {
+        let mut registry = lock_registry(&self.inner);
+        let id = registry.next_id;
+        registry.next_id += 1;
+        registry.senders.insert(id, sender);
+        OutputListenerHandle {
+            id,
+            registry: Arc::downgrade(&self.inner),
+        }
+    }
+
+    /// 当前注册的监听器数量
+    pub fn len(&self) -> usize {
+        lock_registry(&self.inner).senders.len()
+    }
+
+    /// 是否没有注册任何监听器
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// 向所有监听器发送输出通知
+    ///
+    /// 接收端已关闭的监听器会被移除，不影响其他监听器和主通知发送器。
+    pub(super) fn send_output(&self, session_id: &str, data: &str, seq: u64, is_final: bool) {
+        let mut registry = lock_registry(&self.inner);
+        registry.senders.retain(|id, sender| {
+            let delivered = sender.send_output(session_id, data, seq, is_final).is_ok();
+            if !delivered {
+                tracing::debug!("移除已关闭的输出监听器: {} #{}", session_id, id);
+            }
+            delivered
+        });
+    }
+}
+
+/// 输出监听器句柄，被丢弃时从集合中移除对应的监听器
+pub struct OutputListenerHandle {
+    id: u64,
+    registry: Weak<Mutex<ListenerRegistry>>,
+}
+
+impl Drop for OutputListenerHandle {
+    fn drop(&mut self) {
+        if let Some(registry) = self.registry.upgrade() {
+            lock_registry(&registry).senders.remove(&self.id);
+        }
+    }
+}
+
+/// 获取监听器集合的锁
+///
+/// 发送通知不会在持锁时 panic，锁中毒时继续使用内部数据。
+fn lock_registry(registry: &Mutex<ListenerRegistry>) -> std::sync::MutexGuard<'_, ListenerRegistry> {
+    registry.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// 暂停期间检查恢复/停止信号的间隔
+const PAUSE_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
 /// 输出读取器句柄
 pub struct OutputReaderHandle {
     /// 停止信号发送器
     stop_tx: mpsc::Sender<()>,
-    /// 任务句柄
-    task_handle: JoinHandle<()>,
+    /// 暂停标志
+    paused: Arc<AtomicBool>,
+    /// 排空标志，会话关闭时读完剩余输出后静默退出
+    draining: Arc<AtomicBool>,
+    /// 任务句柄，因停止信号退出时返回输出来源
+    task_handle: JoinHandle<Option<OutputSource>>,
 }
 
 impl OutputReaderHandle {
+    /// 暂停输出
+    ///
+    /// 暂停期间读取器不再读取 PTY，由 PTY 自身的缓冲区对子进程施加背压。
+    /// 暂停时已经读到的数据会保留到恢复后再发送。
+    pub fn pause(&self) {
+        self.paused.store(true, Ordering::Release);
+    }
+
+    /// 恢复输出
+    pub fn resume(&self) {
+        self.paused.store(false, Ordering::Release);
+    }
+
+    /// 检查输出是否已暂停
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::Acquire)
+    }
+
+    /// 发送停止信号，不等待任务完成
+    ///
+    /// 读取器最多在 `read_timeout` 之后响应停止信号。阻塞在 `read` 上的读取线程
+    /// 会在 PTY 关闭后退出。
+    pub fn request_stop(&self) {
+        let _ = self.stop_tx.try_send(());
+    }
+
+    /// 进入排空模式，不等待任务完成
+    ///
+    /// 排空模式下读取器继续转发读到的输出（忽略暂停），遇到 EOF 或读取错误时
+    /// 静默退出，不发送状态通知。应在终止 PTY 进程之前调用。
+    pub fn begin_drain(&self) {
+        self.draining.store(true, Ordering::Release);
+    }
+
+    /// 排空剩余输出后停止读取器
+    ///
+    /// 等待读取器读到 EOF，最多等待 `timeout`；超时后发送停止信号，不再等待。
+    pub async fn drain(self, timeout: Duration) {
+        self.begin_drain();
+
+        let mut task_handle = self.task_handle;
+        if tokio::time::timeout(timeout, &mut task_handle).await.is_err() {
+            tracing::warn!("等待输出读取器读完剩余输出超时");
+            let _ = self.stop_tx.try_send(());
+        }
+    }
+
     /// 停止输出读取器
     pub async fn stop(self) {
         // 发送停止信号
-        let _ = self.stop_tx.send(()).await;
+        self.request_stop();
         // 等待任务完成
         let _ = self.task_handle.await;
     }
 
+    /// 分离输出读取器
+    ///
+    /// 停止读取循环并交还输出来源，PTY 中的数据留在原处，子进程继续运行。
+    /// 读取器已经因 EOF 或错误退出时返回 `None`。
+    pub async fn detach(self) -> Option<OutputSource> {
+        self.request_stop();
+        self.task_handle.await.ok().flatten()
+    }
+
     /// 检查任务是否已完成
     pub fn is_finished(&self) -> bool {
         self.task_handle.is_finished()
     }
 }
 
-/// 处理 OSC 序列并发送相应通知
+/// 处理 OSC 序列并发送相应通知，返回移除 OSC 序列后的数据
+///
+/// 序列之间的文本按顺序交给 `command_tracker`，用于得到 OSC 133 标记之间的命令文本。
 fn process_osc_sequences(
     session_id: &str,
     data: &str,
     osc_handler: &OscHandler,
+    command_tracker: &mut CommandTracker,
+    session_info: Option<&Arc<RwLock<SessionInfo>>>,
     notification_sender: &NotificationSender,
 ) -> String {
-    let (stripped_data, sequences) = osc_handler.strip_sequences(data);
+    let mut stripped_data = String::with_capacity(data.len());
+    let mut last_end = 0;
+
+    for outcome in osc_handler.extract_sequences(data) {
+        let text = &data[last_end..outcome.start];
+        stripped_data.push_str(text);
+        command_tracker.text(text);
+        last_end = outcome.end;
 
-    for sequence in sequences {
-        match sequence {
-            OscSequence::WorkingDirectory(cwd) => {
-                tracing::debug!("检测到工作目录变更: {} -> {}", session_id, cwd);
-                if let Err(e) = notification_sender.send_cwd(session_id, &cwd) {
+        for diagnostic in &outcome.diagnostics {
+            match diagnostic {
+                OscDiagnostic::ClipboardRejected(rejection) => {
+                    tracing::debug!("剪贴板写入被拒绝: {} ({})", session_id, rejection.reason());
+                    if let Err(e) = notification_sender.send_clipboard_rejected(session_id, rejection) {
+                        tracing::error!("发送剪贴板拒绝通知失败: {}", e);
+                    }
+                }
+                _ => tracing::debug!("丢弃无效的 OSC 序列: {} ({:?})", session_id, diagnostic),
+            }
+        }
+
+        match outcome.sequence {
+            OscSequence::Title(title) => {
+                tracing::debug!("检测到标题变更: {} -> {}", session_id, title);
+                if let Some(info) = session_info {
+                    info.blocking_write().title = Some(title.clone());
+                }
+                if let Err(e) = notification_sender.send_title(session_id, &title) {
+                    tracing::error!("发送标题通知失败: {}", e);
+                }
+            }
+            OscSequence::WorkingDirectory { host, path: cwd } => {
+                tracing::debug!("检测到工作目录变更: {} -> {:?} {}", session_id, host, cwd);
+                if let Some(info) = session_info {
+                    info.blocking_write().cwd = Some(cwd.clone());
+                }
+                if let Err(e) = notification_sender.send_cwd(session_id, &cwd, host.as_deref()) {
                     tracing::error!("发送工作目录通知失败: {}", e);
                 }
             }
@@ -89,124 +327,576 @@ fn process_osc_sequences(
                     tracing::error!("发送剪贴板通知失败: {}", e);
                 }
             }
-            OscSequence::Unknown => {
-                // 忽略未知序列
+            OscSequence::ColorSet(target, color) => {
+                tracing::debug!("检测到颜色设置: {} {:?} -> {}", session_id, target, color.to_hex());
+                if let Err(e) = notification_sender.send_color(
+                    session_id,
+                    target.name(),
+                    target.index(),
+                    Some(&color.to_hex()),
+                ) {
+                    tracing::error!("发送颜色通知失败: {}", e);
+                }
+            }
+            OscSequence::ColorQuery(target) => {
+                // 查询已从输出中移除，交由前端应答
+                tracing::debug!("检测到颜色查询: {} {:?}", session_id, target);
+                if let Err(e) =
+                    notification_sender.send_color(session_id, target.name(), target.index(), None)
+                {
+                    tracing::error!("发送颜色通知失败: {}", e);
+                }
+            }
+            OscSequence::PromptMarker(marker) => {
+                if let Some(finished) = command_tracker.marker(marker) {
+                    tracing::debug!("命令执行结束: {} (exit_code={:?})", session_id, finished.exit_code);
+                    if let Err(e) = notification_sender.send_command_finished(
+                        session_id,
+                        finished.exit_code,
+                        finished.command.as_deref(),
+                    ) {
+                        tracing::error!("发送命令结束通知失败: {}", e);
+                    }
+                }
+            }
+            OscSequence::Invalid | OscSequence::Unknown => {
+                // 忽略无效和未知序列
             }
         }
     }
 
+    let rest = &data[last_end..];
+    stripped_data.push_str(rest);
+    command_tracker.text(rest);
     stripped_data
 }
 
+/// PTY 输出来源
+///
+/// 在独立线程中阻塞读取 PTY 输出，读取到的每块数据通过容量为 0 的通道交给读取循环，
+/// 循环未取走之前不会继续读取，暂停或分离时 PTY 的背压不受影响。读取到 EOF 或错误后
+/// 发送一个空块或错误并退出；来源被丢弃后，线程在下一次读取返回后退出。
+///
+/// 分离输出读取器时来源被保留下来，重新附加时继续使用，已经读出但尚未发送的数据不会丢失。
+pub struct OutputSource {
+    /// 读取线程发送的数据块
+    chunks: std_mpsc::Receiver<std::io::Result<Vec<u8>>>,
+    /// 已经取出但尚未处理的数据块
+    pending: Option<std::io::Result<Vec<u8>>>,
+    /// 注入的输出
+    injected: OutputInjector,
+}
+
+impl OutputSource {
+    /// 启动读取线程
+    pub fn spawn(session_id: &str, reader: Box<dyn Read + Send>, buffer_size: usize) -> Self {
+        Self {
+            chunks: spawn_read_thread(session_id, reader, buffer_size),
+            pending: None,
+            injected: OutputInjector::default(),
+        }
+    }
+
+    /// 向这个来源注入输出的句柄
+    pub fn injector(&self) -> OutputInjector {
+        self.injected.clone()
+    }
+
+    /// 等待下一块数据，优先返回之前放回的数据块，其次是注入的输出
+    fn recv_timeout(
+        &mut self,
+        timeout: Duration,
+    ) -> Result<std::io::Result<Vec<u8>>, std_mpsc::RecvTimeoutError> {
+        if let Some(result) = self.pending.take() {
+            return Ok(result);
+        }
+        if let Some(data) = self.injected.take() {
+            return Ok(Ok(data));
+        }
+        self.chunks.recv_timeout(timeout)
+    }
+
+    /// 放回尚未处理的数据块，下次读取时优先返回
+    fn push_back(&mut self, result: std::io::Result<Vec<u8>>) {
+        self.pending = Some(result);
+    }
+}
+
+/// 输出注入句柄
+///
+/// 注入的数据像 PTY 输出一样经过 OSC 处理和模式跟踪，再作为 `terminal.output` 通知发送，
+/// 不会写入子进程的输入。读取循环在等待 PTY 输出之前取走注入的数据，空闲时最迟在
+/// 一个读取超时（[`OutputReaderConfig::read_timeout`]）后发送。克隆后共享同一队列。
+#[derive(Clone, Default)]
+pub struct OutputInjector {
+    queue: Arc<Mutex<VecDeque<Vec<u8>>>>,
+}
+
+impl OutputInjector {
+    /// 追加一块注入的输出，空数据被忽略（空块表示 EOF）
+    pub fn inject(&self, data: Vec<u8>) {
+        if !data.is_empty() {
+            self.queue.lock().unwrap_or_else(|e| e.into_inner()).push_back(data);
+        }
+    }
+
+    /// 取出最早注入的一块输出
+    fn take(&self) -> Option<Vec<u8>> {
+        self.queue.lock().unwrap_or_else(|e| e.into_inner()).pop_front()
+    }
+}
+
+/// 在独立线程中阻塞读取 PTY 输出
+fn spawn_read_thread(
+    session_id: &str,
+    mut reader: Box<dyn Read + Send>,
+    buffer_size: usize,
+) -> std_mpsc::Receiver<std::io::Result<Vec<u8>>> {
+    let (chunk_tx, chunk_rx) = std_mpsc::sync_channel(0);
+
+    let spawned = std::thread::Builder::new()
+        .name(format!("pty-reader-{}", session_id))
+        .spawn(move || loop {
+            let mut buffer = vec![0u8; buffer_size];
+            match reader.read(&mut buffer) {
+                Ok(n) => {
+                    buffer.truncate(n);
+                    if chunk_tx.send(Ok(buffer)).is_err() || n == 0 {
+                        break;
+                    }
+                }
+                Err(ref e) if e.kind() == std::io::ErrorKind::WouldBlock => {
+                    // 非阻塞读取，没有数据可读，短暂休眠后继续
+                    std::thread::sleep(Duration::from_millis(10));
+                }
+                Err(ref e) if e.kind() == std::io::ErrorKind::Interrupted => {
+                    // 被中断，继续读取
+                    continue;
+                }
+                Err(e) => {
+                    let _ = chunk_tx.send(Err(e));
+                    break;
+                }
+            }
+        });
+
+    if let Err(e) = spawned {
+        tracing::error!("创建 PTY 读取线程失败: {}", e);
+    }
+
+    chunk_rx
+}
+
+/// 暂停或没有客户端接收通知时阻塞等待
+///
+/// 进入排空模式时视为恢复。返回 false 表示等待期间收到了停止信号。
+fn wait_while_paused(
+    paused: &AtomicBool,
+    draining: &AtomicBool,
+    notification_sender: &NotificationSender,
+    stop_rx: &mut mpsc::Receiver<()>,
+) -> bool {
+    while (paused.load(Ordering::Acquire) || !notification_sender.is_attached())
+        && !draining.load(Ordering::Acquire)
+    {
+        if stop_rx.try_recv().is_ok() {
+            return false;
+        }
+        std::thread::sleep(PAUSE_POLL_INTERVAL);
+    }
+    true
+}
+
+/// 输出速率限制器（令牌桶）
+///
+/// 令牌按速率匀速补充，最多积累 0.1 秒的量作为突发额度。
+struct RateLimiter {
+    /// 每秒补充的令牌数（字节）
+    rate: f64,
+    /// 令牌桶容量，也是单次放行的最大字节数
+    burst: usize,
+    /// 当前令牌数
+    tokens: f64,
+    /// 上次补充令牌的时间
+    last_refill: Instant,
+}
+
+impl RateLimiter {
+    /// 创建限制器，速率为 0 时返回 `None`
+    fn new(bytes_per_sec: u64) -> Option<Self> {
+        if bytes_per_sec == 0 {
+            return None;
+        }
+        let burst = usize::try_from((bytes_per_sec / 10).max(1)).unwrap_or(usize::MAX);
+        Some(Self {
+            rate: bytes_per_sec as f64,
+            burst,
+            tokens: burst as f64,
+            last_refill: Instant::now(),
+        })
+    }
+
+    /// 单次放行的最大字节数
+    fn burst(&self) -> usize {
+        self.burst
+    }
+
+    /// 补充令牌并返回凑够 `bytes` 个令牌还需等待的时间
+    fn delay_for(&mut self, bytes: usize) -> Duration {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last_refill).as_secs_f64();
+        self.last_refill = now;
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.burst as f64);
+
+        let missing = bytes as f64 - self.tokens;
+        if missing <= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(missing / self.rate)
+        }
+    }
+
+    /// 等待直到可以放行 `bytes` 个字节，然后扣除相应的令牌
+    ///
+    /// 进入排空模式时立即放行，不拖慢会话关闭。返回 false 表示等待期间收到了停止信号，
+    /// 此时不扣除令牌。
+    fn acquire(
+        &mut self,
+        bytes: usize,
+        draining: &AtomicBool,
+        stop_rx: &mut mpsc::Receiver<()>,
+    ) -> bool {
+        loop {
+            let delay = self.delay_for(bytes);
+            if delay.is_zero() || draining.load(Ordering::Acquire) {
+                break;
+            }
+            if stop_rx.try_recv().is_ok() {
+                return false;
+            }
+            std::thread::sleep(delay.min(PAUSE_POLL_INTERVAL));
+        }
+        self.tokens -= bytes as f64;
+        true
+    }
+}
+
+/// 可以解码的前缀长度
+///
+/// 末尾是被截断的多字节字符时返回截断处的位置，其余情况（包括中间有非法字节）返回全部长度，
+/// 非法数据照常按原样发送。
+fn complete_utf8_len(data: &[u8]) -> usize {
+    match std::str::from_utf8(data) {
+        Err(e) if e.error_len().is_none() => e.valid_up_to(),
+        _ => data.len(),
+    }
+}
+
+/// 分块发送输出数据
+///
+/// 将数据按 `chunk_size` 拆分，每块单独 base64 编码后发送，
+/// 每块从 `counters` 分配一个序号，最后一块标记为 final。发送成功的字节计入 `counters`。
+fn send_output_chunked(
+    session_id: &str,
+    data: &[u8],
+    chunk_size: usize,
+    notification_sender: &NotificationSender,
+    listeners: &OutputListeners,
+    counters: &ByteCounters,
+) -> Result<(), mpsc::error::SendError<JsonRpcNotification>> {
+    let mut chunks = data.chunks(chunk_size.max(1)).peekable();
+
+    while let Some(chunk) = chunks.next() {
+        let encoded = codec::encode_output(chunk);
+        let is_final = chunks.peek().is_none();
+        let seq = counters.next_output_seq();
+        notification_sender.send_output(session_id, &encoded, seq, is_final)?;
+        counters.add_out(chunk.len());
+        listeners.send_output(session_id, &encoded, seq, is_final);
+    }
+
+    Ok(())
+}
+
+/// 扫描终端模式变更，更新会话信息并发送通知
+fn process_mode_changes(
+    session_id: &str,
+    data: &[u8],
+    csi_scanner: &mut CsiScanner,
+    session_info: Option<&Arc<RwLock<SessionInfo>>>,
+    notification_sender: &NotificationSender,
+) {
+    if !csi_scanner.scan(data) {
+        return;
+    }
+
+    let modes = csi_scanner.modes().clone();
+    tracing::debug!("检测到终端模式变更: {} -> {:?}", session_id, modes);
+
+    if let Some(info) = session_info {
+        info.blocking_write().modes = modes.clone();
+    }
+
+    if let Err(e) = notification_sender.send_mode(session_id, &modes) {
+        tracing::error!("发送终端模式通知失败: {}", e);
+    }
+}
+
 /// 启动 PTY 输出读取器
 ///
 /// 在后台任务中异步读取 PTY 输出，并通过 NotificationSender 发送到前端。
 /// 当进程退出时，发送状态变更通知。
 /// 
 /// 如果启用了 OSC 处理，会自动检测并处理 OSC 序列：
+/// - OSC 0/2: 更新会话标题并发送标题变更通知
 /// - OSC 7: 发送工作目录变更通知
 /// - OSC 52: 发送剪贴板内容通知
+///
+/// 如果启用了模式跟踪，终端模式变更时会更新 `session_info` 并发送 `session.mode` 通知。
 pub fn start_output_reader(
     session_id: String,
     reader: Box<dyn Read + Send>,
     notification_sender: NotificationSender,
+    session_info: Option<Arc<RwLock<SessionInfo>>>,
+    config: OutputReaderConfig,
+) -> OutputReaderHandle {
+    start_output_reader_with_listeners(
+        session_id,
+        reader,
+        notification_sender,
+        OutputListeners::new(),
+        Arc::new(ByteCounters::new()),
+        session_info,
+        config,
+    )
+}
+
+/// 启动 PTY 输出读取器，并把输出同时发送给附加监听器
+///
+/// 监听器可以在读取器运行期间随时注册或移除，只接收 `terminal.output` 通知。
+/// 发送给前端的输出字节数计入 `counters`。
+pub fn start_output_reader_with_listeners(
+    session_id: String,
+    reader: Box<dyn Read + Send>,
+    notification_sender: NotificationSender,
+    listeners: OutputListeners,
+    counters: Arc<ByteCounters>,
+    session_info: Option<Arc<RwLock<SessionInfo>>>,
+    config: OutputReaderConfig,
+) -> OutputReaderHandle {
+    let source = OutputSource::spawn(&session_id, reader, config.buffer_size);
+    start_output_reader_from_source(
+        session_id,
+        source,
+        notification_sender,
+        listeners,
+        counters,
+        session_info,
+        config,
+    )
+}
+
+/// 从已有的输出来源启动输出读取器
+///
+/// 用于重新附加之前通过 [`OutputReaderHandle::detach`] 分离的读取器。
+pub fn start_output_reader_from_source(
+    session_id: String,
+    mut source: OutputSource,
+    notification_sender: NotificationSender,
+    listeners: OutputListeners,
+    counters: Arc<ByteCounters>,
+    session_info: Option<Arc<RwLock<SessionInfo>>>,
     config: OutputReaderConfig,
 ) -> OutputReaderHandle {
     let (stop_tx, mut stop_rx) = mpsc::channel::<()>(1);
+    let paused = Arc::new(AtomicBool::new(false));
+    let paused_flag = paused.clone();
+    let draining = Arc::new(AtomicBool::new(false));
+    let draining_flag = draining.clone();
 
     // 创建 OSC 处理器
     let osc_handler = if config.enable_osc_processing {
-        Some(OscHandler::new().with_max_clipboard_size(config.max_clipboard_size))
+        Some(
+            OscHandler::new()
+                .with_max_clipboard_size(config.max_clipboard_size)
+                .with_clipboard_sanitization(config.sanitize_clipboard),
+        )
+    } else {
+        None
+    };
+
+    // 跟踪 OSC 133 标记之间的命令
+    let mut command_tracker = CommandTracker::new();
+
+    // 创建 CSI 扫描器
+    let mut csi_scanner = if config.enable_mode_tracking {
+        Some(CsiScanner::new())
     } else {
         None
     };
 
     let task_handle = tokio::task::spawn_blocking(move || {
-        let mut reader = reader;
-        let mut buffer = vec![0u8; config.buffer_size];
+        // 上次读取末尾被截断的 UTF-8 多字节字符
+        let mut utf8_pending: Vec<u8> = Vec::new();
+        // 因停止信号退出时交还输出来源，读到 EOF 或出错时来源已无用
+        let mut stopped = false;
+        // 只限制输出数据，EOF 和错误不经过限制器，进程退出可以立即报告
+        let mut rate_limiter = config.rate_limit_bytes_per_sec.and_then(RateLimiter::new);
 
         loop {
             // 检查是否收到停止信号
             if stop_rx.try_recv().is_ok() {
                 tracing::debug!("输出读取器收到停止信号: {}", session_id);
+                stopped = true;
                 break;
             }
 
-            // 尝试读取数据
-            match reader.read(&mut buffer) {
-                Ok(0) => {
+            // 等待读取线程的数据，超时后重新检查停止信号
+            let result = match source.recv_timeout(config.read_timeout) {
+                Ok(result) => result,
+                Err(std_mpsc::RecvTimeoutError::Timeout) => continue,
+                Err(std_mpsc::RecvTimeoutError::Disconnected) => {
+                    // 读取线程在发送 EOF 或错误后才会退出，不应该走到这里
+                    tracing::debug!("读取线程已退出: {}", session_id);
+                    break;
+                }
+            };
+
+            // 关闭会话时 PTY 被终止，读取结果不再代表进程状态
+            if stop_rx.try_recv().is_ok() {
+                tracing::debug!("输出读取器收到停止信号: {}", session_id);
+                source.push_back(result);
+                stopped = true;
+                break;
+            }
+
+            // 会话正在关闭：转发剩余输出，读完后静默退出
+            let closing = draining_flag.load(Ordering::Acquire);
+
+            match result {
+                _ if closing && result.as_ref().map_or(true, |chunk| chunk.is_empty()) => {
+                    tracing::debug!("输出读取器已排空剩余输出: {}", session_id);
+                    break;
+                }
+                Ok(chunk) if chunk.is_empty() => {
                     // EOF - 进程已退出
                     tracing::info!("PTY 输出 EOF，进程已退出: {}", session_id);
                     
-                    // 发送状态变更通知
-                    if let Err(e) = notification_sender.send_status(
+                    // 记录结束时间，发送状态变更通知，会话被服务器结束时带上原因
+                    let (reason, timing) = match session_info.as_ref() {
+                        Some(info) => {
+                            let mut info = info.blocking_write();
+                            info.mark_ended(SessionStatus::Done, config.clock.unix_secs());
+                            (info.exit_reason.clone(), info.timing())
+                        }
+                        None => (None, None),
+                    };
+                    if let Err(e) = notification_sender.send_status_with_reason(
                         &session_id,
-                        &serde_json::to_string(&SessionStatus::Done).unwrap().trim_matches('"'),
+                        serde_json::to_string(&SessionStatus::Done).unwrap().trim_matches('"'),
                         Some(0), // 默认退出码为 0
+                        reason.as_deref(),
+                        timing,
                     ) {
                         tracing::error!("发送状态通知失败: {}", e);
                     }
                     break;
                 }
-                Ok(n) => {
-                    let data = &buffer[..n];
+                Ok(mut chunk) => {
+                    // 暂停时持有这块数据直到恢复，不再继续读取
+                    if !wait_while_paused(&paused_flag, &draining_flag, &notification_sender, &mut stop_rx) {
+                        tracing::debug!("输出读取器在暂停期间收到停止信号: {}", session_id);
+                        source.push_back(Ok(chunk));
+                        stopped = true;
+                        break;
+                    }
+
+                    // 限速时每次最多放行一个突发额度，剩余部分交还来源留到下一轮
+                    if let Some(ref mut limiter) = rate_limiter {
+                        let allowed = chunk.len().min(limiter.burst());
+                        if !limiter.acquire(allowed, &draining_flag, &mut stop_rx) {
+                            tracing::debug!("输出读取器在限速等待期间收到停止信号: {}", session_id);
+                            source.push_back(Ok(chunk));
+                            stopped = true;
+                            break;
+                        }
+                        if allowed < chunk.len() {
+                            source.push_back(Ok(chunk.split_off(allowed)));
+                        }
+                    }
+
+                    let data = chunk.as_slice();
+
+                    // 跟踪终端模式
+                    if let Some(ref mut scanner) = csi_scanner {
+                        process_mode_changes(
+                            &session_id,
+                            data,
+                            scanner,
+                            session_info.as_ref(),
+                            &notification_sender,
+                        );
+                    }
                     
                     // 尝试将数据转换为字符串以处理 OSC 序列
                     let output_data = if let Some(ref handler) = osc_handler {
+                        // 拼接上次读取末尾不完整的多字节字符，并留下本次末尾不完整的部分
+                        let mut data = std::mem::take(&mut utf8_pending);
+                        data.extend_from_slice(&chunk);
+                        utf8_pending = data.split_off(complete_utf8_len(&data));
+
                         // 尝试 UTF-8 解码
-                        match std::str::from_utf8(data) {
+                        match std::str::from_utf8(&data) {
                             Ok(text) => {
                                 // 处理 OSC 序列
                                 let processed = process_osc_sequences(
                                     &session_id,
                                     text,
                                     handler,
+                                    &mut command_tracker,
+                                    session_info.as_ref(),
                                     &notification_sender,
                                 );
-                                processed.into_bytes()
+                                if config.strip_osc_from_output {
+                                    processed.into_bytes()
+                                } else {
+                                    data
+                                }
                             }
                             Err(_) => {
                                 // 非 UTF-8 数据，直接传递
-                                data.to_vec()
+                                data
                             }
                         }
                     } else {
-                        data.to_vec()
+                        chunk
                     };
 
                     // 如果处理后还有数据，编码为 base64 并发送
                     if !output_data.is_empty() {
-                        let encoded = base64::Engine::encode(
-                            &base64::engine::general_purpose::STANDARD,
-                            &output_data,
-                        );
-
                         tracing::trace!("读取 PTY 输出: {} bytes", output_data.len());
 
-                        if let Err(e) = notification_sender.send_output(&session_id, &encoded) {
+                        if let Err(e) = send_output_chunked(
+                            &session_id,
+                            &output_data,
+                            config.output_chunk_size,
+                            &notification_sender,
+                            &listeners,
+                            &counters,
+                        ) {
                             tracing::error!("发送输出通知失败: {}", e);
                             break;
                         }
                     }
                 }
-                Err(ref e) if e.kind() == std::io::ErrorKind::WouldBlock => {
-                    // 非阻塞读取，没有数据可读，短暂休眠后继续
-                    std::thread::sleep(Duration::from_millis(10));
-                }
-                Err(ref e) if e.kind() == std::io::ErrorKind::Interrupted => {
-                    // 被中断，继续读取
-                    continue;
-                }
                 Err(e) => {
                     // 其他错误
                     tracing::error!("读取 PTY 输出错误: {}", e);
                     
-                    // 发送错误状态通知
-                    if let Err(send_err) = notification_sender.send_status(
-                        &session_id,
-                        &serde_json::to_string(&SessionStatus::Error).unwrap().trim_matches('"'),
-                        None,
-                    ) {
+                    // 发送带错误详情的状态通知
+                    if let Err(send_err) = notification_sender
+                        .send_error_status(&session_id, &TerminalError::IoError(e))
+                    {
                         tracing::error!("发送错误状态通知失败: {}", send_err);
                     }
                     break;
@@ -214,11 +904,28 @@ pub fn start_output_reader(
             }
         }
 
+        // 读取结束时残留的不完整字节不会再被补全，原样发送
+        if !utf8_pending.is_empty() {
+            if let Err(e) = send_output_chunked(
+                &session_id,
+                &utf8_pending,
+                config.output_chunk_size,
+                &notification_sender,
+                &listeners,
+                &counters,
+            ) {
+                tracing::error!("发送输出通知失败: {}", e);
+            }
+        }
+
         tracing::debug!("输出读取器退出: {}", session_id);
+        stopped.then_some(source)
     });
 
     OutputReaderHandle {
         stop_tx,
+        paused,
+        draining,
         task_handle,
     }
 }
@@ -252,6 +959,41 @@ mod tests {
     use std::io::Cursor;
     use tokio::sync::mpsc as tokio_mpsc;
 
+    /// 创建测试用的共享会话信息
+    fn test_session_info() -> Arc<RwLock<SessionInfo>> {
+        Arc::new(RwLock::new(SessionInfo {
+            id: "test-session".to_string(),
+            connection_type: crate::rpc::types::ConnectionType::Local {
+                shell_path: None,
+                cwd: None,
+                env: None,
+            },
+            status: SessionStatus::Running,
+            title: None,
+            name: None,
+            cwd: None,
+            exit_code: None,
+            created_at: 0,
+            modes: Default::default(),
+            ssh: None,
+            exit_reason: None,
+            ended_at: None,
+            duration_secs: None,
+            pid: None,
+            resolved_addr: None,
+        }))
+    }
+
+    #[test]
+    fn test_buffer_size_clamped() {
+        let config = OutputReaderConfig::default().with_buffer_size(1);
+        assert_eq!(config.buffer_size, OutputReaderConfig::MIN_BUFFER_SIZE);
+        let config = OutputReaderConfig::default().with_buffer_size(usize::MAX);
+        assert_eq!(config.buffer_size, OutputReaderConfig::MAX_BUFFER_SIZE);
+        let config = OutputReaderConfig::default().with_buffer_size(16 * 1024);
+        assert_eq!(config.buffer_size, 16 * 1024);
+    }
+
     #[tokio::test]
     async fn test_output_reader_with_data() {
         // 创建测试数据
@@ -267,6 +1009,7 @@ mod tests {
             "test-session".to_string(),
             reader,
             sender,
+            None,
             OutputReaderConfig::default(),
         );
 
@@ -298,6 +1041,7 @@ mod tests {
             "test-session".to_string(),
             reader,
             sender,
+            None,
             OutputReaderConfig::default(),
         );
 
@@ -324,12 +1068,14 @@ mod tests {
         // 创建通知发送器
         let (tx, mut rx) = tokio_mpsc::unbounded_channel();
         let sender = NotificationSender::new_for_test(tx);
+        let info = test_session_info();
 
         // 启动输出读取器（启用 OSC 处理）
         let handle = start_output_reader(
             "test-session".to_string(),
             reader,
             sender,
+            Some(info.clone()),
             OutputReaderConfig::default(),
         );
 
@@ -349,6 +1095,9 @@ mod tests {
         let cwd_params = cwd_notif.unwrap().params.as_ref().unwrap();
         assert_eq!(cwd_params["cwd"], "/home/user");
 
+        // 会话信息中的工作目录也应更新
+        assert_eq!(info.read().await.cwd.as_deref(), Some("/home/user"));
+
         // 应该收到输出通知（不包含 OSC 序列）
         let output_notif = notifications.iter().find(|n| n.method == "terminal.output");
         assert!(output_notif.is_some(), "Should receive output notification");
@@ -358,41 +1107,282 @@ mod tests {
     }
 
     #[tokio::test]
-    async fn test_output_reader_with_osc52() {
-        // 创建包含 OSC 52 序列的测试数据
-        // "Hello" in base64 is "SGVsbG8="
-        let test_data = b"text\x1b]52;c;SGVsbG8=\x07more";
+    async fn test_output_reader_with_tmux_passthrough_osc52() {
+        // tmux 直通包装中的 OSC 52，内部 ESC 被转义为两个 ESC
+        let test_data = b"text\x1bPtmux;\x1b\x1b]52;c;SGVsbG8=\x07\x1b\\more";
         let reader: Box<dyn Read + Send> = Box::new(Cursor::new(test_data.to_vec()));
 
-        // 创建通知发送器
         let (tx, mut rx) = tokio_mpsc::unbounded_channel();
         let sender = NotificationSender::new_for_test(tx);
 
-        // 启动输出读取器
         let handle = start_output_reader(
             "test-session".to_string(),
             reader,
             sender,
+            None,
             OutputReaderConfig::default(),
         );
 
-        // 等待一段时间让读取器处理数据
         tokio::time::sleep(Duration::from_millis(100)).await;
 
-        // 收集所有通知
         let mut notifications = Vec::new();
         while let Ok(notif) = rx.try_recv() {
             notifications.push(notif);
         }
 
-        // 应该收到剪贴板通知
         let clipboard_notif = notifications.iter().find(|n| n.method == "session.clipboard");
-        assert!(clipboard_notif.is_some(), "Should receive clipboard notification");
-        
+        assert!(clipboard_notif.is_some(), "Should receive clipboard notification through tmux");
         let clipboard_params = clipboard_notif.unwrap().params.as_ref().unwrap();
-        assert_eq!(clipboard_params["content"], "Hello");
+        assert_eq!(clipboard_params["content"], "SGVsbG8=");
+        assert_eq!(clipboard_params["is_utf8"], true);
+
+        // 输出中不包含直通包装
+        let mut output = Vec::new();
+        for notif in notifications.iter().filter(|n| n.method == "terminal.output") {
+            output.extend(
+                codec::decode_input(notif.params.as_ref().unwrap()["data"].as_str().unwrap()).unwrap(),
+            );
+        }
+        assert_eq!(output, b"textmore");
+
+        handle.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_output_reader_with_osc52() {
+        // 创建包含 OSC 52 序列的测试数据
+        // "Hello" in base64 is "SGVsbG8="
+        let test_data = b"text\x1b]52;c;SGVsbG8=\x07more";
+        let reader: Box<dyn Read + Send> = Box::new(Cursor::new(test_data.to_vec()));
+
+        // 创建通知发送器
+        let (tx, mut rx) = tokio_mpsc::unbounded_channel();
+        let sender = NotificationSender::new_for_test(tx);
+
+        // 启动输出读取器
+        let handle = start_output_reader(
+            "test-session".to_string(),
+            reader,
+            sender,
+            None,
+            OutputReaderConfig::default(),
+        );
+
+        // 等待一段时间让读取器处理数据
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        // 收集所有通知
+        let mut notifications = Vec::new();
+        while let Ok(notif) = rx.try_recv() {
+            notifications.push(notif);
+        }
+
+        // 应该收到剪贴板通知
+        let clipboard_notif = notifications.iter().find(|n| n.method == "session.clipboard");
+        assert!(clipboard_notif.is_some(), "Should receive clipboard notification");
+        
+        let clipboard_params = clipboard_notif.unwrap().params.as_ref().unwrap();
+        assert_eq!(clipboard_params["content"], "SGVsbG8=");
+        assert_eq!(clipboard_params["is_utf8"], true);
+
+        // 停止读取器
+        handle.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_output_reader_binary_clipboard() {
+        // 不清理时非 UTF-8 的剪贴板内容原样以 Base64 发送
+        let bytes = [0xff, 0xfe, 0x00, 0x01];
+        let test_data = format!("\x1b]52;c;{}\x07", codec::encode_output(&bytes));
+        let reader: Box<dyn Read + Send> = Box::new(Cursor::new(test_data.into_bytes()));
+
+        let (tx, mut rx) = tokio_mpsc::unbounded_channel();
+        let sender = NotificationSender::new_for_test(tx);
+        let config = OutputReaderConfig {
+            sanitize_clipboard: false,
+            ..Default::default()
+        };
+        let handle = start_output_reader("test-session".to_string(), reader, sender, None, config);
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let mut notifications = Vec::new();
+        while let Ok(notif) = rx.try_recv() {
+            notifications.push(notif);
+        }
+
+        assert!(notifications.iter().all(|n| n.method != "session.clipboard_rejected"));
+        let clipboard_notif = notifications.iter().find(|n| n.method == "session.clipboard");
+        let clipboard_params = clipboard_notif.unwrap().params.as_ref().unwrap();
+        assert_eq!(clipboard_params["is_utf8"], false);
+        assert_eq!(
+            codec::decode_input(clipboard_params["content"].as_str().unwrap()).unwrap(),
+            bytes
+        );
+
+        handle.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_output_reader_clipboard_sanitization() {
+        let payload = "ls\x1b[201~\r\nrm -rf /tmp/x\n";
+
+        for sanitize in [true, false] {
+            let test_data = format!("\x1b]52;c;{}\x07", codec::encode_output(payload.as_bytes()));
+            let reader: Box<dyn Read + Send> = Box::new(Cursor::new(test_data.into_bytes()));
+
+            let (tx, mut rx) = tokio_mpsc::unbounded_channel();
+            let sender = NotificationSender::new_for_test(tx);
+            let config = OutputReaderConfig {
+                sanitize_clipboard: sanitize,
+                ..Default::default()
+            };
+            let handle = start_output_reader("test-session".to_string(), reader, sender, None, config);
+
+            tokio::time::sleep(Duration::from_millis(100)).await;
+
+            let mut notifications = Vec::new();
+            while let Ok(notif) = rx.try_recv() {
+                notifications.push(notif);
+            }
+            let clipboard = notifications
+                .iter()
+                .find(|n| n.method == "session.clipboard")
+                .expect("Should receive clipboard notification");
+            let content = codec::decode_input(
+                clipboard.params.as_ref().unwrap()["content"].as_str().unwrap(),
+            )
+            .unwrap();
+
+            let expected: &[u8] = if sanitize {
+                b"ls[201~\nrm -rf /tmp/x\n"
+            } else {
+                payload.as_bytes()
+            };
+            assert_eq!(content, expected, "sanitize = {}", sanitize);
+
+            handle.stop().await;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_output_reader_keeps_osc_when_not_stripping() {
+        let test_data = b"\x1b]2;vim\x07a\x1b]7;file:///tmp\x07b\x1b]52;c;SGVsbG8=\x07c";
+
+        for strip in [true, false] {
+            let reader: Box<dyn Read + Send> = Box::new(Cursor::new(test_data.to_vec()));
+            let (tx, mut rx) = tokio_mpsc::unbounded_channel();
+            let sender = NotificationSender::new_for_test(tx);
+            let config = OutputReaderConfig {
+                strip_osc_from_output: strip,
+                ..Default::default()
+            };
+            let handle = start_output_reader("test-session".to_string(), reader, sender, None, config);
+
+            tokio::time::sleep(Duration::from_millis(100)).await;
+
+            let mut notifications = Vec::new();
+            while let Ok(notif) = rx.try_recv() {
+                notifications.push(notif);
+            }
+
+            // 两种模式都发送副作用通知
+            for method in ["session.title", "session.cwd", "session.clipboard"] {
+                assert!(
+                    notifications.iter().any(|n| n.method == method),
+                    "strip = {}: missing {}",
+                    strip,
+                    method
+                );
+            }
+
+            let output: Vec<u8> = notifications
+                .iter()
+                .filter(|n| n.method == "terminal.output")
+                .flat_map(|n| codec::decode_input(n.params.as_ref().unwrap()["data"].as_str().unwrap()).unwrap())
+                .collect();
+            let expected: &[u8] = if strip { b"abc" } else { test_data };
+            assert_eq!(output, expected, "strip = {}", strip);
+
+            handle.stop().await;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_output_reader_command_finished() {
+        let test_data = "\x1b]133;A\x07$ \x1b]133;B\x07false\r\n\x1b]133;C\x07\x1b]133;D;1\x07\x1b]133;A\x07$ ";
+        let reader: Box<dyn Read + Send> = Box::new(Cursor::new(test_data.as_bytes().to_vec()));
+
+        let (tx, mut rx) = tokio_mpsc::unbounded_channel();
+        let sender = NotificationSender::new_for_test(tx);
+        let handle = start_output_reader(
+            "test-session".to_string(),
+            reader,
+            sender,
+            None,
+            OutputReaderConfig::default(),
+        );
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let mut notifications = Vec::new();
+        while let Ok(notif) = rx.try_recv() {
+            notifications.push(notif);
+        }
+
+        let finished: Vec<_> = notifications
+            .iter()
+            .filter(|n| n.method == "session.command_finished")
+            .collect();
+        assert_eq!(finished.len(), 1);
+        let params = finished[0].params.as_ref().unwrap();
+        assert_eq!(params["session_id"], "test-session");
+        assert_eq!(params["exit_code"], 1);
+        assert_eq!(params["command"], "false");
+
+        // 标记从输出中移除
+        let output: Vec<u8> = notifications
+            .iter()
+            .filter(|n| n.method == "terminal.output")
+            .flat_map(|n| codec::decode_input(n.params.as_ref().unwrap()["data"].as_str().unwrap()).unwrap())
+            .collect();
+        assert_eq!(output, b"$ false\r\n$ ");
+
+        handle.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_output_reader_clipboard_too_large() {
+        let payload = "A".repeat(64);
+        let test_data = format!("text\x1b]52;c;{}\x07more", payload);
+        let reader: Box<dyn Read + Send> = Box::new(Cursor::new(test_data.into_bytes()));
+
+        let (tx, mut rx) = tokio_mpsc::unbounded_channel();
+        let sender = NotificationSender::new_for_test(tx);
+
+        let config = OutputReaderConfig {
+            max_clipboard_size: 16,
+            ..Default::default()
+        };
+        let handle = start_output_reader("test-session".to_string(), reader, sender, None, config);
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let mut notifications = Vec::new();
+        while let Ok(notif) = rx.try_recv() {
+            notifications.push(notif);
+        }
+
+        assert!(notifications.iter().all(|n| n.method != "session.clipboard"));
+        let rejected = notifications
+            .iter()
+            .find(|n| n.method == "session.clipboard_rejected")
+            .expect("Should receive clipboard rejected notification");
+        let params = rejected.params.as_ref().unwrap();
+        assert_eq!(params["reason"], "too_large");
+        assert_eq!(params["size"], 64);
+        assert_eq!(params["limit"], 16);
 
-        // 停止读取器
         handle.stop().await;
     }
 
@@ -407,13 +1397,16 @@ mod tests {
         let sender = NotificationSender::new_for_test(tx);
 
         // 启动输出读取器（禁用 OSC 处理）
-        let mut config = OutputReaderConfig::default();
-        config.enable_osc_processing = false;
-        
+        let config = OutputReaderConfig {
+            enable_osc_processing: false,
+            ..Default::default()
+        };
+
         let handle = start_output_reader(
             "test-session".to_string(),
             reader,
             sender,
+            None,
             config,
         );
 
@@ -437,4 +1430,701 @@ mod tests {
         // 停止读取器
         handle.stop().await;
     }
+
+    #[tokio::test]
+    async fn test_output_reader_mode_tracking() {
+        let test_data = b"\x1b[?1049h\x1b[?1000;1006hscreen";
+        let reader: Box<dyn Read + Send> = Box::new(Cursor::new(test_data.to_vec()));
+
+        let (tx, mut rx) = tokio_mpsc::unbounded_channel();
+        let sender = NotificationSender::new_for_test(tx);
+
+        let info = test_session_info();
+
+        let handle = start_output_reader(
+            "test-session".to_string(),
+            reader,
+            sender,
+            Some(info.clone()),
+            OutputReaderConfig::default(),
+        );
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let mut notifications = Vec::new();
+        while let Ok(notif) = rx.try_recv() {
+            notifications.push(notif);
+        }
+
+        // 单次读取中的多个变更只产生一条通知
+        let mode_notifs: Vec<_> = notifications
+            .iter()
+            .filter(|n| n.method == "session.mode")
+            .collect();
+        assert_eq!(mode_notifs.len(), 1, "Should receive one mode notification");
+
+        let params = mode_notifs[0].params.as_ref().unwrap();
+        assert_eq!(params["modes"]["alt_screen"], true);
+        assert_eq!(params["modes"]["mouse_tracking"], "normal");
+        assert_eq!(params["modes"]["sgr_mouse"], true);
+
+        // 会话信息应反映最新模式
+        let modes = info.read().await.modes.clone();
+        assert!(modes.alt_screen);
+        assert!(modes.sgr_mouse);
+
+        handle.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_output_reader_with_osc_color() {
+        let test_data = b"\x1b]4;1;rgb:ff/00/00\x07a\x1b]11;?\x1b\\b";
+        let reader: Box<dyn Read + Send> = Box::new(Cursor::new(test_data.to_vec()));
+
+        let (tx, mut rx) = tokio_mpsc::unbounded_channel();
+        let sender = NotificationSender::new_for_test(tx);
+
+        let handle = start_output_reader(
+            "test-session".to_string(),
+            reader,
+            sender,
+            None,
+            OutputReaderConfig::default(),
+        );
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let mut notifications = Vec::new();
+        while let Ok(notif) = rx.try_recv() {
+            notifications.push(notif);
+        }
+
+        let colors: Vec<_> = notifications
+            .iter()
+            .filter(|n| n.method == "session.color")
+            .map(|n| n.params.clone().unwrap())
+            .collect();
+        assert_eq!(colors.len(), 2);
+        assert_eq!(colors[0]["target"], "palette");
+        assert_eq!(colors[0]["index"], 1);
+        assert_eq!(colors[0]["color"], "#ff0000");
+        assert_eq!(colors[0]["query"], false);
+        assert_eq!(colors[1]["target"], "background");
+        assert_eq!(colors[1]["query"], true);
+        assert!(colors[1].get("color").is_none());
+
+        // 颜色序列不出现在输出中
+        let output = notifications.iter().find(|n| n.method == "terminal.output").unwrap();
+        let data = codec::decode_input(output.params.as_ref().unwrap()["data"].as_str().unwrap()).unwrap();
+        assert_eq!(data, b"ab");
+
+        handle.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_output_reader_with_osc_title() {
+        let test_data = b"\x1b]2;vim README.md\x07text";
+        let reader: Box<dyn Read + Send> = Box::new(Cursor::new(test_data.to_vec()));
+
+        let (tx, mut rx) = tokio_mpsc::unbounded_channel();
+        let sender = NotificationSender::new_for_test(tx);
+
+        let info = test_session_info();
+
+        let handle = start_output_reader(
+            "test-session".to_string(),
+            reader,
+            sender,
+            Some(info.clone()),
+            OutputReaderConfig::default(),
+        );
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let mut notifications = Vec::new();
+        while let Ok(notif) = rx.try_recv() {
+            notifications.push(notif);
+        }
+
+        let title_notif = notifications.iter().find(|n| n.method == "session.title");
+        assert!(title_notif.is_some(), "Should receive title notification");
+        assert_eq!(title_notif.unwrap().params.as_ref().unwrap()["title"], "vim README.md");
+
+        assert_eq!(info.read().await.title.as_deref(), Some("vim README.md"));
+
+        handle.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_output_reader_chunks_large_read() {
+        // 1MB 的单次读取
+        let test_data: Vec<u8> = (0..1024 * 1024).map(|i| b'a' + (i % 26) as u8).collect();
+        let reader: Box<dyn Read + Send> = Box::new(Cursor::new(test_data.clone()));
+
+        let (tx, mut rx) = tokio_mpsc::unbounded_channel();
+        let sender = NotificationSender::new_for_test(tx);
+
+        let config = OutputReaderConfig {
+            buffer_size: 1024 * 1024,
+            output_chunk_size: 64 * 1024,
+            ..Default::default()
+        };
+
+        let handle = start_output_reader("test-session".to_string(), reader, sender, None, config);
+
+        tokio::time::sleep(Duration::from_millis(300)).await;
+
+        let mut chunks = Vec::new();
+        while let Ok(notif) = rx.try_recv() {
+            if notif.method == "terminal.output" {
+                chunks.push(notif.params.unwrap());
+            }
+        }
+
+        assert_eq!(chunks.len(), 16, "1MB should be split into 16 chunks of 64KB");
+
+        // 序号连续递增，只有最后一块标记为 final
+        let mut reassembled = Vec::new();
+        for (i, chunk) in chunks.iter().enumerate() {
+            assert_eq!(chunk["seq"], i as u64);
+            assert_eq!(chunk["final"], i == chunks.len() - 1);
+            let decoded = codec::decode_input(chunk["data"].as_str().unwrap()).unwrap();
+            reassembled.extend_from_slice(&decoded);
+        }
+        assert_eq!(reassembled, test_data);
+
+        handle.stop().await;
+    }
+
+    /// 按需提供数据的测试 reader，发送端关闭时返回 EOF
+    struct ChannelReader {
+        rx: std::sync::mpsc::Receiver<Vec<u8>>,
+    }
+
+    impl Read for ChannelReader {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            match self.rx.recv() {
+                Ok(data) => {
+                    let n = data.len().min(buf.len());
+                    buf[..n].copy_from_slice(&data[..n]);
+                    Ok(n)
+                }
+                Err(_) => Ok(0),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_output_seq_continues_across_readers() {
+        let (tx, mut rx) = tokio_mpsc::unbounded_channel();
+        let sender = NotificationSender::new_for_test(tx);
+        let counters = Arc::new(ByteCounters::new());
+        let config = OutputReaderConfig {
+            output_chunk_size: 4,
+            ..Default::default()
+        };
+
+        // 每次读取单独发送，10 字节按 4 字节拆成 3 块
+        let (data_tx, data_rx) = std::sync::mpsc::channel();
+        let reader: Box<dyn Read + Send> = Box::new(ChannelReader { rx: data_rx });
+        let handle = start_output_reader_with_listeners(
+            "test-session".to_string(),
+            reader,
+            sender.clone(),
+            OutputListeners::new(),
+            counters.clone(),
+            None,
+            config.clone(),
+        );
+        for data in ["first", "second", "0123456789"] {
+            data_tx.send(data.as_bytes().to_vec()).unwrap();
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        drop(data_tx);
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        handle.stop().await;
+
+        // 重新启动读取器（如重新附加）后序号继续递增
+        let reader: Box<dyn Read + Send> = Box::new(Cursor::new(b"again".to_vec()));
+        let handle = start_output_reader_with_listeners(
+            "test-session".to_string(),
+            reader,
+            sender,
+            OutputListeners::new(),
+            counters,
+            None,
+            config,
+        );
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        handle.stop().await;
+
+        let mut seqs = Vec::new();
+        let mut output = Vec::new();
+        while let Ok(notif) = rx.try_recv() {
+            if notif.method == "terminal.output" {
+                let params = notif.params.unwrap();
+                seqs.push(params["seq"].as_u64().unwrap());
+                output.extend(codec::decode_input(params["data"].as_str().unwrap()).unwrap());
+            }
+        }
+        assert_eq!(output, b"firstsecond0123456789again");
+        assert_eq!(seqs, (0..seqs.len() as u64).collect::<Vec<_>>());
+        assert_eq!(seqs.len(), 9);
+    }
+
+    #[tokio::test]
+    async fn test_output_reader_pause_resume() {
+        let (data_tx, data_rx) = std::sync::mpsc::channel();
+        let reader: Box<dyn Read + Send> = Box::new(ChannelReader { rx: data_rx });
+
+        let (tx, mut rx) = tokio_mpsc::unbounded_channel();
+        let sender = NotificationSender::new_for_test(tx);
+
+        let handle = start_output_reader(
+            "test-session".to_string(),
+            reader,
+            sender,
+            None,
+            OutputReaderConfig::default(),
+        );
+
+        handle.pause();
+        assert!(handle.is_paused());
+
+        data_tx.send(b"while paused".to_vec()).unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(rx.try_recv().is_err(), "Should not receive output while paused");
+
+        handle.resume();
+        assert!(!handle.is_paused());
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let notif = rx.try_recv().expect("Should receive output after resume");
+        assert_eq!(notif.method, "terminal.output");
+
+        data_tx.send(b"after resume".to_vec()).unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(rx.try_recv().is_ok(), "Should keep streaming after resume");
+
+        drop(data_tx);
+        handle.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_output_reader_waits_for_client() {
+        let (data_tx, data_rx) = std::sync::mpsc::channel();
+        let reader: Box<dyn Read + Send> = Box::new(ChannelReader { rx: data_rx });
+
+        let (tx, mut rx) = tokio_mpsc::unbounded_channel();
+        let sender = NotificationSender::new_for_test(tx);
+        sender.set_attached(false);
+
+        let handle = start_output_reader(
+            "test-session".to_string(),
+            reader,
+            sender.clone(),
+            None,
+            OutputReaderConfig::default(),
+        );
+
+        // 没有客户端时输出留在来源中，不进入通知队列
+        data_tx.send(b"while detached".to_vec()).unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(rx.try_recv().is_err(), "Should not queue output without a client");
+        assert!(!handle.is_paused());
+
+        sender.set_attached(true);
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(collect_output(&mut rx), b"while detached");
+
+        drop(data_tx);
+        handle.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_output_reader_alt_screen_split_across_reads() {
+        let (data_tx, data_rx) = std::sync::mpsc::channel();
+        let reader: Box<dyn Read + Send> = Box::new(ChannelReader { rx: data_rx });
+
+        let (tx, mut rx) = tokio_mpsc::unbounded_channel();
+        let sender = NotificationSender::new_for_test(tx);
+        let info = test_session_info();
+
+        let handle = start_output_reader(
+            "test-session".to_string(),
+            reader,
+            sender,
+            Some(info.clone()),
+            OutputReaderConfig::default(),
+        );
+
+        let alt_screen_notifications = |rx: &mut tokio_mpsc::UnboundedReceiver<JsonRpcNotification>| {
+            let mut values = Vec::new();
+            while let Ok(notif) = rx.try_recv() {
+                if notif.method == "session.mode" {
+                    values.push(notif.params.unwrap()["modes"]["alt_screen"].as_bool().unwrap());
+                }
+            }
+            values
+        };
+
+        // 进入备用屏幕的序列被拆在两次读取之间
+        data_tx.send(b"vim\x1b[?10".to_vec()).unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(alt_screen_notifications(&mut rx).is_empty());
+        assert!(!info.read().await.modes.alt_screen);
+
+        data_tx.send(b"49h".to_vec()).unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(alt_screen_notifications(&mut rx), vec![true]);
+        assert!(info.read().await.modes.alt_screen);
+
+        // 离开备用屏幕，ESC 单独落在上一次读取的末尾
+        data_tx.send(b"editing\x1b".to_vec()).unwrap();
+        data_tx.send(b"[?1049l$ ".to_vec()).unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(alt_screen_notifications(&mut rx), vec![false]);
+        assert!(!info.read().await.modes.alt_screen);
+
+        drop(data_tx);
+        handle.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_output_reader_injected_output() {
+        let (data_tx, data_rx) = std::sync::mpsc::channel::<Vec<u8>>();
+        let reader: Box<dyn Read + Send> = Box::new(ChannelReader { rx: data_rx });
+        let source = OutputSource::spawn("test-session", reader, 4096);
+        let injector = source.injector();
+
+        let (tx, mut rx) = tokio_mpsc::unbounded_channel();
+        let sender = NotificationSender::new_for_test(tx);
+        let info = test_session_info();
+
+        let handle = start_output_reader_from_source(
+            "test-session".to_string(),
+            source,
+            sender,
+            OutputListeners::new(),
+            Arc::new(ByteCounters::new()),
+            Some(info.clone()),
+            OutputReaderConfig {
+                read_timeout: Duration::from_millis(20),
+                ..Default::default()
+            },
+        );
+
+        // PTY 没有输出时注入的数据也会发送，并且经过 OSC 处理
+        injector.inject(b"\x1b]2;injected\x07Connection lost\r\n".to_vec());
+        injector.inject(Vec::new());
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        assert_eq!(collect_output(&mut rx), b"Connection lost\r\n");
+        assert_eq!(info.read().await.title.as_deref(), Some("injected"));
+        assert!(!handle.is_finished(), "Empty injection must not be treated as EOF");
+
+        drop(data_tx);
+        handle.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_output_reader_multibyte_char_split_across_reads() {
+        let (data_tx, data_rx) = std::sync::mpsc::channel();
+        let reader: Box<dyn Read + Send> = Box::new(ChannelReader { rx: data_rx });
+
+        let (tx, mut rx) = tokio_mpsc::unbounded_channel();
+        let sender = NotificationSender::new_for_test(tx);
+        let info = test_session_info();
+
+        let handle = start_output_reader(
+            "test-session".to_string(),
+            reader,
+            sender,
+            Some(info.clone()),
+            OutputReaderConfig::default(),
+        );
+
+        // "中" 的三个字节被拆在两次读取之间，OSC 序列紧随其后
+        let text = "中\x1b]2;标题\x07文".as_bytes();
+        data_tx.send(text[..2].to_vec()).unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        data_tx.send(text[2..].to_vec()).unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let mut notifications = Vec::new();
+        while let Ok(notif) = rx.try_recv() {
+            notifications.push(notif);
+        }
+
+        let title_notif = notifications.iter().find(|n| n.method == "session.title");
+        assert!(title_notif.is_some(), "Should detect OSC after split multibyte char");
+        assert_eq!(info.read().await.title.as_deref(), Some("标题"));
+
+        // 输出中不包含 OSC 序列，且多字节字符完整
+        let mut output = Vec::new();
+        for notif in notifications.iter().filter(|n| n.method == "terminal.output") {
+            output.extend(
+                codec::decode_input(notif.params.as_ref().unwrap()["data"].as_str().unwrap()).unwrap(),
+            );
+        }
+        assert_eq!(String::from_utf8(output).unwrap(), "中文");
+
+        drop(data_tx);
+        handle.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_output_reader_flushes_incomplete_tail_on_eof() {
+        let (data_tx, data_rx) = std::sync::mpsc::channel();
+        let reader: Box<dyn Read + Send> = Box::new(ChannelReader { rx: data_rx });
+
+        let (tx, mut rx) = tokio_mpsc::unbounded_channel();
+        let sender = NotificationSender::new_for_test(tx);
+
+        let handle = start_output_reader(
+            "test-session".to_string(),
+            reader,
+            sender,
+            None,
+            OutputReaderConfig::default(),
+        );
+
+        data_tx.send(b"ab\xe4\xb8".to_vec()).unwrap();
+        drop(data_tx);
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let mut output = Vec::new();
+        while let Ok(notif) = rx.try_recv() {
+            if notif.method == "terminal.output" {
+                output.extend(
+                    codec::decode_input(notif.params.unwrap()["data"].as_str().unwrap()).unwrap(),
+                );
+            }
+        }
+        assert_eq!(output, b"ab\xe4\xb8");
+        assert!(handle.is_finished());
+    }
+
+    #[test]
+    fn test_complete_utf8_len() {
+        assert_eq!(complete_utf8_len(b"abc"), 3);
+        assert_eq!(complete_utf8_len("a中".as_bytes()), 4);
+        // 末尾被截断的多字节字符
+        assert_eq!(complete_utf8_len(b"a\xe4\xb8"), 1);
+        assert_eq!(complete_utf8_len(b"\xe4"), 0);
+        // 非法字节不保留
+        assert_eq!(complete_utf8_len(b"a\xff"), 2);
+        assert_eq!(complete_utf8_len(b"\xffa\xe4\xb8"), 4);
+    }
+
+    #[tokio::test]
+    async fn test_output_reader_exit_detected_while_paused() {
+        let (data_tx, data_rx) = std::sync::mpsc::channel::<Vec<u8>>();
+        let reader: Box<dyn Read + Send> = Box::new(ChannelReader { rx: data_rx });
+
+        let (tx, mut rx) = tokio_mpsc::unbounded_channel();
+        let sender = NotificationSender::new_for_test(tx);
+
+        let handle = start_output_reader(
+            "test-session".to_string(),
+            reader,
+            sender,
+            None,
+            OutputReaderConfig::default(),
+        );
+
+        handle.pause();
+
+        // 进程退出（EOF）在暂停期间仍然应该被报告
+        drop(data_tx);
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let notif = rx.try_recv().expect("Should receive status notification while paused");
+        assert_eq!(notif.method, "session.status");
+        assert!(handle.is_finished());
+    }
+
+    /// 收集通知中的 `terminal.output` 数据
+    fn collect_output(rx: &mut tokio_mpsc::UnboundedReceiver<JsonRpcNotification>) -> Vec<u8> {
+        let mut output = Vec::new();
+        while let Ok(notif) = rx.try_recv() {
+            if notif.method == "terminal.output" {
+                output.extend(
+                    codec::decode_input(notif.params.unwrap()["data"].as_str().unwrap()).unwrap(),
+                );
+            }
+        }
+        output
+    }
+
+    #[tokio::test]
+    async fn test_output_reader_fans_out_to_listeners() {
+        let (data_tx, data_rx) = std::sync::mpsc::channel();
+        let reader: Box<dyn Read + Send> = Box::new(ChannelReader { rx: data_rx });
+
+        let (tx, mut rx) = tokio_mpsc::unbounded_channel();
+        let listeners = OutputListeners::new();
+        let (first_tx, mut first_rx) = tokio_mpsc::unbounded_channel();
+        let first = listeners.add(NotificationSender::new_for_test(first_tx));
+        let (second_tx, mut second_rx) = tokio_mpsc::unbounded_channel();
+        let _second = listeners.add(NotificationSender::new_for_test(second_tx));
+        assert_eq!(listeners.len(), 2);
+        let counters = Arc::new(ByteCounters::new());
+
+        let handle = start_output_reader_with_listeners(
+            "test-session".to_string(),
+            reader,
+            NotificationSender::new_for_test(tx),
+            listeners.clone(),
+            counters.clone(),
+            None,
+            OutputReaderConfig::default(),
+        );
+
+        data_tx.send(b"hello".to_vec()).unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(collect_output(&mut rx), b"hello");
+        assert_eq!(collect_output(&mut first_rx), b"hello");
+        assert_eq!(collect_output(&mut second_rx), b"hello");
+
+        // 读取过程中移除一个监听器
+        drop(first);
+        assert_eq!(listeners.len(), 1);
+        data_tx.send(b"world".to_vec()).unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(collect_output(&mut rx), b"world");
+        assert!(collect_output(&mut first_rx).is_empty());
+        assert_eq!(collect_output(&mut second_rx), b"world");
+
+        // 只统计发送给主通知发送器的字节
+        assert_eq!(counters.bytes_out(), 10);
+        assert_eq!(counters.bytes_in(), 0);
+
+        drop(data_tx);
+        handle.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_output_reader_drops_closed_listener() {
+        let (data_tx, data_rx) = std::sync::mpsc::channel();
+        let reader: Box<dyn Read + Send> = Box::new(ChannelReader { rx: data_rx });
+
+        let (tx, mut rx) = tokio_mpsc::unbounded_channel();
+        let listeners = OutputListeners::new();
+        let (listener_tx, listener_rx) = tokio_mpsc::unbounded_channel();
+        let _listener = listeners.add(NotificationSender::new_for_test(listener_tx));
+
+        let handle = start_output_reader_with_listeners(
+            "test-session".to_string(),
+            reader,
+            NotificationSender::new_for_test(tx),
+            listeners.clone(),
+            Arc::new(ByteCounters::new()),
+            None,
+            OutputReaderConfig::default(),
+        );
+
+        // 监听器的接收端关闭不影响主输出
+        drop(listener_rx);
+        data_tx.send(b"data".to_vec()).unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(collect_output(&mut rx), b"data");
+        assert!(listeners.is_empty());
+
+        drop(data_tx);
+        handle.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_output_reader_idle_stops_promptly() {
+        // reader 一直阻塞，没有任何输出
+        let (data_tx, data_rx) = std::sync::mpsc::channel::<Vec<u8>>();
+        let reader: Box<dyn Read + Send> = Box::new(ChannelReader { rx: data_rx });
+
+        let (tx, mut rx) = tokio_mpsc::unbounded_channel();
+        let sender = NotificationSender::new_for_test(tx);
+
+        let handle = start_output_reader(
+            "test-session".to_string(),
+            reader,
+            sender,
+            None,
+            OutputReaderConfig {
+                read_timeout: Duration::from_millis(20),
+                ..Default::default()
+            },
+        );
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!handle.is_finished());
+
+        let started = std::time::Instant::now();
+        tokio::time::timeout(Duration::from_secs(1), handle.stop())
+            .await
+            .expect("Idle reader should stop without waiting for output");
+        assert!(started.elapsed() < Duration::from_millis(500));
+
+        // 停止信号导致的退出不发送状态通知
+        assert!(rx.try_recv().is_err());
+        drop(data_tx);
+    }
+
+    #[tokio::test]
+    async fn test_output_reader_rate_limited() {
+        // 2000 字节/秒，突发额度 200 字节：1000 字节约需 0.4 秒
+        let data = vec![b'x'; 1000];
+        let reader: Box<dyn Read + Send> = Box::new(Cursor::new(data.clone()));
+
+        let (tx, mut rx) = tokio_mpsc::unbounded_channel();
+        let sender = NotificationSender::new_for_test(tx);
+
+        let started = std::time::Instant::now();
+        let handle = start_output_reader(
+            "test-session".to_string(),
+            reader,
+            sender,
+            None,
+            OutputReaderConfig {
+                rate_limit_bytes_per_sec: Some(2000),
+                ..Default::default()
+            },
+        );
+
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        let mut output = collect_output(&mut rx);
+        assert!(
+            !output.is_empty() && output.len() < data.len(),
+            "output should be paced, got {} bytes after 150ms",
+            output.len()
+        );
+
+        // EOF 紧跟最后一块输出报告，不额外等待
+        let mut done = false;
+        while !done {
+            let notif = tokio::time::timeout(Duration::from_secs(2), rx.recv())
+                .await
+                .expect("Rate limited output should finish")
+                .unwrap();
+            match notif.method.as_str() {
+                "terminal.output" => output.extend(
+                    codec::decode_input(notif.params.unwrap()["data"].as_str().unwrap()).unwrap(),
+                ),
+                "session.status" => done = true,
+                _ => {}
+            }
+        }
+        let elapsed = started.elapsed();
+
+        assert_eq!(output, data);
+        assert!(elapsed >= Duration::from_millis(350), "finished too fast: {:?}", elapsed);
+        assert!(elapsed < Duration::from_millis(1500), "finished too slow: {:?}", elapsed);
+        handle.stop().await;
+    }
+
+    #[test]
+    fn test_rate_limiter_zero_disabled() {
+        assert!(RateLimiter::new(0).is_none());
+        assert_eq!(RateLimiter::new(5).unwrap().burst(), 1);
+        assert_eq!(RateLimiter::new(10_000).unwrap().burst(), 1000);
+    }
 }
@@ -0,0 +1,581 @@
+//! 不分配 PTY 的本地进程
+//!
+//! 通过系统 shell（Unix 上 `sh -c`，Windows 上 `cmd /C`）运行命令，stdin/stdout/stderr
+//! 都使用管道，适合在 TTY 下行为不同的非交互式工具。stdout 和 stderr 的输出都作为
+//! `terminal.output` 通知发送，进程退出后发送带退出码的 `done` 状态通知。
+//! 没有终端尺寸，调整大小不做任何事；不处理 OSC 序列和终端模式。
+
+use std::collections::HashMap;
+use std::process::{ExitStatus, Stdio};
+use std::sync::Arc;
+
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt};
+use tokio::process::{Child, ChildStderr, ChildStdin, ChildStdout, Command};
+use tokio::sync::{mpsc, Mutex, RwLock};
+use tokio::task::JoinHandle;
+
+use crate::rpc::server::NotificationSender;
+use crate::rpc::types::{SessionInfo, SessionStatus, SessionTiming};
+use crate::utils::codec;
+use crate::utils::error::TerminalError;
+use crate::utils::time::SharedClock;
+
+use super::local::inherited_env;
+use super::output::OutputListeners;
+use super::stats::ByteCounters;
+
+/// 启动输出任务前保留的子进程和输出管道
+struct PendingProcess {
+    child: Child,
+    stdout: ChildStdout,
+    stderr: ChildStderr,
+}
+
+/// 通过管道通信的本地进程
+pub struct LocalProcess {
+    /// 子进程 PID
+    pid: Option<u32>,
+    /// 子进程输入，关闭输入后为 `None`
+    stdin: Mutex<Option<ChildStdin>>,
+    /// 尚未启动输出任务的子进程
+    pending: std::sync::Mutex<Option<PendingProcess>>,
+    /// 通知输出任务终止子进程
+    kill_tx: Option<mpsc::Sender<()>>,
+    /// 输出任务句柄
+    task: Option<JoinHandle<()>>,
+}
+
+impl LocalProcess {
+    /// 启动进程，输出在 [`start`](Self::start) 之前留在管道中
+    ///
+    /// `cwd` 应为已校验的目录。与本地 PTY 会话相同，只继承基础变量和 `inherit_env`
+    /// 中的变量，`env` 追加在它们之上。
+    pub fn spawn(
+        command: &str,
+        cwd: Option<&str>,
+        env: Option<&HashMap<String, String>>,
+        inherit_env: &[String],
+    ) -> Result<Self, TerminalError> {
+        let mut cmd = shell_command(command);
+        if let Some(dir) = cwd {
+            cmd.current_dir(dir);
+        }
+        cmd.env_clear();
+        cmd.envs(inherited_env(inherit_env));
+        if let Some(env) = env {
+            cmd.envs(env);
+        }
+        cmd.stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
+
+        let mut child = cmd
+            .spawn()
+            .map_err(|e| TerminalError::PtyCreationFailed(format!("启动进程失败: {}: {}", command, e)))?;
+        let stdin = child.stdin.take();
+        let (Some(stdout), Some(stderr)) = (child.stdout.take(), child.stderr.take()) else {
+            return Err(TerminalError::PtyCreationFailed("无法获取进程输出管道".to_string()));
+        };
+
+        Ok(Self {
+            pid: child.id(),
+            stdin: Mutex::new(stdin),
+            pending: std::sync::Mutex::new(Some(PendingProcess {
+                child,
+                stdout,
+                stderr,
+            })),
+            kill_tx: None,
+            task: None,
+        })
+    }
+
+    /// 启动输出任务
+    ///
+    /// 转发 stdout 和 stderr 直到两者都关闭，然后等待进程退出，更新 `session_info`
+    /// （包括按 `clock` 记录的结束时间）并发送状态通知。通过 [`kill`](Self::kill)
+    /// 终止时不发送状态通知。
+    #[allow(clippy::too_many_arguments)]
+    pub fn start(
+        &mut self,
+        session_id: String,
+        notification_sender: NotificationSender,
+        listeners: OutputListeners,
+        counters: Arc<ByteCounters>,
+        session_info: Arc<RwLock<SessionInfo>>,
+        buffer_size: usize,
+        clock: SharedClock,
+    ) -> Result<(), TerminalError> {
+        let pending = lock_pending(&self.pending).take().ok_or_else(|| {
+            TerminalError::InvalidRequest(format!("输出读取器已经启动: {}", session_id))
+        })?;
+
+        let (kill_tx, kill_rx) = mpsc::channel(1);
+        self.kill_tx = Some(kill_tx);
+        self.task = Some(tokio::spawn(run_process(
+            session_id,
+            pending,
+            kill_rx,
+            notification_sender,
+            listeners,
+            counters,
+            session_info,
+            buffer_size,
+            clock,
+        )));
+        Ok(())
+    }
+
+    /// 子进程 PID
+    pub fn process_id(&self) -> Option<u32> {
+        self.pid
+    }
+
+    /// 写入进程输入
+    ///
+    /// 输入已关闭或进程不再读取时返回 `SessionClosed`。
+    pub async fn write(&self, session_id: &str, data: &[u8]) -> Result<(), TerminalError> {
+        let mut stdin = self.stdin.lock().await;
+        let pipe = stdin
+            .as_mut()
+            .ok_or_else(|| TerminalError::SessionClosed(format!("{}: 输入已关闭", session_id)))?;
+
+        let result = match pipe.write_all(data).await {
+            Ok(()) => pipe.flush().await,
+            Err(e) => Err(e),
+        };
+        result.map_err(|e| match e.kind() {
+            std::io::ErrorKind::BrokenPipe => {
+                TerminalError::SessionClosed(format!("{}: {}", session_id, e))
+            }
+            _ => TerminalError::IoError(e),
+        })
+    }
+
+    /// 刷新进程输入，等待正在进行的写入完成
+    ///
+    /// 输入已关闭或进程不再读取时返回 `SessionClosed`。
+    pub async fn flush(&self, session_id: &str) -> Result<(), TerminalError> {
+        let mut stdin = self.stdin.lock().await;
+        let pipe = stdin
+            .as_mut()
+            .ok_or_else(|| TerminalError::SessionClosed(format!("{}: 输入已关闭", session_id)))?;
+        pipe.flush()
+            .await
+            .map_err(|e| TerminalError::SessionClosed(format!("{}: {}", session_id, e)))
+    }
+
+    /// 关闭进程输入，进程读到 EOF
+    pub async fn close_input(&self) {
+        self.stdin.lock().await.take();
+    }
+
+    /// 终止进程
+    pub fn kill(&self) -> Result<(), TerminalError> {
+        if let Some(pending) = lock_pending(&self.pending).as_mut() {
+            return pending.child.start_kill().map_err(TerminalError::IoError);
+        }
+        if let Some(kill_tx) = &self.kill_tx {
+            let _ = kill_tx.try_send(());
+        }
+        Ok(())
+    }
+
+    /// 输出任务是否已结束，未启动时返回 false
+    pub fn is_finished(&self) -> bool {
+        self.task.as_ref().is_some_and(|task| task.is_finished())
+    }
+
+    /// 等待输出任务发送剩余输出后退出，超时后中止
+    pub async fn wait_finished(&mut self, timeout: std::time::Duration) {
+        let Some(mut task) = self.task.take() else {
+            return;
+        };
+        if tokio::time::timeout(timeout, &mut task).await.is_err() {
+            tracing::warn!("等待进程输出结束超时");
+            task.abort();
+        }
+    }
+}
+
+/// 通过系统 shell 运行命令
+fn shell_command(command: &str) -> Command {
+    #[cfg(unix)]
+    {
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c").arg(command);
+        cmd
+    }
+
+    #[cfg(windows)]
+    {
+        let mut cmd = Command::new("cmd.exe");
+        cmd.arg("/C").arg(command);
+        cmd
+    }
+}
+
+fn lock_pending(
+    pending: &std::sync::Mutex<Option<PendingProcess>>,
+) -> std::sync::MutexGuard<'_, Option<PendingProcess>> {
+    pending.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// 输出任务：转发输出并报告退出状态
+#[allow(clippy::too_many_arguments)]
+async fn run_process(
+    session_id: String,
+    pending: PendingProcess,
+    mut kill_rx: mpsc::Receiver<()>,
+    notification_sender: NotificationSender,
+    listeners: OutputListeners,
+    counters: Arc<ByteCounters>,
+    session_info: Arc<RwLock<SessionInfo>>,
+    buffer_size: usize,
+    clock: SharedClock,
+) {
+    let PendingProcess {
+        mut child,
+        mut stdout,
+        mut stderr,
+    } = pending;
+    let mut stdout_buffer = vec![0u8; buffer_size];
+    let mut stderr_buffer = vec![0u8; buffer_size];
+    let (mut stdout_open, mut stderr_open) = (true, true);
+    let mut killed = false;
+
+    // 终止后继续读取，把已经写入管道的输出发送完
+    while stdout_open || stderr_open {
+        // 没有客户端接收通知时停止读取，由管道缓冲区对子进程施加背压
+        if !killed && !notification_sender.is_attached() {
+            tokio::select! {
+                _ = notification_sender.wait_attached() => {}
+                _ = kill_rx.recv() => {
+                    tracing::debug!("终止进程: {}", session_id);
+                    killed = true;
+                    let _ = child.start_kill();
+                }
+            }
+            continue;
+        }
+
+        let (result, from_stdout) = tokio::select! {
+            result = read_chunk(&mut stdout, &mut stdout_buffer), if stdout_open => (result, true),
+            result = read_chunk(&mut stderr, &mut stderr_buffer), if stderr_open => (result, false),
+            _ = kill_rx.recv(), if !killed => {
+                tracing::debug!("终止进程: {}", session_id);
+                killed = true;
+                let _ = child.start_kill();
+                continue;
+            }
+        };
+
+        let n = match result {
+            Ok(n) if n > 0 => n,
+            Ok(_) | Err(_) => {
+                if let Err(e) = result {
+                    tracing::warn!("读取进程输出错误: {}: {}", session_id, e);
+                }
+                if from_stdout {
+                    stdout_open = false;
+                } else {
+                    stderr_open = false;
+                }
+                continue;
+            }
+        };
+        let buffer = if from_stdout { &stdout_buffer } else { &stderr_buffer };
+
+        let encoded = codec::encode_output(&buffer[..n]);
+        let seq = counters.next_output_seq();
+        if let Err(e) = notification_sender.send_output(&session_id, &encoded, seq, true) {
+            tracing::error!("发送输出通知失败: {}", e);
+            break;
+        }
+        listeners.send_output(&session_id, &encoded, seq, true);
+        counters.add_out(n);
+    }
+
+    let status = tokio::select! {
+        status = child.wait() => status,
+        _ = kill_rx.recv(), if !killed => {
+            killed = true;
+            let _ = child.start_kill();
+            child.wait().await
+        }
+    };
+
+    let status = match status {
+        Ok(status) => status,
+        Err(e) => {
+            tracing::error!("等待进程退出失败: {}: {}", session_id, e);
+            session_info
+                .write()
+                .await
+                .mark_ended(SessionStatus::Error, clock.unix_secs());
+            if !killed {
+                if let Err(send_err) =
+                    notification_sender.send_error_status(&session_id, &TerminalError::IoError(e))
+                {
+                    tracing::error!("发送错误状态通知失败: {}", send_err);
+                }
+            }
+            return;
+        }
+    };
+
+    tracing::info!("进程退出: {} ({})", session_id, status);
+    let timing = {
+        let mut info = session_info.write().await;
+        info.mark_ended(SessionStatus::Done, clock.unix_secs());
+        info.exit_code = status.code();
+        info.timing()
+    };
+    if !killed {
+        send_exit_status(&notification_sender, &session_id, status, timing);
+    }
+}
+
+/// 读取一块输出，被中断时重试
+async fn read_chunk<R: AsyncRead + Unpin>(reader: &mut R, buffer: &mut [u8]) -> std::io::Result<usize> {
+    loop {
+        match reader.read(buffer).await {
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            result => return result,
+        }
+    }
+}
+
+/// 发送退出状态通知：正常退出时带退出码，被信号终止时带信号名
+fn send_exit_status(
+    sender: &NotificationSender,
+    session_id: &str,
+    status: ExitStatus,
+    timing: Option<SessionTiming>,
+) {
+    #[cfg(unix)]
+    if let Some(signal) = std::os::unix::process::ExitStatusExt::signal(&status) {
+        let core_dumped = std::os::unix::process::ExitStatusExt::core_dumped(&status);
+        if let Err(e) =
+            sender.send_signal_status(session_id, &signal_name(signal), core_dumped, None, timing)
+        {
+            tracing::error!("发送状态通知失败: {}", e);
+        }
+        return;
+    }
+
+    if let Err(e) = sender.send_status_with_reason(session_id, "done", status.code(), None, timing) {
+        tracing::error!("发送状态通知失败: {}", e);
+    }
+}
+
+/// 信号名（与 SSH 的 `exit-signal` 一致，不带 `SIG` 前缀）
+#[cfg(unix)]
+fn signal_name(signal: i32) -> String {
+    match signal {
+        libc::SIGABRT => "ABRT",
+        libc::SIGALRM => "ALRM",
+        libc::SIGFPE => "FPE",
+        libc::SIGHUP => "HUP",
+        libc::SIGILL => "ILL",
+        libc::SIGINT => "INT",
+        libc::SIGKILL => "KILL",
+        libc::SIGPIPE => "PIPE",
+        libc::SIGQUIT => "QUIT",
+        libc::SIGSEGV => "SEGV",
+        libc::SIGTERM => "TERM",
+        libc::SIGUSR1 => "USR1",
+        other => return other.to_string(),
+    }
+    .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rpc::types::{ConnectionType, JsonRpcNotification};
+    use crate::utils::time::system_clock;
+    use tokio::sync::mpsc::UnboundedReceiver;
+
+    fn test_session_info(command: &str) -> Arc<RwLock<SessionInfo>> {
+        Arc::new(RwLock::new(SessionInfo {
+            id: "test-session".to_string(),
+            connection_type: ConnectionType::LocalProcess {
+                command: command.to_string(),
+                cwd: None,
+                env: None,
+            },
+            status: SessionStatus::Running,
+            title: None,
+            name: None,
+            cwd: None,
+            exit_code: None,
+            created_at: 0,
+            modes: Default::default(),
+            ssh: None,
+            exit_reason: None,
+            ended_at: None,
+            duration_secs: None,
+            pid: None,
+            resolved_addr: None,
+        }))
+    }
+
+    /// 启动进程并收集输出，直到收到状态通知
+    async fn run_to_exit(
+        command: &str,
+        env: Option<&HashMap<String, String>>,
+    ) -> (Vec<u8>, serde_json::Value, Arc<RwLock<SessionInfo>>) {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let info = test_session_info(command);
+        let mut process = LocalProcess::spawn(command, None, env, &[]).unwrap();
+        process
+            .start(
+                "test-session".to_string(),
+                NotificationSender::new_for_test(tx),
+                OutputListeners::new(),
+                Arc::new(ByteCounters::new()),
+                info.clone(),
+                4096,
+                system_clock(),
+            )
+            .unwrap();
+
+        let (output, status) = collect_until_status(&mut rx).await;
+        (output, status, info)
+    }
+
+    async fn collect_until_status(
+        rx: &mut UnboundedReceiver<JsonRpcNotification>,
+    ) -> (Vec<u8>, serde_json::Value) {
+        let mut output = Vec::new();
+        loop {
+            let notification = tokio::time::timeout(std::time::Duration::from_secs(5), rx.recv())
+                .await
+                .expect("timed out waiting for notification")
+                .unwrap();
+            let params = notification.params.unwrap();
+            match notification.method.as_str() {
+                "terminal.output" => output
+                    .extend(codec::decode_input(params["data"].as_str().unwrap()).unwrap()),
+                "session.status" => return (output, params),
+                other => panic!("unexpected notification: {}", other),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_echo_output_and_exit_code() {
+        let (output, status, info) = run_to_exit("echo hi", None).await;
+        assert_eq!(output, b"hi\n");
+        assert_eq!(status["status"], "done");
+        assert_eq!(status["exit_code"], 0);
+
+        let info = info.read().await;
+        assert_eq!(info.status, SessionStatus::Done);
+        assert_eq!(info.exit_code, Some(0));
+        let ended_at = info.ended_at.unwrap();
+        assert!(ended_at >= info.created_at);
+        assert_eq!(status["ended_at"], ended_at);
+        assert_eq!(status["duration_secs"], info.duration_secs.unwrap());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_stderr_env_and_exit_code() {
+        let env = HashMap::from([("PROCESS_TEST_VAR".to_string(), "value".to_string())]);
+        let (output, status, _) =
+            run_to_exit("echo \"$PROCESS_TEST_VAR\" >&2; [ -t 1 ] && echo tty; exit 3", Some(&env)).await;
+        // stdout 不是 TTY
+        assert_eq!(output, b"value\n");
+        assert_eq!(status["exit_code"], 3);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_inherit_env_allowlist() {
+        std::env::set_var("TERMINAL_PROCESS_INHERITED", "passed");
+        std::env::set_var("TERMINAL_PROCESS_NOT_INHERITED", "hidden");
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let command = "echo \"[$TERMINAL_PROCESS_INHERITED][$TERMINAL_PROCESS_NOT_INHERITED]\"";
+        let mut process =
+            LocalProcess::spawn(command, None, None, &["TERMINAL_PROCESS_INHERITED".to_string()])
+                .unwrap();
+        process
+            .start(
+                "test-session".to_string(),
+                NotificationSender::new_for_test(tx),
+                OutputListeners::new(),
+                Arc::new(ByteCounters::new()),
+                test_session_info(command),
+                4096,
+                system_clock(),
+            )
+            .unwrap();
+
+        let (output, _) = collect_until_status(&mut rx).await;
+        assert_eq!(output, b"[passed][]\n");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_stdin_and_close_input() {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let mut process = LocalProcess::spawn("cat", None, None, &[]).unwrap();
+        process
+            .start(
+                "test-session".to_string(),
+                NotificationSender::new_for_test(tx),
+                OutputListeners::new(),
+                Arc::new(ByteCounters::new()),
+                test_session_info("cat"),
+                4096,
+                system_clock(),
+            )
+            .unwrap();
+
+        process.write("test-session", b"hello\n").await.unwrap();
+        process.close_input().await;
+        assert!(matches!(
+            process.write("test-session", b"more").await,
+            Err(TerminalError::SessionClosed(_))
+        ));
+
+        let (output, status) = collect_until_status(&mut rx).await;
+        assert_eq!(output, b"hello\n");
+        assert_eq!(status["exit_code"], 0);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_kill_without_status() {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let info = test_session_info("sleep 30");
+        let mut process = LocalProcess::spawn("exec sleep 30", None, None, &[]).unwrap();
+        process
+            .start(
+                "test-session".to_string(),
+                NotificationSender::new_for_test(tx),
+                OutputListeners::new(),
+                Arc::new(ByteCounters::new()),
+                info.clone(),
+                4096,
+                system_clock(),
+            )
+            .unwrap();
+
+        process.kill().unwrap();
+        process.wait_finished(std::time::Duration::from_secs(5)).await;
+        assert!(rx.try_recv().is_err());
+        assert_eq!(info.read().await.status, SessionStatus::Done);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_signal_name() {
+        assert_eq!(signal_name(libc::SIGKILL), "KILL");
+        assert_eq!(signal_name(libc::SIGTERM), "TERM");
+        assert_eq!(signal_name(libc::SIGWINCH), libc::SIGWINCH.to_string());
+    }
+}
@@ -5,120 +5,575 @@
 use std::collections::HashMap;
 use std::io::Read;
 use std::sync::Arc;
-use std::time::{SystemTime, UNIX_EPOCH};
-use tokio::sync::Mutex;
+use tokio::sync::{Mutex, RwLock};
 
 use crate::rpc::server::NotificationSender;
 use crate::rpc::types::{ConnectionType, SessionInfo, SessionStatus, TermSize};
 use crate::utils::error::TerminalError;
+use crate::utils::time::SharedClock;
 
-use super::local::LocalPty;
-use super::output::{start_output_reader, OutputReaderConfig, OutputReaderHandle};
+#[cfg(target_os = "linux")]
+use super::cwd::{start_cwd_poller, CwdPollerHandle};
+use super::idle::{start_idle_monitor, IdleMonitorHandle};
+use super::local::{resolve_cwd, LocalPty};
+use super::output::{
+    start_output_reader_from_source, OutputInjector, OutputListenerHandle, OutputListeners,
+    OutputReaderConfig, OutputReaderHandle, OutputSource,
+};
+use super::process::LocalProcess;
+use super::stats::ByteCounters;
+
+/// 默认的输入写入分块大小
+pub const DEFAULT_WRITE_CHUNK_SIZE: usize = 32 * 1024;
+/// 输入写入分块的最小值
+pub const MIN_WRITE_CHUNK_SIZE: usize = 512;
+/// 输入写入分块的最大值
+pub const MAX_WRITE_CHUNK_SIZE: usize = 1024 * 1024;
+
+/// 关闭会话时等待读取剩余输出的最长时间
+const OUTPUT_DRAIN_TIMEOUT: std::time::Duration = std::time::Duration::from_millis(500);
+
+/// 检查 IO 错误是否表示 PTY 另一端已关闭
+///
+/// Linux 上 slave 端全部关闭后写入 master 返回 `EIO`。
+fn is_closed_pipe(err: &std::io::Error) -> bool {
+    use std::io::ErrorKind;
+
+    if matches!(
+        err.kind(),
+        ErrorKind::BrokenPipe | ErrorKind::ConnectionReset | ErrorKind::UnexpectedEof
+    ) {
+        return true;
+    }
+
+    #[cfg(unix)]
+    if err.raw_os_error() == Some(libc::EIO) {
+        return true;
+    }
+
+    false
+}
+
+/// 批量操作中已校验的一步：输入已解码，信号已解析
+#[derive(Debug, Clone, PartialEq)]
+pub enum BatchStep {
+    /// 调整终端大小
+    Resize(TermSize),
+    /// 写入输入
+    Input(Vec<u8>),
+    /// 发送信号
+    Signal(i32),
+}
 
 /// PTY 会话
 pub struct PtySession {
-    /// 会话信息
-    pub info: SessionInfo,
+    /// 会话 ID
+    session_id: String,
+    /// 会话信息（与输出读取器共享）
+    info: Arc<RwLock<SessionInfo>>,
     /// 本地 PTY 实例（仅用于本地连接）
     local_pty: Option<Arc<Mutex<LocalPty>>>,
+    /// 不分配 PTY 的本地进程（仅用于本地进程连接）
+    local_process: Option<LocalProcess>,
     /// 输出读取器句柄
     output_reader: Option<OutputReaderHandle>,
+    /// 附加输出监听器（与输出读取器共享）
+    output_listeners: OutputListeners,
+    /// 分离输出读取器后保留的输出来源
+    detached_output: Option<OutputSource>,
+    /// 输入/输出字节计数器（与输出读取器共享）
+    counters: Arc<ByteCounters>,
+    /// 启动输出读取器时使用的配置
+    output_config: OutputReaderConfig,
+    /// 是否允许注入输出
+    allow_inject: bool,
+    /// 向 PTY 写入输入时每次写入的最大字节数
+    write_chunk_size: usize,
+    /// 输入写入队列，写入、同步、批量操作和关闭输入按请求顺序依次进行
+    ///
+    /// 需要同时持有 PTY 锁时先获取此锁。
+    write_queue: Arc<Mutex<()>>,
+    /// 是否只读（拒绝输入和调整大小）
+    read_only: bool,
+    /// 向输出来源注入输出的句柄，启动输出读取器后可用
+    output_injector: Option<OutputInjector>,
+    /// 工作目录轮询器句柄
+    #[cfg(target_os = "linux")]
+    cwd_poller: Option<CwdPollerHandle>,
+    /// 空闲监控器句柄
+    idle_monitor: Option<IdleMonitorHandle>,
+    /// 时间来源
+    clock: SharedClock,
 }
 
 impl PtySession {
     /// 创建新会话（不启动 PTY）
-    pub fn new(id: String, connection_type: ConnectionType) -> Self {
-        let created_at = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_secs();
+    pub fn new(id: String, connection_type: ConnectionType, clock: SharedClock) -> Self {
+        let created_at = clock.unix_secs();
 
         Self {
-            info: SessionInfo {
+            session_id: id.clone(),
+            info: Arc::new(RwLock::new(SessionInfo {
                 id,
                 connection_type,
                 status: SessionStatus::Init,
                 title: None,
+                name: None,
                 cwd: None,
                 exit_code: None,
                 created_at,
-            },
+                modes: Default::default(),
+                ssh: None,
+                exit_reason: None,
+                ended_at: None,
+                duration_secs: None,
+                pid: None,
+                resolved_addr: None,
+            })),
             local_pty: None,
+            local_process: None,
             output_reader: None,
+            output_listeners: OutputListeners::new(),
+            detached_output: None,
+            counters: Arc::new(ByteCounters::new()),
+            output_config: OutputReaderConfig {
+                clock: clock.clone(),
+                ..Default::default()
+            },
+            allow_inject: false,
+            write_chunk_size: DEFAULT_WRITE_CHUNK_SIZE,
+            write_queue: Arc::new(Mutex::new(())),
+            read_only: false,
+            output_injector: None,
+            #[cfg(target_os = "linux")]
+            cwd_poller: None,
+            idle_monitor: None,
+            clock,
         }
     }
 
     /// 创建并启动本地 PTY 会话
+    ///
+    /// `login_shell` 为 true 时在 Unix 上以登录 shell 启动，见 [`LocalPty::with_login_shell`]；
+    /// `inherit_env` 中的变量从插件环境继承，见 [`LocalPty::with_inherited_env`]。
+    #[allow(clippy::too_many_arguments)]
     pub fn new_local(
         id: String,
         shell_path: Option<String>,
         cwd: Option<String>,
         env: Option<HashMap<String, String>>,
         term_size: TermSize,
+        login_shell: bool,
+        inherit_env: &[String],
+        clock: SharedClock,
     ) -> Result<Self, TerminalError> {
-        let created_at = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_secs();
+        let created_at = clock.unix_secs();
 
         // 创建本地 PTY
-        let local_pty = LocalPty::new(shell_path.clone(), cwd.clone(), env.clone(), term_size)?;
+        let local_pty = LocalPty::with_inherited_env(
+            shell_path,
+            cwd,
+            env.clone(),
+            term_size,
+            login_shell,
+            inherit_env,
+        )?;
+
+        // 记录实际使用的 shell、工作目录和 TERM，而不是请求中的原始值
+        let mut env = env.unwrap_or_default();
+        if let Some(term) = local_pty.env().get("TERM") {
+            env.insert("TERM".to_string(), term.clone());
+        }
+        let connection_type = ConnectionType::Local {
+            shell_path: Some(local_pty.shell_path().to_string_lossy().into_owned()),
+            cwd: local_pty.cwd().map(str::to_string),
+            env: Some(env),
+        };
+        let pid = local_pty.process_id();
 
         Ok(Self {
-            info: SessionInfo {
+            session_id: id.clone(),
+            info: Arc::new(RwLock::new(SessionInfo {
                 id,
-                connection_type: ConnectionType::Local {
-                    shell_path,
-                    cwd,
-                    env,
-                },
+                connection_type,
                 status: SessionStatus::Running,
                 title: None,
+                name: None,
                 cwd: None,
                 exit_code: None,
                 created_at,
-            },
+                modes: Default::default(),
+                ssh: None,
+                exit_reason: None,
+                ended_at: None,
+                duration_secs: None,
+                pid,
+                resolved_addr: None,
+            })),
             local_pty: Some(Arc::new(Mutex::new(local_pty))),
+            local_process: None,
             output_reader: None,
+            output_listeners: OutputListeners::new(),
+            detached_output: None,
+            counters: Arc::new(ByteCounters::new()),
+            output_config: OutputReaderConfig {
+                clock: clock.clone(),
+                ..Default::default()
+            },
+            allow_inject: false,
+            write_chunk_size: DEFAULT_WRITE_CHUNK_SIZE,
+            write_queue: Arc::new(Mutex::new(())),
+            read_only: false,
+            output_injector: None,
+            #[cfg(target_os = "linux")]
+            cwd_poller: None,
+            idle_monitor: None,
+            clock,
         })
     }
 
+    /// 创建并启动不分配 PTY 的本地进程会话
+    ///
+    /// 通过系统 shell 运行 `command`，输出在启动输出读取器后开始发送。
+    /// `inherit_env` 中的变量从插件环境继承，见 [`LocalProcess::spawn`]。
+    pub async fn new_local_process(
+        id: String,
+        command: String,
+        cwd: Option<String>,
+        env: Option<HashMap<String, String>>,
+        inherit_env: &[String],
+        clock: SharedClock,
+    ) -> Result<Self, TerminalError> {
+        let cwd = cwd.as_deref().map(resolve_cwd).transpose()?;
+        let process = LocalProcess::spawn(&command, cwd.as_deref(), env.as_ref(), inherit_env)?;
+
+        let mut session = Self::new(id, ConnectionType::LocalProcess { command, cwd, env }, clock);
+        session.info.write().await.pid = process.process_id();
+        session.local_process = Some(process);
+        session.set_status(SessionStatus::Running).await;
+        Ok(session)
+    }
+
     /// 启动输出读取器
     /// 
-    /// 开始异步读取 PTY 输出并通过通知发送到前端。
+    /// 开始异步读取 PTY 输出并通过通知发送到前端。本地进程会话转发 stdout 和 stderr，
+    /// 只能启动一次。
     pub async fn start_output_reader(
         &mut self,
         notification_sender: NotificationSender,
     ) -> Result<(), TerminalError> {
+        if let Some(process) = &mut self.local_process {
+            process.start(
+                self.session_id.clone(),
+                notification_sender,
+                self.output_listeners.clone(),
+                self.counters.clone(),
+                self.info.clone(),
+                self.output_config.buffer_size,
+                self.clock.clone(),
+            )?;
+            tracing::info!("启动进程输出任务: {}", self.session_id);
+            return Ok(());
+        }
+
         if self.output_reader.is_some() {
-            tracing::warn!("输出读取器已经在运行: {}", self.info.id);
+            tracing::warn!("输出读取器已经在运行: {}", self.session_id);
             return Ok(());
         }
 
-        let reader = self.try_clone_reader().await?;
-        let handle = start_output_reader(
-            self.info.id.clone(),
-            reader,
+        // 之前分离时保留的来源中可能还有未发送的数据，优先继续使用
+        let source = match self.detached_output.take() {
+            Some(source) => source,
+            None => {
+                let reader = self.try_clone_reader().await?;
+                OutputSource::spawn(&self.session_id, reader, self.output_config.buffer_size)
+            }
+        };
+        self.output_injector = Some(source.injector());
+        let handle = start_output_reader_from_source(
+            self.session_id.clone(),
+            source,
             notification_sender,
-            OutputReaderConfig::default(),
+            self.output_listeners.clone(),
+            self.counters.clone(),
+            Some(self.info.clone()),
+            self.output_config.clone(),
         );
 
         self.output_reader = Some(handle);
-        tracing::info!("启动输出读取器: {}", self.info.id);
+        tracing::info!("启动输出读取器: {}", self.session_id);
+        Ok(())
+    }
+
+    /// 注册附加输出监听器
+    ///
+    /// 监听器接收与主通知发送器相同的 `terminal.output` 通知，可以在输出读取器启动前后注册。
+    /// 返回的句柄被丢弃时移除监听器。
+    pub fn add_output_listener(&self, sender: NotificationSender) -> OutputListenerHandle {
+        self.output_listeners.add(sender)
+    }
+
+    /// 当前注册的附加输出监听器数量
+    pub fn output_listener_count(&self) -> usize {
+        self.output_listeners.len()
+    }
+
+    /// 替换字节计数器
+    ///
+    /// 需要在启动输出读取器之前调用，已运行的读取器继续使用原来的计数器。
+    pub fn set_counters(&mut self, counters: Arc<ByteCounters>) {
+        self.counters = counters;
+    }
+
+    /// 字节计数器
+    pub fn counters(&self) -> &ByteCounters {
+        &self.counters
+    }
+
+    /// 设置 PTY 读取缓冲区大小，超出范围时被限制到最近的边界
+    ///
+    /// 从下一次启动输出读取器起生效，分离后重新附加时继续使用原来的缓冲区。
+    pub fn set_read_buffer_size(&mut self, buffer_size: usize) {
+        self.output_config = self.output_config.clone().with_buffer_size(buffer_size);
+    }
+
+    /// 输入写入分块大小
+    pub fn write_chunk_size(&self) -> usize {
+        self.write_chunk_size
+    }
+
+    /// 设置输入写入分块大小，限制在 [`MIN_WRITE_CHUNK_SIZE`] 到 [`MAX_WRITE_CHUNK_SIZE`] 之间
+    pub fn set_write_chunk_size(&mut self, chunk_size: usize) {
+        self.write_chunk_size = chunk_size.clamp(MIN_WRITE_CHUNK_SIZE, MAX_WRITE_CHUNK_SIZE);
+    }
+
+    /// 设置输出速率上限（字节/秒），0 表示不限制
+    ///
+    /// 与读取缓冲区大小一样从下一次启动输出读取器起生效。
+    pub fn set_rate_limit(&mut self, bytes_per_sec: u64) {
+        self.output_config.rate_limit_bytes_per_sec = Some(bytes_per_sec).filter(|&rate| rate > 0);
+    }
+
+    /// 设置是否去掉 OSC 52 剪贴板内容中的控制字符，从下一次启动输出读取器起生效
+    pub fn set_sanitize_clipboard(&mut self, enabled: bool) {
+        self.output_config.sanitize_clipboard = enabled;
+    }
+
+    /// 设置是否从输出中去掉 OSC 序列，从下一次启动输出读取器起生效
+    pub fn set_strip_osc_from_output(&mut self, enabled: bool) {
+        self.output_config.strip_osc_from_output = enabled;
+    }
+
+    /// 设置是否允许通过 [`inject_output`](Self::inject_output) 注入输出
+    pub fn set_allow_inject(&mut self, allow: bool) {
+        self.allow_inject = allow;
+    }
+
+    /// 设置是否只读
+    pub fn set_read_only(&mut self, read_only: bool) {
+        self.read_only = read_only;
+    }
+
+    /// 是否只读
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    /// 检查会话是否接受输入，只读时返回 `InvalidRequest`
+    pub fn ensure_writable(&self) -> Result<(), TerminalError> {
+        if self.read_only {
+            return Err(TerminalError::InvalidRequest(format!(
+                "会话为只读: {}",
+                self.session_id
+            )));
+        }
+        Ok(())
+    }
+
+    /// 启动输出读取器时使用的配置
+    pub fn output_config(&self) -> &OutputReaderConfig {
+        &self.output_config
+    }
+
+    /// 启动工作目录轮询器
+    ///
+    /// 定期读取 shell 进程的 `/proc/<pid>/cwd`，用于不发出 OSC 7 的 shell。
+    #[cfg(target_os = "linux")]
+    pub async fn start_cwd_poller(
+        &mut self,
+        notification_sender: NotificationSender,
+        interval: std::time::Duration,
+    ) -> Result<(), TerminalError> {
+        if self.cwd_poller.is_some() {
+            tracing::warn!("工作目录轮询器已经在运行: {}", self.session_id);
+            return Ok(());
+        }
+
+        let pid = self.process_id().await.ok_or_else(|| {
+            TerminalError::InvalidRequest(format!("会话没有本地进程: {}", self.session_id))
+        })?;
+
+        self.cwd_poller = Some(start_cwd_poller(
+            self.session_id.clone(),
+            pid,
+            self.info.clone(),
+            notification_sender,
+            interval,
+        ));
+        tracing::info!("启动工作目录轮询器: {}", self.session_id);
         Ok(())
     }
 
+    /// 启动空闲监控器
+    ///
+    /// 超过 `timeout` 没有输入（`reset_on_output` 为 true 时也包括输出）时终止进程，
+    /// 见 [`idle`](super::idle)。只支持本地 PTY 会话。
+    pub fn start_idle_monitor(
+        &mut self,
+        timeout: std::time::Duration,
+        reset_on_output: bool,
+    ) -> Result<(), TerminalError> {
+        if self.idle_monitor.is_some() {
+            tracing::warn!("空闲监控器已经在运行: {}", self.session_id);
+            return Ok(());
+        }
+
+        let pty = self.local_pty.clone().ok_or_else(|| {
+            TerminalError::InvalidRequest(format!("会话没有本地 PTY: {}", self.session_id))
+        })?;
+
+        self.idle_monitor = Some(start_idle_monitor(
+            self.session_id.clone(),
+            pty,
+            self.counters.clone(),
+            self.info.clone(),
+            self.clock.clone(),
+            timeout,
+            reset_on_output,
+        ));
+        tracing::info!("启动空闲监控器: {} ({:?})", self.session_id, timeout);
+        Ok(())
+    }
+
+    /// 获取本地会话启动时使用的完整环境变量
+    ///
+    /// 非本地会话返回 `None`。
+    pub async fn env(&self) -> Option<HashMap<String, String>> {
+        match &self.local_pty {
+            Some(pty) => Some(pty.lock().await.env().clone()),
+            None => None,
+        }
+    }
+
+    /// 获取本地子进程 PID
+    pub async fn process_id(&self) -> Option<u32> {
+        if let Some(process) = &self.local_process {
+            return process.process_id();
+        }
+        match &self.local_pty {
+            Some(pty) => pty.lock().await.process_id(),
+            None => None,
+        }
+    }
+
+    /// 关闭会话：终止 PTY 进程并排空输出读取器
+    ///
+    /// 进程刚退出时 PTY 中可能还有尚未读取的输出。先让读取器进入排空模式，
+    /// 再终止进程，读取器会把剩余输出读完并发送后在 EOF 处退出，
+    /// 这样命令的最后几行输出不会丢失。
+    pub async fn close(&mut self) -> Result<(), TerminalError> {
+        if let Some(handle) = &self.output_reader {
+            handle.begin_drain();
+        }
+
+        #[cfg(target_os = "linux")]
+        if let Some(poller) = self.cwd_poller.take() {
+            poller.stop().await;
+        }
+        if let Some(monitor) = self.idle_monitor.take() {
+            monitor.stop().await;
+        }
+
+        let result = self.kill().await;
+        if let Some(process) = &mut self.local_process {
+            process.wait_finished(OUTPUT_DRAIN_TIMEOUT).await;
+        }
+        if let Some(handle) = self.output_reader.take() {
+            handle.drain(OUTPUT_DRAIN_TIMEOUT).await;
+            tracing::info!("停止输出读取器: {}", self.session_id);
+        }
+        result
+    }
+
+    /// 分离输出读取器
+    ///
+    /// 停止读取循环但保留子进程，之后的输出留在 PTY 中，由 PTY 的缓冲区对子进程施加背压。
+    /// 与暂停不同，读取任务会完全退出，之后通过 [`attach_output`](Self::attach_output) 重新附加。
+    pub async fn detach_output(&mut self) -> Result<(), TerminalError> {
+        let handle = self.output_reader.take().ok_or_else(|| {
+            TerminalError::InvalidRequest(format!("输出读取器未运行: {}", self.session_id))
+        })?;
+        self.detached_output = handle.detach().await;
+        tracing::info!("分离输出读取器: {}", self.session_id);
+        Ok(())
+    }
+
+    /// 重新附加输出读取器
+    ///
+    /// 使用新的通知发送器启动读取器。分离时保留了输出来源则继续使用，
+    /// 否则（如读取器已经退出）重新克隆 PTY reader。
+    pub async fn attach_output(
+        &mut self,
+        notification_sender: NotificationSender,
+    ) -> Result<(), TerminalError> {
+        if self.output_reader.is_some() {
+            return Err(TerminalError::InvalidRequest(format!(
+                "输出读取器已经在运行: {}",
+                self.session_id
+            )));
+        }
+        self.start_output_reader(notification_sender).await
+    }
+
     /// 停止输出读取器
     pub async fn stop_output_reader(&mut self) {
         if let Some(handle) = self.output_reader.take() {
             handle.stop().await;
-            tracing::info!("停止输出读取器: {}", self.info.id);
+            tracing::info!("停止输出读取器: {}", self.session_id);
         }
     }
 
+    /// 暂停输出读取器
+    pub fn pause_output(&self) -> Result<(), TerminalError> {
+        let handle = self.output_reader.as_ref().ok_or_else(|| {
+            TerminalError::InvalidRequest(format!("输出读取器未运行: {}", self.session_id))
+        })?;
+        handle.pause();
+        tracing::debug!("暂停输出: {}", self.session_id);
+        Ok(())
+    }
+
+    /// 恢复输出读取器
+    pub fn resume_output(&self) -> Result<(), TerminalError> {
+        let handle = self.output_reader.as_ref().ok_or_else(|| {
+            TerminalError::InvalidRequest(format!("输出读取器未运行: {}", self.session_id))
+        })?;
+        handle.resume();
+        tracing::debug!("恢复输出: {}", self.session_id);
+        Ok(())
+    }
+
+    /// 检查输出是否已暂停
+    pub fn is_output_paused(&self) -> bool {
+        self.output_reader.as_ref().is_some_and(|h| h.is_paused())
+    }
+
     /// 检查输出读取器是否已完成
     pub fn is_output_reader_finished(&self) -> bool {
-        self.output_reader.as_ref().map_or(true, |h| h.is_finished())
+        if let Some(process) = &self.local_process {
+            return process.is_finished();
+        }
+        self.output_reader.as_ref().is_none_or(|h| h.is_finished())
     }
 
     /// 获取 PTY reader（用于读取输出）
@@ -132,25 +587,218 @@ impl PtySession {
     }
 
     /// 写入数据到 PTY
+    ///
+    /// 本地 PTY 会话在阻塞线程中按 [`set_write_chunk_size`](Self::set_write_chunk_size)
+    /// 设置的大小分块写入，写入期间不持有 PTY 的锁，调整大小和终止进程不需要等待写入完成；
+    /// 关闭输入后剩余的分块不再写入。调用方不再等待（future 被丢弃）时已开始的写入继续完成，
+    /// 之后的写入仍然排在它后面。
+    ///
+    /// 子进程已退出或 PTY 已关闭时返回 `SessionClosed`，而不是底层的 IO 错误。
     pub async fn write(&self, data: &[u8]) -> Result<(), TerminalError> {
-        if let Some(pty) = &self.local_pty {
+        if let Some(process) = &self.local_process {
+            process.write(&self.session_id, data).await?;
+            self.counters.add_in(data.len());
+            Ok(())
+        } else if let Some(pty) = &self.local_pty {
+            let queued = self.write_queue.clone().lock_owned().await;
+            let writer = {
+                let mut pty = pty.lock().await;
+                self.ensure_input_open(&mut pty)?;
+                pty.writer()
+            };
+
+            let data = data.to_vec();
+            let chunk_size = self.write_chunk_size;
+            let counters = self.counters.clone();
+            let result = tokio::task::spawn_blocking(move || {
+                let _queued = queued;
+                writer.write_chunks(&data, chunk_size, |len| counters.add_in(len))
+            })
+            .await
+            .map_err(|e| TerminalError::IoError(std::io::Error::other(e.to_string())))?;
+            result.map_err(|e| self.closed_pipe_to_session_closed(e))
+        } else {
+            Err(TerminalError::SessionNotFound("No PTY available".to_string()))
+        }
+    }
+
+    /// 等待之前的写入完成并刷新输入，返回此时累计写入的字节数
+    ///
+    /// 同步排在写入队列中，返回时排在前面的写入（包括批量操作）都已完成。
+    /// 只能保证数据已交给 PTY，不能保证子进程已经读取。
+    pub async fn sync(&self) -> Result<u64, TerminalError> {
+        if let Some(process) = &self.local_process {
+            process.flush(&self.session_id).await?;
+        } else if let Some(pty) = &self.local_pty {
+            let _queued = self.write_queue.lock().await;
             let mut pty = pty.lock().await;
-            pty.write(data)
+            self.ensure_running(&mut pty)?;
+            pty.flush().map_err(|e| self.closed_pipe_to_session_closed(e))?;
+        } else {
+            return Err(TerminalError::SessionNotFound("No PTY available".to_string()));
+        }
+        Ok(self.counters.bytes_in())
+    }
+
+    /// 关闭 PTY 输入，子进程读到 EOF
+    ///
+    /// 之后的 [`write`](Self::write) 返回 `SessionClosed`，输出读取器继续运行。
+    pub async fn close_input(&self) -> Result<(), TerminalError> {
+        if let Some(process) = &self.local_process {
+            process.close_input().await;
+            Ok(())
+        } else if let Some(pty) = &self.local_pty {
+            // 先让进行中的写入停止，再排队关闭
+            let writer = pty.lock().await.writer();
+            writer.cancel();
+            let _queued = self.write_queue.lock().await;
+            pty.lock().await.close_input();
+            Ok(())
         } else {
             Err(TerminalError::SessionNotFound("No PTY available".to_string()))
         }
     }
 
-    /// 调整 PTY 大小
+    /// 注入输出
+    ///
+    /// 数据像 PTY 输出一样显示在终端中，不会写入子进程的输入。创建会话时未允许注入时返回
+    /// `InvalidRequest`；输出读取器已读到 EOF 时返回 `SessionClosed`。分离期间注入的输出
+    /// 在重新附加后发送。
+    pub fn inject_output(&self, data: Vec<u8>) -> Result<(), TerminalError> {
+        if !self.allow_inject {
+            return Err(TerminalError::InvalidRequest(format!(
+                "会话未允许注入输出: {}",
+                self.session_id
+            )));
+        }
+        let injector = self.output_injector.as_ref().ok_or_else(|| {
+            TerminalError::InvalidRequest(format!("会话没有输出读取器: {}", self.session_id))
+        })?;
+        if self.output_reader.as_ref().is_some_and(|h| h.is_finished()) {
+            return Err(TerminalError::SessionClosed(format!(
+                "{}: 输出已结束",
+                self.session_id
+            )));
+        }
+
+        injector.inject(data);
+        Ok(())
+    }
+
+    /// 调整 PTY 大小，本地进程会话没有终端尺寸，直接返回成功
     pub async fn resize(&self, term_size: TermSize) -> Result<(), TerminalError> {
-        if let Some(pty) = &self.local_pty {
-            let pty = pty.lock().await;
-            pty.resize(term_size)
+        if self.local_process.is_some() {
+            Ok(())
+        } else if let Some(pty) = &self.local_pty {
+            self.resize_pty(&mut *pty.lock().await, term_size)
         } else {
             Err(TerminalError::SessionNotFound("No PTY available".to_string()))
         }
     }
 
+    /// 在持有 PTY 锁时按顺序执行一组操作，某项失败后不再执行后续操作
+    ///
+    /// 返回已执行操作的结果，最后一项可能是失败的那一项。本地 PTY 会话在整个批次中持有
+    /// PTY 的锁，其他请求的输入或调整大小不会插入到批次中间。
+    pub async fn apply_batch(&self, steps: &[BatchStep]) -> Vec<Result<(), TerminalError>> {
+        let mut results = Vec::with_capacity(steps.len());
+
+        if let Some(pty) = &self.local_pty {
+            let _queued = self.write_queue.lock().await;
+            let mut pty = pty.lock().await;
+            for step in steps {
+                let result = match step {
+                    BatchStep::Resize(term_size) => self.resize_pty(&mut pty, term_size.clone()),
+                    BatchStep::Input(data) => self.write_pty(&mut pty, data),
+                    BatchStep::Signal(signal) => self.signal_pty(&mut pty, *signal),
+                };
+                let failed = result.is_err();
+                results.push(result);
+                if failed {
+                    break;
+                }
+            }
+        } else {
+            for step in steps {
+                let result = match step {
+                    BatchStep::Resize(term_size) => self.resize(term_size.clone()).await,
+                    BatchStep::Input(data) => self.write(data).await,
+                    BatchStep::Signal(_) => Err(TerminalError::InvalidRequest(format!(
+                        "{}: 只有本地 PTY 会话支持发送信号",
+                        self.session_id
+                    ))),
+                };
+                let failed = result.is_err();
+                results.push(result);
+                if failed {
+                    break;
+                }
+            }
+        }
+        results
+    }
+
+    /// 进程已退出时返回 `SessionClosed`
+    fn ensure_running(&self, pty: &mut LocalPty) -> Result<(), TerminalError> {
+        if let Ok(Some(status)) = pty.try_wait() {
+            return Err(TerminalError::SessionClosed(format!(
+                "{}: 进程已退出 (code={})",
+                self.session_id,
+                status.exit_code()
+            )));
+        }
+        Ok(())
+    }
+
+    /// 进程已退出或输入已关闭时返回 `SessionClosed`
+    fn ensure_input_open(&self, pty: &mut LocalPty) -> Result<(), TerminalError> {
+        self.ensure_running(pty)?;
+        if pty.is_input_closed() {
+            return Err(TerminalError::SessionClosed(format!(
+                "{}: 输入已关闭",
+                self.session_id
+            )));
+        }
+        Ok(())
+    }
+
+    /// 写入已锁定的 PTY
+    fn write_pty(&self, pty: &mut LocalPty, data: &[u8]) -> Result<(), TerminalError> {
+        self.ensure_input_open(pty)?;
+        pty.write(data).map_err(|e| self.closed_pipe_to_session_closed(e))?;
+        self.counters.add_in(data.len());
+        Ok(())
+    }
+
+    /// 调整已锁定的 PTY 的大小
+    fn resize_pty(&self, pty: &mut LocalPty, term_size: TermSize) -> Result<(), TerminalError> {
+        self.ensure_running(pty)?;
+        pty.resize(term_size).map_err(|e| self.closed_pipe_to_session_closed(e))
+    }
+
+    /// 向已锁定的 PTY 的前台进程组发送信号
+    fn signal_pty(&self, pty: &mut LocalPty, signal: i32) -> Result<(), TerminalError> {
+        self.ensure_running(pty)?;
+        #[cfg(unix)]
+        {
+            pty.signal(signal)
+        }
+        #[cfg(windows)]
+        {
+            Err(TerminalError::InvalidRequest(format!("当前平台不支持发送信号: {}", signal)))
+        }
+    }
+
+    /// PTY 已关闭导致的 IO 错误转换为 `SessionClosed`
+    fn closed_pipe_to_session_closed(&self, error: TerminalError) -> TerminalError {
+        match error {
+            TerminalError::IoError(io_err) if is_closed_pipe(&io_err) => {
+                TerminalError::SessionClosed(format!("{}: {}", self.session_id, io_err))
+            }
+            other => other,
+        }
+    }
+
     /// 检查子进程是否已退出
     pub async fn try_wait(&self) -> Result<Option<portable_pty::ExitStatus>, TerminalError> {
         if let Some(pty) = &self.local_pty {
@@ -163,7 +811,9 @@ impl PtySession {
 
     /// 终止 PTY 进程
     pub async fn kill(&self) -> Result<(), TerminalError> {
-        if let Some(pty) = &self.local_pty {
+        if let Some(process) = &self.local_process {
+            process.kill()
+        } else if let Some(pty) = &self.local_pty {
             let mut pty = pty.lock().await;
             pty.kill()
         } else {
@@ -177,32 +827,42 @@ impl PtySession {
     }
 
     /// 更新状态
-    pub fn set_status(&mut self, status: SessionStatus) {
-        self.info.status = status;
+    pub async fn set_status(&self, status: SessionStatus) {
+        self.info.write().await.status = status;
     }
 
     /// 设置退出码
-    pub fn set_exit_code(&mut self, code: i32) {
-        self.info.exit_code = Some(code);
+    pub async fn set_exit_code(&self, code: i32) {
+        self.info.write().await.exit_code = Some(code);
     }
 
     /// 设置标题
-    pub fn set_title(&mut self, title: String) {
-        self.info.title = Some(title);
+    pub async fn set_title(&self, title: String) {
+        self.info.write().await.title = Some(title);
+    }
+
+    /// 设置显示名称
+    pub async fn set_name(&self, name: Option<String>) {
+        self.info.write().await.name = name;
     }
 
     /// 设置工作目录
-    pub fn set_cwd(&mut self, cwd: String) {
-        self.info.cwd = Some(cwd);
+    pub async fn set_cwd(&self, cwd: String) {
+        self.info.write().await.cwd = Some(cwd);
     }
 
     /// 获取会话 ID
     pub fn id(&self) -> &str {
-        &self.info.id
+        &self.session_id
+    }
+
+    /// 获取会话信息（异步）
+    pub async fn info(&self) -> SessionInfo {
+        self.info.read().await.clone()
     }
 
-    /// 获取会话信息
-    pub fn info(&self) -> &SessionInfo {
-        &self.info
+    /// 获取会话信息引用
+    pub fn info_ref(&self) -> Arc<RwLock<SessionInfo>> {
+        self.info.clone()
     }
 }
@@ -0,0 +1,92 @@
+//! 字节统计
+//!
+//! 统计写入会话的输入字节数和发送给前端的输出字节数。会话计数器可以挂在
+//! 管理器的总计数器下，累加时同时更新总数，会话关闭后总数仍然保留。
+//!
+//! 会话计数器同时分配输出通知的序号，序号跟随会话而不是输出读取器，
+//! 分离后重新附加仍然连续，客户端可以据此发现丢失或乱序的通知。
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+/// 输入/输出字节计数器
+#[derive(Debug, Default)]
+pub struct ByteCounters {
+    /// 写入 PTY 的字节数
+    bytes_in: AtomicU64,
+    /// 作为输出通知发送的字节数（解码后，不含被移除的 OSC 序列）
+    bytes_out: AtomicU64,
+    /// 下一个输出通知的序号（不累加到上级）
+    output_seq: AtomicU64,
+    /// 同时累加的上级计数器
+    parent: Option<Arc<ByteCounters>>,
+}
+
+impl ByteCounters {
+    /// 创建独立的计数器
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 创建挂在 `parent` 下的计数器
+    pub fn with_parent(parent: Arc<ByteCounters>) -> Self {
+        Self {
+            parent: Some(parent),
+            ..Self::default()
+        }
+    }
+
+    /// 累加输入字节数
+    pub fn add_in(&self, bytes: usize) {
+        self.bytes_in.fetch_add(bytes as u64, Ordering::Relaxed);
+        if let Some(parent) = &self.parent {
+            parent.add_in(bytes);
+        }
+    }
+
+    /// 累加输出字节数
+    pub fn add_out(&self, bytes: usize) {
+        self.bytes_out.fetch_add(bytes as u64, Ordering::Relaxed);
+        if let Some(parent) = &self.parent {
+            parent.add_out(bytes);
+        }
+    }
+
+    /// 分配下一个输出通知的序号，从 0 开始
+    pub fn next_output_seq(&self) -> u64 {
+        self.output_seq.fetch_add(1, Ordering::Relaxed)
+    }
+
+    /// 输入字节数
+    pub fn bytes_in(&self) -> u64 {
+        self.bytes_in.load(Ordering::Relaxed)
+    }
+
+    /// 输出字节数
+    pub fn bytes_out(&self) -> u64 {
+        self.bytes_out.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_counters_propagate_to_parent() {
+        let total = Arc::new(ByteCounters::new());
+        let first = ByteCounters::with_parent(total.clone());
+        let second = ByteCounters::with_parent(total.clone());
+
+        first.add_in(3);
+        first.add_out(10);
+        second.add_out(5);
+
+        assert_eq!(first.bytes_in(), 3);
+        assert_eq!(first.bytes_out(), 10);
+        assert_eq!(second.bytes_in(), 0);
+        assert_eq!(second.bytes_out(), 5);
+        assert_eq!(total.bytes_in(), 3);
+        assert_eq!(total.bytes_out(), 15);
+    }
+}
@@ -2,12 +2,28 @@
 //!
 //! 实现 JSON-RPC 方法的注册和分发。
 
+use std::collections::{BTreeMap, HashMap};
+
 use super::server::NotificationSender;
 use super::types::{
-    CloseSessionRequest, CreateSessionRequest, CreateSessionResponse, GetSessionRequest,
-    InputRequest, JsonRpcError, JsonRpcResponse, ResizeRequest, SessionInfo,
+    AttachSessionRequest, BatchRequest, ClearSessionRequest, CloseSessionRequest, CloseWhereRequest,
+    CreateSessionRequest, CreateSessionResponse, DetachSessionRequest, EofSessionRequest,
+    GetEnvRequest, GetSessionRequest, InjectOutputRequest, InputRequest, JsonRpcError,
+    JsonRpcResponse, ListOscCodesResponse, ListShellsResponse, OscCodeInfo,
+    PauseSessionRequest, QuerySessionsRequest, ResizeRequest, ResumeSessionRequest, SelfTestResponse,
+    RenameSessionRequest, SessionEnvResponse, SetReadOnlyRequest, SetTitleRequest, SyncSessionRequest,
+    WriteRawRequest,
 };
-use crate::pty::PtyManager;
+use crate::pty::{LocalPty, OutputReaderConfig, PtyManager};
+use crate::shell::detect::{detect_default_shell, list_shells};
+use crate::shell::osc;
+use crate::utils::error::{error_catalog, TerminalError};
+
+/// `session.env` 响应中环境变量的最大总字节数
+const MAX_ENV_RESPONSE_SIZE: usize = 64 * 1024;
+
+/// `server.selftest` 等待测试程序退出的最长时间
+const SELF_TEST_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
 
 /// RPC 方法处理器
 pub struct RpcMethods {
@@ -34,6 +50,11 @@ impl RpcMethods {
         self.pty_manager.set_notification_sender(sender);
     }
 
+    /// 关闭所有会话，用于服务器退出前清理
+    pub async fn close_all_sessions(&mut self) {
+        self.pty_manager.close_all_sessions().await;
+    }
+
     /// 调用指定方法
     pub async fn call(
         &mut self,
@@ -44,16 +65,628 @@ impl RpcMethods {
         match method {
             "session.create" => self.session_create(params, id).await,
             "session.input" => self.session_input(params, id).await,
+            "session.write_raw" => self.session_write_raw(params, id).await,
+            "session.batch" => self.session_batch(params, id).await,
             "session.resize" => self.session_resize(params, id).await,
             "session.close" => self.session_close(params, id).await,
+            "session.close_where" => self.session_close_where(params, id).await,
             "session.list" => self.session_list(id).await,
             "session.get" => self.session_get(params, id).await,
+            "session.query" => self.session_query(params, id).await,
+            "session.set_title" => self.session_set_title(params, id).await,
+            "session.rename" => self.session_rename(params, id).await,
+            "session.set_read_only" => self.session_set_read_only(params, id),
+            "session.pause" => self.session_pause(params, id).await,
+            "session.resume" => self.session_resume(params, id).await,
+            "session.detach" => self.session_detach(params, id).await,
+            "session.attach" => self.session_attach(params, id).await,
+            "session.env" => self.session_env(params, id).await,
+            "session.clear" => self.session_clear(params, id).await,
+            "session.eof" => self.session_eof(params, id).await,
+            "session.sync" => self.session_sync(params, id).await,
+            "session.inject_output" => self.session_inject_output(params, id),
+            "server.error_catalog" => self.server_error_catalog(id),
+            "server.stats" => self.server_stats(id),
+            "server.selftest" => self.server_selftest(id).await,
+            "server.list_shells" => self.server_list_shells(id),
+            "server.list_osc_codes" => self.server_list_osc_codes(id),
             _ => JsonRpcResponse::error(id, JsonRpcError::method_not_found(method)),
         }
     }
 
-    /// 创建会话
-    async fn session_create(
+    /// 创建会话
+    async fn session_create(
+        &mut self,
+        params: Option<serde_json::Value>,
+        id: serde_json::Value,
+    ) -> JsonRpcResponse {
+        let params = match params {
+            Some(p) => p,
+            None => {
+                return JsonRpcResponse::error(id, JsonRpcError::invalid_params("缺少参数"));
+            }
+        };
+
+        let request: CreateSessionRequest = match serde_json::from_value(params) {
+            Ok(r) => r,
+            Err(e) => {
+                return JsonRpcResponse::error(
+                    id,
+                    JsonRpcError::invalid_params(format!("参数解析错误: {}", e)),
+                );
+            }
+        };
+
+        // 预先确定会话 ID，创建失败时客户端可以用它关联 session.status 错误通知
+        let session_id = match self.pty_manager.resolve_session_id(&request) {
+            Ok(session_id) => session_id,
+            Err(TerminalError::InvalidRequest(message)) => {
+                return JsonRpcResponse::error(id, JsonRpcError::invalid_params(message));
+            }
+            Err(e) => return JsonRpcResponse::error(id, JsonRpcError::internal_error(e.to_string())),
+        };
+
+        match self
+            .pty_manager
+            .create_session_with_id(session_id.clone(), request)
+            .await
+        {
+            Ok(session_id) => {
+                let response = CreateSessionResponse { session_id };
+                JsonRpcResponse::success(id, serde_json::to_value(response).unwrap())
+            }
+            Err(e) => {
+                let mut error = JsonRpcError::internal_error(e.to_string());
+                // 超过 `timeout_ms` 时使用超时错误码，客户端可以据此重试
+                if matches!(e, TerminalError::ConnectionTimeout(_)) {
+                    error.code = e.rpc_code();
+                }
+                error.data = Some(serde_json::json!({
+                    "session_id": session_id,
+                    "error_type": e.error_type(),
+                    "error_code": e.code(),
+                    "recoverable": e.is_recoverable(),
+                }));
+                JsonRpcResponse::error(id, error)
+            }
+        }
+    }
+
+    /// 发送输入
+    async fn session_input(
+        &mut self,
+        params: Option<serde_json::Value>,
+        id: serde_json::Value,
+    ) -> JsonRpcResponse {
+        let params = match params {
+            Some(p) => p,
+            None => {
+                return JsonRpcResponse::error(id, JsonRpcError::invalid_params("缺少参数"));
+            }
+        };
+
+        let request: InputRequest = match serde_json::from_value(params) {
+            Ok(r) => r,
+            Err(e) => {
+                return JsonRpcResponse::error(
+                    id,
+                    JsonRpcError::invalid_params(format!("参数解析错误: {}", e)),
+                );
+            }
+        };
+
+        match self.pty_manager.send_input(&request.session_id, &request.data).await {
+            Ok(()) => JsonRpcResponse::success(id, serde_json::Value::Null),
+            Err(TerminalError::InvalidRequest(message)) => {
+                JsonRpcResponse::error(id, JsonRpcError::invalid_params(message))
+            }
+            // 会话已结束使用专用错误码，便于前端区分
+            Err(e @ TerminalError::SessionClosed(_)) => JsonRpcResponse::error(id, e.into()),
+            Err(e) => JsonRpcResponse::error(id, JsonRpcError::internal_error(e.to_string())),
+        }
+    }
+
+    /// 发送按键或十六进制输入
+    async fn session_write_raw(
+        &mut self,
+        params: Option<serde_json::Value>,
+        id: serde_json::Value,
+    ) -> JsonRpcResponse {
+        let params = match params {
+            Some(p) => p,
+            None => {
+                return JsonRpcResponse::error(id, JsonRpcError::invalid_params("缺少参数"));
+            }
+        };
+
+        let request: WriteRawRequest = match serde_json::from_value(params) {
+            Ok(r) => r,
+            Err(e) => {
+                return JsonRpcResponse::error(
+                    id,
+                    JsonRpcError::invalid_params(format!("参数解析错误: {}", e)),
+                );
+            }
+        };
+
+        match self.pty_manager.write_raw(&request).await {
+            Ok(()) => JsonRpcResponse::success(id, serde_json::Value::Null),
+            Err(TerminalError::InvalidRequest(message)) => {
+                JsonRpcResponse::error(id, JsonRpcError::invalid_params(message))
+            }
+            Err(e @ TerminalError::SessionClosed(_)) => JsonRpcResponse::error(id, e.into()),
+            Err(e) => JsonRpcResponse::error(id, JsonRpcError::internal_error(e.to_string())),
+        }
+    }
+
+    /// 按顺序执行一组调整大小、输入和信号操作，期间不会插入其他请求的操作
+    async fn session_batch(
+        &mut self,
+        params: Option<serde_json::Value>,
+        id: serde_json::Value,
+    ) -> JsonRpcResponse {
+        let params = match params {
+            Some(p) => p,
+            None => {
+                return JsonRpcResponse::error(id, JsonRpcError::invalid_params("缺少参数"));
+            }
+        };
+
+        let request: BatchRequest = match serde_json::from_value(params) {
+            Ok(r) => r,
+            Err(e) => {
+                return JsonRpcResponse::error(
+                    id,
+                    JsonRpcError::invalid_params(format!("参数解析错误: {}", e)),
+                );
+            }
+        };
+
+        match self.pty_manager.batch(&request).await {
+            Ok(response) => JsonRpcResponse::success(id, serde_json::to_value(response).unwrap()),
+            Err(TerminalError::InvalidRequest(message)) => {
+                JsonRpcResponse::error(id, JsonRpcError::invalid_params(message))
+            }
+            Err(e) => JsonRpcResponse::error(id, JsonRpcError::internal_error(e.to_string())),
+        }
+    }
+
+    /// 调整大小
+    async fn session_resize(
+        &mut self,
+        params: Option<serde_json::Value>,
+        id: serde_json::Value,
+    ) -> JsonRpcResponse {
+        let params = match params {
+            Some(p) => p,
+            None => {
+                return JsonRpcResponse::error(id, JsonRpcError::invalid_params("缺少参数"));
+            }
+        };
+
+        let request: ResizeRequest = match serde_json::from_value(params) {
+            Ok(r) => r,
+            Err(e) => {
+                return JsonRpcResponse::error(
+                    id,
+                    JsonRpcError::invalid_params(format!("参数解析错误: {}", e)),
+                );
+            }
+        };
+
+        match self
+            .pty_manager
+            .resize_session(&request.session_id, request.term_size)
+            .await
+        {
+            Ok(()) => JsonRpcResponse::success(id, serde_json::Value::Null),
+            Err(TerminalError::InvalidRequest(message)) => {
+                JsonRpcResponse::error(id, JsonRpcError::invalid_params(message))
+            }
+            // 会话已结束时客户端应停止发送调整请求
+            Err(e @ TerminalError::SessionClosed(_)) => JsonRpcResponse::error(id, e.into()),
+            Err(e) => JsonRpcResponse::error(id, JsonRpcError::internal_error(e.to_string())),
+        }
+    }
+
+    /// 关闭会话
+    async fn session_close(
+        &mut self,
+        params: Option<serde_json::Value>,
+        id: serde_json::Value,
+    ) -> JsonRpcResponse {
+        let params = match params {
+            Some(p) => p,
+            None => {
+                return JsonRpcResponse::error(id, JsonRpcError::invalid_params("缺少参数"));
+            }
+        };
+
+        let request: CloseSessionRequest = match serde_json::from_value(params) {
+            Ok(r) => r,
+            Err(e) => {
+                return JsonRpcResponse::error(
+                    id,
+                    JsonRpcError::invalid_params(format!("参数解析错误: {}", e)),
+                );
+            }
+        };
+
+        match self.pty_manager.close_session(&request.session_id).await {
+            Ok(()) => JsonRpcResponse::success(id, serde_json::Value::Null),
+            Err(e) => JsonRpcResponse::error(id, JsonRpcError::internal_error(e.to_string())),
+        }
+    }
+
+    /// 关闭所有满足条件的会话
+    async fn session_close_where(
+        &mut self,
+        params: Option<serde_json::Value>,
+        id: serde_json::Value,
+    ) -> JsonRpcResponse {
+        let params = match params {
+            Some(p) => p,
+            None => {
+                return JsonRpcResponse::error(id, JsonRpcError::invalid_params("缺少参数"));
+            }
+        };
+
+        let request: CloseWhereRequest = match serde_json::from_value(params) {
+            Ok(r) => r,
+            Err(e) => {
+                return JsonRpcResponse::error(
+                    id,
+                    JsonRpcError::invalid_params(format!("参数解析错误: {}", e)),
+                );
+            }
+        };
+
+        if request.is_empty() {
+            return JsonRpcResponse::error(id, JsonRpcError::invalid_params("至少需要一个过滤条件"));
+        }
+
+        match self.pty_manager.close_where(&request).await {
+            Ok(response) => JsonRpcResponse::success(id, serde_json::to_value(response).unwrap()),
+            Err(e) => JsonRpcResponse::error(id, JsonRpcError::internal_error(e.to_string())),
+        }
+    }
+
+    /// 列出所有会话
+    async fn session_list(&self, id: serde_json::Value) -> JsonRpcResponse {
+        let sessions = self.pty_manager.list_sessions().await;
+        JsonRpcResponse::success(id, serde_json::to_value(sessions).unwrap())
+    }
+
+    /// 查询满足条件的会话
+    async fn session_query(
+        &self,
+        params: Option<serde_json::Value>,
+        id: serde_json::Value,
+    ) -> JsonRpcResponse {
+        let params = match params {
+            Some(p) => p,
+            None => {
+                return JsonRpcResponse::error(id, JsonRpcError::invalid_params("缺少参数"));
+            }
+        };
+
+        let request: QuerySessionsRequest = match serde_json::from_value(params) {
+            Ok(r) => r,
+            Err(e) => {
+                return JsonRpcResponse::error(
+                    id,
+                    JsonRpcError::invalid_params(format!("参数解析错误: {}", e)),
+                );
+            }
+        };
+
+        let sessions = self.pty_manager.query_sessions(&request).await;
+        JsonRpcResponse::success(id, serde_json::to_value(sessions).unwrap())
+    }
+
+    /// 列出所有错误类型的错误码和分类
+    fn server_error_catalog(&self, id: serde_json::Value) -> JsonRpcResponse {
+        JsonRpcResponse::success(id, serde_json::to_value(error_catalog()).unwrap())
+    }
+
+    /// 获取会话数量和输入/输出字节统计
+    fn server_stats(&self, id: serde_json::Value) -> JsonRpcResponse {
+        JsonRpcResponse::success(id, serde_json::to_value(self.pty_manager.stats()).unwrap())
+    }
+
+    /// 检查当前环境能否创建 PTY
+    ///
+    /// 自检使用独立的 PTY，不会创建会话。
+    async fn server_selftest(&self, id: serde_json::Value) -> JsonRpcResponse {
+        let result = tokio::task::spawn_blocking(|| LocalPty::self_test(SELF_TEST_TIMEOUT))
+            .await
+            .unwrap_or_else(|e| Err(TerminalError::PtyCreationFailed(e.to_string())));
+
+        if let Err(e) = &result {
+            tracing::warn!("PTY 自检失败: {}", e);
+        }
+
+        let response = SelfTestResponse {
+            pty_ok: result.is_ok(),
+            default_shell: detect_default_shell(),
+            error: result.err().map(|e| e.to_string()),
+        };
+        JsonRpcResponse::success(id, serde_json::to_value(response).unwrap())
+    }
+
+    /// 列出系统中可用的 shell
+    fn server_list_shells(&self, id: serde_json::Value) -> JsonRpcResponse {
+        let response = ListShellsResponse {
+            shells: list_shells(),
+            default_shell: detect_default_shell(),
+        };
+        JsonRpcResponse::success(id, serde_json::to_value(response).unwrap())
+    }
+
+    /// 列出支持的 OSC 代码及按默认会话配置是否启用
+    fn server_list_osc_codes(&self, id: serde_json::Value) -> JsonRpcResponse {
+        let config = OutputReaderConfig::default();
+        let codes = osc::supported_codes()
+            .map(|(code, feature)| OscCodeInfo {
+                code,
+                feature: feature.name().to_string(),
+                enabled: config.osc_feature_enabled(feature),
+            })
+            .collect();
+        let response = ListOscCodesResponse { codes };
+        JsonRpcResponse::success(id, serde_json::to_value(response).unwrap())
+    }
+
+    /// 获取会话信息
+    async fn session_get(
+        &self,
+        params: Option<serde_json::Value>,
+        id: serde_json::Value,
+    ) -> JsonRpcResponse {
+        let params = match params {
+            Some(p) => p,
+            None => {
+                return JsonRpcResponse::error(id, JsonRpcError::invalid_params("缺少参数"));
+            }
+        };
+
+        let request: GetSessionRequest = match serde_json::from_value(params) {
+            Ok(r) => r,
+            Err(e) => {
+                return JsonRpcResponse::error(
+                    id,
+                    JsonRpcError::invalid_params(format!("参数解析错误: {}", e)),
+                );
+            }
+        };
+
+        match self.pty_manager.get_session(&request.session_id).await {
+            Some(session) => JsonRpcResponse::success(id, serde_json::to_value(session).unwrap()),
+            None => JsonRpcResponse::error(
+                id,
+                JsonRpcError::invalid_params(format!("会话不存在: {}", request.session_id)),
+            ),
+        }
+    }
+
+    /// 设置会话标题
+    async fn session_set_title(
+        &mut self,
+        params: Option<serde_json::Value>,
+        id: serde_json::Value,
+    ) -> JsonRpcResponse {
+        let params = match params {
+            Some(p) => p,
+            None => {
+                return JsonRpcResponse::error(id, JsonRpcError::invalid_params("缺少参数"));
+            }
+        };
+
+        let request: SetTitleRequest = match serde_json::from_value(params) {
+            Ok(r) => r,
+            Err(e) => {
+                return JsonRpcResponse::error(
+                    id,
+                    JsonRpcError::invalid_params(format!("参数解析错误: {}", e)),
+                );
+            }
+        };
+
+        match self
+            .pty_manager
+            .set_title(&request.session_id, request.title)
+            .await
+        {
+            Ok(()) => JsonRpcResponse::success(id, serde_json::Value::Null),
+            Err(e) => JsonRpcResponse::error(id, JsonRpcError::internal_error(e.to_string())),
+        }
+    }
+
+    /// 设置会话显示名称
+    async fn session_rename(
+        &mut self,
+        params: Option<serde_json::Value>,
+        id: serde_json::Value,
+    ) -> JsonRpcResponse {
+        let params = match params {
+            Some(p) => p,
+            None => {
+                return JsonRpcResponse::error(id, JsonRpcError::invalid_params("缺少参数"));
+            }
+        };
+
+        let request: RenameSessionRequest = match serde_json::from_value(params) {
+            Ok(r) => r,
+            Err(e) => {
+                return JsonRpcResponse::error(
+                    id,
+                    JsonRpcError::invalid_params(format!("参数解析错误: {}", e)),
+                );
+            }
+        };
+
+        match self
+            .pty_manager
+            .rename_session(&request.session_id, request.name)
+            .await
+        {
+            Ok(()) => JsonRpcResponse::success(id, serde_json::Value::Null),
+            Err(e) => JsonRpcResponse::error(id, JsonRpcError::internal_error(e.to_string())),
+        }
+    }
+
+    /// 设置会话是否只读
+    fn session_set_read_only(
+        &mut self,
+        params: Option<serde_json::Value>,
+        id: serde_json::Value,
+    ) -> JsonRpcResponse {
+        let params = match params {
+            Some(p) => p,
+            None => {
+                return JsonRpcResponse::error(id, JsonRpcError::invalid_params("缺少参数"));
+            }
+        };
+
+        let request: SetReadOnlyRequest = match serde_json::from_value(params) {
+            Ok(r) => r,
+            Err(e) => {
+                return JsonRpcResponse::error(
+                    id,
+                    JsonRpcError::invalid_params(format!("参数解析错误: {}", e)),
+                );
+            }
+        };
+
+        match self
+            .pty_manager
+            .set_read_only(&request.session_id, request.read_only)
+        {
+            Ok(()) => JsonRpcResponse::success(id, serde_json::Value::Null),
+            Err(e) => JsonRpcResponse::error(id, JsonRpcError::internal_error(e.to_string())),
+        }
+    }
+
+    /// 暂停会话输出
+    async fn session_pause(
+        &mut self,
+        params: Option<serde_json::Value>,
+        id: serde_json::Value,
+    ) -> JsonRpcResponse {
+        let params = match params {
+            Some(p) => p,
+            None => {
+                return JsonRpcResponse::error(id, JsonRpcError::invalid_params("缺少参数"));
+            }
+        };
+
+        let request: PauseSessionRequest = match serde_json::from_value(params) {
+            Ok(r) => r,
+            Err(e) => {
+                return JsonRpcResponse::error(
+                    id,
+                    JsonRpcError::invalid_params(format!("参数解析错误: {}", e)),
+                );
+            }
+        };
+
+        match self.pty_manager.pause_session(&request.session_id).await {
+            Ok(()) => JsonRpcResponse::success(id, serde_json::Value::Null),
+            Err(e) => JsonRpcResponse::error(id, JsonRpcError::internal_error(e.to_string())),
+        }
+    }
+
+    /// 恢复会话输出
+    async fn session_resume(
+        &mut self,
+        params: Option<serde_json::Value>,
+        id: serde_json::Value,
+    ) -> JsonRpcResponse {
+        let params = match params {
+            Some(p) => p,
+            None => {
+                return JsonRpcResponse::error(id, JsonRpcError::invalid_params("缺少参数"));
+            }
+        };
+
+        let request: ResumeSessionRequest = match serde_json::from_value(params) {
+            Ok(r) => r,
+            Err(e) => {
+                return JsonRpcResponse::error(
+                    id,
+                    JsonRpcError::invalid_params(format!("参数解析错误: {}", e)),
+                );
+            }
+        };
+
+        match self.pty_manager.resume_session(&request.session_id).await {
+            Ok(()) => JsonRpcResponse::success(id, serde_json::Value::Null),
+            Err(e) => JsonRpcResponse::error(id, JsonRpcError::internal_error(e.to_string())),
+        }
+    }
+
+    /// 分离会话输出
+    ///
+    /// 停止输出读取器但保留子进程，与暂停不同，读取任务会完全退出。
+    async fn session_detach(
+        &mut self,
+        params: Option<serde_json::Value>,
+        id: serde_json::Value,
+    ) -> JsonRpcResponse {
+        let params = match params {
+            Some(p) => p,
+            None => {
+                return JsonRpcResponse::error(id, JsonRpcError::invalid_params("缺少参数"));
+            }
+        };
+
+        let request: DetachSessionRequest = match serde_json::from_value(params) {
+            Ok(r) => r,
+            Err(e) => {
+                return JsonRpcResponse::error(
+                    id,
+                    JsonRpcError::invalid_params(format!("参数解析错误: {}", e)),
+                );
+            }
+        };
+
+        match self.pty_manager.detach_session(&request.session_id).await {
+            Ok(()) => JsonRpcResponse::success(id, serde_json::Value::Null),
+            Err(e) => JsonRpcResponse::error(id, JsonRpcError::internal_error(e.to_string())),
+        }
+    }
+
+    /// 重新附加会话输出
+    async fn session_attach(
+        &mut self,
+        params: Option<serde_json::Value>,
+        id: serde_json::Value,
+    ) -> JsonRpcResponse {
+        let params = match params {
+            Some(p) => p,
+            None => {
+                return JsonRpcResponse::error(id, JsonRpcError::invalid_params("缺少参数"));
+            }
+        };
+
+        let request: AttachSessionRequest = match serde_json::from_value(params) {
+            Ok(r) => r,
+            Err(e) => {
+                return JsonRpcResponse::error(
+                    id,
+                    JsonRpcError::invalid_params(format!("参数解析错误: {}", e)),
+                );
+            }
+        };
+
+        match self.pty_manager.attach_session(&request.session_id).await {
+            Ok(()) => JsonRpcResponse::success(id, serde_json::Value::Null),
+            Err(e) => JsonRpcResponse::error(id, JsonRpcError::internal_error(e.to_string())),
+        }
+    }
+
+    /// 发送 EOF（关闭会话输入）
+    async fn session_eof(
         &mut self,
         params: Option<serde_json::Value>,
         id: serde_json::Value,
@@ -65,7 +698,7 @@ impl RpcMethods {
             }
         };
 
-        let request: CreateSessionRequest = match serde_json::from_value(params) {
+        let request: EofSessionRequest = match serde_json::from_value(params) {
             Ok(r) => r,
             Err(e) => {
                 return JsonRpcResponse::error(
@@ -75,18 +708,18 @@ impl RpcMethods {
             }
         };
 
-        match self.pty_manager.create_session(request).await {
-            Ok(session_id) => {
-                let response = CreateSessionResponse { session_id };
-                JsonRpcResponse::success(id, serde_json::to_value(response).unwrap())
+        match self.pty_manager.eof_session(&request.session_id).await {
+            Ok(()) => JsonRpcResponse::success(id, serde_json::Value::Null),
+            Err(TerminalError::InvalidRequest(message)) => {
+                JsonRpcResponse::error(id, JsonRpcError::invalid_params(message))
             }
             Err(e) => JsonRpcResponse::error(id, JsonRpcError::internal_error(e.to_string())),
         }
     }
 
-    /// 发送输入
-    async fn session_input(
-        &mut self,
+    /// 等待之前的输入写入完成
+    async fn session_sync(
+        &self,
         params: Option<serde_json::Value>,
         id: serde_json::Value,
     ) -> JsonRpcResponse {
@@ -97,7 +730,7 @@ impl RpcMethods {
             }
         };
 
-        let request: InputRequest = match serde_json::from_value(params) {
+        let request: SyncSessionRequest = match serde_json::from_value(params) {
             Ok(r) => r,
             Err(e) => {
                 return JsonRpcResponse::error(
@@ -107,15 +740,16 @@ impl RpcMethods {
             }
         };
 
-        match self.pty_manager.send_input(&request.session_id, &request.data).await {
-            Ok(()) => JsonRpcResponse::success(id, serde_json::Value::Null),
+        match self.pty_manager.sync_session(&request.session_id).await {
+            Ok(response) => JsonRpcResponse::success(id, serde_json::to_value(response).unwrap()),
+            Err(e @ TerminalError::SessionClosed(_)) => JsonRpcResponse::error(id, e.into()),
             Err(e) => JsonRpcResponse::error(id, JsonRpcError::internal_error(e.to_string())),
         }
     }
 
-    /// 调整大小
-    async fn session_resize(
-        &mut self,
+    /// 注入输出
+    fn session_inject_output(
+        &self,
         params: Option<serde_json::Value>,
         id: serde_json::Value,
     ) -> JsonRpcResponse {
@@ -126,7 +760,7 @@ impl RpcMethods {
             }
         };
 
-        let request: ResizeRequest = match serde_json::from_value(params) {
+        let request: InjectOutputRequest = match serde_json::from_value(params) {
             Ok(r) => r,
             Err(e) => {
                 return JsonRpcResponse::error(
@@ -136,18 +770,15 @@ impl RpcMethods {
             }
         };
 
-        match self
-            .pty_manager
-            .resize_session(&request.session_id, request.term_size)
-            .await
-        {
+        match self.pty_manager.inject_output(&request.session_id, &request.data) {
             Ok(()) => JsonRpcResponse::success(id, serde_json::Value::Null),
+            Err(e @ TerminalError::SessionClosed(_)) => JsonRpcResponse::error(id, e.into()),
             Err(e) => JsonRpcResponse::error(id, JsonRpcError::internal_error(e.to_string())),
         }
     }
 
-    /// 关闭会话
-    async fn session_close(
+    /// 清屏
+    async fn session_clear(
         &mut self,
         params: Option<serde_json::Value>,
         id: serde_json::Value,
@@ -159,7 +790,7 @@ impl RpcMethods {
             }
         };
 
-        let request: CloseSessionRequest = match serde_json::from_value(params) {
+        let request: ClearSessionRequest = match serde_json::from_value(params) {
             Ok(r) => r,
             Err(e) => {
                 return JsonRpcResponse::error(
@@ -169,20 +800,22 @@ impl RpcMethods {
             }
         };
 
-        match self.pty_manager.close_session(&request.session_id).await {
-            Ok(()) => JsonRpcResponse::success(id, serde_json::Value::Null),
+        match self
+            .pty_manager
+            .clear_session(&request.session_id, request.mode)
+            .await
+        {
+            Ok(mode) => JsonRpcResponse::success(id, serde_json::json!({ "mode": mode })),
+            Err(TerminalError::InvalidRequest(message)) => {
+                JsonRpcResponse::error(id, JsonRpcError::invalid_params(message))
+            }
+            Err(e @ TerminalError::SessionClosed(_)) => JsonRpcResponse::error(id, e.into()),
             Err(e) => JsonRpcResponse::error(id, JsonRpcError::internal_error(e.to_string())),
         }
     }
 
-    /// 列出所有会话
-    async fn session_list(&self, id: serde_json::Value) -> JsonRpcResponse {
-        let sessions = self.pty_manager.list_sessions().await;
-        JsonRpcResponse::success(id, serde_json::to_value(sessions).unwrap())
-    }
-
-    /// 获取会话信息
-    async fn session_get(
+    /// 获取本地会话的环境变量
+    async fn session_env(
         &self,
         params: Option<serde_json::Value>,
         id: serde_json::Value,
@@ -194,7 +827,7 @@ impl RpcMethods {
             }
         };
 
-        let request: GetSessionRequest = match serde_json::from_value(params) {
+        let request: GetEnvRequest = match serde_json::from_value(params) {
             Ok(r) => r,
             Err(e) => {
                 return JsonRpcResponse::error(
@@ -204,13 +837,43 @@ impl RpcMethods {
             }
         };
 
-        match self.pty_manager.get_session(&request.session_id).await {
-            Some(session) => JsonRpcResponse::success(id, serde_json::to_value(session).unwrap()),
-            None => JsonRpcResponse::error(
-                id,
-                JsonRpcError::invalid_params(format!("会话不存在: {}", request.session_id)),
-            ),
+        match self.pty_manager.get_env(&request.session_id).await {
+            Ok(env) => {
+                let response = cap_env(env, MAX_ENV_RESPONSE_SIZE);
+                JsonRpcResponse::success(id, serde_json::to_value(response).unwrap())
+            }
+            Err(e @ (TerminalError::SessionNotFound(_) | TerminalError::InvalidRequest(_))) => {
+                JsonRpcResponse::error(id, JsonRpcError::invalid_params(e.to_string()))
+            }
+            Err(e) => JsonRpcResponse::error(id, JsonRpcError::internal_error(e.to_string())),
+        }
+    }
+}
+
+/// 按名称排序并限制环境变量的总大小
+///
+/// 超出 `max_size` 的变量被省略，并在响应中标记 `truncated`。
+fn cap_env(env: HashMap<String, String>, max_size: usize) -> SessionEnvResponse {
+    let mut sorted: Vec<_> = env.into_iter().collect();
+    sorted.sort();
+
+    let mut capped = BTreeMap::new();
+    let mut size = 0;
+    let mut truncated = false;
+
+    for (key, value) in sorted {
+        let entry_size = key.len() + value.len();
+        if size + entry_size > max_size {
+            truncated = true;
+            continue;
         }
+        size += entry_size;
+        capped.insert(key, value);
+    }
+
+    SessionEnvResponse {
+        env: capped,
+        truncated,
     }
 }
 
@@ -258,6 +921,446 @@ mod tests {
         let error = response.error.unwrap();
         assert_eq!(error.code, -32602); // Invalid params
     }
+
+    #[tokio::test]
+    async fn test_close_where_requires_filter() {
+        let mut methods = RpcMethods::new();
+
+        let response = methods
+            .call("session.close_where", Some(serde_json::json!({})), serde_json::json!(1))
+            .await;
+        assert_eq!(response.error.unwrap().code, -32602);
+
+        let response = methods
+            .call(
+                "session.close_where",
+                Some(serde_json::json!({ "connection_type": "telnet" })),
+                serde_json::json!(2),
+            )
+            .await;
+        assert_eq!(response.error.unwrap().code, -32602);
+
+        let response = methods
+            .call(
+                "session.close_where",
+                Some(serde_json::json!({ "connection_type": "ssh", "host": "example.com" })),
+                serde_json::json!(3),
+            )
+            .await;
+        let result = response.result.unwrap();
+        assert_eq!(result["closed"], serde_json::json!([]));
+        assert_eq!(result["errors"], serde_json::json!([]));
+    }
+
+    #[tokio::test]
+    async fn test_list_shells() {
+        let mut methods = RpcMethods::new();
+        let response = methods.call("server.list_shells", None, serde_json::json!(1)).await;
+
+        let result = response.result.unwrap();
+        assert!(!result["default_shell"].as_str().unwrap().is_empty());
+        let shells = result["shells"].as_array().unwrap();
+        assert!(!shells.is_empty());
+        for shell in shells {
+            assert!(shell["path"].is_string());
+            assert!(shell["name"].is_string());
+            assert!(shell["kind"].is_string());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_list_osc_codes() {
+        let mut methods = RpcMethods::new();
+        let response = methods.call("server.list_osc_codes", None, serde_json::json!(1)).await;
+
+        let result = response.result.unwrap();
+        let codes: Vec<u64> = result["codes"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|entry| entry["code"].as_u64().unwrap())
+            .collect();
+        for known in [0, 2, 4, 7, 10, 11, 52] {
+            assert!(codes.contains(&known), "missing OSC {}", known);
+        }
+        for entry in result["codes"].as_array().unwrap() {
+            assert!(entry["feature"].is_string());
+            assert_eq!(entry["enabled"], true);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_selftest_does_not_create_session() {
+        let mut methods = RpcMethods::new();
+        let response = methods.call("server.selftest", None, serde_json::json!(1)).await;
+
+        let result = response.result.unwrap();
+        assert!(!result["default_shell"].as_str().unwrap().is_empty());
+        if result["pty_ok"] == true {
+            assert!(result.get("error").is_none());
+        } else {
+            println!("PTY creation failed (may be expected in CI): {}", result["error"]);
+            assert!(result["error"].is_string());
+        }
+        assert_eq!(methods.pty_manager.session_count(), 0);
+        assert_eq!(methods.pty_manager.stats().sessions_created, 0);
+    }
+
+    #[tokio::test]
+    async fn test_eof_missing_session() {
+        let mut methods = RpcMethods::new();
+
+        let response = methods.call("session.eof", None, serde_json::json!(1)).await;
+        assert_eq!(response.error.unwrap().code, -32602);
+
+        let response = methods
+            .call(
+                "session.eof",
+                Some(serde_json::json!({ "session_id": "missing" })),
+                serde_json::json!(2),
+            )
+            .await;
+        assert!(response.error.unwrap().message.contains("missing"));
+    }
+
+    #[tokio::test]
+    async fn test_sync_missing_session() {
+        let mut methods = RpcMethods::new();
+
+        let response = methods.call("session.sync", None, serde_json::json!(1)).await;
+        assert_eq!(response.error.unwrap().code, -32602);
+
+        let response = methods
+            .call(
+                "session.sync",
+                Some(serde_json::json!({ "session_id": "missing" })),
+                serde_json::json!(2),
+            )
+            .await;
+        assert!(response.error.unwrap().message.contains("missing"));
+    }
+
+    #[tokio::test]
+    async fn test_inject_output_missing_session() {
+        let mut methods = RpcMethods::new();
+
+        let response = methods
+            .call(
+                "session.inject_output",
+                Some(serde_json::json!({ "session_id": "missing", "data": "aGk=" })),
+                serde_json::json!(1),
+            )
+            .await;
+        assert!(response.error.unwrap().message.contains("missing"));
+    }
+
+    #[tokio::test]
+    async fn test_server_stats_empty() {
+        let mut methods = RpcMethods::new();
+        let response = methods.call("server.stats", None, serde_json::json!(1)).await;
+
+        let stats = response.result.unwrap();
+        assert_eq!(stats["sessions_created"], 0);
+        assert_eq!(stats["sessions_open"], 0);
+        assert_eq!(stats["bytes_in"], 0);
+        assert_eq!(stats["bytes_out"], 0);
+        assert_eq!(stats["notifications_dropped"], 0);
+        assert_eq!(stats["notifications_oversized"], 0);
+        assert_eq!(stats["sessions"], serde_json::json!([]));
+    }
+
+    #[tokio::test]
+    async fn test_error_catalog() {
+        let mut methods = RpcMethods::new();
+        let response = methods.call("server.error_catalog", None, serde_json::json!(1)).await;
+
+        let catalog = response.result.unwrap();
+        let entries = catalog.as_array().unwrap();
+        assert_eq!(entries.len(), error_catalog().len());
+        let not_found = entries
+            .iter()
+            .find(|e| e["error_type"] == "session_not_found")
+            .unwrap();
+        assert_eq!(not_found["rpc_code"], -32001);
+        assert_eq!(not_found["code"], 1003);
+        assert_eq!(not_found["recoverable"], false);
+    }
+
+    #[tokio::test]
+    async fn test_set_title_unknown_session() {
+        let mut methods = RpcMethods::new();
+        let response = methods.call(
+            "session.set_title",
+            Some(serde_json::json!({"session_id": "missing", "title": "t"})),
+            serde_json::json!(1)
+        ).await;
+
+        assert!(response.error.is_some());
+        assert_eq!(response.error.unwrap().code, -32603);
+    }
+
+    #[tokio::test]
+    async fn test_create_failure_includes_session_id() {
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let mut methods = RpcMethods::with_notification_sender(NotificationSender::new_for_test(tx));
+        let response = methods.call(
+            "session.create",
+            Some(serde_json::json!({
+                "connection": {"type": "local", "shell_path": "/nonexistent/shell"},
+                "term_size": {"rows": 24, "cols": 80}
+            })),
+            serde_json::json!(1)
+        ).await;
+
+        let error = response.error.unwrap();
+        assert_eq!(error.code, -32603);
+        let data = error.data.unwrap();
+        assert_eq!(data["error_type"], "pty_creation_failed");
+
+        // 错误通知与 RPC 错误使用同一个会话 ID
+        let notification = rx.try_recv().unwrap();
+        let params = notification.params.unwrap();
+        assert_eq!(params["status"], "error");
+        assert_eq!(params["session_id"], data["session_id"]);
+    }
+
+    #[tokio::test]
+    async fn test_read_only_rejects_input() {
+        let mut methods = RpcMethods::new();
+        let response = methods.call(
+            "session.create",
+            Some(serde_json::json!({
+                "connection": {"type": "ssh", "host": "example.com"},
+                "term_size": {"rows": 24, "cols": 80},
+                "read_only": true
+            })),
+            serde_json::json!(1)
+        ).await;
+        let session_id = response.result.unwrap()["session_id"].clone();
+
+        for (method, params) in [
+            ("session.input", serde_json::json!({"session_id": session_id, "data": "aGk="})),
+            ("session.write_raw", serde_json::json!({"session_id": session_id, "key": "Enter"})),
+            (
+                "session.resize",
+                serde_json::json!({"session_id": session_id, "term_size": {"rows": 40, "cols": 120}}),
+            ),
+        ] {
+            let response = methods.call(method, Some(params), serde_json::json!(2)).await;
+            let error = response.error.unwrap();
+            assert_eq!(error.code, -32602, "{}", method);
+            assert!(error.message.contains("只读"), "{}", method);
+        }
+
+        let response = methods.call(
+            "session.set_read_only",
+            Some(serde_json::json!({"session_id": session_id, "read_only": false})),
+            serde_json::json!(3)
+        ).await;
+        assert!(response.error.is_none());
+
+        // SSH 占位会话没有通道，但输入不再因只读被拒绝
+        let response = methods.call(
+            "session.input",
+            Some(serde_json::json!({"session_id": session_id, "data": "aGk="})),
+            serde_json::json!(4)
+        ).await;
+        assert!(!response.error.unwrap().message.contains("只读"));
+    }
+
+    #[tokio::test]
+    async fn test_env_unknown_session() {
+        let mut methods = RpcMethods::new();
+        let response = methods.call(
+            "session.env",
+            Some(serde_json::json!({"session_id": "missing"})),
+            serde_json::json!(1)
+        ).await;
+
+        assert_eq!(response.error.unwrap().code, -32602);
+    }
+
+    #[tokio::test]
+    async fn test_create_with_client_session_id() {
+        let mut methods = RpcMethods::new();
+        let create = |session_id: serde_json::Value, generate_id_on_conflict: bool| {
+            serde_json::json!({
+                "connection": {"type": "ssh", "host": "example.com"},
+                "term_size": {"rows": 24, "cols": 80},
+                "session_id": session_id,
+                "generate_id_on_conflict": generate_id_on_conflict
+            })
+        };
+
+        // 指定的 ID 可用时直接使用
+        let response = methods
+            .call("session.create", Some(create("build-01.main".into(), false)), serde_json::json!(1))
+            .await;
+        assert_eq!(response.result.unwrap()["session_id"], "build-01.main");
+
+        // 重复时拒绝，不创建会话
+        let response = methods
+            .call("session.create", Some(create("build-01.main".into(), false)), serde_json::json!(2))
+            .await;
+        let error = response.error.unwrap();
+        assert_eq!(error.code, -32602);
+        assert!(error.message.contains("build-01.main"));
+        assert_eq!(methods.pty_manager.session_count(), 1);
+
+        // 重复时按需生成 UUID
+        let response = methods
+            .call("session.create", Some(create("build-01.main".into(), true)), serde_json::json!(3))
+            .await;
+        let generated = response.result.unwrap()["session_id"].as_str().unwrap().to_string();
+        assert!(uuid::Uuid::parse_str(&generated).is_ok());
+
+        // 未指定时生成 UUID
+        let response = methods
+            .call("session.create", Some(create(serde_json::Value::Null, false)), serde_json::json!(4))
+            .await;
+        let generated = response.result.unwrap()["session_id"].as_str().unwrap().to_string();
+        assert!(uuid::Uuid::parse_str(&generated).is_ok());
+        assert_eq!(methods.pty_manager.session_count(), 3);
+
+        // 格式无效的 ID 总是被拒绝
+        for invalid in ["", "has space", "../etc", "x".repeat(129).as_str()] {
+            let response = methods
+                .call("session.create", Some(create(invalid.into(), true)), serde_json::json!(5))
+                .await;
+            assert_eq!(response.error.unwrap().code, -32602, "{:?} should be rejected", invalid);
+        }
+        assert_eq!(methods.pty_manager.session_count(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_env_ssh_session_rejected() {
+        let mut methods = RpcMethods::new();
+        let created = methods.call(
+            "session.create",
+            Some(serde_json::json!({
+                "connection": {"type": "ssh", "host": "example.com"},
+                "term_size": {"rows": 24, "cols": 80}
+            })),
+            serde_json::json!(1)
+        ).await;
+        let session_id = created.result.unwrap()["session_id"].clone();
+
+        let response = methods.call(
+            "session.env",
+            Some(serde_json::json!({"session_id": session_id})),
+            serde_json::json!(2)
+        ).await;
+
+        assert_eq!(response.error.unwrap().code, -32602);
+    }
+
+    #[tokio::test]
+    async fn test_env_local_session() {
+        let mut methods = RpcMethods::new();
+        let created = methods.call(
+            "session.create",
+            Some(serde_json::json!({
+                "connection": {"type": "local", "env": {"PLUGIN_TEST_ENV": "42"}},
+                "term_size": {"rows": 24, "cols": 80}
+            })),
+            serde_json::json!(1)
+        ).await;
+        let session_id = match created.result {
+            Some(result) => result["session_id"].clone(),
+            None => {
+                println!("PTY creation failed (may be expected in CI): {:?}", created.error);
+                return;
+            }
+        };
+
+        let response = methods.call(
+            "session.env",
+            Some(serde_json::json!({"session_id": session_id})),
+            serde_json::json!(2)
+        ).await;
+
+        let result = response.result.unwrap();
+        assert_eq!(result["env"]["PLUGIN_TEST_ENV"], "42");
+        assert_eq!(result["env"]["TERM"], "xterm-256color");
+        assert_eq!(result["truncated"], false);
+
+        methods.call(
+            "session.close",
+            Some(serde_json::json!({"session_id": session_id})),
+            serde_json::json!(3)
+        ).await;
+    }
+
+    #[tokio::test]
+    async fn test_clear_unknown_session() {
+        let mut methods = RpcMethods::new();
+        let response = methods.call(
+            "session.clear",
+            Some(serde_json::json!({"session_id": "missing"})),
+            serde_json::json!(1)
+        ).await;
+
+        assert_eq!(response.error.unwrap().code, -32603);
+    }
+
+    #[tokio::test]
+    async fn test_clear_local_session() {
+        let mut methods = RpcMethods::new();
+        let created = methods.call(
+            "session.create",
+            Some(serde_json::json!({
+                "connection": {"type": "local", "shell_path": "/bin/sh"},
+                "term_size": {"rows": 24, "cols": 80}
+            })),
+            serde_json::json!(1)
+        ).await;
+        let session_id = match created.result {
+            Some(result) => result["session_id"].clone(),
+            None => {
+                println!("PTY creation failed (may be expected in CI): {:?}", created.error);
+                return;
+            }
+        };
+
+        // 未指定方式时按 shell 类型选择
+        let response = methods.call(
+            "session.clear",
+            Some(serde_json::json!({"session_id": session_id})),
+            serde_json::json!(2)
+        ).await;
+        assert_eq!(response.result.unwrap()["mode"], "form_feed");
+
+        let response = methods.call(
+            "session.clear",
+            Some(serde_json::json!({"session_id": session_id, "mode": "scrollback"})),
+            serde_json::json!(3)
+        ).await;
+        assert_eq!(response.result.unwrap()["mode"], "scrollback");
+
+        methods.call(
+            "session.close",
+            Some(serde_json::json!({"session_id": session_id})),
+            serde_json::json!(4)
+        ).await;
+    }
+
+    #[test]
+    fn test_cap_env_truncates() {
+        let env: HashMap<String, String> = (0..10)
+            .map(|i| (format!("VAR_{}", i), "x".repeat(10)))
+            .collect();
+
+        // 每个变量 15 字节，限制 50 字节只能容纳 3 个
+        let response = cap_env(env, 50);
+        assert!(response.truncated);
+        assert_eq!(response.env.len(), 3);
+        assert!(response.env.contains_key("VAR_0"));
+
+        let small: HashMap<String, String> = [("A".to_string(), "1".to_string())].into();
+        let response = cap_env(small, 50);
+        assert!(!response.truncated);
+        assert_eq!(response.env.len(), 1);
+    }
 }
 
 /// Property-based tests for RPC error responses
@@ -317,7 +1420,17 @@ mod proptests {
         ) {
             // Skip known valid methods
             let valid_methods = ["session.create", "session.input", "session.resize", 
-                                 "session.close", "session.list", "session.get"];
+                                 "session.close", "session.list", "session.get",
+                                 "session.set_title", "session.pause", "session.resume",
+                                 "session.env", "session.clear", "session.detach",
+                                 "session.attach", "server.error_catalog",
+                                 "server.stats", "session.close_where",
+                                 "server.selftest", "session.eof",
+                                 "session.inject_output", "session.write_raw",
+                                 "server.list_shells", "server.list_osc_codes", "session.rename",
+                                 "session.set_read_only", "session.query",
+                                 "session.batch",
+                                 "session.sync"];
             if valid_methods.contains(&method.as_str()) {
                 return Ok(());
             }
@@ -352,6 +1465,20 @@ mod proptests {
                 Just("session.resize"),
                 Just("session.close"),
                 Just("session.get"),
+                Just("session.query"),
+                Just("session.set_title"),
+                Just("session.rename"),
+                Just("session.set_read_only"),
+                Just("session.pause"),
+                Just("session.resume"),
+                Just("session.env"),
+                Just("session.clear"),
+                Just("session.detach"),
+                Just("session.attach"),
+                Just("session.inject_output"),
+                Just("session.write_raw"),
+                Just("session.batch"),
+                Just("session.sync"),
             ],
             id in request_id_strategy()
         ) {
@@ -382,6 +1509,17 @@ mod proptests {
                 Just("session.resize"),
                 Just("session.close"),
                 Just("session.get"),
+                Just("session.set_title"),
+                Just("session.rename"),
+                Just("session.set_read_only"),
+                Just("session.pause"),
+                Just("session.resume"),
+                Just("session.env"),
+                Just("session.clear"),
+                Just("session.detach"),
+                Just("session.attach"),
+                Just("session.batch"),
+                Just("session.sync"),
             ],
             params in invalid_params_strategy(),
             id in request_id_strategy()
@@ -407,6 +1545,28 @@ mod proptests {
             prop_assert_eq!(response.jsonrpc, "2.0", "jsonrpc version should be 2.0");
         }
 
+        /// Feature: terminal-plugin, Property 3: RPC 错误响应格式
+        /// *对于任意*方法名和参数，响应都应回显请求 ID，且 result 与 error 恰好有一个
+        #[test]
+        fn prop_response_echoes_request_id(
+            method in method_name_strategy(),
+            params in invalid_params_strategy(),
+            id in request_id_strategy()
+        ) {
+            let rt = tokio::runtime::Runtime::new().unwrap();
+            let response = rt.block_on(async {
+                let mut methods = RpcMethods::new();
+                methods.call(&method, params, id.clone()).await
+            });
+
+            prop_assert_eq!(response.jsonrpc, "2.0", "jsonrpc version should be 2.0");
+            prop_assert_eq!(response.id, id, "Response should echo the request id");
+            prop_assert!(
+                response.result.is_some() != response.error.is_some(),
+                "Response should have exactly one of result and error"
+            );
+        }
+
         /// Feature: terminal-plugin, Property 3: RPC 错误响应格式
         /// *对于任意*错误响应，都应该符合 JSON-RPC 2.0 规范
         #[test]
@@ -6,5 +6,5 @@ pub mod server;
 pub mod methods;
 pub mod types;
 
-pub use server::{RpcServer, NotificationSender};
+pub use server::{NotificationDropPolicy, NotificationSender, RpcServer, RpcServerConfig};
 pub use types::*;
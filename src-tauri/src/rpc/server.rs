@@ -2,39 +2,160 @@
 //!
 //! 通过 stdin/stdout 实现 JSON-RPC 2.0 通信。
 
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::Arc;
-use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
-use tokio::sync::{mpsc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use futures::stream::{FuturesUnordered, StreamExt};
+use tokio::sync::{mpsc, oneshot, watch, Mutex};
 
 use super::methods::RpcMethods;
-use super::types::{JsonRpcNotification, JsonRpcRequest, JsonRpcResponse};
+use crate::shell::osc::ClipboardRejection;
+use crate::ssh::host_key::HostKeyChange;
+use crate::utils::codec;
+use crate::utils::error::TerminalError;
+use super::types::{
+    AuthenticateRequest, JsonRpcError, JsonRpcNotification, JsonRpcRequest, JsonRpcResponse,
+    PingRequest, PingResponse,
+    SessionError, SessionTiming, TerminalModes,
+};
+
+/// 通知接收端关闭后的处理策略
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum NotificationDropPolicy {
+    /// 返回发送错误，由调用方处理
+    #[default]
+    Propagate,
+    /// 静默丢弃并计数，发送视为成功；用于客户端正常断开或服务器退出时
+    Drop,
+}
 
 /// 通知发送器，可以克隆并在多个地方使用
+///
+/// 丢弃策略和丢弃计数在所有克隆之间共享。
 #[derive(Clone)]
 pub struct NotificationSender {
     tx: mpsc::UnboundedSender<JsonRpcNotification>,
+    /// 接收端关闭时是否丢弃通知
+    drop_when_closed: Arc<AtomicBool>,
+    /// 因接收端关闭而丢弃的通知数
+    dropped: Arc<AtomicU64>,
+    /// 单条通知序列化后的最大字节数（不含换行符）
+    max_size: usize,
+    /// 因超过最大字节数而丢弃的通知数
+    oversized: Arc<AtomicU64>,
+    /// 是否有客户端在接收通知
+    attached: Arc<watch::Sender<bool>>,
 }
 
 impl NotificationSender {
+    /// 使用指定的丢弃策略和单条通知最大字节数创建通知发送器
+    fn with_policy(
+        tx: mpsc::UnboundedSender<JsonRpcNotification>,
+        policy: NotificationDropPolicy,
+        max_size: usize,
+    ) -> Self {
+        Self {
+            tx,
+            drop_when_closed: Arc::new(AtomicBool::new(policy == NotificationDropPolicy::Drop)),
+            dropped: Arc::new(AtomicU64::new(0)),
+            max_size,
+            oversized: Arc::new(AtomicU64::new(0)),
+            attached: Arc::new(watch::Sender::new(true)),
+        }
+    }
+
     /// 创建新的通知发送器（用于测试）
     #[cfg(test)]
     pub fn new_for_test(tx: mpsc::UnboundedSender<JsonRpcNotification>) -> Self {
-        Self { tx }
+        Self::with_policy(tx, NotificationDropPolicy::Propagate, DEFAULT_MAX_NOTIFICATION_SIZE)
+    }
+
+    /// 当前的丢弃策略
+    pub fn drop_policy(&self) -> NotificationDropPolicy {
+        if self.drop_when_closed.load(Ordering::Relaxed) {
+            NotificationDropPolicy::Drop
+        } else {
+            NotificationDropPolicy::Propagate
+        }
+    }
+
+    /// 修改丢弃策略，对所有克隆生效
+    pub fn set_drop_policy(&self, policy: NotificationDropPolicy) {
+        self.drop_when_closed
+            .store(policy == NotificationDropPolicy::Drop, Ordering::Relaxed);
+    }
+
+    /// 因接收端关闭而丢弃的通知数
+    pub fn dropped_count(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    /// 因超过最大字节数而丢弃的通知数
+    pub fn oversized_count(&self) -> u64 {
+        self.oversized.load(Ordering::Relaxed)
+    }
+
+    /// 是否有客户端在接收通知
+    ///
+    /// 没有客户端时输出读取器暂停读取，由 PTY 缓冲区（SSH 会话为通道窗口）对子进程施加背压，
+    /// 通知不会在服务器中无限堆积。
+    pub fn is_attached(&self) -> bool {
+        *self.attached.borrow()
+    }
+
+    /// 设置是否有客户端在接收通知，对所有克隆生效
+    pub fn set_attached(&self, attached: bool) {
+        self.attached.send_replace(attached);
+    }
+
+    /// 等待客户端连接
+    pub async fn wait_attached(&self) {
+        let _ = self.attached.subscribe().wait_for(|&attached| attached).await;
     }
 
     /// 发送通知
+    ///
+    /// 序列化后超过最大字节数的通知被丢弃并计数，发送视为成功，避免单条消息占满传输通道。
+    /// 接收端已关闭时，`Drop` 策略下丢弃通知并返回成功，只在第一次丢弃时记录日志。
     pub fn send(&self, notification: JsonRpcNotification) -> Result<(), mpsc::error::SendError<JsonRpcNotification>> {
-        self.tx.send(notification)
+        let size = serialized_size(&notification);
+        if size > self.max_size {
+            self.oversized.fetch_add(1, Ordering::Relaxed);
+            tracing::warn!(
+                "通知 {} 序列化后 {} 字节，超过上限 {} 字节，已丢弃",
+                notification.method,
+                size,
+                self.max_size
+            );
+            return Ok(());
+        }
+
+        match self.tx.send(notification) {
+            Err(e) if self.drop_when_closed.load(Ordering::Relaxed) => {
+                if self.dropped.fetch_add(1, Ordering::Relaxed) == 0 {
+                    tracing::debug!("通知接收端已关闭，丢弃后续通知: {}", e.0.method);
+                }
+                Ok(())
+            }
+            result => result,
+        }
     }
 
     /// 发送终端输出通知
-    pub fn send_output(&self, session_id: &str, data: &str) -> Result<(), mpsc::error::SendError<JsonRpcNotification>> {
+    ///
+    /// `seq` 在会话内单调递增，`is_final` 标记一次读取拆分出的最后一个分块，
+    /// 客户端按 `seq` 顺序拼接直到 `final` 为 true 即可还原完整输出。
+    pub fn send_output(&self, session_id: &str, data: &str, seq: u64, is_final: bool) -> Result<(), mpsc::error::SendError<JsonRpcNotification>> {
         let notification = JsonRpcNotification {
             jsonrpc: "2.0".to_string(),
             method: "terminal.output".to_string(),
             params: Some(serde_json::json!({
                 "session_id": session_id,
-                "data": data
+                "data": data,
+                "seq": seq,
+                "final": is_final
             })),
         };
         self.send(notification)
@@ -42,6 +163,21 @@ impl NotificationSender {
 
     /// 发送会话状态变更通知
     pub fn send_status(&self, session_id: &str, status: &str, exit_code: Option<i32>) -> Result<(), mpsc::error::SendError<JsonRpcNotification>> {
+        self.send_status_with_reason(session_id, status, exit_code, None, None)
+    }
+
+    /// 发送带结束原因的会话状态变更通知
+    ///
+    /// 会话被服务器结束（如空闲超时）时 `reason` 说明原因；会话已结束时 `timing`
+    /// 附带 `ended_at` 和 `duration_secs`。其余字段与 `send_status` 相同。
+    pub fn send_status_with_reason(
+        &self,
+        session_id: &str,
+        status: &str,
+        exit_code: Option<i32>,
+        reason: Option<&str>,
+        timing: Option<SessionTiming>,
+    ) -> Result<(), mpsc::error::SendError<JsonRpcNotification>> {
         let mut params = serde_json::json!({
             "session_id": session_id,
             "status": status
@@ -49,6 +185,40 @@ impl NotificationSender {
         if let Some(code) = exit_code {
             params["exit_code"] = serde_json::json!(code);
         }
+        if let Some(reason) = reason {
+            params["reason"] = serde_json::json!(reason);
+        }
+        insert_timing(&mut params, timing);
+        let notification = JsonRpcNotification {
+            jsonrpc: "2.0".to_string(),
+            method: "session.status".to_string(),
+            params: Some(params),
+        };
+        self.send(notification)
+    }
+
+    /// 发送进程被信号终止的状态通知
+    ///
+    /// `{ session_id, status: "done", signal, core_dumped, error_message?, ended_at?, duration_secs? }`，
+    /// 信号名不带 `SIG` 前缀（如 `KILL`），没有错误信息时省略 `error_message`。
+    pub fn send_signal_status(
+        &self,
+        session_id: &str,
+        signal: &str,
+        core_dumped: bool,
+        error_message: Option<&str>,
+        timing: Option<SessionTiming>,
+    ) -> Result<(), mpsc::error::SendError<JsonRpcNotification>> {
+        let mut params = serde_json::json!({
+            "session_id": session_id,
+            "status": "done",
+            "signal": signal,
+            "core_dumped": core_dumped
+        });
+        if let Some(message) = error_message {
+            params["error_message"] = serde_json::json!(message);
+        }
+        insert_timing(&mut params, timing);
         let notification = JsonRpcNotification {
             jsonrpc: "2.0".to_string(),
             method: "session.status".to_string(),
@@ -57,14 +227,94 @@ impl NotificationSender {
         self.send(notification)
     }
 
+    /// 发送错误状态通知
+    ///
+    /// 在 `session.status` 通知中附带错误详情:
+    /// `{ session_id, status: "error", error: { message, error_type, code, recoverable } }`。
+    pub fn send_error_status(&self, session_id: &str, error: &TerminalError) -> Result<(), mpsc::error::SendError<JsonRpcNotification>> {
+        let notification = JsonRpcNotification {
+            jsonrpc: "2.0".to_string(),
+            method: "session.status".to_string(),
+            params: Some(serde_json::json!({
+                "session_id": session_id,
+                "status": "error",
+                "error": SessionError::from(error)
+            })),
+        };
+        self.send(notification)
+    }
+
     /// 发送工作目录变更通知
-    pub fn send_cwd(&self, session_id: &str, cwd: &str) -> Result<(), mpsc::error::SendError<JsonRpcNotification>> {
+    ///
+    /// `{ session_id, cwd, host? }`，`host` 为 OSC 7 `file://` URL 中的主机名，
+    /// 没有主机名（包括轮询得到的工作目录）时省略。
+    pub fn send_cwd(
+        &self,
+        session_id: &str,
+        cwd: &str,
+        host: Option<&str>,
+    ) -> Result<(), mpsc::error::SendError<JsonRpcNotification>> {
+        let mut params = serde_json::json!({
+            "session_id": session_id,
+            "cwd": cwd
+        });
+        if let Some(host) = host {
+            params["host"] = serde_json::json!(host);
+        }
         let notification = JsonRpcNotification {
             jsonrpc: "2.0".to_string(),
             method: "session.cwd".to_string(),
+            params: Some(params),
+        };
+        self.send(notification)
+    }
+
+    /// 发送命令执行结束通知
+    ///
+    /// 由 OSC 133 `D` 标记触发：`{ session_id, exit_code?, command? }`，
+    /// 没有退出码或无法得到命令文本时省略对应字段。
+    pub fn send_command_finished(
+        &self,
+        session_id: &str,
+        exit_code: Option<i32>,
+        command: Option<&str>,
+    ) -> Result<(), mpsc::error::SendError<JsonRpcNotification>> {
+        let mut params = serde_json::json!({ "session_id": session_id });
+        if let Some(code) = exit_code {
+            params["exit_code"] = serde_json::json!(code);
+        }
+        if let Some(command) = command {
+            params["command"] = serde_json::json!(command);
+        }
+        let notification = JsonRpcNotification {
+            jsonrpc: "2.0".to_string(),
+            method: "session.command_finished".to_string(),
+            params: Some(params),
+        };
+        self.send(notification)
+    }
+
+    /// 发送主机密钥变更通知
+    ///
+    /// SSH 握手暂停，等待通过 [`HostKeyPrompts::resolve`](crate::ssh::host_key::HostKeyPrompts::resolve)
+    /// 提交的决定。
+    pub fn send_host_key_changed(
+        &self,
+        session_id: &str,
+        change: &HostKeyChange,
+    ) -> Result<(), mpsc::error::SendError<JsonRpcNotification>> {
+        let notification = JsonRpcNotification {
+            jsonrpc: "2.0".to_string(),
+            method: "session.host_key_changed".to_string(),
             params: Some(serde_json::json!({
                 "session_id": session_id,
-                "cwd": cwd
+                "host": change.host,
+                "port": change.port,
+                "key_type": change.key_type,
+                "old_fingerprint": change.old_fingerprint,
+                "new_fingerprint": change.new_fingerprint,
+                "known_hosts_path": change.known_hosts_path,
+                "line": change.line,
             })),
         };
         self.send(notification)
@@ -83,18 +333,247 @@ impl NotificationSender {
         self.send(notification)
     }
 
+    /// 发送会话重命名通知，清除名称时 `name` 为 `null`
+    pub fn send_renamed(&self, session_id: &str, name: Option<&str>) -> Result<(), mpsc::error::SendError<JsonRpcNotification>> {
+        let notification = JsonRpcNotification {
+            jsonrpc: "2.0".to_string(),
+            method: "session.renamed".to_string(),
+            params: Some(serde_json::json!({
+                "session_id": session_id,
+                "name": name
+            })),
+        };
+        self.send(notification)
+    }
+
+    /// 发送会话就绪通知
+    ///
+    /// 在 shell 第一次输出后发送，表示终端已经可以交互。
+    pub fn send_ready(&self, session_id: &str) -> Result<(), mpsc::error::SendError<JsonRpcNotification>> {
+        let notification = JsonRpcNotification {
+            jsonrpc: "2.0".to_string(),
+            method: "session.ready".to_string(),
+            params: Some(serde_json::json!({
+                "session_id": session_id
+            })),
+        };
+        self.send(notification)
+    }
+
+    /// 发送终端模式变更通知
+    pub fn send_mode(&self, session_id: &str, modes: &TerminalModes) -> Result<(), mpsc::error::SendError<JsonRpcNotification>> {
+        let notification = JsonRpcNotification {
+            jsonrpc: "2.0".to_string(),
+            method: "session.mode".to_string(),
+            params: Some(serde_json::json!({
+                "session_id": session_id,
+                "modes": modes
+            })),
+        };
+        self.send(notification)
+    }
+
+    /// 发送颜色变更通知
+    ///
+    /// `target` 为 `palette`、`foreground` 或 `background`，调色板颜色附带 `index`。
+    /// `color` 为 `None` 表示程序查询了该颜色（`query` 为 true）。
+    pub fn send_color(
+        &self,
+        session_id: &str,
+        target: &str,
+        index: Option<u8>,
+        color: Option<&str>,
+    ) -> Result<(), mpsc::error::SendError<JsonRpcNotification>> {
+        let mut params = serde_json::json!({
+            "session_id": session_id,
+            "target": target,
+            "query": color.is_none()
+        });
+        if let Some(index) = index {
+            params["index"] = serde_json::json!(index);
+        }
+        if let Some(color) = color {
+            params["color"] = serde_json::json!(color);
+        }
+        let notification = JsonRpcNotification {
+            jsonrpc: "2.0".to_string(),
+            method: "session.color".to_string(),
+            params: Some(params),
+        };
+        self.send(notification)
+    }
+
     /// 发送剪贴板内容通知
-    pub fn send_clipboard(&self, session_id: &str, content: &str) -> Result<(), mpsc::error::SendError<JsonRpcNotification>> {
+    ///
+    /// 内容为 Base64 编码的原始字节，`is_utf8` 表示解码后是否为有效的 UTF-8 文本。
+    pub fn send_clipboard(&self, session_id: &str, content: &[u8]) -> Result<(), mpsc::error::SendError<JsonRpcNotification>> {
         let notification = JsonRpcNotification {
             jsonrpc: "2.0".to_string(),
             method: "session.clipboard".to_string(),
             params: Some(serde_json::json!({
                 "session_id": session_id,
-                "content": content
+                "content": codec::encode_output(content),
+                "is_utf8": std::str::from_utf8(content).is_ok()
             })),
         };
         self.send(notification)
     }
+
+    /// 发送剪贴板写入被拒绝通知
+    ///
+    /// 数据过大时附带尝试写入的大小和限制，便于前端提示用户。
+    pub fn send_clipboard_rejected(
+        &self,
+        session_id: &str,
+        rejection: &ClipboardRejection,
+    ) -> Result<(), mpsc::error::SendError<JsonRpcNotification>> {
+        let mut params = serde_json::json!({
+            "session_id": session_id,
+            "reason": rejection.reason()
+        });
+        if let ClipboardRejection::TooLarge { size, limit } = rejection {
+            params["size"] = serde_json::json!(size);
+            params["limit"] = serde_json::json!(limit);
+        }
+        let notification = JsonRpcNotification {
+            jsonrpc: "2.0".to_string(),
+            method: "session.clipboard_rejected".to_string(),
+            params: Some(params),
+        };
+        self.send(notification)
+    }
+}
+
+/// 在状态通知参数中加入结束时间和运行时长
+fn insert_timing(params: &mut serde_json::Value, timing: Option<SessionTiming>) {
+    if let Some(timing) = timing {
+        params["ended_at"] = serde_json::json!(timing.ended_at);
+        params["duration_secs"] = serde_json::json!(timing.duration_secs);
+    }
+}
+
+/// RPC 服务器配置
+#[derive(Debug, Clone)]
+pub struct RpcServerConfig {
+    /// 通知批量刷新的最长等待时间
+    ///
+    /// 为零时每条通知写入后立即刷新；否则收到第一条通知后最多等待这么久，
+    /// 把期间到达的通知合并为一次写入和刷新。请求的响应总是立即刷新。
+    pub notification_flush_interval: Duration,
+    /// 单次批量刷新的最大通知数，达到后立即刷新
+    pub notification_flush_max_messages: usize,
+    /// 单个请求的最大字节数（不含换行符），超出的请求被丢弃并返回解析错误
+    pub max_request_size: usize,
+    /// 通知接收端关闭后的处理策略
+    pub notification_drop_policy: NotificationDropPolicy,
+    /// 单条通知序列化后的最大字节数（不含换行符），超出的通知被丢弃并计入
+    /// `notifications_oversized`
+    pub max_notification_size: usize,
+    /// TCP 连接的认证令牌
+    ///
+    /// 设置后每个 TCP 连接的第一个请求必须是携带该令牌的 `server.authenticate`；
+    /// 绑定到非回环地址时必须设置。
+    pub auth_token: Option<String>,
+}
+
+/// 默认最大请求大小
+pub const DEFAULT_MAX_REQUEST_SIZE: usize = 16 * 1024 * 1024;
+
+/// 认证请求的最大字节数，认证前不按 `max_request_size` 缓冲大块数据
+const MAX_AUTH_REQUEST_SIZE: usize = 4096;
+
+/// 等待认证请求的最长时间
+const AUTH_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// 接受 TCP 连接失败后，再次接受前等待的时间
+const ACCEPT_RETRY_DELAY: Duration = Duration::from_millis(100);
+
+/// 默认单条通知最大字节数，足以容纳 1MiB 读取缓冲区或剪贴板内容的 base64 编码
+pub const DEFAULT_MAX_NOTIFICATION_SIZE: usize = 4 * 1024 * 1024;
+
+impl Default for RpcServerConfig {
+    fn default() -> Self {
+        Self {
+            notification_flush_interval: Duration::ZERO,
+            notification_flush_max_messages: 256,
+            max_request_size: DEFAULT_MAX_REQUEST_SIZE,
+            notification_drop_policy: NotificationDropPolicy::default(),
+            max_notification_size: DEFAULT_MAX_NOTIFICATION_SIZE,
+            auth_token: None,
+        }
+    }
+}
+
+/// 读取一行请求的结果
+#[derive(Debug, PartialEq, Eq)]
+enum RequestLine {
+    /// 完整的一行（不含换行符和换行符前的 `\r`）
+    Line,
+    /// 超过最大长度，已跳过到下一个换行符
+    TooLong,
+    /// 输入流结束
+    Eof,
+}
+
+/// 读取一行请求到缓冲区
+///
+/// 与 `read_line` 不同，这里不会无限制地累积数据：超过 `max_size` 后丢弃剩余内容，
+/// 直到遇到换行符为止。读取的是原始字节，UTF-8 校验交给调用方。
+///
+/// 行以 `\n` 结束，`\r\n` 结尾时去掉 `\r`；单独的 `\r` 不结束一行。输入在没有换行符的
+/// 情况下结束时，最后的不完整行也作为一行返回。
+async fn read_request_line<R>(
+    reader: &mut R,
+    buffer: &mut Vec<u8>,
+    max_size: usize,
+) -> std::io::Result<RequestLine>
+where
+    R: AsyncBufRead + Unpin,
+{
+    buffer.clear();
+    let mut read_any = false;
+    let mut too_long = false;
+
+    loop {
+        let available = reader.fill_buf().await?;
+        if available.is_empty() {
+            // EOF：没有读到任何数据时结束，否则把最后一行当作完整请求
+            if !read_any {
+                return Ok(RequestLine::Eof);
+            }
+            break;
+        }
+        read_any = true;
+
+        let (chunk, found_newline) = match available.iter().position(|&b| b == b'\n') {
+            Some(i) => (&available[..i], true),
+            None => (available, false),
+        };
+
+        if !too_long {
+            if buffer.len() + chunk.len() > max_size {
+                too_long = true;
+                buffer.clear();
+            } else {
+                buffer.extend_from_slice(chunk);
+            }
+        }
+
+        let consumed = chunk.len() + usize::from(found_newline);
+        reader.consume(consumed);
+
+        if found_newline {
+            break;
+        }
+    }
+
+    if too_long {
+        return Ok(RequestLine::TooLong);
+    }
+    if buffer.last() == Some(&b'\r') {
+        buffer.pop();
+    }
+    Ok(RequestLine::Line)
 }
 
 /// RPC 服务器
@@ -102,13 +581,28 @@ pub struct RpcServer {
     methods: Arc<Mutex<RpcMethods>>,
     notification_rx: Arc<Mutex<mpsc::UnboundedReceiver<JsonRpcNotification>>>,
     notification_sender: NotificationSender,
+    /// TCP 连接各自的通知订阅
+    subscribers: NotificationSubscribers,
+    /// 服务器启动时间，作为心跳时间戳的基准
+    started_at: Instant,
+    /// 服务器配置
+    config: RpcServerConfig,
 }
 
 impl RpcServer {
     /// 创建新的 RPC 服务器
     pub fn new() -> Self {
+        Self::with_config(RpcServerConfig::default())
+    }
+
+    /// 使用指定配置创建 RPC 服务器
+    pub fn with_config(config: RpcServerConfig) -> Self {
         let (tx, rx) = mpsc::unbounded_channel();
-        let notification_sender = NotificationSender { tx };
+        let notification_sender = NotificationSender::with_policy(
+            tx,
+            config.notification_drop_policy,
+            config.max_notification_size,
+        );
         
         // 创建带通知发送器的 RpcMethods
         let methods = RpcMethods::with_notification_sender(notification_sender.clone());
@@ -116,7 +610,10 @@ impl RpcServer {
         Self {
             methods: Arc::new(Mutex::new(methods)),
             notification_rx: Arc::new(Mutex::new(rx)),
+            subscribers: NotificationSubscribers::new(notification_sender.clone()),
             notification_sender,
+            started_at: Instant::now(),
+            config,
         }
     }
 
@@ -127,78 +624,316 @@ impl RpcServer {
 
     /// 运行 RPC 服务器
     pub async fn run(&self) -> anyhow::Result<()> {
-        let stdin = tokio::io::stdin();
-        let stdout = Arc::new(Mutex::new(tokio::io::stdout()));
-        let mut reader = BufReader::new(stdin);
+        self.serve(tokio::io::stdin(), tokio::io::stdout()).await
+    }
 
-        let mut line = String::new();
+    /// 在 Unix 域套接字上运行 RPC 服务器
+    ///
+    /// 依次接受客户端连接，同一时间只服务一个客户端。客户端断开后会话保持运行，
+    /// 下一个连接的客户端可以继续使用这些会话。断开期间输出读取器暂停读取，
+    /// 子进程的输出留在 PTY 中，重新连接后继续发送；已经产生的状态等通知在重新连接后发送。
+    /// `path` 处已有的套接字文件会被替换；存在其他类型的文件时返回错误。
+    /// 套接字文件权限设为 0600，只有当前用户可以连接。
+    #[cfg(unix)]
+    pub async fn run_on_socket(&self, path: impl AsRef<std::path::Path>) -> anyhow::Result<()> {
+        use std::os::unix::fs::{FileTypeExt, PermissionsExt};
 
-        // 启动通知发送任务
-        let notification_rx = self.notification_rx.clone();
-        let stdout_for_notifications = stdout.clone();
-        let notification_task = tokio::spawn(async move {
-            let mut rx = notification_rx.lock().await;
-            while let Some(notification) = rx.recv().await {
-                let mut stdout = stdout_for_notifications.lock().await;
-                if let Ok(json) = serde_json::to_string(&notification) {
-                    let _ = stdout.write_all(json.as_bytes()).await;
-                    let _ = stdout.write_all(b"\n").await;
-                    let _ = stdout.flush().await;
-                }
+        let path = path.as_ref();
+        if let Ok(metadata) = std::fs::symlink_metadata(path) {
+            if !metadata.file_type().is_socket() {
+                anyhow::bail!("路径已存在且不是套接字: {}", path.display());
             }
-        });
+            std::fs::remove_file(path)?;
+        }
+
+        let listener = tokio::net::UnixListener::bind(path)?;
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))?;
+        tracing::info!("在套接字上等待连接: {}", path.display());
 
         loop {
-            line.clear();
-            let bytes_read = reader.read_line(&mut line).await?;
+            let (stream, _) = listener.accept().await?;
+            tracing::info!("客户端已连接: {}", path.display());
+            let (input, output) = stream.into_split();
+            self.serve_connection(input, output, true, None).await?;
+            tracing::info!("客户端已断开，会话保持运行: {}", path.display());
+        }
+    }
 
-            if bytes_read == 0 {
-                // EOF，退出
-                tracing::info!("stdin 关闭，退出");
-                break;
+    /// 在 TCP 地址上运行 RPC 服务器
+    ///
+    /// 供测试工具、IDE 集成等其他进程控制插件。每个连接有自己的读取循环，所有连接共享
+    /// 同一组会话，客户端断开后会话保持运行。每个连接都有自己的通知订阅，收到所有会话的通知；
+    /// 没有连接时通知留在队列中，下一个连接的客户端会收到。绑定到非回环地址时必须设置
+    /// [`RpcServerConfig::auth_token`]。
+    pub async fn run_on_tcp(&self, addr: impl tokio::net::ToSocketAddrs) -> anyhow::Result<()> {
+        let listener = tokio::net::TcpListener::bind(addr).await?;
+        self.serve_tcp(listener).await
+    }
+
+    /// 在已绑定的 TCP 监听器上运行 RPC 服务器，规则与 [`run_on_tcp`](Self::run_on_tcp) 相同
+    pub async fn serve_tcp(&self, listener: tokio::net::TcpListener) -> anyhow::Result<()> {
+        let local_addr = listener.local_addr()?;
+        if !local_addr.ip().is_loopback() && self.config.auth_token.is_none() {
+            anyhow::bail!("绑定到非回环地址时必须设置认证令牌: {}", local_addr);
+        }
+        tracing::info!("在 TCP 地址上等待连接: {}", local_addr);
+
+        // 通知由分发任务从共享通道转发给每个连接的订阅，没有连接时暂停转发
+        self.notification_sender.set_attached(false);
+        let dispatch = dispatch_notifications(self.notification_rx.clone(), self.subscribers.clone());
+        tokio::pin!(dispatch);
+
+        let mut connections = FuturesUnordered::new();
+        let retry_accept = tokio::time::sleep(Duration::ZERO);
+        tokio::pin!(retry_accept);
+        loop {
+            tokio::select! {
+                accepted = listener.accept(), if retry_accept.is_elapsed() => match accepted {
+                    Ok((stream, peer)) => {
+                        tracing::info!("TCP 客户端已连接: {}", peer);
+                        connections.push(self.serve_tcp_client(stream, peer));
+                    }
+                    // 文件描述符耗尽、对端在握手完成前断开等错误只影响这一次连接，
+                    // 稍后继续接受，已连接的客户端不受影响
+                    Err(e) => {
+                        tracing::warn!("接受 TCP 连接失败: {}", e);
+                        retry_accept
+                            .as_mut()
+                            .reset(tokio::time::Instant::now() + ACCEPT_RETRY_DELAY);
+                    }
+                },
+                () = &mut retry_accept, if !retry_accept.is_elapsed() => {}
+                Some(()) = connections.next(), if !connections.is_empty() => {}
+                () = &mut dispatch => anyhow::bail!("通知通道已关闭"),
             }
+        }
+    }
 
-            let line_trimmed = line.trim();
-            if line_trimmed.is_empty() {
-                continue;
+    /// 服务一个 TCP 客户端，配置了令牌时先完成认证
+    async fn serve_tcp_client(&self, stream: tokio::net::TcpStream, peer: std::net::SocketAddr) {
+        let (input, mut output) = stream.into_split();
+        let mut input = BufReader::new(input);
+
+        if let Some(token) = &self.config.auth_token {
+            match self.authenticate(&mut input, &mut output, token).await {
+                Ok(true) => tracing::debug!("TCP 客户端认证成功: {}", peer),
+                Ok(false) => {
+                    tracing::warn!("TCP 客户端认证失败，断开连接: {}", peer);
+                    return;
+                }
+                Err(e) => {
+                    tracing::warn!("TCP 客户端认证时连接出错: {} - {}", peer, e);
+                    return;
+                }
+            }
+        }
+
+        let subscription = self.subscribers.subscribe();
+        if let Err(e) = self.serve_connection(input, output, true, Some(subscription)).await {
+            tracing::warn!("TCP 连接出错: {} - {}", peer, e);
+        }
+        tracing::info!("TCP 客户端已断开，会话保持运行: {}", peer);
+    }
+
+    /// 读取第一个请求并校验 `server.authenticate` 中的令牌，写回认证结果
+    ///
+    /// 请求最多 [`MAX_AUTH_REQUEST_SIZE`] 字节，超过 [`AUTH_TIMEOUT`] 未读到完整的一行时返回
+    /// `TimedOut` 错误。
+    async fn authenticate<R, W>(&self, input: &mut R, output: &mut W, token: &str) -> std::io::Result<bool>
+    where
+        R: AsyncBufRead + Unpin,
+        W: AsyncWrite + Unpin,
+    {
+        let mut line = Vec::new();
+        let max_size = self.config.max_request_size.min(MAX_AUTH_REQUEST_SIZE);
+        let read = tokio::time::timeout(AUTH_TIMEOUT, read_request_line(input, &mut line, max_size))
+            .await
+            .map_err(|_| std::io::Error::new(std::io::ErrorKind::TimedOut, "等待认证请求超时"))?;
+        let request = match read? {
+            RequestLine::Eof => return Ok(false),
+            RequestLine::TooLong => None,
+            RequestLine::Line => serde_json::from_slice(&line)
+                .ok()
+                .and_then(|value| parse_request(value).ok()),
+        };
+
+        let (id, authenticated) = match request {
+            Some(request) if request.method == "server.authenticate" => {
+                let authenticated = request
+                    .params
+                    .and_then(|params| serde_json::from_value::<AuthenticateRequest>(params).ok())
+                    .is_some_and(|params| tokens_match(&params.token, token));
+                (request.id, authenticated)
+            }
+            Some(request) => (request.id, false),
+            None => (serde_json::Value::Null, false),
+        };
+
+        let response = if authenticated {
+            JsonRpcResponse::success(id, serde_json::json!({ "authenticated": true }))
+        } else {
+            JsonRpcResponse::error(id, JsonRpcError::invalid_request("认证失败"))
+        };
+        let response_json = serde_json::to_string(&response).map_err(std::io::Error::other)?;
+        output.write_all(response_json.as_bytes()).await?;
+        output.write_all(b"\n").await?;
+        output.flush().await?;
+        Ok(authenticated)
+    }
+
+    /// 在指定的输入输出流上运行 RPC 服务器
+    pub async fn serve<R, W>(&self, input: R, output: W) -> anyhow::Result<()>
+    where
+        R: AsyncRead + Unpin,
+        W: AsyncWrite + Unpin + Send + 'static,
+    {
+        self.serve_connection(input, output, false, None).await
+    }
+
+    /// 服务一个连接，直到输入结束或写入失败
+    ///
+    /// `keep_sessions` 为 false 时写入失败会关闭所有会话，为 true 时会话保持运行，
+    /// 供之后的连接继续使用。`subscription` 为 `None` 时直接从共享通道接收通知，
+    /// 同一时间只有一个这样的连接接收通知。
+    async fn serve_connection<R, W>(
+        &self,
+        input: R,
+        output: W,
+        keep_sessions: bool,
+        subscription: Option<NotificationSubscription>,
+    ) -> anyhow::Result<()>
+    where
+        R: AsyncRead + Unpin,
+        W: AsyncWrite + Unpin + Send + 'static,
+    {
+        let output = Arc::new(Mutex::new(output));
+        let mut reader = BufReader::new(input);
+
+        let mut line = Vec::new();
+
+        // 启动通知发送任务
+        let notification_rx = self.notification_rx.clone();
+        let output_for_notifications = output.clone();
+        let config = self.config.clone();
+        let sender = self.notification_sender.clone();
+        let (drain_tx, drain_rx) = oneshot::channel::<()>();
+        let mut notification_task = tokio::spawn(async move {
+            let drain = async {
+                let _ = drain_rx.await;
+            };
+            match subscription {
+                Some(mut subscription) => {
+                    forward_notifications(&mut subscription.rx, &output_for_notifications, &config, drain).await
+                }
+                None => {
+                    let mut rx = notification_rx.lock().await;
+                    let _attached = AttachedGuard::new(sender);
+                    forward_notifications(&mut rx, &output_for_notifications, &config, drain).await
+                }
             }
+        });
+
+        loop {
+            let next = tokio::select! {
+                // 通知任务只会因写入失败而结束，说明 stdout 已被关闭
+                result = &mut notification_task => {
+                    match result {
+                        Ok(Err(e)) => tracing::warn!("写入通知失败，断开连接: {}", e),
+                        _ => tracing::warn!("通知任务意外结束，断开连接"),
+                    }
+                    if !keep_sessions {
+                        self.methods.lock().await.close_all_sessions().await;
+                    }
+                    return Ok(());
+                }
+                next = read_request_line(&mut reader, &mut line, self.config.max_request_size) => next?,
+            };
 
-            // 解析 JSON-RPC 请求
-            let response = self.handle_request(line_trimmed).await;
+            let response = match next {
+                RequestLine::Eof => {
+                    // EOF，退出
+                    tracing::info!("输入流关闭，退出");
+                    break;
+                }
+                RequestLine::TooLong => {
+                    tracing::warn!("请求超过最大长度 {} 字节，已丢弃", self.config.max_request_size);
+                    JsonRpcResponse::error(
+                        serde_json::Value::Null,
+                        JsonRpcError::parse_error(format!(
+                            "请求超过最大长度 {} 字节",
+                            self.config.max_request_size
+                        )),
+                    )
+                }
+                RequestLine::Line => match std::str::from_utf8(&line) {
+                    Ok(text) => {
+                        let text = text.trim();
+                        if text.is_empty() {
+                            continue;
+                        }
+                        // 解析 JSON-RPC 请求
+                        self.handle_request(text).await
+                    }
+                    Err(e) => {
+                        tracing::warn!("请求不是有效的 UTF-8: {}", e);
+                        JsonRpcResponse::error(
+                            serde_json::Value::Null,
+                            JsonRpcError::parse_error(format!("请求不是有效的 UTF-8: {}", e)),
+                        )
+                    }
+                },
+            };
 
             // 发送响应
             let response_json = serde_json::to_string(&response)?;
-            let mut stdout = stdout.lock().await;
-            stdout.write_all(response_json.as_bytes()).await?;
-            stdout.write_all(b"\n").await?;
-            stdout.flush().await?;
+            if let Err(e) = write_response(&output, &response_json).await {
+                tracing::warn!("写入响应失败，断开连接: {}", e);
+                notification_task.abort();
+                if !keep_sessions {
+                    self.methods.lock().await.close_all_sessions().await;
+                }
+                return Ok(());
+            }
         }
 
-        // 取消通知任务
-        notification_task.abort();
+        // 写出已排队的通知后再退出，避免丢失最后的输出
+        let _ = drain_tx.send(());
+        match tokio::time::timeout(NOTIFICATION_DRAIN_TIMEOUT, &mut notification_task).await {
+            Ok(Ok(Err(e))) => tracing::warn!("排空通知时写入失败: {}", e),
+            Ok(_) => {}
+            Err(_) => {
+                tracing::warn!("排空通知超时");
+                notification_task.abort();
+            }
+        }
 
         Ok(())
     }
 
     /// 处理单个请求
+    ///
+    /// 不是合法 JSON 时返回 parse error (-32700)；JSON 合法但不是有效的请求对象
+    /// （缺少 method、字段类型错误等）时返回 invalid request (-32600)。
     async fn handle_request(&self, line: &str) -> JsonRpcResponse {
         // 解析 JSON
-        let request: JsonRpcRequest = match serde_json::from_str(line) {
-            Ok(req) => req,
+        let value: serde_json::Value = match serde_json::from_str(line) {
+            Ok(value) => value,
             Err(e) => {
                 return JsonRpcResponse::error(
                     serde_json::Value::Null,
-                    super::types::JsonRpcError::parse_error(format!("JSON 解析错误: {}", e)),
+                    JsonRpcError::parse_error(format!("JSON 解析错误: {}", e)),
                 );
             }
         };
 
-        // 验证 JSON-RPC 版本
-        if request.jsonrpc != "2.0" {
-            return JsonRpcResponse::error(
-                request.id,
-                super::types::JsonRpcError::invalid_request("无效的 JSON-RPC 版本"),
-            );
+        let request = match parse_request(value) {
+            Ok(request) => request,
+            Err((id, error)) => return JsonRpcResponse::error(id, error),
+        };
+
+        // 心跳不经过 RpcMethods，避免被耗时操作持有的锁阻塞
+        if request.method == "server.ping" {
+            return self.handle_ping(request.params, request.id);
         }
 
         // 调用方法
@@ -206,6 +941,30 @@ impl RpcServer {
         methods.call(&request.method, request.params, request.id).await
     }
 
+    /// 处理心跳请求
+    ///
+    /// 原样返回客户端的 nonce 和服务器单调时钟时间戳，用于测量往返延迟。
+    fn handle_ping(&self, params: Option<serde_json::Value>, id: serde_json::Value) -> JsonRpcResponse {
+        let request: PingRequest = match params {
+            Some(p) => match serde_json::from_value(p) {
+                Ok(r) => r,
+                Err(e) => {
+                    return JsonRpcResponse::error(
+                        id,
+                        JsonRpcError::invalid_params(format!("参数解析错误: {}", e)),
+                    );
+                }
+            },
+            None => PingRequest::default(),
+        };
+
+        let response = PingResponse {
+            nonce: request.nonce,
+            timestamp_us: self.started_at.elapsed().as_micros() as u64,
+        };
+        JsonRpcResponse::success(id, serde_json::to_value(response).unwrap())
+    }
+
     /// 发送通知（用于异步事件）- 直接发送，不经过通道
     pub async fn send_notification(&self, notification: JsonRpcNotification) -> anyhow::Result<()> {
         self.notification_sender.send(notification)
@@ -213,47 +972,322 @@ impl RpcServer {
     }
 }
 
-impl Default for RpcServer {
-    fn default() -> Self {
-        Self::new()
-    }
-}
+/// 退出前等待通知写出的最长时间
+const NOTIFICATION_DRAIN_TIMEOUT: Duration = Duration::from_secs(1);
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+/// 连接转发通知期间标记客户端已连接，结束（包括任务被取消）时清除
+struct AttachedGuard(NotificationSender);
 
-    #[test]
-    fn test_notification_sender_clone() {
-        let server = RpcServer::new();
-        let sender1 = server.notification_sender();
-        let sender2 = sender1.clone();
-        
-        // Both senders should be able to send
-        assert!(sender1.send_output("test-session", "dGVzdA==").is_ok());
-        assert!(sender2.send_status("test-session", "running", None).is_ok());
+impl AttachedGuard {
+    fn new(sender: NotificationSender) -> Self {
+        sender.set_attached(true);
+        Self(sender)
     }
+}
 
-    #[test]
-    fn test_notification_sender_output() {
-        let (tx, mut rx) = mpsc::unbounded_channel();
-        let sender = NotificationSender { tx };
-        
-        sender.send_output("session-123", "SGVsbG8=").unwrap();
-        
-        let notification = rx.try_recv().unwrap();
-        assert_eq!(notification.method, "terminal.output");
-        assert!(notification.params.is_some());
-        
-        let params = notification.params.unwrap();
-        assert_eq!(params["session_id"], "session-123");
-        assert_eq!(params["data"], "SGVsbG8=");
+impl Drop for AttachedGuard {
+    fn drop(&mut self) {
+        self.0.set_attached(false);
     }
+}
 
-    #[test]
-    fn test_notification_sender_status() {
-        let (tx, mut rx) = mpsc::unbounded_channel();
-        let sender = NotificationSender { tx };
+/// TCP 连接的通知订阅表，所有克隆共享
+///
+/// 有订阅时标记客户端已连接，最后一个订阅取消时清除。
+#[derive(Clone)]
+struct NotificationSubscribers {
+    inner: Arc<std::sync::Mutex<SubscriberTable>>,
+    sender: NotificationSender,
+}
+
+#[derive(Default)]
+struct SubscriberTable {
+    next_id: u64,
+    senders: HashMap<u64, mpsc::UnboundedSender<JsonRpcNotification>>,
+}
+
+impl NotificationSubscribers {
+    fn new(sender: NotificationSender) -> Self {
+        Self {
+            inner: Arc::new(std::sync::Mutex::new(SubscriberTable::default())),
+            sender,
+        }
+    }
+
+    /// 新增一个订阅，之后分发的通知都会发给它
+    fn subscribe(&self) -> NotificationSubscription {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let id = {
+            let mut table = self.inner.lock().unwrap();
+            let id = table.next_id;
+            table.next_id += 1;
+            table.senders.insert(id, tx);
+            id
+        };
+        self.sender.set_attached(true);
+        NotificationSubscription {
+            id,
+            rx,
+            subscribers: self.clone(),
+        }
+    }
+
+    fn unsubscribe(&self, id: u64) {
+        let mut table = self.inner.lock().unwrap();
+        table.senders.remove(&id);
+        if table.senders.is_empty() {
+            self.sender.set_attached(false);
+        }
+    }
+
+    /// 把通知发给所有订阅，没有任何订阅收到时原样返回
+    fn broadcast(&self, notification: JsonRpcNotification) -> Option<JsonRpcNotification> {
+        let table = self.inner.lock().unwrap();
+        let mut delivered = false;
+        for tx in table.senders.values() {
+            delivered |= tx.send(notification.clone()).is_ok();
+        }
+        if delivered {
+            None
+        } else {
+            Some(notification)
+        }
+    }
+}
+
+/// 一个连接的通知订阅，释放时取消
+struct NotificationSubscription {
+    id: u64,
+    rx: mpsc::UnboundedReceiver<JsonRpcNotification>,
+    subscribers: NotificationSubscribers,
+}
+
+impl Drop for NotificationSubscription {
+    fn drop(&mut self) {
+        self.subscribers.unsubscribe(self.id);
+    }
+}
+
+/// 把共享通道中的通知转发给所有订阅，通道关闭时返回
+///
+/// 没有订阅时暂停转发，通知留在共享通道中，等下一个订阅出现后再发送。
+async fn dispatch_notifications(
+    notification_rx: Arc<Mutex<mpsc::UnboundedReceiver<JsonRpcNotification>>>,
+    subscribers: NotificationSubscribers,
+) {
+    let mut rx = notification_rx.lock().await;
+    let mut pending = None;
+    loop {
+        subscribers.sender.wait_attached().await;
+        let notification = match pending.take() {
+            Some(notification) => notification,
+            None => match rx.recv().await {
+                Some(notification) => notification,
+                None => return,
+            },
+        };
+        // 等待期间最后一个订阅可能已经取消，留到下一个订阅出现
+        pending = subscribers.broadcast(notification);
+    }
+}
+
+/// 将通知写入输出流，直到通道关闭或 `drain` 完成
+///
+/// 按配置把短时间内到达的通知合并为一次写入和刷新，减少高吞吐时的系统调用。
+/// `drain` 完成后写出通道中已排队的通知再返回。
+/// 写入失败（如 stdout 被关闭导致的 BrokenPipe）时返回错误。
+async fn forward_notifications<W>(
+    rx: &mut mpsc::UnboundedReceiver<JsonRpcNotification>,
+    output: &Mutex<W>,
+    config: &RpcServerConfig,
+    drain: impl std::future::Future<Output = ()>,
+) -> std::io::Result<()>
+where
+    W: AsyncWrite + Unpin,
+{
+    let mut buffer = Vec::new();
+    tokio::pin!(drain);
+
+    loop {
+        let notification = tokio::select! {
+            biased;
+            notification = rx.recv() => match notification {
+                Some(notification) => notification,
+                None => break,
+            },
+            _ = &mut drain => {
+                buffer.clear();
+                while let Ok(notification) = rx.try_recv() {
+                    append_notification(&mut buffer, &notification);
+                }
+                if !buffer.is_empty() {
+                    let mut output = output.lock().await;
+                    output.write_all(&buffer).await?;
+                    output.flush().await?;
+                }
+                break;
+            }
+        };
+
+        buffer.clear();
+        append_notification(&mut buffer, &notification);
+
+        if !config.notification_flush_interval.is_zero() {
+            let deadline = tokio::time::Instant::now() + config.notification_flush_interval;
+            let mut pending = 1;
+
+            while pending < config.notification_flush_max_messages {
+                match tokio::time::timeout_at(deadline, rx.recv()).await {
+                    Ok(Some(notification)) => {
+                        append_notification(&mut buffer, &notification);
+                        pending += 1;
+                    }
+                    // 通道关闭或等待超时
+                    Ok(None) | Err(_) => break,
+                }
+            }
+        }
+
+        if buffer.is_empty() {
+            continue;
+        }
+
+        let mut output = output.lock().await;
+        output.write_all(&buffer).await?;
+        output.flush().await?;
+    }
+
+    Ok(())
+}
+
+/// 写入一条响应并立即刷新
+async fn write_response<W>(output: &Mutex<W>, response_json: &str) -> std::io::Result<()>
+where
+    W: AsyncWrite + Unpin,
+{
+    let mut output = output.lock().await;
+    output.write_all(response_json.as_bytes()).await?;
+    output.write_all(b"\n").await?;
+    output.flush().await
+}
+
+/// 比较认证令牌，耗时与不匹配的位置无关
+fn tokens_match(provided: &str, expected: &str) -> bool {
+    provided.len() == expected.len()
+        && provided
+            .bytes()
+            .zip(expected.bytes())
+            .fold(0u8, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+/// 只计数不保存的写入器，用于计算序列化后的大小
+struct SizeCounter(usize);
+
+impl std::io::Write for SizeCounter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0 += buf.len();
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// 通知序列化为 JSON 后的字节数（不含换行符）
+fn serialized_size(notification: &JsonRpcNotification) -> usize {
+    let mut counter = SizeCounter(0);
+    let _ = serde_json::to_writer(&mut counter, notification);
+    counter.0
+}
+
+/// 序列化通知并追加到缓冲区
+fn append_notification(buffer: &mut Vec<u8>, notification: &JsonRpcNotification) {
+    if serde_json::to_writer(&mut *buffer, notification).is_ok() {
+        buffer.push(b'\n');
+    }
+}
+
+impl Default for RpcServer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// 校验请求对象的结构并转换为 [`JsonRpcRequest`]
+///
+/// 失败时返回用于错误响应的 id（能取到时使用请求中的 id）和 invalid request 错误。
+fn parse_request(
+    value: serde_json::Value,
+) -> Result<JsonRpcRequest, (serde_json::Value, JsonRpcError)> {
+    let Some(object) = value.as_object() else {
+        return Err((
+            serde_json::Value::Null,
+            JsonRpcError::invalid_request("请求必须是 JSON 对象"),
+        ));
+    };
+    let id = object.get("id").cloned().unwrap_or(serde_json::Value::Null);
+
+    // 验证 JSON-RPC 版本
+    match object.get("jsonrpc") {
+        Some(serde_json::Value::String(version)) if version == "2.0" => {}
+        Some(serde_json::Value::String(_)) => {
+            return Err((id, JsonRpcError::invalid_request("无效的 JSON-RPC 版本")));
+        }
+        Some(_) => {
+            return Err((id, JsonRpcError::invalid_request("jsonrpc 字段必须是字符串")));
+        }
+        None => return Err((id, JsonRpcError::invalid_request("缺少 jsonrpc 字段"))),
+    }
+
+    match object.get("method") {
+        Some(serde_json::Value::String(_)) => {}
+        Some(_) => return Err((id, JsonRpcError::invalid_request("method 字段必须是字符串"))),
+        None => return Err((id, JsonRpcError::invalid_request("缺少 method 字段"))),
+    }
+
+    serde_json::from_value(value)
+        .map_err(|e| (id, JsonRpcError::invalid_request(format!("无效的请求: {}", e))))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_notification_sender_clone() {
+        let server = RpcServer::new();
+        let sender1 = server.notification_sender();
+        let sender2 = sender1.clone();
+        
+        // Both senders should be able to send
+        assert!(sender1.send_output("test-session", "dGVzdA==", 0, true).is_ok());
+        assert!(sender2.send_status("test-session", "running", None).is_ok());
+    }
+
+    #[test]
+    fn test_notification_sender_output() {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let sender = NotificationSender::new_for_test(tx);
+        
+        sender.send_output("session-123", "SGVsbG8=", 7, false).unwrap();
+        
+        let notification = rx.try_recv().unwrap();
+        assert_eq!(notification.method, "terminal.output");
+        assert!(notification.params.is_some());
+        
+        let params = notification.params.unwrap();
+        assert_eq!(params["session_id"], "session-123");
+        assert_eq!(params["data"], "SGVsbG8=");
+        assert_eq!(params["seq"], 7);
+        assert_eq!(params["final"], false);
+    }
+
+    #[test]
+    fn test_notification_sender_status() {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let sender = NotificationSender::new_for_test(tx);
         
         sender.send_status("session-123", "done", Some(0)).unwrap();
         
@@ -266,12 +1300,95 @@ mod tests {
         assert_eq!(params["exit_code"], 0);
     }
 
+    #[test]
+    fn test_notification_sender_status_with_reason() {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let sender = NotificationSender::new_for_test(tx);
+
+        sender
+            .send_status_with_reason("session-123", "done", Some(0), Some("idle_timeout"), None)
+            .unwrap();
+        sender.send_status("session-123", "running", None).unwrap();
+
+        let params = rx.try_recv().unwrap().params.unwrap();
+        assert_eq!(params["status"], "done");
+        assert_eq!(params["exit_code"], 0);
+        assert_eq!(params["reason"], "idle_timeout");
+
+        let params = rx.try_recv().unwrap().params.unwrap();
+        assert!(params.get("reason").is_none());
+        assert!(params.get("ended_at").is_none());
+    }
+
+    #[test]
+    fn test_notification_sender_status_with_timing() {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let sender = NotificationSender::new_for_test(tx);
+
+        let timing = SessionTiming {
+            ended_at: 1_700_000_192,
+            duration_secs: 192,
+        };
+        sender
+            .send_status_with_reason("session-123", "done", Some(0), None, Some(timing))
+            .unwrap();
+        sender.send_signal_status("session-123", "KILL", false, None, Some(timing)).unwrap();
+
+        for _ in 0..2 {
+            let params = rx.try_recv().unwrap().params.unwrap();
+            assert_eq!(params["ended_at"], 1_700_000_192);
+            assert_eq!(params["duration_secs"], 192);
+        }
+    }
+
+    #[test]
+    fn test_notification_sender_signal_status() {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let sender = NotificationSender::new_for_test(tx);
+
+        sender.send_signal_status("session-123", "KILL", false, None, None).unwrap();
+        sender.send_signal_status("session-123", "SEGV", true, Some("crashed"), None).unwrap();
+
+        let params = rx.try_recv().unwrap().params.unwrap();
+        assert_eq!(params["status"], "done");
+        assert_eq!(params["signal"], "KILL");
+        assert_eq!(params["core_dumped"], false);
+        assert!(params.get("error_message").is_none());
+        assert!(params.get("exit_code").is_none());
+
+        let params = rx.try_recv().unwrap().params.unwrap();
+        assert_eq!(params["signal"], "SEGV");
+        assert_eq!(params["core_dumped"], true);
+        assert_eq!(params["error_message"], "crashed");
+    }
+
+    #[test]
+    fn test_notification_sender_error_status() {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let sender = NotificationSender::new_for_test(tx);
+
+        let error = TerminalError::PtyCreationFailed("No such file or directory".to_string());
+        sender.send_error_status("session-123", &error).unwrap();
+
+        let notification = rx.try_recv().unwrap();
+        assert_eq!(notification.method, "session.status");
+
+        let params = notification.params.unwrap();
+        assert_eq!(params["session_id"], "session-123");
+        assert_eq!(params["status"], "error");
+        assert_eq!(params["error"]["message"], error.to_string());
+        assert_eq!(params["error"]["error_type"], "pty_creation_failed");
+        assert_eq!(params["error"]["code"], error.code());
+        assert_eq!(params["error"]["recoverable"], false);
+        assert!(params.get("exit_code").is_none());
+    }
+
     #[test]
     fn test_notification_sender_cwd() {
         let (tx, mut rx) = mpsc::unbounded_channel();
-        let sender = NotificationSender { tx };
+        let sender = NotificationSender::new_for_test(tx);
         
-        sender.send_cwd("session-123", "/home/user").unwrap();
+        sender.send_cwd("session-123", "/home/user", None).unwrap();
         
         let notification = rx.try_recv().unwrap();
         assert_eq!(notification.method, "session.cwd");
@@ -279,12 +1396,36 @@ mod tests {
         let params = notification.params.unwrap();
         assert_eq!(params["session_id"], "session-123");
         assert_eq!(params["cwd"], "/home/user");
+        assert!(params.get("host").is_none());
+
+        sender.send_cwd("session-123", "/srv", Some("remote")).unwrap();
+        let params = rx.try_recv().unwrap().params.unwrap();
+        assert_eq!(params["host"], "remote");
+    }
+
+    #[test]
+    fn test_notification_sender_command_finished() {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let sender = NotificationSender::new_for_test(tx);
+
+        sender.send_command_finished("session-123", Some(1), Some("false")).unwrap();
+        sender.send_command_finished("session-123", None, None).unwrap();
+
+        let notification = rx.try_recv().unwrap();
+        assert_eq!(notification.method, "session.command_finished");
+        let params = notification.params.unwrap();
+        assert_eq!(params["exit_code"], 1);
+        assert_eq!(params["command"], "false");
+
+        let params = rx.try_recv().unwrap().params.unwrap();
+        assert!(params.get("exit_code").is_none());
+        assert!(params.get("command").is_none());
     }
 
     #[test]
     fn test_notification_sender_title() {
         let (tx, mut rx) = mpsc::unbounded_channel();
-        let sender = NotificationSender { tx };
+        let sender = NotificationSender::new_for_test(tx);
         
         sender.send_title("session-123", "vim").unwrap();
         
@@ -295,4 +1436,846 @@ mod tests {
         assert_eq!(params["session_id"], "session-123");
         assert_eq!(params["title"], "vim");
     }
+
+    #[test]
+    fn test_notification_sender_mode() {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let sender = NotificationSender::new_for_test(tx);
+
+        let modes = TerminalModes {
+            alt_screen: true,
+            ..Default::default()
+        };
+        sender.send_mode("session-123", &modes).unwrap();
+
+        let notification = rx.try_recv().unwrap();
+        assert_eq!(notification.method, "session.mode");
+
+        let params = notification.params.unwrap();
+        assert_eq!(params["session_id"], "session-123");
+        assert_eq!(params["modes"]["alt_screen"], true);
+        assert_eq!(params["modes"]["mouse_tracking"], "none");
+    }
+
+    #[test]
+    fn test_notification_sender_closed_receiver() {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let sender = NotificationSender::new_for_test(tx);
+        drop(rx);
+
+        // 默认策略返回发送错误
+        assert_eq!(sender.drop_policy(), NotificationDropPolicy::Propagate);
+        assert!(sender.send_output("session-123", "SGVsbG8=", 0, true).is_err());
+        assert_eq!(sender.dropped_count(), 0);
+
+        // 丢弃策略对所有克隆生效，发送视为成功并计数
+        let clone = sender.clone();
+        sender.set_drop_policy(NotificationDropPolicy::Drop);
+        for seq in 0..100 {
+            assert!(clone.send_output("session-123", "SGVsbG8=", seq, true).is_ok());
+        }
+        assert!(sender.send_status("session-123", "done", Some(0)).is_ok());
+        assert_eq!(sender.dropped_count(), 101);
+        assert_eq!(clone.dropped_count(), 101);
+    }
+
+    #[test]
+    fn test_drop_policy_from_config() {
+        let server = RpcServer::with_config(RpcServerConfig {
+            notification_drop_policy: NotificationDropPolicy::Drop,
+            ..Default::default()
+        });
+        let sender = server.notification_sender();
+        assert_eq!(sender.drop_policy(), NotificationDropPolicy::Drop);
+        // 接收端仍在时正常发送，不计入丢弃
+        assert!(sender.send_title("session-123", "vim").is_ok());
+        assert_eq!(sender.dropped_count(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_oversized_notification_dropped() {
+        let server = RpcServer::with_config(RpcServerConfig {
+            max_notification_size: 256,
+            ..Default::default()
+        });
+        let sender = server.notification_sender();
+
+        let large = "A".repeat(1024);
+        sender.send_output("session-1", "dGVzdA==", 0, true).unwrap();
+        assert!(sender.send_output("session-1", &large, 1, true).is_ok());
+        sender.send_output("session-1", "dGVzdA==", 2, true).unwrap();
+        assert_eq!(sender.oversized_count(), 1);
+        assert_eq!(sender.dropped_count(), 0);
+
+        // 超限的通知不会写出，前后的通知不受影响
+        let writer = MockWriter::default();
+        server.serve(&b""[..], writer.clone()).await.unwrap();
+        let lines = writer.lines();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["params"]["seq"], 0);
+        assert_eq!(lines[1]["params"]["seq"], 2);
+    }
+
+    #[tokio::test]
+    async fn test_malformed_requests() {
+        let server = RpcServer::new();
+        let cases = [
+            // 不是合法 JSON
+            (r#"{"jsonrpc":"2.0","#, -32700, serde_json::Value::Null),
+            // JSON 合法但结构无效
+            (r#"[1,2]"#, -32600, serde_json::Value::Null),
+            (r#"{"jsonrpc":"2.0","id":1}"#, -32600, serde_json::json!(1)),
+            (r#"{"jsonrpc":"2.0","method":42,"id":2}"#, -32600, serde_json::json!(2)),
+            (r#"{"jsonrpc":"1.0","method":"session.list","id":3}"#, -32600, serde_json::json!(3)),
+            (r#"{"jsonrpc":2.0,"method":"session.list","id":4}"#, -32600, serde_json::json!(4)),
+            (r#"{"method":"session.list","id":5}"#, -32600, serde_json::json!(5)),
+            (r#"{"jsonrpc":"2.0","method":"session.list"}"#, -32600, serde_json::Value::Null),
+        ];
+
+        for (line, code, id) in cases {
+            let response = server.handle_request(line).await;
+            let error = response.error.unwrap_or_else(|| panic!("expected error for {}", line));
+            assert_eq!(error.code, code, "{}: {}", line, error.message);
+            assert_eq!(response.id, id, "{}", line);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_invalid_request_messages() {
+        let server = RpcServer::new();
+        let message = |line: &'static str| {
+            let server = &server;
+            async move { server.handle_request(line).await.error.unwrap().message }
+        };
+
+        assert_eq!(message(r#"{"jsonrpc":"2.0","id":1}"#).await, "缺少 method 字段");
+        assert_eq!(message(r#"{"jsonrpc":"2.0","method":null,"id":1}"#).await, "method 字段必须是字符串");
+        assert_eq!(message(r#"{"jsonrpc":"2.1","method":"x","id":1}"#).await, "无效的 JSON-RPC 版本");
+        assert_eq!(message(r#"{"jsonrpc":true,"method":"x","id":1}"#).await, "jsonrpc 字段必须是字符串");
+
+        // 参数错误仍由方法返回 invalid params
+        let response = server
+            .handle_request(r#"{"jsonrpc":"2.0","method":"session.get","params":{},"id":1}"#)
+            .await;
+        assert_eq!(response.error.unwrap().code, -32602);
+    }
+
+    #[tokio::test]
+    async fn test_ping_echoes_nonce() {
+        let server = RpcServer::new();
+        let response = server
+            .handle_request(r#"{"jsonrpc":"2.0","method":"server.ping","params":{"nonce":"abc-123"},"id":1}"#)
+            .await;
+
+        assert!(response.error.is_none());
+        assert_eq!(response.id, 1);
+        let result = response.result.unwrap();
+        assert_eq!(result["nonce"], "abc-123");
+        assert!(result["timestamp_us"].is_u64());
+    }
+
+    #[tokio::test]
+    async fn test_ping_timestamp_is_monotonic() {
+        let server = RpcServer::new();
+        let ping = r#"{"jsonrpc":"2.0","method":"server.ping","params":{"nonce":1},"id":1}"#;
+
+        let first = server.handle_request(ping).await.result.unwrap();
+        tokio::time::sleep(std::time::Duration::from_millis(2)).await;
+        let second = server.handle_request(ping).await.result.unwrap();
+
+        assert!(second["timestamp_us"].as_u64() > first["timestamp_us"].as_u64());
+    }
+
+    #[tokio::test]
+    async fn test_ping_without_params() {
+        let server = RpcServer::new();
+        let response = server
+            .handle_request(r#"{"jsonrpc":"2.0","method":"server.ping","id":2}"#)
+            .await;
+
+        assert!(response.error.is_none());
+        assert!(response.result.unwrap()["nonce"].is_null());
+    }
+
+    #[tokio::test]
+    async fn test_ping_not_blocked_by_methods_lock() {
+        let server = RpcServer::new();
+        // 模拟耗时操作持有方法锁
+        let _guard = server.methods.lock().await;
+
+        let response = tokio::time::timeout(
+            std::time::Duration::from_secs(1),
+            server.handle_request(r#"{"jsonrpc":"2.0","method":"server.ping","params":{"nonce":"x"},"id":3}"#),
+        )
+        .await
+        .expect("ping should not wait for the methods lock");
+
+        assert_eq!(response.result.unwrap()["nonce"], "x");
+    }
+
+    /// 记录写入内容和刷新次数的输出流
+    #[derive(Clone, Default)]
+    struct MockWriter {
+        data: Arc<std::sync::Mutex<Vec<u8>>>,
+        flushes: Arc<std::sync::atomic::AtomicUsize>,
+    }
+
+    impl MockWriter {
+        fn lines(&self) -> Vec<serde_json::Value> {
+            let data = self.data.lock().unwrap();
+            String::from_utf8_lossy(&data)
+                .lines()
+                .map(|line| serde_json::from_str(line).unwrap())
+                .collect()
+        }
+
+        fn flush_count(&self) -> usize {
+            self.flushes.load(std::sync::atomic::Ordering::SeqCst)
+        }
+    }
+
+    impl AsyncWrite for MockWriter {
+        fn poll_write(
+            self: std::pin::Pin<&mut Self>,
+            _cx: &mut std::task::Context<'_>,
+            buf: &[u8],
+        ) -> std::task::Poll<std::io::Result<usize>> {
+            self.data.lock().unwrap().extend_from_slice(buf);
+            std::task::Poll::Ready(Ok(buf.len()))
+        }
+
+        fn poll_flush(
+            self: std::pin::Pin<&mut Self>,
+            _cx: &mut std::task::Context<'_>,
+        ) -> std::task::Poll<std::io::Result<()>> {
+            self.flushes.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            std::task::Poll::Ready(Ok(()))
+        }
+
+        fn poll_shutdown(
+            self: std::pin::Pin<&mut Self>,
+            _cx: &mut std::task::Context<'_>,
+        ) -> std::task::Poll<std::io::Result<()>> {
+            std::task::Poll::Ready(Ok(()))
+        }
+    }
+
+    /// 发送指定数量的输出通知后关闭通道，返回写入结果
+    async fn forward_test_notifications(count: u64, config: RpcServerConfig) -> MockWriter {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let sender = NotificationSender::new_for_test(tx);
+        for seq in 0..count {
+            sender.send_output("session-1", "dGVzdA==", seq, true).unwrap();
+        }
+        drop(sender);
+
+        let writer = MockWriter::default();
+        let output = Mutex::new(writer.clone());
+        forward_notifications(&mut rx, &output, &config, std::future::pending())
+            .await
+            .unwrap();
+        writer
+    }
+
+    #[tokio::test]
+    async fn test_notifications_flush_each_by_default() {
+        let writer = forward_test_notifications(10, RpcServerConfig::default()).await;
+        assert_eq!(writer.lines().len(), 10);
+        assert_eq!(writer.flush_count(), 10);
+    }
+
+    #[tokio::test]
+    async fn test_notifications_batched_flush() {
+        let config = RpcServerConfig {
+            notification_flush_interval: Duration::from_millis(50),
+            notification_flush_max_messages: 1000,
+            ..Default::default()
+        };
+        let writer = forward_test_notifications(100, config).await;
+
+        let lines = writer.lines();
+        assert_eq!(lines.len(), 100);
+        assert_eq!(writer.flush_count(), 1);
+
+        // 批量写入不改变通知顺序
+        for (i, line) in lines.iter().enumerate() {
+            assert_eq!(line["params"]["seq"], i as u64);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_notifications_batch_limited_by_max_messages() {
+        let config = RpcServerConfig {
+            notification_flush_interval: Duration::from_millis(50),
+            notification_flush_max_messages: 10,
+            ..Default::default()
+        };
+        let writer = forward_test_notifications(100, config).await;
+        assert_eq!(writer.lines().len(), 100);
+        assert_eq!(writer.flush_count(), 10);
+    }
+
+    #[tokio::test]
+    async fn test_serve_drains_notifications_on_eof() {
+        let server = RpcServer::new();
+        let sender = server.notification_sender();
+        for seq in 0..20 {
+            sender.send_output("session-1", "dGVzdA==", seq, true).unwrap();
+        }
+
+        // stdin 立即关闭，排队的通知仍应全部写出
+        let writer = MockWriter::default();
+        server.serve(&b""[..], writer.clone()).await.unwrap();
+
+        let lines = writer.lines();
+        assert_eq!(lines.len(), 20);
+        assert_eq!(lines[19]["params"]["seq"], 19);
+    }
+
+    #[tokio::test]
+    async fn test_serve_responds_and_flushes() {
+        let server = RpcServer::new();
+        let writer = MockWriter::default();
+        server.serve(PING, writer.clone()).await.unwrap();
+
+        let lines = writer.lines();
+        assert_eq!(lines.len(), 1);
+        assert_eq!(lines[0]["result"]["nonce"], 7);
+        assert!(writer.flush_count() >= 1);
+    }
+
+    /// 写入总是返回 BrokenPipe 的输出流，模拟父进程关闭 stdout
+    struct ClosedWriter;
+
+    impl AsyncWrite for ClosedWriter {
+        fn poll_write(
+            self: std::pin::Pin<&mut Self>,
+            _cx: &mut std::task::Context<'_>,
+            _buf: &[u8],
+        ) -> std::task::Poll<std::io::Result<usize>> {
+            std::task::Poll::Ready(Err(std::io::ErrorKind::BrokenPipe.into()))
+        }
+
+        fn poll_flush(
+            self: std::pin::Pin<&mut Self>,
+            _cx: &mut std::task::Context<'_>,
+        ) -> std::task::Poll<std::io::Result<()>> {
+            std::task::Poll::Ready(Err(std::io::ErrorKind::BrokenPipe.into()))
+        }
+
+        fn poll_shutdown(
+            self: std::pin::Pin<&mut Self>,
+            _cx: &mut std::task::Context<'_>,
+        ) -> std::task::Poll<std::io::Result<()>> {
+            std::task::Poll::Ready(Ok(()))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_forward_notifications_reports_write_error() {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let sender = NotificationSender::new_for_test(tx);
+        sender.send_status("session-1", "running", None).unwrap();
+
+        let output = Mutex::new(ClosedWriter);
+        let err = forward_notifications(
+            &mut rx,
+            &output,
+            &RpcServerConfig::default(),
+            std::future::pending(),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::BrokenPipe);
+    }
+
+    #[tokio::test]
+    async fn test_serve_shuts_down_when_stdout_closed() {
+        let server = RpcServer::new();
+
+        // 先创建一个会话，退出时应被关闭
+        let created = server
+            .methods
+            .lock()
+            .await
+            .call(
+                "session.create",
+                Some(serde_json::json!({
+                    "connection": {"type": "ssh", "host": "example.com"},
+                    "term_size": {"rows": 24, "cols": 80}
+                })),
+                serde_json::json!(1),
+            )
+            .await;
+        assert!(created.error.is_none());
+
+        // stdin 保持打开，只有 stdout 写入失败才能让 serve 返回
+        let (_stdin_tx, stdin_rx) = tokio::io::duplex(64);
+        server.notification_sender().send_status("session-1", "running", None).unwrap();
+
+        tokio::time::timeout(Duration::from_secs(5), server.serve(stdin_rx, ClosedWriter))
+            .await
+            .expect("stdout 关闭后 serve 应该退出")
+            .unwrap();
+
+        let listed = server
+            .methods
+            .lock()
+            .await
+            .call("session.list", None, serde_json::json!(2))
+            .await;
+        assert_eq!(listed.result.unwrap(), serde_json::json!([]));
+    }
+
+    const PING: &[u8] = b"{\"jsonrpc\":\"2.0\",\"method\":\"server.ping\",\"params\":{\"nonce\":7},\"id\":1}\n";
+
+    #[tokio::test]
+    async fn test_read_request_line_skips_oversized_line() {
+        let mut input = vec![b'a'; 100];
+        input.extend_from_slice(b"\nshort\ntail");
+        let mut reader = BufReader::with_capacity(16, input.as_slice());
+        let mut line = Vec::new();
+
+        assert_eq!(read_request_line(&mut reader, &mut line, 10).await.unwrap(), RequestLine::TooLong);
+        assert!(line.is_empty());
+
+        assert_eq!(read_request_line(&mut reader, &mut line, 10).await.unwrap(), RequestLine::Line);
+        assert_eq!(line, b"short");
+
+        // 没有换行符的最后一行也应被读取
+        assert_eq!(read_request_line(&mut reader, &mut line, 10).await.unwrap(), RequestLine::Line);
+        assert_eq!(line, b"tail");
+
+        assert_eq!(read_request_line(&mut reader, &mut line, 10).await.unwrap(), RequestLine::Eof);
+    }
+
+    #[tokio::test]
+    async fn test_read_request_line_strips_carriage_return() {
+        let input: &[u8] = b"first\r\n\r\nmid\rdle\r\nlast\r";
+        let mut reader = BufReader::with_capacity(4, input);
+        let mut line = Vec::new();
+
+        assert_eq!(read_request_line(&mut reader, &mut line, 64).await.unwrap(), RequestLine::Line);
+        assert_eq!(line, b"first");
+        assert_eq!(read_request_line(&mut reader, &mut line, 64).await.unwrap(), RequestLine::Line);
+        assert_eq!(line, b"");
+        // 行中间的 `\r` 保留
+        assert_eq!(read_request_line(&mut reader, &mut line, 64).await.unwrap(), RequestLine::Line);
+        assert_eq!(line, b"mid\rdle");
+        // 输入结束时的不完整行同样去掉结尾的 `\r`
+        assert_eq!(read_request_line(&mut reader, &mut line, 64).await.unwrap(), RequestLine::Line);
+        assert_eq!(line, b"last");
+        assert_eq!(read_request_line(&mut reader, &mut line, 64).await.unwrap(), RequestLine::Eof);
+    }
+
+    #[tokio::test]
+    async fn test_serve_handles_line_endings() {
+        let server = RpcServer::new();
+        let writer = MockWriter::default();
+
+        // `\r\n` 结尾的请求，以及输入结束前没有换行符的最后一个请求
+        let mut input = b"{\"jsonrpc\":\"2.0\",\"method\":\"server.ping\",\"params\":{\"nonce\":1},\"id\":1}\r\n".to_vec();
+        input.extend_from_slice(b"{\"jsonrpc\":\"2.0\",\"method\":\"server.ping\",\"params\":{\"nonce\":2},\"id\":2}");
+
+        server.serve(input.as_slice(), writer.clone()).await.unwrap();
+
+        let lines = writer.lines();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["result"]["nonce"], 1);
+        assert_eq!(lines[1]["result"]["nonce"], 2);
+    }
+
+    #[tokio::test]
+    async fn test_serve_reports_partial_line_at_eof() {
+        let server = RpcServer::new();
+        let writer = MockWriter::default();
+
+        // 输入在请求中途结束，返回解析错误而不是静默丢弃
+        let mut input = PING.to_vec();
+        input.extend_from_slice(b"{\"jsonrpc\":\"2.0\",\"method\":\"server.pi");
+
+        server.serve(input.as_slice(), writer.clone()).await.unwrap();
+
+        let lines = writer.lines();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["result"]["nonce"], 7);
+        assert_eq!(lines[1]["error"]["code"], -32700);
+    }
+
+    #[tokio::test]
+    async fn test_serve_rejects_oversized_request() {
+        let server = RpcServer::with_config(RpcServerConfig {
+            max_request_size: 128,
+            ..Default::default()
+        });
+        let writer = MockWriter::default();
+
+        let mut input = b"{\"jsonrpc\":\"2.0\",\"method\":\"server.ping\",\"params\":{\"nonce\":\"".to_vec();
+        input.extend(std::iter::repeat_n(b'x', 1024));
+        input.extend_from_slice(b"\"},\"id\":0}\n");
+        input.extend_from_slice(PING);
+
+        server.serve(input.as_slice(), writer.clone()).await.unwrap();
+
+        let lines = writer.lines();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["error"]["code"], -32700);
+        assert!(lines[0]["id"].is_null());
+        // 超长请求之后的请求仍然正常处理
+        assert_eq!(lines[1]["result"]["nonce"], 7);
+    }
+
+    #[tokio::test]
+    async fn test_serve_rejects_invalid_utf8() {
+        let server = RpcServer::new();
+        let writer = MockWriter::default();
+
+        let mut input = b"\xff\xfe\x00garbage\x80\n".to_vec();
+        input.extend_from_slice(PING);
+
+        server.serve(input.as_slice(), writer.clone()).await.unwrap();
+
+        let lines = writer.lines();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["error"]["code"], -32700);
+        assert_eq!(lines[1]["result"]["nonce"], 7);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_socket_clients_share_sessions() {
+        use tokio::net::UnixStream;
+
+        /// 发送一个请求并读取响应，跳过中间的通知
+        async fn call(
+            lines: &mut tokio::io::Lines<BufReader<tokio::net::unix::OwnedReadHalf>>,
+            writer: &mut tokio::net::unix::OwnedWriteHalf,
+            request: serde_json::Value,
+        ) -> serde_json::Value {
+            writer.write_all(format!("{}\n", request).as_bytes()).await.unwrap();
+            loop {
+                let line = lines.next_line().await.unwrap().expect("连接意外关闭");
+                let message: serde_json::Value = serde_json::from_str(&line).unwrap();
+                if message.get("id") == request.get("id") {
+                    return message;
+                }
+            }
+        }
+
+        let path = std::env::temp_dir().join(format!("terminal-rpc-{}.sock", uuid::Uuid::new_v4()));
+        let server = RpcServer::new();
+
+        let clients = async {
+            // 等待套接字创建
+            let connect = || async {
+                loop {
+                    if let Ok(stream) = UnixStream::connect(&path).await {
+                        return stream;
+                    }
+                    tokio::time::sleep(Duration::from_millis(10)).await;
+                }
+            };
+
+            let (read, mut write) = connect().await.into_split();
+            let mut lines = BufReader::new(read).lines();
+            let created = call(&mut lines, &mut write, serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "method": "session.create",
+                "params": {
+                    "connection": {"type": "ssh", "host": "example.com"},
+                    "term_size": {"rows": 24, "cols": 80}
+                }
+            }))
+            .await;
+            let session_id = created["result"]["session_id"].clone();
+            let first = call(&mut lines, &mut write, serde_json::json!({
+                "jsonrpc": "2.0", "id": 2, "method": "session.list"
+            }))
+            .await;
+            drop((lines, write));
+
+            // 第二个客户端看到同样的会话
+            let (read, mut write) = connect().await.into_split();
+            let mut lines = BufReader::new(read).lines();
+            let second = call(&mut lines, &mut write, serde_json::json!({
+                "jsonrpc": "2.0", "id": 3, "method": "session.list"
+            }))
+            .await;
+
+            (session_id, first["result"].clone(), second["result"].clone())
+        };
+
+        let (session_id, first, second) = tokio::select! {
+            result = server.run_on_socket(&path) => panic!("服务器意外退出: {:?}", result),
+            result = tokio::time::timeout(Duration::from_secs(10), clients) => result.unwrap(),
+        };
+
+        assert_eq!(first.as_array().unwrap().len(), 1);
+        assert_eq!(first[0]["id"], session_id);
+        assert_eq!(second, first);
+
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_run_on_socket_refuses_regular_file() {
+        let path = std::env::temp_dir().join(format!("terminal-rpc-{}.sock", uuid::Uuid::new_v4()));
+        std::fs::write(&path, b"not a socket").unwrap();
+
+        let server = RpcServer::new();
+        assert!(server.run_on_socket(&path).await.is_err());
+        assert_eq!(std::fs::read(&path).unwrap(), b"not a socket");
+
+        let _ = std::fs::remove_file(&path);
+    }
+
+    /// 在 TCP 连接上发送一个请求并读取响应，跳过中间的通知
+    async fn tcp_call(
+        lines: &mut tokio::io::Lines<BufReader<tokio::net::tcp::OwnedReadHalf>>,
+        writer: &mut tokio::net::tcp::OwnedWriteHalf,
+        request: serde_json::Value,
+    ) -> Option<serde_json::Value> {
+        writer.write_all(format!("{}\n", request).as_bytes()).await.unwrap();
+        while let Some(line) = lines.next_line().await.unwrap() {
+            let message: serde_json::Value = serde_json::from_str(&line).unwrap();
+            if message.get("id") == request.get("id") {
+                return Some(message);
+            }
+        }
+        None
+    }
+
+    #[tokio::test]
+    async fn test_tcp_session_list() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = RpcServer::new();
+
+        let client = async {
+            let (read, mut write) = tokio::net::TcpStream::connect(addr).await.unwrap().into_split();
+            let mut lines = BufReader::new(read).lines();
+            tcp_call(&mut lines, &mut write, serde_json::json!({
+                "jsonrpc": "2.0", "id": 1, "method": "session.list"
+            }))
+            .await
+        };
+
+        let response = tokio::select! {
+            result = server.serve_tcp(listener) => panic!("服务器意外退出: {:?}", result),
+            result = tokio::time::timeout(Duration::from_secs(10), client) => result.unwrap(),
+        };
+        assert_eq!(response.unwrap()["result"], serde_json::json!([]));
+    }
+
+    /// 读取 TCP 连接上的消息，直到某个会话的输出中出现 `marker`，返回该会话 ID 和读到的响应
+    async fn tcp_wait_for_output(
+        lines: &mut tokio::io::Lines<BufReader<tokio::net::tcp::OwnedReadHalf>>,
+        marker: &str,
+    ) -> (serde_json::Value, Vec<serde_json::Value>) {
+        let mut outputs: HashMap<String, Vec<u8>> = HashMap::new();
+        let mut responses = Vec::new();
+        while let Some(line) = lines.next_line().await.unwrap() {
+            let message: serde_json::Value = serde_json::from_str(&line).unwrap();
+            if message.get("id").is_some() {
+                responses.push(message);
+                continue;
+            }
+            if message["method"] != "terminal.output" {
+                continue;
+            }
+            let params = &message["params"];
+            let output = outputs.entry(params["session_id"].as_str().unwrap().to_string()).or_default();
+            output.extend(codec::decode_input(params["data"].as_str().unwrap()).unwrap());
+            if String::from_utf8_lossy(output).contains(marker) {
+                return (params["session_id"].clone(), responses);
+            }
+        }
+        panic!("连接关闭前没有收到输出: {}", marker);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_tcp_clients_each_receive_notifications() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = RpcServer::new();
+
+        let clients = async {
+            // 第一个客户端先完成一次调用，保证它已经在接收通知
+            let (read, mut first_write) = tokio::net::TcpStream::connect(addr).await.unwrap().into_split();
+            let mut first = BufReader::new(read).lines();
+            tcp_call(&mut first, &mut first_write, serde_json::json!({
+                "jsonrpc": "2.0", "id": 1, "method": "session.list"
+            }))
+            .await
+            .unwrap();
+
+            // 第二个客户端创建会话，输出可能先于响应到达，所以不能用 tcp_call 跳过通知
+            let (read, mut second_write) = tokio::net::TcpStream::connect(addr).await.unwrap().into_split();
+            let mut second = BufReader::new(read).lines();
+            let request = serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": 2,
+                "method": "session.create",
+                "params": {
+                    "connection": {"type": "local_process", "command": "echo tcp-fanout"},
+                    "term_size": {"rows": 24, "cols": 80}
+                }
+            });
+            second_write.write_all(format!("{}\n", request).as_bytes()).await.unwrap();
+            let (second_session, mut responses) = tcp_wait_for_output(&mut second, "tcp-fanout").await;
+            if responses.is_empty() {
+                responses.push(serde_json::from_str(&second.next_line().await.unwrap().unwrap()).unwrap());
+            }
+            let created = responses[0]["result"]["session_id"].clone();
+
+            // 第一个客户端同样收到这个会话的输出
+            let (first_session, _) = tcp_wait_for_output(&mut first, "tcp-fanout").await;
+            (created, second_session, first_session)
+        };
+
+        let (created, second_session, first_session) = tokio::select! {
+            result = server.serve_tcp(listener) => panic!("服务器意外退出: {:?}", result),
+            result = tokio::time::timeout(Duration::from_secs(10), clients) => result.unwrap(),
+        };
+        assert!(created.is_string(), "创建会话失败");
+        assert_eq!(second_session, created);
+        assert_eq!(first_session, created);
+    }
+
+    #[tokio::test]
+    async fn test_tcp_requires_auth_token() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = RpcServer::with_config(RpcServerConfig {
+            auth_token: Some("secret".to_string()),
+            ..Default::default()
+        });
+
+        let clients = async {
+            // 没有先认证：收到错误后连接被关闭
+            let (read, mut write) = tokio::net::TcpStream::connect(addr).await.unwrap().into_split();
+            let mut lines = BufReader::new(read).lines();
+            let rejected = tcp_call(&mut lines, &mut write, serde_json::json!({
+                "jsonrpc": "2.0", "id": 1, "method": "session.list"
+            }))
+            .await
+            .unwrap();
+            assert_eq!(rejected["error"]["code"], -32600);
+            assert!(lines.next_line().await.unwrap().is_none());
+
+            // 令牌错误
+            let (read, mut write) = tokio::net::TcpStream::connect(addr).await.unwrap().into_split();
+            let mut lines = BufReader::new(read).lines();
+            let wrong = tcp_call(&mut lines, &mut write, serde_json::json!({
+                "jsonrpc": "2.0", "id": 2, "method": "server.authenticate", "params": {"token": "wrong"}
+            }))
+            .await
+            .unwrap();
+            assert_eq!(wrong["error"]["code"], -32600);
+
+            // 认证成功后可以正常调用
+            let (read, mut write) = tokio::net::TcpStream::connect(addr).await.unwrap().into_split();
+            let mut lines = BufReader::new(read).lines();
+            let auth = tcp_call(&mut lines, &mut write, serde_json::json!({
+                "jsonrpc": "2.0", "id": 3, "method": "server.authenticate", "params": {"token": "secret"}
+            }))
+            .await
+            .unwrap();
+            assert_eq!(auth["result"]["authenticated"], true);
+            tcp_call(&mut lines, &mut write, serde_json::json!({
+                "jsonrpc": "2.0", "id": 4, "method": "session.list"
+            }))
+            .await
+            .unwrap()
+        };
+
+        let listed = tokio::select! {
+            result = server.serve_tcp(listener) => panic!("服务器意外退出: {:?}", result),
+            result = tokio::time::timeout(Duration::from_secs(10), clients) => result.unwrap(),
+        };
+        assert_eq!(listed["result"], serde_json::json!([]));
+    }
+
+    #[tokio::test]
+    async fn test_tcp_auth_request_limits() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = RpcServer::with_config(RpcServerConfig {
+            auth_token: Some("secret".to_string()),
+            ..Default::default()
+        });
+
+        let clients = async {
+            // 认证请求过大：返回错误后关闭连接
+            let (read, mut write) = tokio::net::TcpStream::connect(addr).await.unwrap().into_split();
+            let mut lines = BufReader::new(read).lines();
+            let padding = "x".repeat(MAX_AUTH_REQUEST_SIZE);
+            let request = serde_json::json!({
+                "jsonrpc": "2.0", "id": 1, "method": "server.authenticate",
+                "params": {"token": "secret", "padding": padding}
+            });
+            write.write_all(format!("{}\n", request).as_bytes()).await.unwrap();
+            // 请求没有被解析，响应的 id 为 null
+            let rejected: serde_json::Value =
+                serde_json::from_str(&lines.next_line().await.unwrap().unwrap()).unwrap();
+            assert_eq!(rejected["id"], serde_json::Value::Null);
+            assert_eq!(rejected["error"]["code"], -32600);
+            assert!(lines.next_line().await.unwrap().is_none());
+
+            // 一直不发送认证请求：超时后关闭连接
+            let (read, _write) = tokio::net::TcpStream::connect(addr).await.unwrap().into_split();
+            let mut lines = BufReader::new(read).lines();
+            let started = tokio::time::Instant::now();
+            assert!(lines.next_line().await.unwrap().is_none());
+            started.elapsed()
+        };
+
+        let waited = tokio::select! {
+            result = server.serve_tcp(listener) => panic!("服务器意外退出: {:?}", result),
+            result = tokio::time::timeout(AUTH_TIMEOUT * 2, clients) => result.unwrap(),
+        };
+        assert!(waited >= AUTH_TIMEOUT - Duration::from_secs(1));
+    }
+
+    #[tokio::test]
+    async fn test_tcp_non_loopback_requires_token() {
+        let server = RpcServer::new();
+        let result = server.run_on_tcp("0.0.0.0:0").await;
+        assert!(result.unwrap_err().to_string().contains("认证令牌"));
+    }
+
+    #[test]
+    fn test_tokens_match() {
+        assert!(tokens_match("secret", "secret"));
+        assert!(!tokens_match("secreT", "secret"));
+        assert!(!tokens_match("secret-longer", "secret"));
+        assert!(!tokens_match("", "secret"));
+    }
+
+    #[tokio::test]
+    async fn test_detached_after_connection_ends() {
+        let server = RpcServer::new();
+        let sender = server.notification_sender();
+        assert!(sender.is_attached());
+
+        let input: &[u8] = b"";
+        server.serve(input, MockWriter::default()).await.unwrap();
+        assert!(!sender.is_attached());
+    }
+
+    #[tokio::test]
+    async fn test_serve_rejects_embedded_null() {
+        let server = RpcServer::new();
+        let writer = MockWriter::default();
+
+        let input: &[u8] = b"{\"jsonrpc\":\"2.0\",\x00\"id\":1}\n";
+        server.serve(input, writer.clone()).await.unwrap();
+
+        let lines = writer.lines();
+        assert_eq!(lines.len(), 1);
+        assert_eq!(lines[0]["error"]["code"], -32700);
+    }
 }
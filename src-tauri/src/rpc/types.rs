@@ -3,23 +3,46 @@
 //! 定义 JSON-RPC 请求、响应和通知的数据结构。
 
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+/// 日志和调试输出中替代敏感信息的占位符
+pub const REDACTED: &str = "***";
 
 /// 终端尺寸
+///
+/// 像素尺寸供 sixel、kitty 图形协议等按像素绘制图像的程序使用，未知时为 0。
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub struct TermSize {
     pub rows: u16,
     pub cols: u16,
+    /// 窗口宽度（像素）
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub pixel_width: u16,
+    /// 窗口高度（像素）
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub pixel_height: u16,
 }
 
 impl Default for TermSize {
     fn default() -> Self {
-        Self { rows: 24, cols: 80 }
+        Self {
+            rows: 24,
+            cols: 80,
+            pixel_width: 0,
+            pixel_height: 0,
+        }
     }
 }
 
+fn is_zero(value: &u16) -> bool {
+    *value == 0
+}
+
 /// 连接类型
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+///
+/// SSH 密码只用于创建会话，不会出现在序列化结果和调试输出中。
+#[derive(Clone, Serialize, Deserialize, PartialEq)]
 #[serde(tag = "type", rename_all = "lowercase")]
 pub enum ConnectionType {
     /// 本地 PTY 连接
@@ -31,6 +54,15 @@ pub enum ConnectionType {
         #[serde(skip_serializing_if = "Option::is_none")]
         env: Option<HashMap<String, String>>,
     },
+    /// 不分配 PTY 的本地进程，通过系统 shell 运行命令，stdin/stdout/stderr 使用管道
+    #[serde(rename = "local_process")]
+    LocalProcess {
+        command: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        cwd: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        env: Option<HashMap<String, String>>,
+    },
     /// SSH 远程连接
     Ssh {
         host: String,
@@ -40,11 +72,85 @@ pub enum ConnectionType {
         user: Option<String>,
         #[serde(skip_serializing_if = "Option::is_none")]
         identity_file: Option<String>,
-        #[serde(skip_serializing_if = "Option::is_none")]
+        #[serde(default, skip_serializing)]
         password: Option<String>,
+        /// 启用 zlib 压缩（需服务器同样支持）
+        #[serde(default)]
+        compression: bool,
+        /// 可恢复错误（超时、主机解析失败）的最大重试次数，默认不重试
+        #[serde(default)]
+        max_retries: u32,
+        /// 第一次重试前的等待时间（毫秒），之后每次翻倍
+        #[serde(default = "default_retry_delay_ms")]
+        retry_delay_ms: u64,
+        /// 通道意外关闭而连接仍然存活时重新打开通道的最大次数，默认不重新打开
+        ///
+        /// 每次重新打开前按 `retry_delay_ms` 指数退避并加入随机抖动。
+        #[serde(default)]
+        channel_retries: u32,
+        /// 在第一次收到 shell 输出时发送 `session.ready` 通知
+        #[serde(default)]
+        notify_ready: bool,
+        /// 请求 shell 前通过 env 请求发送的环境变量（类似 OpenSSH 的 `SendEnv`），按名称排序发送；
+        /// 服务器通常只接受 `AcceptEnv` 中列出的变量
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        env: Option<HashMap<String, String>>,
     },
 }
 
+fn default_retry_delay_ms() -> u64 {
+    crate::ssh::retry::DEFAULT_RETRY_BASE_DELAY_MS
+}
+
+impl fmt::Debug for ConnectionType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Local {
+                shell_path,
+                cwd,
+                env,
+            } => f
+                .debug_struct("Local")
+                .field("shell_path", shell_path)
+                .field("cwd", cwd)
+                .field("env", env)
+                .finish(),
+            Self::LocalProcess { command, cwd, env } => f
+                .debug_struct("LocalProcess")
+                .field("command", command)
+                .field("cwd", cwd)
+                .field("env", env)
+                .finish(),
+            Self::Ssh {
+                host,
+                port,
+                user,
+                identity_file,
+                password,
+                compression,
+                max_retries,
+                retry_delay_ms,
+                channel_retries,
+                notify_ready,
+                env,
+            } => f
+                .debug_struct("Ssh")
+                .field("host", host)
+                .field("port", port)
+                .field("user", user)
+                .field("identity_file", identity_file)
+                .field("password", &password.as_ref().map(|_| REDACTED))
+                .field("compression", compression)
+                .field("max_retries", max_retries)
+                .field("retry_delay_ms", retry_delay_ms)
+                .field("channel_retries", channel_retries)
+                .field("notify_ready", notify_ready)
+                .field("env", env)
+                .finish(),
+        }
+    }
+}
+
 /// 会话状态
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
 #[serde(rename_all = "lowercase")]
@@ -61,6 +167,36 @@ pub enum SessionStatus {
     Error,
 }
 
+/// 鼠标报告模式
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum MouseTrackingMode {
+    /// 未启用鼠标报告
+    #[default]
+    None,
+    /// X10 兼容模式 (?9)
+    X10,
+    /// 按键按下/释放 (?1000)
+    Normal,
+    /// 按键按下时的移动 (?1002)
+    ButtonEvent,
+    /// 所有移动 (?1003)
+    AnyEvent,
+}
+
+/// 终端模式状态
+///
+/// 由 CSI 扫描器根据终端输出中的 DEC 私有模式序列维护。
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct TerminalModes {
+    /// 鼠标报告模式
+    pub mouse_tracking: MouseTrackingMode,
+    /// 是否使用 SGR 鼠标编码 (?1006)
+    pub sgr_mouse: bool,
+    /// 是否处于备用屏幕 (?47/?1047/?1049)
+    pub alt_screen: bool,
+}
+
 /// 会话信息
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SessionInfo {
@@ -69,11 +205,87 @@ pub struct SessionInfo {
     pub status: SessionStatus,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub title: Option<String>,
+    /// 用户设置的显示名称，不随 OSC 标题变化
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub cwd: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub exit_code: Option<i32>,
     pub created_at: u64,
+    #[serde(default)]
+    pub modes: TerminalModes,
+    /// SSH 握手诊断信息（仅已连接的 SSH 会话）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ssh: Option<SshDiagnostics>,
+    /// 会话被服务器结束的原因，如 `idle_timeout`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exit_reason: Option<String>,
+    /// 会话结束（进入 `done` 或 `error`）的 Unix 时间戳（秒）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ended_at: Option<u64>,
+    /// 从创建到结束的运行时长（秒）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration_secs: Option<u64>,
+    /// 本地子进程的 PID，可用于 `renice` 或附加调试器；SSH 会话为 `None`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pid: Option<u32>,
+    /// SSH 会话实际连接的地址（`ip:port`），DNS 返回多个地址时用于确认连到了哪台服务器；
+    /// 连接前为 `None`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resolved_addr: Option<String>,
+}
+
+impl SessionInfo {
+    /// 把会话置为结束状态并记录结束时间
+    ///
+    /// 已经记录过结束时间时只更新状态，保留第一次结束的时间。
+    pub fn mark_ended(&mut self, status: SessionStatus, ended_at: u64) {
+        self.status = status;
+        if self.ended_at.is_none() {
+            self.ended_at = Some(ended_at);
+            self.duration_secs = Some(ended_at.saturating_sub(self.created_at));
+        }
+    }
+
+    /// 结束时间和运行时长，会话未结束时为 `None`
+    pub fn timing(&self) -> Option<SessionTiming> {
+        Some(SessionTiming {
+            ended_at: self.ended_at?,
+            duration_secs: self.duration_secs?,
+        })
+    }
+}
+
+/// 会话的结束时间和运行时长，附带在结束状态通知中
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SessionTiming {
+    /// 结束时的 Unix 时间戳（秒）
+    pub ended_at: u64,
+    /// 运行时长（秒）
+    pub duration_secs: u64,
+}
+
+/// SSH 握手协商结果，仅用于诊断
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SshDiagnostics {
+    /// 服务器版本字符串，如 `SSH-2.0-OpenSSH_9.6`
+    pub server_version: String,
+    /// 密钥交换算法
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub kex: Option<String>,
+    /// 主机密钥算法
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub host_key: Option<String>,
+    /// 对称加密算法
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cipher: Option<String>,
+    /// MAC 算法（AEAD 密码不使用单独的 MAC）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mac: Option<String>,
+    /// 压缩算法
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub compression: Option<String>,
 }
 
 // ============ RPC 请求类型 ============
@@ -83,6 +295,112 @@ pub struct SessionInfo {
 pub struct CreateSessionRequest {
     pub connection: ConnectionType,
     pub term_size: TermSize,
+    /// 通过 /proc 轮询工作目录（仅 Linux 本地会话，默认关闭）
+    #[serde(default)]
+    pub track_cwd: bool,
+    /// 从指定会话继承工作目录（仅在未指定 cwd 时生效）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub inherit_cwd_from: Option<String>,
+    /// 以登录 shell 启动（仅 Unix 本地会话，argv[0] 为 `-<basename>`，默认关闭）
+    #[serde(default)]
+    pub login_shell: bool,
+    /// 除基础变量外，从插件环境继承的变量名（仅本地会话），`env` 中的值优先
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub inherit_env: Vec<String>,
+    /// 在子进程环境中设置 `FORCE_COLOR`、`CLICOLOR_FORCE` 和 `COLORTERM`，让不检测 TTY 的
+    /// 工具也输出颜色；`env` 中的值优先。SSH 会话通过通道的 env 请求发送，是否生效取决于服务器
+    #[serde(default)]
+    pub force_color: bool,
+    /// 子进程使用的 locale，如 `zh_CN.UTF-8`，设置到 `LANG` 和 `LC_ALL`；`env` 中的值优先。
+    /// SSH 会话通过通道的 env 请求发送
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub locale: Option<String>,
+    /// 未指定 `locale` 且继承的 locale 缺失或为 `C`/`POSIX` 时使用 `en_US.UTF-8`（仅本地会话）
+    #[serde(default)]
+    pub fallback_utf8_locale: bool,
+    /// PTY 读取缓冲区大小（字节），限制在 512 到 1MiB 之间，默认 4096
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub read_buffer_size: Option<usize>,
+    /// 向 PTY 写入输入时的分块大小（字节），限制在 512 到 1MiB 之间，默认 32768。
+    /// 大块粘贴分多次写入，分块之间让出执行权
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub write_chunk_size: Option<usize>,
+    /// 输出速率上限（字节/秒），默认不限制
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rate_limit_bytes_per_sec: Option<u64>,
+    /// 允许通过 `session.inject_output` 注入输出（默认关闭）
+    #[serde(default)]
+    pub allow_inject: bool,
+    /// 未指定 shell 时优先使用 PowerShell（`pwsh`，其次 `powershell`），仅 Windows 生效
+    #[serde(default)]
+    pub prefer_powershell: bool,
+    /// 去掉 OSC 52 剪贴板内容中的控制字符（包括转义序列的 ESC），防止粘贴时注入命令，默认开启。
+    /// 关闭时 `session.clipboard` 发送原始内容
+    #[serde(default = "default_true")]
+    pub sanitize_clipboard: bool,
+    /// 从 `terminal.output` 中去掉 OSC 序列，默认开启。关闭时仍然发送标题、工作目录、
+    /// 剪贴板等通知，输出原样发送，供自行解析 OSC 的前端使用
+    #[serde(default = "default_true")]
+    pub strip_osc_from_output: bool,
+    /// 空闲超时（秒），超过这么久没有输入时结束会话，仅本地 PTY 会话生效
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub idle_timeout_secs: Option<u64>,
+    /// 输出也重置空闲计时，默认只有输入重置
+    #[serde(default)]
+    pub idle_reset_on_output: bool,
+    /// 客户端指定的会话 ID，最长 128 个字符，只能包含字母、数字、`-`、`_` 和 `.`；
+    /// 未指定时生成 UUID
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session_id: Option<String>,
+    /// 指定的会话 ID 已存在时改为生成 UUID，而不是拒绝请求
+    #[serde(default)]
+    pub generate_id_on_conflict: bool,
+    /// 只读会话：拒绝输入和调整大小，输出照常发送，可通过 `session.set_read_only` 修改
+    #[serde(default)]
+    pub read_only: bool,
+    /// 会话进入 `running` 状态后写入一次的初始输入（base64），如 `cd /path && npm start\r`；
+    /// SSH 会话在 shell 打开后写入
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub initial_input: Option<String>,
+    /// 创建会话（包括 SSH 连接）的最长时间（毫秒），与连接本身的超时配置无关；
+    /// 超时后取消创建并返回 `connection_timeout` 错误，0 或未指定时不限制
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout_ms: Option<u64>,
+}
+
+impl Default for CreateSessionRequest {
+    /// 使用默认 shell 的本地会话，其余选项与反序列化时省略字段的默认值一致
+    fn default() -> Self {
+        Self {
+            connection: ConnectionType::Local {
+                shell_path: None,
+                cwd: None,
+                env: None,
+            },
+            term_size: TermSize::default(),
+            track_cwd: false,
+            inherit_cwd_from: None,
+            login_shell: false,
+            inherit_env: Vec::new(),
+            force_color: false,
+            locale: None,
+            fallback_utf8_locale: false,
+            read_buffer_size: None,
+            write_chunk_size: None,
+            rate_limit_bytes_per_sec: None,
+            allow_inject: false,
+            prefer_powershell: false,
+            sanitize_clipboard: true,
+            strip_osc_from_output: true,
+            idle_timeout_secs: None,
+            idle_reset_on_output: false,
+            session_id: None,
+            generate_id_on_conflict: false,
+            read_only: false,
+            initial_input: None,
+            timeout_ms: None,
+        }
+    }
 }
 
 /// 创建会话响应
@@ -99,6 +417,20 @@ pub struct InputRequest {
     pub data: String,
 }
 
+/// 按键或十六进制输入请求
+///
+/// `key` 和 `hex` 必须且只能指定一个。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WriteRawRequest {
+    pub session_id: String,
+    /// 按键名称，如 `ArrowUp`、`F5`，见 [`crate::shell::keys`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key: Option<String>,
+    /// 十六进制编码的字节，如 `1b5b41`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hex: Option<String>,
+}
+
 /// 调整大小请求
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ResizeRequest {
@@ -112,12 +444,440 @@ pub struct CloseSessionRequest {
     pub session_id: String,
 }
 
+/// 连接类型（不含连接参数）
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ConnectionKind {
+    Local,
+    #[serde(rename = "local_process")]
+    LocalProcess,
+    Ssh,
+}
+
+/// 批量关闭会话的过滤条件
+///
+/// 会话需要满足所有给出的条件，至少需要给出一个条件。`host`、`port`、`user`
+/// 只匹配 SSH 会话，未指定端口的会话按 22 端口匹配。
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CloseWhereRequest {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub connection_type: Option<ConnectionKind>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub host: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub port: Option<u16>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user: Option<String>,
+}
+
+impl CloseWhereRequest {
+    /// 是否没有给出任何条件
+    pub fn is_empty(&self) -> bool {
+        self.connection_type.is_none()
+            && self.host.is_none()
+            && self.port.is_none()
+            && self.user.is_none()
+    }
+
+    /// 连接是否满足所有条件
+    pub fn matches(&self, connection: &ConnectionType) -> bool {
+        match connection {
+            ConnectionType::Local { .. } => {
+                self.connection_type.unwrap_or(ConnectionKind::Local) == ConnectionKind::Local
+                    && self.host.is_none()
+                    && self.port.is_none()
+                    && self.user.is_none()
+            }
+            ConnectionType::LocalProcess { .. } => {
+                self.connection_type.unwrap_or(ConnectionKind::LocalProcess)
+                    == ConnectionKind::LocalProcess
+                    && self.host.is_none()
+                    && self.port.is_none()
+                    && self.user.is_none()
+            }
+            ConnectionType::Ssh { host, port, user, .. } => {
+                self.connection_type.unwrap_or(ConnectionKind::Ssh) == ConnectionKind::Ssh
+                    && self.host.as_ref().is_none_or(|h| h == host)
+                    && self.port.is_none_or(|p| p == port.unwrap_or(22))
+                    && self.user.as_ref().is_none_or(|u| user.as_ref() == Some(u))
+            }
+        }
+    }
+}
+
+/// 查询会话的过滤条件
+///
+/// 会话需要满足所有给出的条件，没有条件时返回所有会话。`status` 和 `connection_type`
+/// 精确匹配；`cwd` 默认精确匹配，`cwd_prefix` 为 true 时也匹配其子目录；`host` 只匹配 SSH 会话。
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct QuerySessionsRequest {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cwd: Option<String>,
+    /// `cwd` 按目录前缀匹配
+    #[serde(default)]
+    pub cwd_prefix: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<SessionStatus>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub connection_type: Option<ConnectionKind>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub host: Option<String>,
+}
+
+impl QuerySessionsRequest {
+    /// 会话是否满足所有条件
+    pub fn matches(&self, info: &SessionInfo) -> bool {
+        if self.status.is_some_and(|status| status != info.status) {
+            return false;
+        }
+        if let Some(cwd) = &self.cwd {
+            let Some(session_cwd) = &info.cwd else {
+                return false;
+            };
+            let matched = if self.cwd_prefix {
+                // 按路径组件比较，`/home/user` 不匹配 `/home/username`
+                std::path::Path::new(session_cwd).starts_with(cwd)
+            } else {
+                session_cwd.trim_end_matches('/') == cwd.trim_end_matches('/')
+            };
+            if !matched {
+                return false;
+            }
+        }
+
+        let (kind, host) = match &info.connection_type {
+            ConnectionType::Local { .. } => (ConnectionKind::Local, None),
+            ConnectionType::LocalProcess { .. } => (ConnectionKind::LocalProcess, None),
+            ConnectionType::Ssh { host, .. } => (ConnectionKind::Ssh, Some(host)),
+        };
+        self.connection_type.is_none_or(|expected| expected == kind)
+            && self.host.as_ref().is_none_or(|expected| host == Some(expected))
+    }
+}
+
+/// 单个会话关闭失败
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CloseFailure {
+    pub session_id: String,
+    pub error: JsonRpcError,
+}
+
+/// 批量关闭会话响应
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CloseWhereResponse {
+    /// 成功关闭的会话 ID（按 ID 排序）
+    pub closed: Vec<String>,
+    /// 关闭失败的会话，这些会话同样已从会话表中移除
+    pub errors: Vec<CloseFailure>,
+}
+
 /// 获取会话请求
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GetSessionRequest {
     pub session_id: String,
 }
 
+/// 暂停输出请求
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PauseSessionRequest {
+    pub session_id: String,
+}
+
+/// 恢复输出请求
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResumeSessionRequest {
+    pub session_id: String,
+}
+
+/// 分离输出请求
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DetachSessionRequest {
+    pub session_id: String,
+}
+
+/// 重新附加输出请求
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AttachSessionRequest {
+    pub session_id: String,
+}
+
+/// 设置标题请求
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SetTitleRequest {
+    pub session_id: String,
+    pub title: String,
+}
+
+/// 设置只读模式请求
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SetReadOnlyRequest {
+    pub session_id: String,
+    pub read_only: bool,
+}
+
+/// 批量操作中的一项
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum BatchOperation {
+    /// 调整终端大小
+    Resize { term_size: TermSize },
+    /// 发送输入，`data` 为 Base64 编码
+    Input { data: String },
+    /// 向终端的前台进程组发送信号，如 `INT`、`SIGTERM`（仅 Unix 本地 PTY 会话）
+    Signal { signal: String },
+}
+
+impl BatchOperation {
+    /// 操作名称，与请求中的 `op` 一致
+    pub fn name(&self) -> &'static str {
+        match self {
+            BatchOperation::Resize { .. } => "resize",
+            BatchOperation::Input { .. } => "input",
+            BatchOperation::Signal { .. } => "signal",
+        }
+    }
+}
+
+/// 批量操作请求（`session.batch`）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchRequest {
+    pub session_id: String,
+    /// 按顺序执行的操作
+    pub operations: Vec<BatchOperation>,
+}
+
+/// 批量操作中一项的执行结果
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct BatchOperationResult {
+    pub op: String,
+    pub ok: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// 批量操作响应
+///
+/// 某项失败后不再执行后续操作，`results` 只包含已执行的操作和失败的那一项。
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct BatchResponse {
+    /// 成功执行的操作数
+    pub applied: usize,
+    pub results: Vec<BatchOperationResult>,
+}
+
+/// 对变更的 SSH 主机密钥的决定
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HostKeyDecision {
+    /// 只在这次连接中接受
+    AcceptOnce,
+    /// 接受并把新密钥写入 known_hosts
+    AcceptPermanent,
+    /// 拒绝连接
+    Reject,
+}
+
+/// 重命名会话请求
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RenameSessionRequest {
+    pub session_id: String,
+    /// 新名称，`null` 或空字符串清除名称
+    #[serde(default)]
+    pub name: Option<String>,
+}
+
+/// 获取会话环境变量请求
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GetEnvRequest {
+    pub session_id: String,
+}
+
+/// 清屏方式
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ClearMode {
+    /// 清除回滚缓冲区、光标归位并清屏 (`ESC[3J ESC[H ESC[2J`)
+    Scrollback,
+    /// 发送 Ctrl+L (`\x0c`)，由 shell 的行编辑器清屏并重绘提示符
+    FormFeed,
+}
+
+impl ClearMode {
+    /// 写入会话输入的字节序列
+    pub fn as_bytes(&self) -> &'static [u8] {
+        match self {
+            ClearMode::Scrollback => b"\x1b[3J\x1b[H\x1b[2J",
+            ClearMode::FormFeed => b"\x0c",
+        }
+    }
+}
+
+/// 发送 EOF 请求
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EofSessionRequest {
+    pub session_id: String,
+}
+
+/// 同步输入请求
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SyncSessionRequest {
+    pub session_id: String,
+}
+
+/// 同步输入响应
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SyncSessionResponse {
+    /// 同步完成时会话累计写入的输入字节数
+    pub bytes_in: u64,
+}
+
+/// 注入输出请求
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InjectOutputRequest {
+    pub session_id: String,
+    /// Base64 编码的输出数据
+    pub data: String,
+}
+
+/// 清屏请求
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClearSessionRequest {
+    pub session_id: String,
+    /// 清屏方式，未指定时根据 shell 类型选择
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mode: Option<ClearMode>,
+}
+
+/// 获取会话环境变量响应
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionEnvResponse {
+    /// 启动 shell 时使用的环境变量（按名称排序）
+    pub env: BTreeMap<String, String>,
+    /// 是否因超出大小限制而省略了部分变量
+    pub truncated: bool,
+}
+
+/// 单个会话的字节统计
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SessionStats {
+    pub session_id: String,
+    /// 写入会话的输入字节数
+    pub bytes_in: u64,
+    /// 发送给前端的输出字节数
+    pub bytes_out: u64,
+}
+
+/// 服务器统计快照
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ServerStats {
+    /// 启动以来成功创建的会话总数
+    pub sessions_created: u64,
+    /// 当前打开的会话数
+    pub sessions_open: usize,
+    /// 所有会话（包括已关闭的）写入的输入字节总数
+    pub bytes_in: u64,
+    /// 所有会话（包括已关闭的）发送的输出字节总数
+    pub bytes_out: u64,
+    /// 因客户端断开而丢弃的通知数
+    pub notifications_dropped: u64,
+    /// 因超过单条通知最大字节数而丢弃的通知数
+    pub notifications_oversized: u64,
+    /// 当前打开会话的统计（按会话 ID 排序）
+    pub sessions: Vec<SessionStats>,
+}
+
+/// 自检响应
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SelfTestResponse {
+    /// 能否创建 PTY 并启动子进程
+    pub pty_ok: bool,
+    /// 检测到的默认 shell
+    pub default_shell: String,
+    /// 自检失败的原因
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Shell 类型
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ShellKind {
+    Bash,
+    Zsh,
+    Fish,
+    /// POSIX sh 及其实现（dash、ash）
+    Sh,
+    /// Korn shell（ksh、mksh）
+    Ksh,
+    /// C shell（csh、tcsh）
+    Csh,
+    Nushell,
+    Cmd,
+    /// Windows PowerShell 和 PowerShell 7（pwsh）
+    PowerShell,
+    Wsl,
+    Other,
+}
+
+/// 系统中可用的 shell
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ShellInfo {
+    /// 可执行文件的绝对路径
+    pub path: String,
+    /// 显示名称
+    pub name: String,
+    /// Shell 类型
+    pub kind: ShellKind,
+}
+
+/// 可用 shell 列表响应
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ListShellsResponse {
+    pub shells: Vec<ShellInfo>,
+    /// 未指定 shell 时使用的默认 shell
+    pub default_shell: String,
+}
+
+/// 支持的 OSC 代码
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct OscCodeInfo {
+    /// `Ps` 代码
+    pub code: u16,
+    /// 功能名称
+    pub feature: String,
+    /// 按默认会话配置是否处理该代码
+    pub enabled: bool,
+}
+
+/// 支持的 OSC 代码列表响应
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ListOscCodesResponse {
+    pub codes: Vec<OscCodeInfo>,
+}
+
+/// TCP 连接的认证请求（`server.authenticate`），必须是连接上的第一个请求
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuthenticateRequest {
+    pub token: String,
+}
+
+/// 心跳请求
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PingRequest {
+    /// 客户端提供的任意值，原样返回
+    #[serde(default)]
+    pub nonce: serde_json::Value,
+}
+
+/// 心跳响应
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PingResponse {
+    /// 请求中的 nonce
+    pub nonce: serde_json::Value,
+    /// 服务器单调时钟时间戳（自服务器启动以来的微秒数）
+    pub timestamp_us: u64,
+}
+
 // ============ RPC 通知类型 ============
 
 /// 终端输出通知
@@ -126,6 +886,16 @@ pub struct OutputNotification {
     pub session_id: String,
     /// Base64 编码的输出数据
     pub data: String,
+    /// 会话内单调递增的分块序号
+    #[serde(default)]
+    pub seq: u64,
+    /// 是否为一次输出的最后一个分块
+    #[serde(rename = "final", default = "default_true")]
+    pub is_final: bool,
+}
+
+fn default_true() -> bool {
+    true
 }
 
 /// 会话状态变更通知
@@ -135,6 +905,24 @@ pub struct SessionStatusNotification {
     pub status: SessionStatus,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub exit_code: Option<i32>,
+    /// 状态为 `error` 时的错误详情
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<SessionError>,
+}
+
+/// 会话错误详情
+///
+/// 由 `TerminalError` 转换而来，字段与 JSON-RPC 错误的 `data` 保持一致。
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SessionError {
+    /// 错误消息
+    pub message: String,
+    /// 错误类型，如 `pty_creation_failed`
+    pub error_type: String,
+    /// 错误码
+    pub code: i32,
+    /// 是否可恢复
+    pub recoverable: bool,
 }
 
 /// 会话标题变更通知
@@ -151,6 +939,29 @@ pub struct SessionCwdNotification {
     pub cwd: String,
 }
 
+/// 颜色变更通知
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionColorNotification {
+    pub session_id: String,
+    /// `palette`、`foreground` 或 `background`
+    pub target: String,
+    /// 调色板索引（仅 `palette`）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub index: Option<u8>,
+    /// `#rrggbb` 格式的颜色，查询时为空
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color: Option<String>,
+    /// 程序是否在查询颜色
+    pub query: bool,
+}
+
+/// 终端模式变更通知
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionModeNotification {
+    pub session_id: String,
+    pub modes: TerminalModes,
+}
+
 // ============ JSON-RPC 2.0 协议类型 ============
 
 /// JSON-RPC 请求
@@ -281,6 +1092,38 @@ mod tests {
         let size = TermSize::default();
         assert_eq!(size.rows, 24);
         assert_eq!(size.cols, 80);
+        assert_eq!((size.pixel_width, size.pixel_height), (0, 0));
+    }
+
+    #[test]
+    fn test_create_session_request_default_matches_omitted_fields() {
+        let parsed: CreateSessionRequest = serde_json::from_value(serde_json::json!({
+            "connection": { "type": "local" },
+            "term_size": { "rows": 24, "cols": 80 }
+        }))
+        .unwrap();
+        assert_eq!(
+            serde_json::to_value(&parsed).unwrap(),
+            serde_json::to_value(CreateSessionRequest::default()).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_term_size_pixel_round_trip() {
+        let size = TermSize {
+            rows: 30,
+            cols: 100,
+            pixel_width: 1000,
+            pixel_height: 600,
+        };
+        let json = serde_json::to_value(&size).unwrap();
+        assert_eq!(json["pixel_width"], 1000);
+        assert_eq!(serde_json::from_value::<TermSize>(json).unwrap(), size);
+
+        // 旧客户端不发送像素尺寸
+        let size: TermSize = serde_json::from_str(r#"{"rows":24,"cols":80}"#).unwrap();
+        assert_eq!(size, TermSize::default());
+        assert_eq!(serde_json::to_string(&size).unwrap(), r#"{"rows":24,"cols":80}"#);
     }
 
     #[test]
@@ -303,12 +1146,249 @@ mod tests {
             user: Some("root".to_string()),
             identity_file: None,
             password: None,
+            compression: false,
+            max_retries: 0,
+            retry_delay_ms: 1000,
+            channel_retries: 0,
+            notify_ready: false,
+            env: None,
         };
         let json = serde_json::to_string(&conn).unwrap();
         assert!(json.contains("\"type\":\"ssh\""));
         assert!(json.contains("\"host\":\"example.com\""));
     }
 
+    #[test]
+    fn test_session_info_name_and_title() {
+        let mut info = SessionInfo {
+            id: "session-1".to_string(),
+            connection_type: ConnectionType::Local {
+                shell_path: None,
+                cwd: None,
+                env: None,
+            },
+            status: SessionStatus::Running,
+            title: Some("vim README.md".to_string()),
+            name: Some("docs".to_string()),
+            cwd: None,
+            exit_code: None,
+            created_at: 0,
+            modes: TerminalModes::default(),
+            ssh: None,
+            exit_reason: None,
+            ended_at: None,
+            duration_secs: None,
+            pid: None,
+            resolved_addr: None,
+        };
+
+        let value = serde_json::to_value(&info).unwrap();
+        assert_eq!(value["name"], "docs");
+        assert_eq!(value["title"], "vim README.md");
+        let parsed: SessionInfo = serde_json::from_value(value).unwrap();
+        assert_eq!(parsed.name.as_deref(), Some("docs"));
+        assert_eq!(parsed.title.as_deref(), Some("vim README.md"));
+
+        // 未设置名称时省略，旧格式也能解析
+        info.name = None;
+        let value = serde_json::to_value(&info).unwrap();
+        assert!(value.get("name").is_none());
+        let parsed: SessionInfo = serde_json::from_value(value).unwrap();
+        assert!(parsed.name.is_none());
+    }
+
+    #[test]
+    fn test_session_info_mark_ended() {
+        let mut info = SessionInfo {
+            id: "session-1".to_string(),
+            connection_type: ConnectionType::Local {
+                shell_path: None,
+                cwd: None,
+                env: None,
+            },
+            status: SessionStatus::Running,
+            title: None,
+            name: None,
+            cwd: None,
+            exit_code: None,
+            created_at: 1_700_000_000,
+            modes: TerminalModes::default(),
+            ssh: None,
+            exit_reason: None,
+            ended_at: None,
+            duration_secs: None,
+            pid: None,
+            resolved_addr: None,
+        };
+        assert!(info.timing().is_none());
+        let value = serde_json::to_value(&info).unwrap();
+        assert!(value.get("ended_at").is_none());
+
+        info.mark_ended(SessionStatus::Done, 1_700_000_192);
+        assert_eq!(info.status, SessionStatus::Done);
+        assert_eq!(
+            info.timing(),
+            Some(SessionTiming {
+                ended_at: 1_700_000_192,
+                duration_secs: 192,
+            })
+        );
+
+        // 再次结束只更新状态，保留第一次的结束时间
+        info.mark_ended(SessionStatus::Error, 1_700_000_300);
+        assert_eq!(info.status, SessionStatus::Error);
+        assert_eq!(info.ended_at, Some(1_700_000_192));
+
+        let value = serde_json::to_value(&info).unwrap();
+        assert_eq!(value["ended_at"], 1_700_000_192);
+        assert_eq!(value["duration_secs"], 192);
+    }
+
+    #[test]
+    fn test_ssh_password_not_serialized() {
+        let info = SessionInfo {
+            id: "session-1".to_string(),
+            connection_type: ConnectionType::Ssh {
+                host: "example.com".to_string(),
+                port: Some(22),
+                user: Some("root".to_string()),
+                identity_file: None,
+                password: Some("hunter2".to_string()),
+                compression: false,
+                max_retries: 0,
+                retry_delay_ms: 1000,
+                channel_retries: 0,
+                notify_ready: false,
+                env: None,
+            },
+            status: SessionStatus::Running,
+            title: None,
+            name: None,
+            cwd: None,
+            exit_code: None,
+            created_at: 0,
+            modes: TerminalModes::default(),
+            ssh: None,
+            exit_reason: None,
+            ended_at: None,
+            duration_secs: None,
+            pid: None,
+            resolved_addr: None,
+        };
+
+        let json = serde_json::to_string(&info).unwrap();
+        assert!(!json.contains("hunter2"));
+        assert!(!json.contains("password"));
+        assert!(json.contains("\"host\":\"example.com\""));
+
+        let debug = format!("{:?}", info);
+        assert!(!debug.contains("hunter2"));
+        assert!(debug.contains(REDACTED));
+    }
+
+    #[test]
+    fn test_ssh_diagnostics_serialization() {
+        let diagnostics = SshDiagnostics {
+            server_version: "SSH-2.0-OpenSSH_9.6".to_string(),
+            kex: Some("curve25519-sha256".to_string()),
+            host_key: Some("ssh-ed25519".to_string()),
+            cipher: Some("chacha20-poly1305@openssh.com".to_string()),
+            mac: None,
+            compression: Some("none".to_string()),
+        };
+
+        let value = serde_json::to_value(&diagnostics).unwrap();
+        assert_eq!(value["server_version"], "SSH-2.0-OpenSSH_9.6");
+        assert_eq!(value["cipher"], "chacha20-poly1305@openssh.com");
+        assert!(value.get("mac").is_none());
+
+        let parsed: SshDiagnostics = serde_json::from_value(value).unwrap();
+        assert_eq!(parsed, diagnostics);
+    }
+
+    #[test]
+    fn test_ssh_password_still_deserialized() {
+        let json = r#"{"type":"ssh","host":"example.com","password":"hunter2"}"#;
+        let conn: ConnectionType = serde_json::from_str(json).unwrap();
+        match conn {
+            ConnectionType::Ssh { password, compression, .. } => {
+                assert_eq!(password.as_deref(), Some("hunter2"));
+                // 未指定时默认不压缩
+                assert!(!compression);
+            }
+            _ => panic!("Expected SSH connection type"),
+        }
+    }
+
+    #[test]
+    fn test_local_process_connection_serialization() {
+        let json = r#"{"type":"local_process","command":"echo hi"}"#;
+        let conn: ConnectionType = serde_json::from_str(json).unwrap();
+        assert_eq!(
+            conn,
+            ConnectionType::LocalProcess {
+                command: "echo hi".to_string(),
+                cwd: None,
+                env: None,
+            }
+        );
+        assert_eq!(serde_json::to_string(&conn).unwrap(), json);
+
+        let filter: CloseWhereRequest =
+            serde_json::from_str(r#"{"connection_type": "local_process"}"#).unwrap();
+        assert!(filter.matches(&conn));
+        assert!(!CloseWhereRequest {
+            connection_type: Some(ConnectionKind::Local),
+            ..Default::default()
+        }
+        .matches(&conn));
+    }
+
+    #[test]
+    fn test_clear_mode_bytes() {
+        assert_eq!(ClearMode::Scrollback.as_bytes(), b"\x1b[3J\x1b[H\x1b[2J");
+        assert_eq!(ClearMode::FormFeed.as_bytes(), b"\x0c");
+
+        let request: ClearSessionRequest =
+            serde_json::from_str(r#"{"session_id": "s1", "mode": "form_feed"}"#).unwrap();
+        assert_eq!(request.mode, Some(ClearMode::FormFeed));
+        let request: ClearSessionRequest = serde_json::from_str(r#"{"session_id": "s1"}"#).unwrap();
+        assert!(request.mode.is_none());
+    }
+
+    #[test]
+    fn test_status_notification_error_serialization() {
+        let notif = SessionStatusNotification {
+            session_id: "session-1".to_string(),
+            status: SessionStatus::Error,
+            exit_code: None,
+            error: Some(SessionError {
+                message: "IO 错误: broken pipe".to_string(),
+                error_type: "io_error".to_string(),
+                code: 1005,
+                recoverable: false,
+            }),
+        };
+
+        let value = serde_json::to_value(&notif).unwrap();
+        assert_eq!(value["status"], "error");
+        assert_eq!(value["error"]["message"], "IO 错误: broken pipe");
+        assert_eq!(value["error"]["error_type"], "io_error");
+        assert_eq!(value["error"]["code"], 1005);
+        assert_eq!(value["error"]["recoverable"], false);
+        assert!(value.get("exit_code").is_none());
+
+        // 没有错误时不输出 error 字段
+        let done = SessionStatusNotification {
+            session_id: "session-1".to_string(),
+            status: SessionStatus::Done,
+            exit_code: Some(0),
+            error: None,
+        };
+        let value = serde_json::to_value(&done).unwrap();
+        assert!(value.get("error").is_none());
+    }
+
     #[test]
     fn test_session_status_serialization() {
         assert_eq!(
@@ -321,6 +1401,19 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_terminal_modes_serialization() {
+        let modes = TerminalModes {
+            mouse_tracking: MouseTrackingMode::ButtonEvent,
+            sgr_mouse: true,
+            alt_screen: false,
+        };
+        let json = serde_json::to_string(&modes).unwrap();
+        assert!(json.contains("\"mouse_tracking\":\"button_event\""));
+        assert!(json.contains("\"sgr_mouse\":true"));
+        assert!(json.contains("\"alt_screen\":false"));
+    }
+
     #[test]
     fn test_json_rpc_error_codes() {
         assert_eq!(JsonRpcError::parse_error("test").code, -32700);
@@ -341,7 +1434,14 @@ mod proptests {
 
     // Strategy for generating TermSize
     fn term_size_strategy() -> impl Strategy<Value = TermSize> {
-        (1u16..500, 1u16..500).prop_map(|(rows, cols)| TermSize { rows, cols })
+        (1u16..500, 1u16..500, 0u16..4000, 0u16..4000).prop_map(
+            |(rows, cols, pixel_width, pixel_height)| TermSize {
+                rows,
+                cols,
+                pixel_width,
+                pixel_height,
+            },
+        )
     }
 
     // Strategy for generating optional strings
@@ -382,14 +1482,62 @@ mod proptests {
             optional_string_strategy(),
             optional_string_strategy(),
             optional_string_strategy(),
+            any::<bool>(),
+            0u32..10,
+            0u64..60_000,
+            0u32..10,
+            any::<bool>(),
+            optional_env_strategy(),
         )
-            .prop_map(|(host, port, user, identity_file, password)| ConnectionType::Ssh {
+            .prop_map(
+                |(host, port, user, identity_file, password, compression, max_retries, retry_delay_ms, channel_retries, notify_ready, env)| {
+                    ConnectionType::Ssh {
+                        host,
+                        port,
+                        user,
+                        identity_file,
+                        password,
+                        compression,
+                        max_retries,
+                        retry_delay_ms,
+                        channel_retries,
+                        notify_ready,
+                        env,
+                    }
+                },
+            )
+    }
+
+    /// 移除不参与序列化的密码，用于往返比较
+    fn without_password(conn: ConnectionType) -> ConnectionType {
+        match conn {
+            ConnectionType::Ssh {
                 host,
                 port,
                 user,
                 identity_file,
-                password,
-            })
+                compression,
+                max_retries,
+                retry_delay_ms,
+                channel_retries,
+                notify_ready,
+                env,
+                ..
+            } => ConnectionType::Ssh {
+                host,
+                port,
+                user,
+                identity_file,
+                password: None,
+                compression,
+                max_retries,
+                retry_delay_ms,
+                channel_retries,
+                notify_ready,
+                env,
+            },
+            local => local,
+        }
     }
 
     // Strategy for generating ConnectionType
@@ -428,6 +1576,14 @@ mod proptests {
                     cwd,
                     exit_code,
                     created_at,
+                    name: None,
+                    modes: Default::default(),
+                    ssh: None,
+                    exit_reason: None,
+                    ended_at: None,
+                    duration_secs: None,
+                    pid: None,
+                    resolved_addr: None,
                 },
             )
     }
@@ -438,6 +1594,7 @@ mod proptests {
             .prop_map(|(connection, term_size)| CreateSessionRequest {
                 connection,
                 term_size,
+                ..Default::default()
             })
     }
 
@@ -458,8 +1615,25 @@ mod proptests {
 
     // Strategy for generating OutputNotification
     fn output_notification_strategy() -> impl Strategy<Value = OutputNotification> {
-        ("[a-f0-9-]{36}", "[A-Za-z0-9+/=]{0,100}")
-            .prop_map(|(session_id, data)| OutputNotification { session_id, data })
+        ("[a-f0-9-]{36}", "[A-Za-z0-9+/=]{0,100}", any::<u64>(), any::<bool>())
+            .prop_map(|(session_id, data, seq, is_final)| OutputNotification {
+                session_id,
+                data,
+                seq,
+                is_final,
+            })
+    }
+
+    // Strategy for generating SessionError
+    fn session_error_strategy() -> impl Strategy<Value = SessionError> {
+        ("[a-zA-Z0-9 ]{0,50}", "[a-z_]{1,30}", 1000i32..2000, any::<bool>()).prop_map(
+            |(message, error_type, code, recoverable)| SessionError {
+                message,
+                error_type,
+                code,
+                recoverable,
+            },
+        )
     }
 
     // Strategy for generating SessionStatusNotification
@@ -468,11 +1642,13 @@ mod proptests {
             "[a-f0-9-]{36}",
             session_status_strategy(),
             prop::option::of(-128i32..128),
+            prop::option::of(session_error_strategy()),
         )
-            .prop_map(|(session_id, status, exit_code)| SessionStatusNotification {
+            .prop_map(|(session_id, status, exit_code, error)| SessionStatusNotification {
                 session_id,
                 status,
                 exit_code,
+                error,
             })
     }
 
@@ -494,7 +1670,7 @@ mod proptests {
         fn prop_connection_type_roundtrip(conn in connection_type_strategy()) {
             let json = serde_json::to_string(&conn).unwrap();
             let deserialized: ConnectionType = serde_json::from_str(&json).unwrap();
-            prop_assert_eq!(conn, deserialized);
+            prop_assert_eq!(without_password(conn), deserialized);
         }
 
         /// Feature: terminal-plugin, Property 2: RPC 请求往返一致性
@@ -506,6 +1682,18 @@ mod proptests {
             prop_assert_eq!(status, deserialized);
         }
 
+        /// Feature: terminal-plugin, Property 2: RPC 请求往返一致性
+        /// *对于任意*有效的 SessionInfo，序列化后再反序列化应产生等价对象
+        #[test]
+        fn prop_session_info_roundtrip(info in session_info_strategy()) {
+            let json = serde_json::to_string(&info).unwrap();
+            let deserialized: SessionInfo = serde_json::from_str(&json).unwrap();
+            prop_assert_eq!(
+                serde_json::to_value(&info).unwrap(),
+                serde_json::to_value(&deserialized).unwrap()
+            );
+        }
+
         /// Feature: terminal-plugin, Property 2: RPC 请求往返一致性
         /// *对于任意*有效的 CreateSessionRequest，序列化后再反序列化应产生等价对象
         #[test]
@@ -513,7 +1701,7 @@ mod proptests {
             let json = serde_json::to_string(&req).unwrap();
             let deserialized: CreateSessionRequest = serde_json::from_str(&json).unwrap();
             prop_assert_eq!(req.term_size, deserialized.term_size);
-            prop_assert_eq!(req.connection, deserialized.connection);
+            prop_assert_eq!(without_password(req.connection), deserialized.connection);
         }
 
         /// Feature: terminal-plugin, Property 2: RPC 请求往返一致性
@@ -544,6 +1732,8 @@ mod proptests {
             let deserialized: OutputNotification = serde_json::from_str(&json).unwrap();
             prop_assert_eq!(notif.session_id, deserialized.session_id);
             prop_assert_eq!(notif.data, deserialized.data);
+            prop_assert_eq!(notif.seq, deserialized.seq);
+            prop_assert_eq!(notif.is_final, deserialized.is_final);
         }
 
         /// Feature: terminal-plugin, Property 2: RPC 请求往返一致性
@@ -555,6 +1745,7 @@ mod proptests {
             prop_assert_eq!(notif.session_id, deserialized.session_id);
             prop_assert_eq!(notif.status, deserialized.status);
             prop_assert_eq!(notif.exit_code, deserialized.exit_code);
+            prop_assert_eq!(notif.error, deserialized.error);
         }
     }
 }
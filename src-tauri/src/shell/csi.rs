@@ -0,0 +1,314 @@
+//! CSI 序列扫描
+//!
+//! 跟踪影响客户端行为的 DEC 私有模式，如鼠标报告和备用屏幕。
+//!
+//! ## CSI 序列格式
+//!
+//! CSI (Control Sequence Introducer) 序列的格式为:
+//! - `ESC [ ? Pm h` 设置私有模式
+//! - `ESC [ ? Pm l` 重置私有模式
+//!
+//! ## 跟踪的模式
+//!
+//! - `?9` / `?1000` / `?1002` / `?1003`: 鼠标报告
+//! - `?1006`: SGR 鼠标编码
+//! - `?47` / `?1047` / `?1049`: 备用屏幕
+//!
+//! 扫描器是一个状态机，状态跨调用保留，因此序列被拆分到多次读取中也能正确识别。
+//! 扫描过程不修改数据，也不分配内存。
+
+use crate::rpc::types::{MouseTrackingMode, TerminalModes};
+
+/// ESC 字节
+const ESC: u8 = 0x1b;
+/// CAN 字节（中止序列）
+const CAN: u8 = 0x18;
+/// SUB 字节（中止序列）
+const SUB: u8 = 0x1a;
+/// 单个序列最多记录的参数个数，超出部分被忽略
+const MAX_PARAMS: usize = 16;
+
+/// 扫描器状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ScanState {
+    /// 普通文本
+    Ground,
+    /// 收到 ESC
+    Escape,
+    /// 处于 CSI 序列中
+    Csi,
+    /// 处于无法识别的 CSI 序列中，等待终止字节
+    CsiIgnore,
+}
+
+/// CSI 扫描器
+///
+/// 从终端输出流中识别 DEC 私有模式的设置和重置，并维护当前模式状态。
+#[derive(Debug, Clone)]
+pub struct CsiScanner {
+    /// 当前状态
+    state: ScanState,
+    /// 是否为私有序列（以 `?` 开头）
+    private: bool,
+    /// 已解析的参数
+    params: [u16; MAX_PARAMS],
+    /// 已解析参数个数
+    param_count: usize,
+    /// 正在解析的参数值
+    current: u16,
+    /// 正在解析的参数是否有数字
+    has_current: bool,
+    /// 当前模式状态
+    modes: TerminalModes,
+}
+
+impl CsiScanner {
+    /// 创建新的 CSI 扫描器
+    pub fn new() -> Self {
+        Self {
+            state: ScanState::Ground,
+            private: false,
+            params: [0; MAX_PARAMS],
+            param_count: 0,
+            current: 0,
+            has_current: false,
+            modes: TerminalModes::default(),
+        }
+    }
+
+    /// 获取当前模式状态
+    pub fn modes(&self) -> &TerminalModes {
+        &self.modes
+    }
+
+    /// 扫描一段终端输出
+    ///
+    /// 返回扫描后模式状态是否发生变化。
+    pub fn scan(&mut self, data: &[u8]) -> bool {
+        let before = self.modes.clone();
+
+        for &byte in data {
+            self.advance(byte);
+        }
+
+        self.modes != before
+    }
+
+    /// 处理单个字节
+    fn advance(&mut self, byte: u8) {
+        match self.state {
+            ScanState::Ground => {
+                if byte == ESC {
+                    self.state = ScanState::Escape;
+                }
+            }
+            ScanState::Escape => match byte {
+                b'[' => self.begin_csi(),
+                // RIS (ESC c): 完全重置终端
+                b'c' => {
+                    self.modes = TerminalModes::default();
+                    self.state = ScanState::Ground;
+                }
+                ESC => {}
+                _ => self.state = ScanState::Ground,
+            },
+            ScanState::Csi | ScanState::CsiIgnore => match byte {
+                ESC => self.state = ScanState::Escape,
+                CAN | SUB => self.state = ScanState::Ground,
+                b'0'..=b'9' if self.state == ScanState::Csi => {
+                    self.current = self
+                        .current
+                        .saturating_mul(10)
+                        .saturating_add(u16::from(byte - b'0'));
+                    self.has_current = true;
+                }
+                b';' if self.state == ScanState::Csi => self.push_param(),
+                b'?' if self.state == ScanState::Csi
+                    && self.param_count == 0
+                    && !self.has_current
+                    && !self.private =>
+                {
+                    self.private = true;
+                }
+                // 参数字节和中间字节：不是我们关心的序列
+                0x20..=0x3f => self.state = ScanState::CsiIgnore,
+                // 终止字节
+                0x40..=0x7e => {
+                    if self.state == ScanState::Csi {
+                        self.push_param();
+                        self.dispatch(byte);
+                    }
+                    self.state = ScanState::Ground;
+                }
+                // 其他控制字符在 CSI 中按规范直接执行，不影响序列
+                _ => {}
+            },
+        }
+    }
+
+    /// 开始新的 CSI 序列
+    fn begin_csi(&mut self) {
+        self.state = ScanState::Csi;
+        self.private = false;
+        self.param_count = 0;
+        self.current = 0;
+        self.has_current = false;
+    }
+
+    /// 结束当前参数
+    fn push_param(&mut self) {
+        if self.param_count < MAX_PARAMS {
+            self.params[self.param_count] = self.current;
+            self.param_count += 1;
+        }
+        self.current = 0;
+        self.has_current = false;
+    }
+
+    /// 执行完整的 CSI 序列
+    fn dispatch(&mut self, final_byte: u8) {
+        if !self.private {
+            return;
+        }
+
+        let enable = match final_byte {
+            b'h' => true,
+            b'l' => false,
+            _ => return,
+        };
+
+        for i in 0..self.param_count {
+            self.apply_private_mode(self.params[i], enable);
+        }
+    }
+
+    /// 应用 DEC 私有模式
+    fn apply_private_mode(&mut self, mode: u16, enable: bool) {
+        let tracking = match mode {
+            9 => Some(MouseTrackingMode::X10),
+            1000 => Some(MouseTrackingMode::Normal),
+            1002 => Some(MouseTrackingMode::ButtonEvent),
+            1003 => Some(MouseTrackingMode::AnyEvent),
+            _ => None,
+        };
+
+        if let Some(tracking) = tracking {
+            if enable {
+                self.modes.mouse_tracking = tracking;
+            } else if self.modes.mouse_tracking == tracking {
+                self.modes.mouse_tracking = MouseTrackingMode::None;
+            }
+            return;
+        }
+
+        match mode {
+            1006 => self.modes.sgr_mouse = enable,
+            47 | 1047 | 1049 => self.modes.alt_screen = enable,
+            _ => {}
+        }
+    }
+}
+
+impl Default for CsiScanner {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_initial_modes() {
+        let scanner = CsiScanner::new();
+        assert_eq!(scanner.modes(), &TerminalModes::default());
+        assert_eq!(scanner.modes().mouse_tracking, MouseTrackingMode::None);
+        assert!(!scanner.modes().alt_screen);
+    }
+
+    #[test]
+    fn test_mouse_tracking_set_and_reset() {
+        let mut scanner = CsiScanner::new();
+
+        assert!(scanner.scan(b"\x1b[?1000h"));
+        assert_eq!(scanner.modes().mouse_tracking, MouseTrackingMode::Normal);
+
+        assert!(scanner.scan(b"\x1b[?1000l"));
+        assert_eq!(scanner.modes().mouse_tracking, MouseTrackingMode::None);
+    }
+
+    #[test]
+    fn test_multiple_params() {
+        let mut scanner = CsiScanner::new();
+        assert!(scanner.scan(b"\x1b[?1002;1006h"));
+        assert_eq!(scanner.modes().mouse_tracking, MouseTrackingMode::ButtonEvent);
+        assert!(scanner.modes().sgr_mouse);
+    }
+
+    #[test]
+    fn test_reset_other_tracking_mode_keeps_current() {
+        let mut scanner = CsiScanner::new();
+        scanner.scan(b"\x1b[?1003h");
+        // 重置一个未启用的鼠标模式不应影响当前模式
+        assert!(!scanner.scan(b"\x1b[?1000l"));
+        assert_eq!(scanner.modes().mouse_tracking, MouseTrackingMode::AnyEvent);
+    }
+
+    #[test]
+    fn test_alt_screen() {
+        let mut scanner = CsiScanner::new();
+        assert!(scanner.scan(b"text\x1b[?1049hmore"));
+        assert!(scanner.modes().alt_screen);
+        assert!(scanner.scan(b"\x1b[?1049l"));
+        assert!(!scanner.modes().alt_screen);
+    }
+
+    #[test]
+    fn test_split_across_buffers() {
+        let mut scanner = CsiScanner::new();
+        assert!(!scanner.scan(b"abc\x1b"));
+        assert!(!scanner.scan(b"[?10"));
+        assert!(scanner.scan(b"49h"));
+        assert!(scanner.modes().alt_screen);
+    }
+
+    #[test]
+    fn test_unchanged_returns_false() {
+        let mut scanner = CsiScanner::new();
+        assert!(scanner.scan(b"\x1b[?1049h"));
+        assert!(!scanner.scan(b"\x1b[?1049h"));
+        assert!(!scanner.scan(b"plain text"));
+    }
+
+    #[test]
+    fn test_non_private_sequences_ignored() {
+        let mut scanner = CsiScanner::new();
+        // 非私有的 SM (ESC [ 4 h) 与颜色序列不影响模式
+        assert!(!scanner.scan(b"\x1b[4h\x1b[1;31mred\x1b[0m"));
+        assert_eq!(scanner.modes(), &TerminalModes::default());
+    }
+
+    #[test]
+    fn test_intermediate_bytes_ignored() {
+        let mut scanner = CsiScanner::new();
+        // DECRQM (ESC [ ? 1049 $ p) 只是查询，不应改变状态
+        assert!(!scanner.scan(b"\x1b[?1049$p"));
+        assert!(!scanner.modes().alt_screen);
+    }
+
+    #[test]
+    fn test_cancelled_sequence() {
+        let mut scanner = CsiScanner::new();
+        assert!(!scanner.scan(b"\x1b[?1049\x18h"));
+        assert!(!scanner.modes().alt_screen);
+    }
+
+    #[test]
+    fn test_full_reset() {
+        let mut scanner = CsiScanner::new();
+        scanner.scan(b"\x1b[?1000;1006;1049h");
+        assert!(scanner.scan(b"\x1bc"));
+        assert_eq!(scanner.modes(), &TerminalModes::default());
+    }
+}
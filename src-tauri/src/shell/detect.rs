@@ -1,21 +1,228 @@
 //! Shell 检测
 //!
-//! 检测系统默认 shell。
+//! 检测系统默认 shell，列出系统中可用的 shell。
 
 use std::env;
 
+use crate::rpc::types::{ClearMode, ShellInfo, ShellKind};
+use crate::utils::resolve_program;
+
+/// 系统登记的合法登录 shell 列表
+#[cfg(unix)]
+const ETC_SHELLS: &str = "/etc/shells";
+
 /// 检测系统默认 shell
+///
+/// 依次尝试环境变量和系统记录的 shell，跳过不存在的（如卸载后 `$SHELL` 仍指向原路径），
+/// 都不可用时退回系统自带的 shell。
 pub fn detect_default_shell() -> String {
+    #[cfg(unix)]
+    let env_shell = env::var("SHELL").ok();
+    #[cfg(windows)]
+    let env_shell = env::var("COMSPEC").ok();
+    default_shell_from(env_shell)
+}
+
+/// 以环境变量指定的 shell（Unix 为 `$SHELL`，Windows 为 `%COMSPEC%`）为首选检测默认 shell
+fn default_shell_from(env_shell: Option<String>) -> String {
     #[cfg(unix)]
     {
-        // Unix: 使用 SHELL 环境变量
-        env::var("SHELL").unwrap_or_else(|_| "/bin/sh".to_string())
+        // Unix: 优先使用 SHELL 环境变量，GUI 启动时可能未设置，再查询 passwd 中的登录 shell，
+        // 最后是 bash 和 sh
+        select_shell(
+            [
+                env_shell,
+                login_shell(),
+                Some("/bin/bash".to_string()),
+            ],
+            "/bin/sh",
+        )
     }
 
     #[cfg(windows)]
     {
-        // Windows: 使用 COMSPEC 环境变量
-        env::var("COMSPEC").unwrap_or_else(|_| "cmd.exe".to_string())
+        // Windows: 优先使用 COMSPEC 环境变量，其次是 PowerShell，COMSPEC 无效时最后退回 cmd
+        select_shell(
+            [
+                env_shell,
+                Some("pwsh.exe".to_string()),
+                Some("powershell.exe".to_string()),
+            ],
+            "cmd.exe",
+        )
+    }
+}
+
+/// 检测 PowerShell，优先使用 PowerShell 7（`pwsh`），其次是 Windows PowerShell
+///
+/// 都不在 PATH 中时返回 `None`。
+#[cfg(windows)]
+pub fn detect_powershell() -> Option<String> {
+    ["pwsh.exe", "powershell.exe"]
+        .into_iter()
+        .find(|shell| resolve_program(shell).is_some())
+        .map(str::to_string)
+}
+
+/// 启动 shell 时附加的参数
+///
+/// PowerShell 不显示启动版权信息，并在执行完启动参数后保持交互；其他 shell 不加参数。
+pub fn shell_args(shell_path: &str) -> &'static [&'static str] {
+    let name = get_shell_name(shell_path).to_ascii_lowercase();
+    match name.trim_end_matches(".exe") {
+        "pwsh" | "powershell" => &["-NoLogo", "-NoExit"],
+        _ => &[],
+    }
+}
+
+/// 选出第一个存在的 shell，都不存在时使用 `fallback`
+fn select_shell(candidates: impl IntoIterator<Item = Option<String>>, fallback: &str) -> String {
+    for shell in candidates.into_iter().flatten() {
+        if shell.is_empty() {
+            continue;
+        }
+        if resolve_program(&shell).is_some() {
+            return shell;
+        }
+        tracing::warn!("shell 不存在，尝试下一个: {}", shell);
+    }
+    tracing::warn!("没有可用的 shell，使用 {}", fallback);
+    fallback.to_string()
+}
+
+/// 查询当前用户在 passwd 数据库中的登录 shell
+#[cfg(unix)]
+fn login_shell() -> Option<String> {
+    use std::ffi::CStr;
+
+    // SAFETY: getuid 总是成功；passwd 是只含整数和指针的 C 结构体，全零是合法值
+    let uid = unsafe { libc::getuid() };
+    let mut passwd: libc::passwd = unsafe { std::mem::zeroed() };
+    let mut result: *mut libc::passwd = std::ptr::null_mut();
+    let mut buf: Vec<libc::c_char> = vec![0; 1024];
+
+    loop {
+        // SAFETY: 所有指针都指向有效的可写内存，buf 的长度与传入的一致
+        let rc = unsafe {
+            libc::getpwuid_r(uid, &mut passwd, buf.as_mut_ptr(), buf.len(), &mut result)
+        };
+        // 缓冲区不够时扩大后重试
+        if rc == libc::ERANGE && buf.len() < 64 * 1024 {
+            buf.resize(buf.len() * 2, 0);
+            continue;
+        }
+        break;
+    }
+
+    if result.is_null() || passwd.pw_shell.is_null() {
+        return None;
+    }
+    // SAFETY: 查询成功时 pw_shell 指向 buf 中以 NUL 结尾的字符串
+    let shell = unsafe { CStr::from_ptr(passwd.pw_shell) }.to_str().ok()?;
+    (!shell.is_empty()).then(|| shell.to_string())
+}
+
+/// 列出系统中可用的 shell
+///
+/// Unix 上读取 `/etc/shells`，跳过不存在的和指向同一文件的路径；Windows 上检查 cmd、
+/// Windows PowerShell、PowerShell 7、Git Bash 和 WSL。默认 shell 不在列表中时追加到末尾。
+pub fn list_shells() -> Vec<ShellInfo> {
+    #[cfg(unix)]
+    let mut shells = {
+        let content = std::fs::read_to_string(ETC_SHELLS).unwrap_or_default();
+        existing_shells(parse_etc_shells(&content))
+    };
+
+    #[cfg(windows)]
+    let mut shells = windows_shells();
+
+    let default_shell = detect_default_shell();
+    if let Some(path) = resolve_program(&default_shell) {
+        let path = path.to_string_lossy().into_owned();
+        if !shells.iter().any(|shell| shell.path == path) {
+            shells.extend(existing_shells(vec![path]));
+        }
+    }
+    shells
+}
+
+/// 解析 `/etc/shells` 的内容，返回去重后的绝对路径，忽略注释和空行
+#[cfg(unix)]
+fn parse_etc_shells(content: &str) -> Vec<String> {
+    let mut paths: Vec<String> = Vec::new();
+    for line in content.lines() {
+        let line = line.split('#').next().unwrap_or_default().trim();
+        if line.starts_with('/') && !paths.iter().any(|path| path == line) {
+            paths.push(line.to_string());
+        }
+    }
+    paths
+}
+
+/// 保留存在且可执行的 shell，解析符号链接后指向同一文件的只保留第一个
+fn existing_shells(paths: Vec<String>) -> Vec<ShellInfo> {
+    let mut seen = Vec::new();
+    let mut shells = Vec::new();
+    for path in paths {
+        if resolve_program(&path).is_none() {
+            continue;
+        }
+        let canonical = std::fs::canonicalize(&path).unwrap_or_else(|_| path.clone().into());
+        if seen.contains(&canonical) {
+            continue;
+        }
+        seen.push(canonical);
+        shells.push(ShellInfo {
+            name: get_shell_name(&path).to_string(),
+            kind: shell_kind(&path),
+            path,
+        });
+    }
+    shells
+}
+
+/// Windows 上的候选 shell
+#[cfg(windows)]
+fn windows_shells() -> Vec<ShellInfo> {
+    let git_bash = env::var("ProgramFiles")
+        .ok()
+        .map(|dir| format!("{}\\Git\\bin\\bash.exe", dir));
+    let candidates = [
+        (env::var("COMSPEC").ok().or(Some("cmd.exe".to_string())), "Command Prompt"),
+        (Some("powershell.exe".to_string()), "Windows PowerShell"),
+        (Some("pwsh.exe".to_string()), "PowerShell"),
+        (git_bash, "Git Bash"),
+        (Some("wsl.exe".to_string()), "WSL"),
+    ];
+
+    candidates
+        .into_iter()
+        .filter_map(|(program, name)| {
+            let path = resolve_program(&program?)?.to_string_lossy().into_owned();
+            Some(ShellInfo {
+                kind: shell_kind(&path),
+                name: name.to_string(),
+                path,
+            })
+        })
+        .collect()
+}
+
+/// 根据可执行文件名判断 shell 类型
+pub fn shell_kind(shell_path: &str) -> ShellKind {
+    let name = get_shell_name(shell_path).to_ascii_lowercase();
+    match name.trim_end_matches(".exe") {
+        "bash" => ShellKind::Bash,
+        "zsh" => ShellKind::Zsh,
+        "fish" => ShellKind::Fish,
+        "sh" | "dash" | "ash" => ShellKind::Sh,
+        "ksh" | "mksh" => ShellKind::Ksh,
+        "csh" | "tcsh" => ShellKind::Csh,
+        "nu" => ShellKind::Nushell,
+        "cmd" => ShellKind::Cmd,
+        "pwsh" | "powershell" => ShellKind::PowerShell,
+        "wsl" => ShellKind::Wsl,
+        _ => ShellKind::Other,
     }
 }
 
@@ -27,6 +234,18 @@ pub fn get_shell_name(shell_path: &str) -> &str {
         .unwrap_or("shell")
 }
 
+/// 根据 shell 选择默认清屏方式
+///
+/// 常见 shell 的行编辑器（readline、zle、fish、PSReadLine）都把 Ctrl+L 绑定为清屏，
+/// cmd.exe 没有对应的按键绑定，改用转义序列。
+pub fn default_clear_mode(shell_path: &str) -> ClearMode {
+    let name = get_shell_name(shell_path).to_ascii_lowercase();
+    match name.trim_end_matches(".exe") {
+        "cmd" => ClearMode::Scrollback,
+        _ => ClearMode::FormFeed,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -37,6 +256,44 @@ mod tests {
         assert!(!shell.is_empty());
     }
 
+    #[test]
+    fn test_select_shell_falls_back() {
+        assert_eq!(
+            select_shell([Some("/nonexistent/shell".to_string())], "fallback"),
+            "fallback"
+        );
+        assert_eq!(select_shell([None, Some(String::new())], "fallback"), "fallback");
+        #[cfg(unix)]
+        assert_eq!(
+            select_shell([Some("/nonexistent/shell".to_string()), Some("/bin/sh".to_string())], "fallback"),
+            "/bin/sh"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_default_shell_ignores_missing_shell_env() {
+        let shell = default_shell_from(Some("/nonexistent/bin/removed-shell".to_string()));
+        assert_ne!(shell, "/nonexistent/bin/removed-shell");
+        assert!(resolve_program(&shell).is_some(), "{} should exist", shell);
+    }
+
+    #[test]
+    fn test_detect_default_shell_exists() {
+        let shell = detect_default_shell();
+        assert!(!shell.is_empty());
+        assert!(resolve_program(&shell).is_some(), "{} should exist", shell);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_login_shell() {
+        // 容器中的用户可能没有 passwd 记录
+        if let Some(shell) = login_shell() {
+            assert!(!shell.is_empty());
+        }
+    }
+
     #[test]
     fn test_get_shell_name() {
         assert_eq!(get_shell_name("/bin/zsh"), "zsh");
@@ -44,4 +301,85 @@ mod tests {
         assert_eq!(get_shell_name("/usr/local/bin/fish"), "fish");
         assert_eq!(get_shell_name("cmd.exe"), "cmd.exe");
     }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_parse_etc_shells() {
+        let content = "# /etc/shells: valid login shells\n\
+                       /bin/sh\n\
+                       \n\
+                       /bin/bash   # comment\n\
+                       /usr/bin/bash\n\
+                       /bin/sh\n\
+                       relative/zsh\n\
+                       \t/usr/bin/fish\n";
+        assert_eq!(
+            parse_etc_shells(content),
+            vec!["/bin/sh", "/bin/bash", "/usr/bin/bash", "/usr/bin/fish"]
+        );
+        assert!(parse_etc_shells("").is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_existing_shells() {
+        let shells = existing_shells(vec![
+            "/bin/sh".to_string(),
+            "/nonexistent/shell".to_string(),
+            "/bin/sh".to_string(),
+        ]);
+        assert_eq!(shells.len(), 1);
+        assert_eq!(shells[0].path, "/bin/sh");
+        assert_eq!(shells[0].name, "sh");
+        assert_eq!(shells[0].kind, ShellKind::Sh);
+    }
+
+    #[test]
+    fn test_list_shells() {
+        let shells = list_shells();
+        assert!(!shells.is_empty());
+        for shell in &shells {
+            assert!(resolve_program(&shell.path).is_some(), "{} should exist", shell.path);
+        }
+    }
+
+    #[test]
+    fn test_shell_kind() {
+        assert_eq!(shell_kind("/bin/bash"), ShellKind::Bash);
+        assert_eq!(shell_kind("/usr/bin/dash"), ShellKind::Sh);
+        assert_eq!(shell_kind("/usr/local/bin/nu"), ShellKind::Nushell);
+        assert_eq!(shell_kind("PWSH.EXE"), ShellKind::PowerShell);
+        assert_eq!(shell_kind("cmd.exe"), ShellKind::Cmd);
+        assert_eq!(shell_kind("/usr/bin/xonsh"), ShellKind::Other);
+    }
+
+    #[test]
+    fn test_shell_args() {
+        assert_eq!(shell_args("pwsh"), ["-NoLogo", "-NoExit"]);
+        #[cfg(windows)]
+        assert_eq!(shell_args("C:\\Program Files\\PowerShell\\7\\pwsh.exe"), ["-NoLogo", "-NoExit"]);
+        assert_eq!(shell_args("/usr/bin/pwsh"), ["-NoLogo", "-NoExit"]);
+        assert_eq!(shell_args("PowerShell.EXE"), ["-NoLogo", "-NoExit"]);
+        assert!(shell_args("cmd.exe").is_empty());
+        assert!(shell_args("/bin/bash").is_empty());
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn test_detect_powershell() {
+        // 没有安装 PowerShell 的精简系统上跳过
+        if let Some(shell) = detect_powershell() {
+            assert!(resolve_program(&shell).is_some(), "{} should exist", shell);
+            assert_eq!(shell_args(&shell), ["-NoLogo", "-NoExit"]);
+        }
+    }
+
+    #[test]
+    fn test_default_clear_mode() {
+        assert_eq!(default_clear_mode("/bin/bash"), ClearMode::FormFeed);
+        assert_eq!(default_clear_mode("/usr/bin/fish"), ClearMode::FormFeed);
+        assert_eq!(default_clear_mode("pwsh.exe"), ClearMode::FormFeed);
+        assert_eq!(default_clear_mode("cmd.exe"), ClearMode::Scrollback);
+        assert_eq!(default_clear_mode("CMD.EXE"), ClearMode::Scrollback);
+    }
 }
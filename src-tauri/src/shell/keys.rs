@@ -0,0 +1,82 @@
+//! 按键转义序列
+//!
+//! `session.write_raw` 使用的按键名称表，名称与浏览器 `KeyboardEvent.key` 一致，
+//! 序列为 xterm 在普通光标模式下发送的值:
+//!
+//! | 按键 | 序列 | 按键 | 序列 |
+//! |------|------|------|------|
+//! | `ArrowUp` / `ArrowDown` | `ESC [ A` / `ESC [ B` | `F1`–`F4` | `ESC O P`–`ESC O S` |
+//! | `ArrowRight` / `ArrowLeft` | `ESC [ C` / `ESC [ D` | `F5` | `ESC [ 15 ~` |
+//! | `Home` / `End` | `ESC [ H` / `ESC [ F` | `F6`–`F8` | `ESC [ 17 ~`–`ESC [ 19 ~` |
+//! | `PageUp` / `PageDown` | `ESC [ 5 ~` / `ESC [ 6 ~` | `F9` / `F10` | `ESC [ 20 ~` / `ESC [ 21 ~` |
+//! | `Insert` / `Delete` | `ESC [ 2 ~` / `ESC [ 3 ~` | `F11` / `F12` | `ESC [ 23 ~` / `ESC [ 24 ~` |
+//! | `Enter` / `Tab` | `CR` / `HT` | `Backspace` / `Escape` | `DEL` / `ESC` |
+//!
+//! 应用光标模式（DECCKM）下方向键应为 `ESC O A` 等，需要时用十六进制直接发送。
+
+/// 按键名称和对应的转义序列
+const KEY_SEQUENCES: &[(&str, &[u8])] = &[
+    ("ArrowUp", b"\x1b[A"),
+    ("ArrowDown", b"\x1b[B"),
+    ("ArrowRight", b"\x1b[C"),
+    ("ArrowLeft", b"\x1b[D"),
+    ("Home", b"\x1b[H"),
+    ("End", b"\x1b[F"),
+    ("PageUp", b"\x1b[5~"),
+    ("PageDown", b"\x1b[6~"),
+    ("Insert", b"\x1b[2~"),
+    ("Delete", b"\x1b[3~"),
+    ("F1", b"\x1bOP"),
+    ("F2", b"\x1bOQ"),
+    ("F3", b"\x1bOR"),
+    ("F4", b"\x1bOS"),
+    ("F5", b"\x1b[15~"),
+    ("F6", b"\x1b[17~"),
+    ("F7", b"\x1b[18~"),
+    ("F8", b"\x1b[19~"),
+    ("F9", b"\x1b[20~"),
+    ("F10", b"\x1b[21~"),
+    ("F11", b"\x1b[23~"),
+    ("F12", b"\x1b[24~"),
+    ("Enter", b"\r"),
+    ("Tab", b"\t"),
+    ("Backspace", b"\x7f"),
+    ("Escape", b"\x1b"),
+];
+
+/// 查找按键对应的转义序列，名称区分大小写
+pub fn key_sequence(name: &str) -> Option<&'static [u8]> {
+    KEY_SEQUENCES
+        .iter()
+        .find(|(key, _)| *key == name)
+        .map(|(_, sequence)| *sequence)
+}
+
+/// 所有支持的按键名称
+pub fn key_names() -> impl Iterator<Item = &'static str> {
+    KEY_SEQUENCES.iter().map(|(key, _)| *key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_key_sequence() {
+        assert_eq!(key_sequence("ArrowUp"), Some(&b"\x1b[A"[..]));
+        assert_eq!(key_sequence("F1"), Some(&b"\x1bOP"[..]));
+        assert_eq!(key_sequence("F12"), Some(&b"\x1b[24~"[..]));
+        assert_eq!(key_sequence("PageDown"), Some(&b"\x1b[6~"[..]));
+        assert_eq!(key_sequence("arrowup"), None);
+        assert_eq!(key_sequence("F13"), None);
+    }
+
+    #[test]
+    fn test_key_names_unique() {
+        let names: Vec<&str> = key_names().collect();
+        for (i, name) in names.iter().enumerate() {
+            assert!(!names[i + 1..].contains(name), "duplicate key {}", name);
+        }
+        assert_eq!(names.len(), KEY_SEQUENCES.len());
+    }
+}
@@ -1,9 +1,13 @@
 //! Shell 集成模块
 //!
-//! 负责 Shell 检测和 OSC 序列处理。
+//! 负责 Shell 检测、OSC 序列处理、命令跟踪、CSI 模式跟踪和按键转义序列。
 
+pub mod csi;
 pub mod detect;
+pub mod keys;
 pub mod osc;
+pub mod prompt;
 
+pub use csi::CsiScanner;
 pub use detect::detect_default_shell;
-pub use osc::{ClipboardData, ClipboardSelection, OscHandler, OscParseResult, OscSequence};
+pub use osc::{ClipboardData, ClipboardSelection, OscHandler, OscParseResult, OscSequence, PromptMarker};
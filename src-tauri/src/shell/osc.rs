@@ -1,6 +1,6 @@
 //! OSC 序列处理
 //!
-//! 处理 OSC 7 (工作目录) 和 OSC 52 (剪贴板) 等特殊序列。
+//! 处理 OSC 0/2 (窗口标题)、OSC 7 (工作目录) 和 OSC 52 (剪贴板) 等特殊序列。
 //!
 //! ## OSC 序列格式
 //!
@@ -10,38 +10,228 @@
 //!
 //! ## 支持的序列
 //!
+//! - OSC 0 / OSC 2: 窗口标题 (`title`)
+//! - OSC 4 / OSC 10 / OSC 11: 调色板、前景色、背景色的设置和查询 (`index;spec`、`spec`)
 //! - OSC 7: 工作目录通知 (`file://hostname/path`)
 //! - OSC 52: 剪贴板操作 (`selection;base64_data`)
+//! - OSC 133: shell 集成的提示符标记 (`A`、`B`、`C`、`D;exit_code`)
+//!
+//! 前端可以通过 `server.list_osc_codes` 查询当前版本支持的代码。
+//!
+//! ## tmux 直通
+//!
+//! tmux 中的程序会把 OSC 序列包装在 DCS 直通中发送:
+//! `ESC P tmux; <内容> ESC \`，内容中的 ESC 被写成两个 ESC。
+//! 解包后的内容按普通 OSC 序列解析。
 
 use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
 
-/// ESC 字符
-const ESC: char = '\x1b';
 /// BEL 字符 (终止符)
 const BEL: char = '\x07';
 /// OSC 起始序列
 const OSC_START: &str = "\x1b]";
 /// ST 终止序列 (ESC \)
 const ST: &str = "\x1b\\";
+/// tmux DCS 直通起始序列
+const TMUX_PASSTHROUGH_START: &str = "\x1bPtmux;";
+
+/// 支持的 OSC `Ps` 代码和对应的功能
+///
+/// [`OscHandler::parse`] 按这张表分派，[`supported_codes`] 也从这里生成，
+/// 新增的代码只需加在这里。
+const OSC_CODES: &[(u16, OscFeature)] = &[
+    (0, OscFeature::Title),
+    (2, OscFeature::Title),
+    (4, OscFeature::Palette),
+    (7, OscFeature::WorkingDirectory),
+    (10, OscFeature::Foreground),
+    (11, OscFeature::Background),
+    (52, OscFeature::Clipboard),
+    (133, OscFeature::PromptMarker),
+];
+
+/// OSC 代码对应的功能
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OscFeature {
+    /// 窗口标题
+    Title,
+    /// 调色板颜色
+    Palette,
+    /// 工作目录
+    WorkingDirectory,
+    /// 默认前景色
+    Foreground,
+    /// 默认背景色
+    Background,
+    /// 剪贴板
+    Clipboard,
+    /// shell 集成的提示符标记
+    PromptMarker,
+}
+
+impl OscFeature {
+    /// 功能名称
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Title => "title",
+            Self::Palette => "palette",
+            Self::WorkingDirectory => "working_directory",
+            Self::Foreground => "foreground",
+            Self::Background => "background",
+            Self::Clipboard => "clipboard",
+            Self::PromptMarker => "prompt_marker",
+        }
+    }
+}
+
+/// 列出支持的 OSC 代码，按代码排序
+pub fn supported_codes() -> impl Iterator<Item = (u16, OscFeature)> {
+    OSC_CODES.iter().copied()
+}
+
+/// 查找 OSC 代码对应的功能
+fn feature_for(ps: &str) -> Option<OscFeature> {
+    let code: u16 = ps.parse().ok()?;
+    OSC_CODES
+        .iter()
+        .find(|(known, _)| *known == code)
+        .map(|(_, feature)| *feature)
+}
 
 /// OSC 序列类型
 #[derive(Debug, Clone, PartialEq)]
 pub enum OscSequence {
+    /// OSC 0 / OSC 2: 窗口标题
+    Title(String),
     /// OSC 7: 工作目录
-    WorkingDirectory(String),
+    WorkingDirectory {
+        /// `file://` URL 中的主机名，主机名为空或直接给出路径时为 `None`
+        host: Option<String>,
+        /// 解码后的路径
+        path: String,
+    },
     /// OSC 52: 剪贴板内容
     Clipboard(ClipboardData),
-    /// 未知或无效序列
+    /// OSC 4 / 10 / 11: 颜色查询 (`?`)
+    ColorQuery(ColorTarget),
+    /// OSC 4 / 10 / 11: 设置颜色
+    ColorSet(ColorTarget, RgbColor),
+    /// OSC 133: 提示符标记
+    PromptMarker(PromptMarker),
+    /// 可识别但内容无效的序列，原因见 [`OscParseOutcome::diagnostics`]
+    Invalid,
+    /// 无法识别的序列
     Unknown,
 }
 
+impl OscSequence {
+    /// OSC 7 工作目录的路径，其他序列返回 `None`
+    pub fn working_directory(&self) -> Option<&str> {
+        match self {
+            Self::WorkingDirectory { path, .. } => Some(path),
+            _ => None,
+        }
+    }
+}
+
+/// OSC 序列被丢弃的原因
+#[derive(Debug, Clone, PartialEq)]
+pub enum OscDiagnostic {
+    /// OSC 52 缺少选择类型或数据部分
+    MalformedClipboard,
+    /// OSC 52 剪贴板写入被拒绝
+    ClipboardRejected(ClipboardRejection),
+    /// OSC 7 中无法解析的工作目录
+    InvalidWorkingDirectory(String),
+    /// OSC 4 中无效的调色板索引
+    InvalidPaletteIndex(String),
+    /// OSC 4 / 10 / 11 中无法解析的颜色规格
+    InvalidColor(String),
+    /// OSC 133 中无法识别的标记
+    InvalidPromptMarker(String),
+}
+
+/// OSC 133 提示符标记
+///
+/// shell 集成脚本在提示符和命令的边界输出这些标记：提示符开始 (`A`)、
+/// 命令输入开始 (`B`)、命令开始执行 (`C`)、命令结束 (`D;exit_code`)。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PromptMarker {
+    /// `A`: 提示符开始
+    PromptStart,
+    /// `B`: 提示符结束，命令输入开始
+    CommandStart,
+    /// `C`: 命令开始执行，之后是命令输出
+    CommandExecuted,
+    /// `D[;exit_code]`: 命令结束，没有给出或无法解析退出码时为 `None`
+    CommandFinished(Option<i32>),
+}
+
+/// OSC 序列解析结果及诊断信息
+#[derive(Debug, Clone, PartialEq)]
+pub struct OscParseOutcome {
+    /// 解析出的序列
+    pub sequence: OscSequence,
+    /// 序列被丢弃的原因，解析成功时为空
+    pub diagnostics: Vec<OscDiagnostic>,
+}
+
+impl OscParseOutcome {
+    /// 解析成功（或无法识别）的结果
+    fn ok(sequence: OscSequence) -> Self {
+        Self {
+            sequence,
+            diagnostics: Vec::new(),
+        }
+    }
+
+    /// 可识别但内容无效的结果
+    fn invalid(diagnostic: OscDiagnostic) -> Self {
+        Self {
+            sequence: OscSequence::Invalid,
+            diagnostics: vec![diagnostic],
+        }
+    }
+}
+
 /// 剪贴板数据
 #[derive(Debug, Clone, PartialEq)]
 pub struct ClipboardData {
     /// 剪贴板选择类型 (c=clipboard, p=primary, q=secondary, s=select, 0-7=cut buffers)
     pub selection: ClipboardSelection,
-    /// 解码后的内容
-    pub content: String,
+    /// 解码后的原始字节，不一定是有效的 UTF-8
+    pub content: Vec<u8>,
+}
+
+impl ClipboardData {
+    /// 内容是有效的 UTF-8 时返回文本
+    pub fn text(&self) -> Option<&str> {
+        std::str::from_utf8(&self.content).ok()
+    }
+}
+
+/// 剪贴板写入被拒绝的原因
+#[derive(Debug, Clone, PartialEq)]
+pub enum ClipboardRejection {
+    /// Base64 数据超过大小限制
+    TooLarge {
+        /// 尝试写入的 Base64 数据长度（字节）
+        size: usize,
+        /// 大小限制（字节）
+        limit: usize,
+    },
+    /// Base64 解码失败
+    InvalidBase64,
+}
+
+impl ClipboardRejection {
+    /// 通知中使用的原因名称
+    pub fn reason(&self) -> &'static str {
+        match self {
+            Self::TooLarge { .. } => "too_large",
+            Self::InvalidBase64 => "invalid_base64",
+        }
+    }
 }
 
 /// 剪贴板选择类型
@@ -73,12 +263,109 @@ impl ClipboardSelection {
     }
 }
 
+/// 颜色序列的目标
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorTarget {
+    /// OSC 4: 调色板颜色
+    Palette(u8),
+    /// OSC 10: 默认前景色
+    Foreground,
+    /// OSC 11: 默认背景色
+    Background,
+}
+
+impl ColorTarget {
+    /// 通知中使用的目标名称
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Palette(_) => "palette",
+            Self::Foreground => "foreground",
+            Self::Background => "background",
+        }
+    }
+
+    /// 调色板索引
+    pub fn index(&self) -> Option<u8> {
+        match self {
+            Self::Palette(index) => Some(*index),
+            _ => None,
+        }
+    }
+}
+
+/// RGB 颜色
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RgbColor {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+impl RgbColor {
+    /// 解析 X11 颜色规格
+    ///
+    /// 支持 `rgb:R/G/B`（每个分量 1-4 位十六进制，按位数缩放）和
+    /// `#RGB`、`#RRGGBB`、`#RRRGGGBBB`、`#RRRRGGGGBBBB`（取每个分量的高 8 位）。
+    /// 不支持颜色名称。
+    pub fn parse(spec: &str) -> Option<Self> {
+        if let Some(rest) = spec.strip_prefix("rgb:") {
+            let mut parts = rest.split('/');
+            let r = parse_scaled_component(parts.next()?)?;
+            let g = parse_scaled_component(parts.next()?)?;
+            let b = parse_scaled_component(parts.next()?)?;
+            if parts.next().is_some() {
+                return None;
+            }
+            return Some(Self { r, g, b });
+        }
+
+        if let Some(hex) = spec.strip_prefix('#') {
+            if !hex.is_ascii() || hex.is_empty() || hex.len() % 3 != 0 || hex.len() > 12 {
+                return None;
+            }
+            let width = hex.len() / 3;
+            let component = |i: usize| -> Option<u8> {
+                let digits = &hex[i * width..(i + 1) * width];
+                let value = u16::from_str_radix(digits, 16).ok()?;
+                // 左对齐到 16 位后取高 8 位
+                Some((value << (16 - 4 * width as u32) >> 8) as u8)
+            };
+            return Some(Self {
+                r: component(0)?,
+                g: component(1)?,
+                b: component(2)?,
+            });
+        }
+
+        None
+    }
+
+    /// 格式化为 `#rrggbb`
+    pub fn to_hex(&self) -> String {
+        format!("#{:02x}{:02x}{:02x}", self.r, self.g, self.b)
+    }
+}
+
+/// 解析 `rgb:` 格式中的一个分量并缩放到 0-255
+fn parse_scaled_component(digits: &str) -> Option<u8> {
+    if digits.is_empty() || digits.len() > 4 {
+        return None;
+    }
+    let value = u32::from_str_radix(digits, 16).ok()?;
+    let max = (1u32 << (4 * digits.len())) - 1;
+    Some(((value * 255 + max / 2) / max) as u8)
+}
+
 /// OSC 解析结果
 #[derive(Debug, Clone)]
 pub struct OscParseResult {
     /// 解析出的 OSC 序列
     pub sequence: OscSequence,
+    /// 序列被丢弃的原因
+    pub diagnostics: Vec<OscDiagnostic>,
     /// 原始 OSC 序列在输入中的起始位置
+    ///
+    /// 同一个 tmux 直通包装中有多个序列时，第一个覆盖整个包装，其余为包装结束处的空区间。
     pub start: usize,
     /// 原始 OSC 序列在输入中的结束位置（不包含）
     pub end: usize,
@@ -90,6 +377,8 @@ pub struct OscParseResult {
 pub struct OscHandler {
     /// 剪贴板数据大小限制 (字节)
     max_clipboard_size: usize,
+    /// 是否去掉剪贴板内容中的控制字符
+    sanitize_clipboard: bool,
 }
 
 impl OscHandler {
@@ -97,6 +386,7 @@ impl OscHandler {
     pub fn new() -> Self {
         Self {
             max_clipboard_size: 1024 * 1024, // 1MB
+            sanitize_clipboard: false,
         }
     }
 
@@ -106,6 +396,12 @@ impl OscHandler {
         self
     }
 
+    /// 设置是否去掉剪贴板内容中的控制字符，见 [`sanitize_clipboard`]
+    pub fn with_clipboard_sanitization(mut self, enabled: bool) -> Self {
+        self.sanitize_clipboard = enabled;
+        self
+    }
+
     /// 获取剪贴板大小限制
     pub fn max_clipboard_size(&self) -> usize {
         self.max_clipboard_size
@@ -114,6 +410,8 @@ impl OscHandler {
     /// 解析 OSC 序列内容
     ///
     /// 输入应该是去掉了 `ESC ]` 前缀和 `BEL`/`ST` 后缀的内容。
+    /// 无法识别的 `Ps` 返回 [`OscSequence::Unknown`]；可识别但内容无效时返回
+    /// [`OscSequence::Invalid`]，并在诊断信息中说明原因。
     ///
     /// # 示例
     ///
@@ -122,32 +420,68 @@ impl OscHandler {
     ///
     /// let handler = OscHandler::new();
     /// let result = handler.parse("7;file://localhost/home/user");
+    /// assert!(result.diagnostics.is_empty());
     /// ```
-    pub fn parse(&self, data: &str) -> OscSequence {
-        // 空数据返回 Unknown
-        if data.is_empty() {
-            return OscSequence::Unknown;
-        }
-
-        // OSC 7: 工作目录
-        if let Some(rest) = data.strip_prefix("7;") {
-            if let Some(path) = self.parse_file_url(rest) {
-                return OscSequence::WorkingDirectory(path);
+    pub fn parse(&self, data: &str) -> OscParseOutcome {
+        // 空数据或没有参数部分返回 Unknown
+        let Some((ps, rest)) = data.split_once(';') else {
+            return OscParseOutcome::ok(OscSequence::Unknown);
+        };
+        let Some(feature) = feature_for(ps) else {
+            return OscParseOutcome::ok(OscSequence::Unknown);
+        };
+
+        match feature {
+            // OSC 0 / OSC 2: 窗口标题
+            OscFeature::Title => OscParseOutcome::ok(OscSequence::Title(rest.to_string())),
+            // OSC 7: 工作目录
+            OscFeature::WorkingDirectory => {
+                if let Some((host, path)) = self.parse_file_url(rest) {
+                    return OscParseOutcome::ok(OscSequence::WorkingDirectory { host, path });
+                }
+                // 尝试直接解析路径（某些终端可能不使用 file:// 前缀）
+                if rest.starts_with('/') {
+                    return OscParseOutcome::ok(OscSequence::WorkingDirectory {
+                        host: None,
+                        path: urlencoding_decode(rest),
+                    });
+                }
+                OscParseOutcome::invalid(OscDiagnostic::InvalidWorkingDirectory(rest.to_string()))
             }
-            // 尝试直接解析路径（某些终端可能不使用 file:// 前缀）
-            if rest.starts_with('/') {
-                return OscSequence::WorkingDirectory(urlencoding_decode(rest));
+            // OSC 52: 剪贴板
+            OscFeature::Clipboard => match self.parse_clipboard(rest) {
+                Some(Ok(clipboard_data)) => OscParseOutcome::ok(OscSequence::Clipboard(clipboard_data)),
+                Some(Err(rejection)) => {
+                    OscParseOutcome::invalid(OscDiagnostic::ClipboardRejected(rejection))
+                }
+                None => OscParseOutcome::invalid(OscDiagnostic::MalformedClipboard),
+            },
+            // OSC 4: 调色板颜色
+            OscFeature::Palette => {
+                let (index, spec) = rest.split_once(';').unwrap_or((rest, ""));
+                match index.parse::<u8>() {
+                    Ok(index) => parse_color(ColorTarget::Palette(index), spec),
+                    Err(_) => OscParseOutcome::invalid(OscDiagnostic::InvalidPaletteIndex(index.to_string())),
+                }
             }
-        }
-
-        // OSC 52: 剪贴板
-        if let Some(rest) = data.strip_prefix("52;") {
-            if let Some(clipboard_data) = self.parse_clipboard(rest) {
-                return OscSequence::Clipboard(clipboard_data);
+            // OSC 10 / OSC 11: 默认前景色、背景色
+            OscFeature::Foreground => parse_color(ColorTarget::Foreground, rest),
+            OscFeature::Background => parse_color(ColorTarget::Background, rest),
+            // OSC 133: 提示符标记，标记之后可能还有 `key=value` 形式的扩展参数
+            OscFeature::PromptMarker => {
+                let mut params = rest.split(';');
+                let marker = match params.next().unwrap_or_default() {
+                    "A" => PromptMarker::PromptStart,
+                    "B" => PromptMarker::CommandStart,
+                    "C" => PromptMarker::CommandExecuted,
+                    "D" => PromptMarker::CommandFinished(params.next().and_then(|code| code.parse().ok())),
+                    other => {
+                        return OscParseOutcome::invalid(OscDiagnostic::InvalidPromptMarker(other.to_string()))
+                    }
+                };
+                OscParseOutcome::ok(OscSequence::PromptMarker(marker))
             }
         }
-
-        OscSequence::Unknown
     }
 
     /// 从原始终端输出中提取所有 OSC 序列
@@ -160,6 +494,21 @@ impl OscHandler {
         while let Some(osc_start) = data[search_start..].find(OSC_START) {
             let absolute_start = search_start + osc_start;
 
+            // tmux 直通包装中的 ESC 被转义为两个 ESC，解包后再提取内部序列
+            if let Some(tmux_offset) = data[search_start..absolute_start].find(TMUX_PASSTHROUGH_START) {
+                let wrapper_start = search_start + tmux_offset;
+                if let Some((payload, wrapper_end)) = unwrap_tmux_passthrough(data, wrapper_start) {
+                    let mut inner = self.extract_sequences(&payload);
+                    for (i, result) in inner.iter_mut().enumerate() {
+                        result.start = if i == 0 { wrapper_start } else { wrapper_end };
+                        result.end = wrapper_end;
+                    }
+                    results.extend(inner);
+                    search_start = wrapper_end;
+                    continue;
+                }
+            }
+
             // 查找终止符 (BEL 或 ST)
             let content_start = absolute_start + OSC_START.len();
             if content_start >= data.len() {
@@ -195,10 +544,11 @@ impl OscHandler {
             let absolute_end = content_start + end_offset + terminator_len;
 
             // 解析 OSC 内容
-            let sequence = self.parse(osc_content);
+            let outcome = self.parse(osc_content);
 
             results.push(OscParseResult {
-                sequence,
+                sequence: outcome.sequence,
+                diagnostics: outcome.diagnostics,
                 start: absolute_start,
                 end: absolute_end,
             });
@@ -211,8 +561,8 @@ impl OscHandler {
 
     /// 从原始终端输出中移除所有 OSC 序列
     ///
-    /// 返回移除 OSC 序列后的数据和提取出的序列列表。
-    pub fn strip_sequences(&self, data: &str) -> (String, Vec<OscSequence>) {
+    /// 返回移除 OSC 序列后的数据和每个序列的解析结果。
+    pub fn strip_sequences(&self, data: &str) -> (String, Vec<OscParseOutcome>) {
         let results = self.extract_sequences(data);
 
         if results.is_empty() {
@@ -222,30 +572,35 @@ impl OscHandler {
         let mut stripped = String::with_capacity(data.len());
         let mut last_end = 0;
 
-        let sequences: Vec<OscSequence> = results
-            .iter()
+        let outcomes: Vec<OscParseOutcome> = results
+            .into_iter()
             .map(|r| {
                 // 添加 OSC 序列之前的内容
                 stripped.push_str(&data[last_end..r.start]);
                 last_end = r.end;
-                r.sequence.clone()
+                OscParseOutcome {
+                    sequence: r.sequence,
+                    diagnostics: r.diagnostics,
+                }
             })
             .collect();
 
         // 添加最后一个 OSC 序列之后的内容
         stripped.push_str(&data[last_end..]);
 
-        (stripped, sequences)
+        (stripped, outcomes)
     }
 
-    /// 解析 file:// URL
-    fn parse_file_url(&self, url: &str) -> Option<String> {
+    /// 解析 file:// URL，返回主机名（为空时为 `None`）和路径
+    ///
+    /// 主机名原样返回，`localhost` 是否视为本机由调用方决定。
+    fn parse_file_url(&self, url: &str) -> Option<(Option<String>, String)> {
         if let Some(rest) = url.strip_prefix("file://") {
-            // 跳过主机名部分（可能为空或 localhost）
             if let Some(path_start) = rest.find('/') {
-                let path = &rest[path_start..];
+                let host = &rest[..path_start];
+                let host = (!host.is_empty()).then(|| urlencoding_decode(host));
                 // URL 解码
-                return Some(urlencoding_decode(path));
+                return Some((host, urlencoding_decode(&rest[path_start..])));
             }
             // 如果没有找到路径分隔符，可能是 Windows 路径 (file:///C:/...)
             // 或者主机名后直接是空的
@@ -257,7 +612,9 @@ impl OscHandler {
     }
 
     /// 解析剪贴板数据
-    fn parse_clipboard(&self, data: &str) -> Option<ClipboardData> {
+    ///
+    /// 格式不正确时返回 `None`；数据过大或无法解码时返回拒绝原因。
+    fn parse_clipboard(&self, data: &str) -> Option<Result<ClipboardData, ClipboardRejection>> {
         // 格式: selection;base64_data
         // selection 可以是 c (clipboard), p (primary), 等
         let parts: Vec<&str> = data.splitn(2, ';').collect();
@@ -282,34 +639,110 @@ impl OscHandler {
                 base64_data.len(),
                 self.max_clipboard_size
             );
-            return None;
+            return Some(Err(ClipboardRejection::TooLarge {
+                size: base64_data.len(),
+                limit: self.max_clipboard_size,
+            }));
         }
 
         // 空数据是有效的（用于查询剪贴板）
         if base64_data.is_empty() {
-            return Some(ClipboardData {
+            return Some(Ok(ClipboardData {
                 selection,
-                content: String::new(),
-            });
+                content: Vec::new(),
+            }));
         }
 
-        // Base64 解码
+        // Base64 解码，剪贴板可以保存二进制数据，不要求 UTF-8
         match BASE64.decode(base64_data) {
-            Ok(bytes) => {
-                match String::from_utf8(bytes) {
-                    Ok(content) => Some(ClipboardData { selection, content }),
-                    Err(_) => {
-                        tracing::warn!("剪贴板数据不是有效的 UTF-8");
-                        None
+            Ok(content) if self.sanitize_clipboard => Some(Ok(ClipboardData {
+                selection,
+                content: sanitize_clipboard(&content),
+            })),
+            Ok(content) => Some(Ok(ClipboardData { selection, content })),
+            Err(e) => {
+                tracing::warn!("Base64 解码失败: {}", e);
+                Some(Err(ClipboardRejection::InvalidBase64))
+            }
+        }
+    }
+}
+
+/// 去掉剪贴板内容中的控制字符，防止粘贴时执行其中的转义序列或控制键
+///
+/// 保留制表符和换行，`\r\n` 和单独的 `\r` 统一为 `\n`。UTF-8 文本按字符处理，
+/// 同时去掉 C1 控制字符；非 UTF-8 内容按字节处理，只去掉 C0 控制字符和 DEL。
+pub fn sanitize_clipboard(content: &[u8]) -> Vec<u8> {
+    fn keep(c: char) -> bool {
+        !c.is_control() || c == '\t' || c == '\n'
+    }
+
+    match std::str::from_utf8(content) {
+        Ok(text) => text
+            .replace("\r\n", "\n")
+            .replace('\r', "\n")
+            .chars()
+            .filter(|&c| keep(c))
+            .collect::<String>()
+            .into_bytes(),
+        Err(_) => {
+            let mut sanitized = Vec::with_capacity(content.len());
+            let mut bytes = content.iter().copied().peekable();
+            while let Some(b) = bytes.next() {
+                match b {
+                    b'\r' => {
+                        bytes.next_if_eq(&b'\n');
+                        sanitized.push(b'\n');
                     }
+                    b'\t' | b'\n' => sanitized.push(b),
+                    0x00..=0x1f | 0x7f => {}
+                    _ => sanitized.push(b),
                 }
             }
-            Err(e) => {
-                tracing::warn!("Base64 解码失败: {}", e);
-                None
+            sanitized
+        }
+    }
+}
+
+/// 解包从 `start` 开始的 tmux 直通序列
+///
+/// 返回去掉转义后的内容和包装在输入中的结束位置（不包含），没有终止符时返回 `None`。
+fn unwrap_tmux_passthrough(data: &str, start: usize) -> Option<(String, usize)> {
+    let content_start = start + TMUX_PASSTHROUGH_START.len();
+    let mut payload = String::new();
+    let mut chars = data[content_start..].char_indices();
+
+    while let Some((offset, c)) = chars.next() {
+        if c != '\x1b' {
+            payload.push(c);
+            continue;
+        }
+        match chars.next()? {
+            (_, '\x1b') => payload.push('\x1b'),
+            (_, '\\') => return Some((payload, content_start + offset + ST.len())),
+            // 未转义的 ESC，按原样保留
+            (_, other) => {
+                payload.push('\x1b');
+                payload.push(other);
             }
         }
     }
+
+    None
+}
+
+/// 解析颜色规格，`?` 表示查询
+///
+/// 一个序列中可以包含多组颜色（如 `4;1;red;2;blue`），只处理第一组。
+fn parse_color(target: ColorTarget, spec: &str) -> OscParseOutcome {
+    let spec = spec.split(';').next().unwrap_or_default();
+    if spec == "?" {
+        return OscParseOutcome::ok(OscSequence::ColorQuery(target));
+    }
+    match RgbColor::parse(spec) {
+        Some(color) => OscParseOutcome::ok(OscSequence::ColorSet(target, color)),
+        None => OscParseOutcome::invalid(OscDiagnostic::InvalidColor(spec.to_string())),
+    }
 }
 
 impl Default for OscHandler {
@@ -363,45 +796,131 @@ pub fn urlencoding_decode(s: &str) -> String {
 mod tests {
     use super::*;
 
+    fn working_directory(host: Option<&str>, path: &str) -> OscSequence {
+        OscSequence::WorkingDirectory {
+            host: host.map(str::to_string),
+            path: path.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_parse_osc4_palette_set() {
+        let handler = OscHandler::new();
+        assert_eq!(
+            handler.parse("4;1;rgb:ff/80/00").sequence,
+            OscSequence::ColorSet(ColorTarget::Palette(1), RgbColor { r: 255, g: 128, b: 0 })
+        );
+        // 四位分量按位数缩放
+        assert_eq!(
+            handler.parse("4;255;rgb:ffff/8080/0000;2;?").sequence,
+            OscSequence::ColorSet(ColorTarget::Palette(255), RgbColor { r: 255, g: 128, b: 0 })
+        );
+        assert_eq!(
+            handler.parse("4;3;#1a2b3c").sequence,
+            OscSequence::ColorSet(ColorTarget::Palette(3), RgbColor { r: 0x1a, g: 0x2b, b: 0x3c })
+        );
+        assert_eq!(handler.parse("4;256;#000000").sequence, OscSequence::Invalid);
+        assert_eq!(
+            handler.parse("4;256;#000000").diagnostics,
+            vec![OscDiagnostic::InvalidPaletteIndex("256".to_string())]
+        );
+        assert_eq!(
+            handler.parse("4;1;red").diagnostics,
+            vec![OscDiagnostic::InvalidColor("red".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_parse_osc11_background_query() {
+        let handler = OscHandler::new();
+        assert_eq!(
+            handler.parse("11;?").sequence,
+            OscSequence::ColorQuery(ColorTarget::Background)
+        );
+        assert_eq!(
+            handler.parse("10;?").sequence,
+            OscSequence::ColorQuery(ColorTarget::Foreground)
+        );
+        assert_eq!(
+            handler.parse("4;7;?").sequence,
+            OscSequence::ColorQuery(ColorTarget::Palette(7))
+        );
+    }
+
+    #[test]
+    fn test_strip_color_sequences() {
+        let handler = OscHandler::new();
+        let (stripped, sequences) = handler.strip_sequences("a\x1b]11;?\x1b\\b\x1b]10;#fff\x07c");
+        assert_eq!(stripped, "abc");
+        assert_eq!(
+            sequences.into_iter().map(|o| o.sequence).collect::<Vec<_>>(),
+            vec![
+                OscSequence::ColorQuery(ColorTarget::Background),
+                OscSequence::ColorSet(ColorTarget::Foreground, RgbColor { r: 0xf0, g: 0xf0, b: 0xf0 }),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_rgb_color_parse() {
+        assert_eq!(RgbColor::parse("rgb:f/8/0"), Some(RgbColor { r: 255, g: 136, b: 0 }));
+        assert_eq!(RgbColor::parse("#abc"), Some(RgbColor { r: 0xa0, g: 0xb0, b: 0xc0 }));
+        assert_eq!(
+            RgbColor::parse("#123456789abc"),
+            Some(RgbColor { r: 0x12, g: 0x56, b: 0x9a })
+        );
+        assert_eq!(RgbColor::parse("rgb:ff/ff"), None);
+        assert_eq!(RgbColor::parse("rgb:ff/ff/ff/ff"), None);
+        assert_eq!(RgbColor::parse("rgb:fffff/0/0"), None);
+        assert_eq!(RgbColor::parse("#12345"), None);
+        assert_eq!(RgbColor::parse("#中文字"), None);
+        assert_eq!(RgbColor { r: 255, g: 128, b: 0 }.to_hex(), "#ff8000");
+    }
+
     #[test]
     fn test_parse_osc7_working_directory() {
         let handler = OscHandler::new();
-        let result = handler.parse("7;file://localhost/home/user/projects");
+        let result = handler.parse("7;file://localhost/home/user/projects").sequence;
         assert_eq!(
             result,
-            OscSequence::WorkingDirectory("/home/user/projects".to_string())
+            working_directory(Some("localhost"), "/home/user/projects")
         );
+        assert_eq!(result.working_directory(), Some("/home/user/projects"));
     }
 
     #[test]
     fn test_parse_osc7_empty_hostname() {
         let handler = OscHandler::new();
         // 某些终端使用空主机名
-        let result = handler.parse("7;file:///home/user/projects");
-        assert_eq!(
-            result,
-            OscSequence::WorkingDirectory("/home/user/projects".to_string())
-        );
+        let result = handler.parse("7;file:///home/user/projects").sequence;
+        assert_eq!(result, working_directory(None, "/home/user/projects"));
     }
 
     #[test]
     fn test_parse_osc7_direct_path() {
         let handler = OscHandler::new();
         // 某些终端直接发送路径
-        let result = handler.parse("7;/home/user/projects");
-        assert_eq!(
-            result,
-            OscSequence::WorkingDirectory("/home/user/projects".to_string())
-        );
+        let result = handler.parse("7;/home/user/projects").sequence;
+        assert_eq!(result, working_directory(None, "/home/user/projects"));
+    }
+
+    #[test]
+    fn test_parse_osc7_named_host() {
+        let handler = OscHandler::new();
+        // 通过 SSH 运行的 shell 报告远程主机名
+        let result = handler.parse("7;file://build-server.local/srv/app").sequence;
+        assert_eq!(result, working_directory(Some("build-server.local"), "/srv/app"));
+        assert_eq!(result.working_directory(), Some("/srv/app"));
+        assert_eq!(OscSequence::Title("t".to_string()).working_directory(), None);
     }
 
     #[test]
     fn test_parse_osc7_url_encoded() {
         let handler = OscHandler::new();
-        let result = handler.parse("7;file://localhost/home/user/my%20project");
+        let result = handler.parse("7;file://localhost/home/user/my%20project").sequence;
         assert_eq!(
             result,
-            OscSequence::WorkingDirectory("/home/user/my project".to_string())
+            working_directory(Some("localhost"), "/home/user/my project")
         );
     }
 
@@ -409,12 +928,12 @@ mod tests {
     fn test_parse_osc52_clipboard() {
         let handler = OscHandler::new();
         // "Hello" in base64 is "SGVsbG8="
-        let result = handler.parse("52;c;SGVsbG8=");
+        let result = handler.parse("52;c;SGVsbG8=").sequence;
         assert_eq!(
             result,
             OscSequence::Clipboard(ClipboardData {
                 selection: ClipboardSelection::Clipboard,
-                content: "Hello".to_string(),
+                content: b"Hello".to_vec(),
             })
         );
     }
@@ -422,12 +941,12 @@ mod tests {
     #[test]
     fn test_parse_osc52_primary() {
         let handler = OscHandler::new();
-        let result = handler.parse("52;p;SGVsbG8=");
+        let result = handler.parse("52;p;SGVsbG8=").sequence;
         assert_eq!(
             result,
             OscSequence::Clipboard(ClipboardData {
                 selection: ClipboardSelection::Primary,
-                content: "Hello".to_string(),
+                content: b"Hello".to_vec(),
             })
         );
     }
@@ -436,27 +955,39 @@ mod tests {
     fn test_parse_osc52_empty_content() {
         let handler = OscHandler::new();
         // 空内容用于查询剪贴板
-        let result = handler.parse("52;c;");
+        let result = handler.parse("52;c;").sequence;
         assert_eq!(
             result,
             OscSequence::Clipboard(ClipboardData {
                 selection: ClipboardSelection::Clipboard,
-                content: String::new(),
+                content: Vec::new(),
             })
         );
     }
 
+    #[test]
+    fn test_parse_osc_title() {
+        let handler = OscHandler::new();
+        assert_eq!(
+            handler.parse("0;user@host: ~").sequence,
+            OscSequence::Title("user@host: ~".to_string())
+        );
+        assert_eq!(handler.parse("2;vim").sequence, OscSequence::Title("vim".to_string()));
+        // OSC 1 只设置图标名称，不是标题
+        assert_eq!(handler.parse("1;icon").sequence, OscSequence::Unknown);
+    }
+
     #[test]
     fn test_parse_invalid_osc() {
         let handler = OscHandler::new();
-        let result = handler.parse("invalid");
+        let result = handler.parse("invalid").sequence;
         assert_eq!(result, OscSequence::Unknown);
     }
 
     #[test]
     fn test_parse_empty_data() {
         let handler = OscHandler::new();
-        let result = handler.parse("");
+        let result = handler.parse("").sequence;
         assert_eq!(result, OscSequence::Unknown);
     }
 
@@ -466,14 +997,134 @@ mod tests {
         // 超过限制的数据
         let large_data = "c;".to_string() + &"A".repeat(100);
         let result = handler.parse(&format!("52;{}", large_data));
-        assert_eq!(result, OscSequence::Unknown);
+        assert_eq!(result.sequence, OscSequence::Invalid);
+        assert_eq!(
+            result.diagnostics,
+            vec![OscDiagnostic::ClipboardRejected(ClipboardRejection::TooLarge {
+                size: 100,
+                limit: 10
+            })]
+        );
     }
 
     #[test]
     fn test_clipboard_invalid_base64() {
         let handler = OscHandler::new();
         let result = handler.parse("52;c;not-valid-base64!!!");
-        assert_eq!(result, OscSequence::Unknown);
+        assert_eq!(result.sequence, OscSequence::Invalid);
+        assert_eq!(
+            result.diagnostics,
+            vec![OscDiagnostic::ClipboardRejected(ClipboardRejection::InvalidBase64)]
+        );
+    }
+
+    #[test]
+    fn test_clipboard_sanitization() {
+        let payload = "echo hi\x1b[201~\r\nrm -rf ~\x07\n\tok\u{9b}";
+        let sequence = format!("52;c;{}", BASE64.encode(payload));
+
+        // 默认保留原始内容
+        match OscHandler::new().parse(&sequence).sequence {
+            OscSequence::Clipboard(data) => assert_eq!(data.content, payload.as_bytes()),
+            other => panic!("Expected Clipboard, got {:?}", other),
+        }
+
+        match OscHandler::new().with_clipboard_sanitization(true).parse(&sequence).sequence {
+            OscSequence::Clipboard(data) => {
+                assert_eq!(data.text(), Some("echo hi[201~\nrm -rf ~\n\tok"));
+            }
+            other => panic!("Expected Clipboard, got {:?}", other),
+        }
+
+        // 非 UTF-8 内容按字节处理
+        assert_eq!(
+            sanitize_clipboard(&[0xff, 0x1b, b'a', b'\r', 0x00, 0xfe]),
+            vec![0xff, b'a', b'\n', 0xfe]
+        );
+    }
+
+    #[test]
+    fn test_clipboard_binary_payload() {
+        let handler = OscHandler::new();
+        let bytes = [0x89, b'P', b'N', b'G', 0xff, 0xfe, 0x00];
+        let result = handler.parse(&format!("52;c;{}", BASE64.encode(bytes)));
+        assert!(result.diagnostics.is_empty());
+        match result.sequence {
+            OscSequence::Clipboard(data) => {
+                assert_eq!(data.content, bytes);
+                assert_eq!(data.text(), None);
+            }
+            other => panic!("Expected Clipboard, got {:?}", other),
+        }
+
+        let text = ClipboardData {
+            selection: ClipboardSelection::Clipboard,
+            content: "你好".as_bytes().to_vec(),
+        };
+        assert_eq!(text.text(), Some("你好"));
+    }
+
+    #[test]
+    fn test_parse_invalid_vs_unknown() {
+        let handler = OscHandler::new();
+
+        // 已识别的 Ps 但内容无效
+        let result = handler.parse("52;c");
+        assert_eq!(result.sequence, OscSequence::Invalid);
+        assert_eq!(result.diagnostics, vec![OscDiagnostic::MalformedClipboard]);
+
+        let result = handler.parse("7;notapath");
+        assert_eq!(result.sequence, OscSequence::Invalid);
+        assert_eq!(
+            result.diagnostics,
+            vec![OscDiagnostic::InvalidWorkingDirectory("notapath".to_string())]
+        );
+
+        // 未识别的 Ps 没有诊断
+        let result = handler.parse("1;icon");
+        assert_eq!(result.sequence, OscSequence::Unknown);
+        assert!(result.diagnostics.is_empty());
+    }
+
+    #[test]
+    fn test_supported_codes() {
+        let codes: Vec<u16> = supported_codes().map(|(code, _)| code).collect();
+        assert_eq!(codes, vec![0, 2, 4, 7, 10, 11, 52, 133]);
+
+        // 表中的每个代码都能被解析，不会落入 Unknown
+        let handler = OscHandler::new();
+        for (code, feature) in supported_codes() {
+            let result = handler.parse(&format!("{};?", code));
+            assert_ne!(result.sequence, OscSequence::Unknown, "code {}", code);
+            assert!(!feature.name().is_empty());
+        }
+    }
+
+    #[test]
+    fn test_parse_osc133_prompt_markers() {
+        let handler = OscHandler::new();
+        let marker = |data: &str| handler.parse(data).sequence;
+
+        assert_eq!(marker("133;A"), OscSequence::PromptMarker(PromptMarker::PromptStart));
+        assert_eq!(marker("133;B"), OscSequence::PromptMarker(PromptMarker::CommandStart));
+        assert_eq!(marker("133;C"), OscSequence::PromptMarker(PromptMarker::CommandExecuted));
+        assert_eq!(
+            marker("133;D;1"),
+            OscSequence::PromptMarker(PromptMarker::CommandFinished(Some(1)))
+        );
+        assert_eq!(
+            marker("133;D"),
+            OscSequence::PromptMarker(PromptMarker::CommandFinished(None))
+        );
+        // 扩展参数被忽略
+        assert_eq!(
+            marker("133;A;aid=42"),
+            OscSequence::PromptMarker(PromptMarker::PromptStart)
+        );
+
+        let result = handler.parse("133;Z");
+        assert_eq!(result.sequence, OscSequence::Invalid);
+        assert_eq!(result.diagnostics, vec![OscDiagnostic::InvalidPromptMarker("Z".to_string())]);
     }
 
     #[test]
@@ -498,7 +1149,7 @@ mod tests {
         assert_eq!(results.len(), 1);
         assert_eq!(
             results[0].sequence,
-            OscSequence::WorkingDirectory("/home/user".to_string())
+            working_directory(Some("localhost"), "/home/user")
         );
         assert_eq!(results[0].start, 11);
     }
@@ -512,13 +1163,13 @@ mod tests {
         assert_eq!(results.len(), 2);
         assert_eq!(
             results[0].sequence,
-            OscSequence::WorkingDirectory("/home".to_string())
+            working_directory(Some("localhost"), "/home")
         );
         assert_eq!(
             results[1].sequence,
             OscSequence::Clipboard(ClipboardData {
                 selection: ClipboardSelection::Clipboard,
-                content: "Hello".to_string(),
+                content: b"Hello".to_vec(),
             })
         );
     }
@@ -532,10 +1183,59 @@ mod tests {
         assert_eq!(results.len(), 1);
         assert_eq!(
             results[0].sequence,
-            OscSequence::WorkingDirectory("/home/user".to_string())
+            working_directory(Some("localhost"), "/home/user")
         );
     }
 
+    #[test]
+    fn test_extract_tmux_passthrough_clipboard() {
+        let handler = OscHandler::new();
+        let data = "a\x1bPtmux;\x1b\x1b]52;c;SGVsbG8=\x07\x1b\\b";
+        let results = handler.extract_sequences(data);
+
+        assert_eq!(results.len(), 1);
+        assert_eq!(
+            results[0].sequence,
+            OscSequence::Clipboard(ClipboardData {
+                selection: ClipboardSelection::Clipboard,
+                content: b"Hello".to_vec(),
+            })
+        );
+        assert_eq!(results[0].start, 1);
+        assert_eq!(results[0].end, data.len() - 1);
+
+        let (stripped, _) = handler.strip_sequences(data);
+        assert_eq!(stripped, "ab");
+    }
+
+    #[test]
+    fn test_extract_tmux_passthrough_multiple_st() {
+        let handler = OscHandler::new();
+        // 内部序列使用 ST 终止，ST 中的 ESC 同样被转义
+        let data = "\x1bPtmux;\x1b\x1b]7;file://localhost/home\x1b\x1b\\\x1b\x1b]2;title\x1b\x1b\\\x1b\\\x1b]0;next\x07end";
+        let (stripped, sequences) = handler.strip_sequences(data);
+
+        assert_eq!(stripped, "end");
+        assert_eq!(
+            sequences.into_iter().map(|o| o.sequence).collect::<Vec<_>>(),
+            vec![
+                working_directory(Some("localhost"), "/home"),
+                OscSequence::Title("title".to_string()),
+                OscSequence::Title("next".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_extract_tmux_passthrough_unterminated() {
+        let handler = OscHandler::new();
+        // 包装没有结束，内部 OSC 按普通序列处理
+        let data = "\x1bPtmux;\x1b\x1b]2;title\x07";
+        let results = handler.extract_sequences(data);
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].sequence, OscSequence::Title("title".to_string()));
+    }
+
     #[test]
     fn test_extract_sequences_no_terminator() {
         let handler = OscHandler::new();
@@ -555,8 +1255,8 @@ mod tests {
         assert_eq!(stripped, "beforeafter");
         assert_eq!(sequences.len(), 1);
         assert_eq!(
-            sequences[0],
-            OscSequence::WorkingDirectory("/home".to_string())
+            sequences[0].sequence,
+            working_directory(Some("localhost"), "/home")
         );
     }
 
@@ -674,9 +1374,9 @@ mod proptests {
         let osc_path = valid_path_strategy();
 
         (
-            normal_text.clone(),
+            normal_text,
             osc_path,
-            normal_text.clone(),
+            normal_text,
             normal_text,
         )
             .prop_map(|(before, path, middle, after)| {
@@ -694,10 +1394,10 @@ mod proptests {
         #[test]
         fn prop_osc7_parses_valid_paths((osc_content, expected_path) in valid_osc7_strategy()) {
             let handler = OscHandler::new();
-            let result = handler.parse(&osc_content);
+            let result = handler.parse(&osc_content).sequence;
 
             match result {
-                OscSequence::WorkingDirectory(path) => {
+                OscSequence::WorkingDirectory { path, .. } => {
                     prop_assert_eq!(
                         path, expected_path,
                         "OSC 7 should parse to the expected path"
@@ -718,12 +1418,12 @@ mod proptests {
         #[test]
         fn prop_osc52_parses_valid_clipboard((osc_content, expected_content, selection_char) in valid_osc52_strategy()) {
             let handler = OscHandler::new();
-            let result = handler.parse(&osc_content);
+            let result = handler.parse(&osc_content).sequence;
 
             match result {
                 OscSequence::Clipboard(data) => {
                     prop_assert_eq!(
-                        data.content, expected_content,
+                        data.content, expected_content.into_bytes(),
                         "OSC 52 should decode to the expected content"
                     );
                     // Verify selection type matches
@@ -752,7 +1452,7 @@ mod proptests {
 
             // This should never panic, regardless of input
             let result = std::panic::catch_unwind(|| {
-                handler.parse(&input)
+                handler.parse(&input).sequence
             });
 
             prop_assert!(
@@ -767,7 +1467,7 @@ mod proptests {
         #[test]
         fn prop_invalid_osc_returns_unknown(input in "[^0-9;][a-zA-Z0-9]{0,50}") {
             let handler = OscHandler::new();
-            let result = handler.parse(&input);
+            let result = handler.parse(&input).sequence;
 
             // Invalid sequences should return Unknown
             prop_assert_eq!(
@@ -808,7 +1508,7 @@ mod proptests {
 
             for (result, expected_path) in results.iter().zip(expected_paths.iter()) {
                 match &result.sequence {
-                    OscSequence::WorkingDirectory(path) => {
+                    OscSequence::WorkingDirectory { path, .. } => {
                         prop_assert_eq!(
                             path, expected_path,
                             "Extracted path should match expected"
@@ -859,13 +1559,18 @@ mod proptests {
             let encoded = BASE64.encode(content.as_bytes());
             let osc_content = format!("52;c;{}", encoded);
 
-            let result = handler.parse(&osc_content);
+            let outcome = handler.parse(&osc_content);
+            let result = outcome.sequence;
 
             if encoded.len() > limit {
                 // Should be rejected
+                prop_assert_eq!(result, OscSequence::Invalid);
                 prop_assert_eq!(
-                    result,
-                    OscSequence::Unknown,
+                    outcome.diagnostics,
+                    vec![OscDiagnostic::ClipboardRejected(ClipboardRejection::TooLarge {
+                        size: encoded.len(),
+                        limit,
+                    })],
                     "Content exceeding size limit should be rejected"
                 );
             } else {
@@ -873,7 +1578,7 @@ mod proptests {
                 match result {
                     OscSequence::Clipboard(data) => {
                         prop_assert_eq!(
-                            data.content, content,
+                            data.content, content.into_bytes(),
                             "Content within limit should be accepted"
                         );
                     }
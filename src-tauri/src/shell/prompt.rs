@@ -0,0 +1,194 @@
+//! 命令跟踪
+//!
+//! 根据 OSC 133 提示符标记跟踪交互式 shell 中每条命令的执行，命令结束 (`D`) 时得到
+//! 退出码和命令文本，用于在前端显示每条命令的成功或失败。
+//!
+//! 命令文本取自 `B`（输入开始）和 `C`（开始执行）之间回显的输入，去掉转义序列和
+//! 控制字符。没有收到 `C` 的 `D`（如在提示符处直接按回车）不算一条命令。
+
+use super::osc::PromptMarker;
+
+/// ESC 字节
+const ESC: char = '\x1b';
+/// 命令文本最多保留的字节数，超出部分被丢弃
+const MAX_COMMAND_TEXT: usize = 4096;
+
+/// 执行结束的命令
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FinishedCommand {
+    /// 退出码，`D` 标记中没有给出时为 `None`
+    pub exit_code: Option<i32>,
+    /// 命令文本，无法得到时为 `None`
+    pub command: Option<String>,
+}
+
+/// 命令跟踪器所处的阶段
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Phase {
+    /// 提示符或尚未收到标记
+    Idle,
+    /// 正在记录输入（`B` 之后）
+    Input(String),
+    /// 命令正在执行（`C` 之后）
+    Running(Option<String>),
+}
+
+/// 命令跟踪器
+///
+/// 输出中的文本通过 [`text`](Self::text) 传入，标记通过 [`marker`](Self::marker) 传入，
+/// 两者必须按在输出中出现的顺序调用。
+#[derive(Debug)]
+pub struct CommandTracker {
+    phase: Phase,
+}
+
+impl CommandTracker {
+    /// 创建新的命令跟踪器
+    pub fn new() -> Self {
+        Self { phase: Phase::Idle }
+    }
+
+    /// 记录标记之间的输出文本，只有在命令输入阶段才保留
+    pub fn text(&mut self, text: &str) {
+        if let Phase::Input(input) = &mut self.phase {
+            let available = MAX_COMMAND_TEXT.saturating_sub(input.len());
+            if available > 0 {
+                let mut end = text.len().min(available);
+                while !text.is_char_boundary(end) {
+                    end -= 1;
+                }
+                input.push_str(&text[..end]);
+            }
+        }
+    }
+
+    /// 处理提示符标记，命令结束时返回结果
+    pub fn marker(&mut self, marker: PromptMarker) -> Option<FinishedCommand> {
+        match marker {
+            PromptMarker::PromptStart => {
+                self.phase = Phase::Idle;
+                None
+            }
+            PromptMarker::CommandStart => {
+                self.phase = Phase::Input(String::new());
+                None
+            }
+            PromptMarker::CommandExecuted => {
+                let command = match std::mem::replace(&mut self.phase, Phase::Idle) {
+                    Phase::Input(input) => clean_command(&input),
+                    _ => None,
+                };
+                self.phase = Phase::Running(command);
+                None
+            }
+            PromptMarker::CommandFinished(exit_code) => {
+                match std::mem::replace(&mut self.phase, Phase::Idle) {
+                    Phase::Running(command) => Some(FinishedCommand { exit_code, command }),
+                    _ => None,
+                }
+            }
+        }
+    }
+}
+
+impl Default for CommandTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// 去掉回显输入中的转义序列和控制字符，结果为空时返回 `None`
+fn clean_command(input: &str) -> Option<String> {
+    let mut command = String::with_capacity(input.len());
+    let mut chars = input.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            // 其他两字节转义序列只跳过下一个字符；CSI 的参数和中间字节之后以 0x40–0x7E 结束
+            ESC => {
+                if chars.next() == Some('[') {
+                    for c in chars.by_ref() {
+                        if ('\x40'..='\x7e').contains(&c) {
+                            break;
+                        }
+                    }
+                }
+            }
+            // 退格删除前一个字符
+            '\x08' | '\x7f' => {
+                command.pop();
+            }
+            c if c.is_control() => {}
+            c => command.push(c),
+        }
+    }
+
+    let command = command.trim();
+    (!command.is_empty()).then(|| command.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_command_finished_with_text() {
+        let mut tracker = CommandTracker::new();
+        assert_eq!(tracker.marker(PromptMarker::PromptStart), None);
+        tracker.text("user@host:~$ ");
+        assert_eq!(tracker.marker(PromptMarker::CommandStart), None);
+        tracker.text("false\x1b[?2004l\r\n");
+        assert_eq!(tracker.marker(PromptMarker::CommandExecuted), None);
+        tracker.text("some output\r\n");
+
+        assert_eq!(
+            tracker.marker(PromptMarker::CommandFinished(Some(1))),
+            Some(FinishedCommand {
+                exit_code: Some(1),
+                command: Some("false".to_string()),
+            })
+        );
+        // 同一条命令只报告一次
+        assert_eq!(tracker.marker(PromptMarker::CommandFinished(Some(1))), None);
+    }
+
+    #[test]
+    fn test_finished_without_executed_is_ignored() {
+        let mut tracker = CommandTracker::new();
+        tracker.marker(PromptMarker::PromptStart);
+        tracker.marker(PromptMarker::CommandStart);
+        tracker.text("\r\n");
+        assert_eq!(tracker.marker(PromptMarker::CommandFinished(Some(0))), None);
+    }
+
+    #[test]
+    fn test_command_without_input_marker() {
+        let mut tracker = CommandTracker::new();
+        tracker.marker(PromptMarker::CommandExecuted);
+        assert_eq!(
+            tracker.marker(PromptMarker::CommandFinished(None)),
+            Some(FinishedCommand {
+                exit_code: None,
+                command: None,
+            })
+        );
+    }
+
+    #[test]
+    fn test_clean_command() {
+        assert_eq!(clean_command("ls -la\r\n"), Some("ls -la".to_string()));
+        assert_eq!(clean_command("lx\x08s"), Some("ls".to_string()));
+        assert_eq!(clean_command("\x1b[32mgit\x1b[0m status"), Some("git status".to_string()));
+        assert_eq!(clean_command("  \r\n"), None);
+    }
+
+    #[test]
+    fn test_command_text_is_bounded() {
+        let mut tracker = CommandTracker::new();
+        tracker.marker(PromptMarker::CommandStart);
+        tracker.text(&"é".repeat(MAX_COMMAND_TEXT));
+        tracker.marker(PromptMarker::CommandExecuted);
+        let finished = tracker.marker(PromptMarker::CommandFinished(Some(0))).unwrap();
+        assert!(finished.command.unwrap().len() <= MAX_COMMAND_TEXT);
+    }
+}
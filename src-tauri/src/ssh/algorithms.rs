@@ -0,0 +1,252 @@
+//! SSH 算法偏好
+//!
+//! 连接只支持特定算法的旧服务器或加固过的服务器时，可以指定各类算法的优先顺序。
+//! 未指定的类别使用 russh 的默认顺序。名称使用 OpenSSH 的写法:
+//!
+//! - 密钥交换: `curve25519-sha256`、`curve25519-sha256@libssh.org`、
+//!   `diffie-hellman-group16-sha512`、`diffie-hellman-group14-sha256`、
+//!   `diffie-hellman-group14-sha1`、`diffie-hellman-group1-sha1`、
+//!   `ecdh-sha2-nistp256`、`ecdh-sha2-nistp384`、`ecdh-sha2-nistp521`
+//! - 加密: `chacha20-poly1305@openssh.com`、`aes256-gcm@openssh.com`、
+//!   `aes256-ctr`、`aes192-ctr`、`aes128-ctr`、`aes256-cbc`、`aes192-cbc`、`aes128-cbc`、
+//!   `3des-cbc`
+//! - MAC: `hmac-sha2-512-etm@openssh.com`、`hmac-sha2-256-etm@openssh.com`、
+//!   `hmac-sha2-512`、`hmac-sha2-256`、`hmac-sha1-etm@openssh.com`、`hmac-sha1`
+//! - 主机密钥: `ssh-ed25519`、`ecdsa-sha2-nistp256`、`ecdsa-sha2-nistp384`、
+//!   `ecdsa-sha2-nistp521`、`rsa-sha2-512`、`rsa-sha2-256`、`ssh-rsa`
+//!
+//! 不接受 `none` 等不加密或不校验的算法。密钥交换列表末尾会自动加上 `ext-info-c`
+//! 和严格密钥交换（`kex-strict-c-v00@openssh.com`）扩展标记。
+
+use std::borrow::Cow;
+
+use russh::keys::key;
+use russh::{cipher, kex, mac, Preferred};
+
+use crate::utils::error::TerminalError;
+
+/// 支持的密钥交换算法
+const KEX_ALGORITHMS: &[kex::Name] = &[
+    kex::CURVE25519,
+    kex::CURVE25519_PRE_RFC_8731,
+    kex::DH_G16_SHA512,
+    kex::DH_G14_SHA256,
+    kex::DH_G14_SHA1,
+    kex::DH_G1_SHA1,
+    kex::ECDH_SHA2_NISTP256,
+    kex::ECDH_SHA2_NISTP384,
+    kex::ECDH_SHA2_NISTP521,
+];
+
+/// 自动追加到密钥交换列表的扩展标记
+const KEX_EXTENSIONS: &[kex::Name] = &[
+    kex::EXTENSION_SUPPORT_AS_CLIENT,
+    kex::EXTENSION_OPENSSH_STRICT_KEX_AS_CLIENT,
+];
+
+/// 支持的加密算法
+const CIPHERS: &[cipher::Name] = &[
+    cipher::CHACHA20_POLY1305,
+    cipher::AES_256_GCM,
+    cipher::AES_256_CTR,
+    cipher::AES_192_CTR,
+    cipher::AES_128_CTR,
+    cipher::AES_256_CBC,
+    cipher::AES_192_CBC,
+    cipher::AES_128_CBC,
+    cipher::TRIPLE_DES_CBC,
+];
+
+/// 支持的 MAC 算法
+const MACS: &[mac::Name] = &[
+    mac::HMAC_SHA512_ETM,
+    mac::HMAC_SHA256_ETM,
+    mac::HMAC_SHA512,
+    mac::HMAC_SHA256,
+    mac::HMAC_SHA1_ETM,
+    mac::HMAC_SHA1,
+];
+
+/// 支持的主机密钥算法
+const HOST_KEY_ALGORITHMS: &[key::Name] = &[
+    key::ED25519,
+    key::ECDSA_SHA2_NISTP256,
+    key::ECDSA_SHA2_NISTP384,
+    key::ECDSA_SHA2_NISTP521,
+    key::RSA_SHA2_512,
+    key::RSA_SHA2_256,
+    key::SSH_RSA,
+];
+
+/// SSH 算法偏好
+///
+/// 每个列表按优先顺序排列，`None` 表示使用 russh 的默认顺序。
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AlgorithmPreferences {
+    /// 密钥交换算法
+    pub kex: Option<Vec<String>>,
+    /// 加密算法
+    pub ciphers: Option<Vec<String>>,
+    /// MAC 算法
+    pub macs: Option<Vec<String>>,
+    /// 主机密钥算法
+    pub host_keys: Option<Vec<String>>,
+}
+
+impl AlgorithmPreferences {
+    /// 检查所有算法名称，名称未知或列表为空时返回 `InvalidRequest`
+    pub fn validate(&self) -> Result<(), TerminalError> {
+        self.apply(&mut Preferred::default())
+    }
+
+    /// 用指定的算法顺序覆盖 `preferred` 中对应的列表
+    ///
+    /// 任意一个列表无效时返回错误，`preferred` 保持不变。
+    pub fn apply(&self, preferred: &mut Preferred) -> Result<(), TerminalError> {
+        let kex = self
+            .kex
+            .as_deref()
+            .map(|names| parse_names("密钥交换", names, KEX_ALGORITHMS, |n| n.as_ref()))
+            .transpose()?;
+        let cipher = self
+            .ciphers
+            .as_deref()
+            .map(|names| parse_names("加密", names, CIPHERS, |n| n.as_ref()))
+            .transpose()?;
+        let mac = self
+            .macs
+            .as_deref()
+            .map(|names| parse_names("MAC", names, MACS, |n| n.as_ref()))
+            .transpose()?;
+        let host_key = self
+            .host_keys
+            .as_deref()
+            .map(|names| parse_names("主机密钥", names, HOST_KEY_ALGORITHMS, |n| n.0))
+            .transpose()?;
+
+        if let Some(mut kex) = kex {
+            kex.extend_from_slice(KEX_EXTENSIONS);
+            preferred.kex = Cow::Owned(kex);
+        }
+        if let Some(cipher) = cipher {
+            preferred.cipher = Cow::Owned(cipher);
+        }
+        if let Some(mac) = mac {
+            preferred.mac = Cow::Owned(mac);
+        }
+        if let Some(host_key) = host_key {
+            preferred.key = Cow::Owned(host_key);
+        }
+        Ok(())
+    }
+}
+
+/// 按名称查找算法，保持给定的顺序并去掉重复项
+fn parse_names<N: Copy + PartialEq>(
+    kind: &str,
+    names: &[String],
+    supported: &[N],
+    name_of: fn(&N) -> &str,
+) -> Result<Vec<N>, TerminalError> {
+    if names.is_empty() {
+        return Err(TerminalError::InvalidRequest(format!("{}算法列表不能为空", kind)));
+    }
+
+    let mut result = Vec::with_capacity(names.len());
+    for name in names {
+        let algorithm = supported
+            .iter()
+            .find(|candidate| name_of(candidate) == name.as_str())
+            .ok_or_else(|| {
+                let available: Vec<&str> = supported.iter().map(name_of).collect();
+                TerminalError::InvalidRequest(format!(
+                    "未知的{}算法: {}（可用: {}）",
+                    kind,
+                    name,
+                    available.join(", ")
+                ))
+            })?;
+        if !result.contains(algorithm) {
+            result.push(*algorithm);
+        }
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(list: &[&str]) -> Option<Vec<String>> {
+        Some(list.iter().map(|s| s.to_string()).collect())
+    }
+
+    #[test]
+    fn test_defaults_unchanged() {
+        let mut preferred = Preferred::default();
+        AlgorithmPreferences::default().apply(&mut preferred).unwrap();
+        assert_eq!(preferred.kex, Preferred::DEFAULT.kex);
+        assert_eq!(preferred.cipher, Preferred::DEFAULT.cipher);
+        assert_eq!(preferred.mac, Preferred::DEFAULT.mac);
+        assert_eq!(preferred.key, Preferred::DEFAULT.key);
+    }
+
+    #[test]
+    fn test_apply_preferences() {
+        let preferences = AlgorithmPreferences {
+            kex: names(&["diffie-hellman-group14-sha1", "curve25519-sha256"]),
+            ciphers: names(&["aes128-cbc", "aes128-cbc"]),
+            macs: names(&["hmac-sha1"]),
+            host_keys: names(&["ssh-rsa", "ssh-ed25519"]),
+        };
+        let mut preferred = Preferred::default();
+        preferences.apply(&mut preferred).unwrap();
+
+        assert_eq!(
+            &*preferred.kex,
+            &[
+                kex::DH_G14_SHA1,
+                kex::CURVE25519,
+                kex::EXTENSION_SUPPORT_AS_CLIENT,
+                kex::EXTENSION_OPENSSH_STRICT_KEX_AS_CLIENT,
+            ]
+        );
+        // 重复项只保留第一次出现的位置
+        assert_eq!(&*preferred.cipher, &[cipher::AES_128_CBC]);
+        assert_eq!(&*preferred.mac, &[mac::HMAC_SHA1]);
+        assert_eq!(&*preferred.key, &[key::SSH_RSA, key::ED25519]);
+        // 未设置的类别保持默认
+        assert_eq!(preferred.compression, Preferred::DEFAULT.compression);
+    }
+
+    #[test]
+    fn test_unknown_algorithm_rejected() {
+        let preferences = AlgorithmPreferences {
+            ciphers: names(&["aes256-ctr", "rot13"]),
+            ..Default::default()
+        };
+        let mut preferred = Preferred::default();
+        match preferences.apply(&mut preferred) {
+            Err(TerminalError::InvalidRequest(message)) => {
+                assert!(message.contains("rot13"));
+                assert!(message.contains("aes256-ctr"));
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+        assert_eq!(preferred.cipher, Preferred::DEFAULT.cipher);
+
+        // 不加密的算法和扩展标记不能直接指定
+        for kex in ["none", "ext-info-c"] {
+            let preferences = AlgorithmPreferences {
+                kex: names(&[kex]),
+                ..Default::default()
+            };
+            assert!(preferences.validate().is_err());
+        }
+        let preferences = AlgorithmPreferences {
+            macs: Some(Vec::new()),
+            ..Default::default()
+        };
+        assert!(preferences.validate().is_err());
+    }
+}
@@ -1,32 +1,52 @@
 //! SSH 认证
 //!
 //! 支持密码和私钥认证方式。
+//!
+//! 密码和私钥密码使用 [`SecretString`] 保存，释放时内存会被清零。
 
+use std::fmt;
 use std::path::Path;
 
 use russh_keys::key::KeyPair;
+use zeroize::Zeroizing;
 
+use crate::rpc::types::REDACTED;
 use crate::utils::error::TerminalError;
+use crate::utils::path::expand_tilde;
+
+/// 释放时清零的字符串，用于保存密码等凭据
+pub type SecretString = Zeroizing<String>;
 
 /// 认证方式
-#[derive(Debug, Clone)]
+///
+/// 调试输出中密码和私钥密码会被替换为占位符。
+#[derive(Clone, Default)]
 pub enum AuthMethod {
     /// 无认证（用于测试或特殊配置）
+    #[default]
     None,
     /// 密码认证
-    Password(String),
+    Password(SecretString),
     /// 私钥认证
     PrivateKey {
         /// 私钥文件路径
         path: String,
         /// 私钥密码（可选）
-        passphrase: Option<String>,
+        passphrase: Option<SecretString>,
     },
 }
 
-impl Default for AuthMethod {
-    fn default() -> Self {
-        Self::None
+impl fmt::Debug for AuthMethod {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::None => f.write_str("None"),
+            Self::Password(_) => f.debug_tuple("Password").field(&REDACTED).finish(),
+            Self::PrivateKey { path, passphrase } => f
+                .debug_struct("PrivateKey")
+                .field("path", path)
+                .field("passphrase", &passphrase.as_ref().map(|_| REDACTED))
+                .finish(),
+        }
     }
 }
 
@@ -70,10 +90,10 @@ pub fn load_private_key(path: &str, passphrase: Option<&str>) -> Result<KeyPair,
         }
     }
 
-    // 读取私钥文件
-    let key_data = std::fs::read_to_string(key_path).map_err(|e| {
+    // 读取私钥文件，文件内容在解析后清零
+    let key_data = Zeroizing::new(std::fs::read_to_string(key_path).map_err(|e| {
         TerminalError::key_load_failed(&path, &format!("无法读取文件: {}", e))
-    })?;
+    })?);
 
     // 解析私钥
     let key = if let Some(pass) = passphrase {
@@ -96,16 +116,6 @@ pub fn load_private_key(path: &str, passphrase: Option<&str>) -> Result<KeyPair,
     Ok(key)
 }
 
-/// 展开路径中的 ~ 为用户主目录
-fn expand_tilde(path: &str) -> String {
-    if path.starts_with("~/") {
-        if let Some(home) = dirs::home_dir() {
-            return path.replacen("~", &home.to_string_lossy(), 1);
-        }
-    }
-    path.to_string()
-}
-
 /// 获取默认 SSH 私钥路径列表
 ///
 /// 返回常见的 SSH 私钥文件路径，按优先级排序。
@@ -164,12 +174,48 @@ mod tests {
     }
 
     #[test]
-    fn test_expand_tilde() {
-        let expanded = expand_tilde("~/test/path");
-        assert!(!expanded.starts_with("~/"));
-        
-        let no_tilde = expand_tilde("/absolute/path");
-        assert_eq!(no_tilde, "/absolute/path");
+    fn test_auth_method_debug_redacts_secrets() {
+        let password = format!("{:?}", AuthMethod::Password(SecretString::new("hunter2".to_string())));
+        assert!(!password.contains("hunter2"));
+        assert!(password.contains(REDACTED));
+
+        let key = format!(
+            "{:?}",
+            AuthMethod::PrivateKey {
+                path: "/path/to/key".to_string(),
+                passphrase: Some(SecretString::new("secret-phrase".to_string())),
+            }
+        );
+        assert!(!key.contains("secret-phrase"));
+        assert!(key.contains("/path/to/key"));
+    }
+
+    /// 记录是否被清零的测试类型
+    struct ZeroizeProbe(std::sync::Arc<std::sync::atomic::AtomicBool>);
+
+    impl zeroize::Zeroize for ZeroizeProbe {
+        fn zeroize(&mut self) {
+            self.0.store(true, std::sync::atomic::Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn test_secret_zeroized_on_drop() {
+        let zeroized = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let secret = Zeroizing::new(ZeroizeProbe(zeroized.clone()));
+
+        assert!(!zeroized.load(std::sync::atomic::Ordering::SeqCst));
+        drop(secret);
+        assert!(zeroized.load(std::sync::atomic::Ordering::SeqCst));
+    }
+
+    #[test]
+    fn test_secret_string_clears_contents() {
+        use zeroize::Zeroize;
+
+        let mut secret = SecretString::new("hunter2".to_string());
+        secret.zeroize();
+        assert!(secret.is_empty());
     }
 
     #[test]
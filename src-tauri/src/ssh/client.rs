@@ -2,17 +2,50 @@
 //!
 //! 使用 russh 建立 SSH 连接，支持密码和私钥认证。
 
-use std::net::ToSocketAddrs;
+use std::borrow::Cow;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::Duration;
 
 use russh::client::{Config, Handle, Handler};
+use russh::compression;
 use russh::keys::key::PublicKey;
 use russh::{ChannelId, Disconnect};
 use tokio::net::TcpStream;
 
+use crate::rpc::types::{HostKeyDecision, SshDiagnostics};
 use crate::utils::error::TerminalError;
 
-use super::auth::AuthMethod;
+use super::algorithms::AlgorithmPreferences;
+use super::auth::{AuthMethod, SecretString};
+use super::config::{self as ssh_config, SshHostConfig};
+use super::diagnostics::{self, HandshakeRecorder};
+use super::host_key::{HostKeyChange, HostKeyChangedCallback};
+use super::known_hosts::{self, HostKeyStatus};
+
+/// 默认的 SSH 版本行超时（秒）
+pub const DEFAULT_BANNER_TIMEOUT_SECS: u64 = 15;
+
+/// 连接使用的地址族，对应 OpenSSH 的 `AddressFamily`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AddrFamily {
+    /// 只使用 IPv4 地址
+    Inet,
+    /// 只使用 IPv6 地址
+    Inet6,
+}
+
+impl AddrFamily {
+    /// 地址是否属于这个地址族
+    pub fn matches(self, addr: &SocketAddr) -> bool {
+        match self {
+            AddrFamily::Inet => addr.is_ipv4(),
+            AddrFamily::Inet6 => addr.is_ipv6(),
+        }
+    }
+}
 
 /// SSH 客户端配置
 #[derive(Debug, Clone)]
@@ -27,6 +60,30 @@ pub struct SshClientConfig {
     pub auth_method: AuthMethod,
     /// 连接超时（秒）
     pub connect_timeout: u64,
+    /// 等待服务器 SSH 版本行的超时（秒）
+    ///
+    /// 有些防火墙或 tarpit 接受 TCP 连接后不发送任何数据，握手会一直挂起。
+    /// 超时后仍未收到版本行时立即失败；已经收到版本行的慢速握手不受影响。
+    pub banner_timeout: u64,
+    /// 跳板机（来自 ssh_config 的 `ProxyJump`，暂不支持连接）
+    pub proxy_jump: Option<String>,
+    /// 是否启用压缩
+    ///
+    /// 启用后优先协商 zlib，适合高延迟、低带宽的链路。压缩需要服务器同样支持，
+    /// 服务器不支持时回退为不压缩。
+    pub compression: bool,
+    /// 密钥交换、加密、MAC 和主机密钥算法的优先顺序，未设置的类别使用 russh 默认值
+    ///
+    /// 可用的名称见 [`algorithms`](super::algorithms) 模块。
+    pub algorithms: AlgorithmPreferences,
+    /// 只连接指定地址族的地址，`None` 时按解析顺序尝试所有地址
+    pub address_family: Option<AddrFamily>,
+    /// 校验主机密钥时按顺序读取的 known_hosts 文件
+    ///
+    /// 默认为 `~/.ssh/known_hosts` 和 `/etc/ssh/ssh_known_hosts`，不存在的文件被忽略。
+    pub known_hosts_files: Vec<PathBuf>,
+    /// 服务器主机密钥与 known_hosts 记录不一致时调用，未设置时直接拒绝连接
+    pub host_key_changed: Option<HostKeyChangedCallback>,
 }
 
 impl Default for SshClientConfig {
@@ -37,27 +94,157 @@ impl Default for SshClientConfig {
             user: String::new(),
             auth_method: AuthMethod::None,
             connect_timeout: 30,
+            banner_timeout: DEFAULT_BANNER_TIMEOUT_SECS,
+            proxy_jump: None,
+            compression: false,
+            algorithms: AlgorithmPreferences::default(),
+            address_family: None,
+            known_hosts_files: known_hosts::default_known_hosts_files(),
+            host_key_changed: None,
         }
     }
 }
 
+impl SshClientConfig {
+    /// 根据 ssh_config 文件中的主机别名生成配置
+    ///
+    /// 配置文件中没有 `IdentityFile` 时不设置认证方式。
+    pub fn from_ssh_config(alias: &str, path: &Path) -> Self {
+        Self::build(
+            alias.to_string(),
+            None,
+            None,
+            None,
+            None,
+            ssh_config::resolve_host(path, alias),
+        )
+    }
+
+    /// 合并显式参数与 ssh_config 中的主机配置，显式参数优先
+    fn build(
+        host: String,
+        port: Option<u16>,
+        user: Option<String>,
+        identity_file: Option<String>,
+        password: Option<String>,
+        host_config: SshHostConfig,
+    ) -> Self {
+        let auth_method = if let Some(key_path) = identity_file {
+            AuthMethod::PrivateKey {
+                path: key_path,
+                passphrase: None,
+            }
+        } else if let Some(pwd) = password {
+            AuthMethod::Password(SecretString::new(pwd))
+        } else if let Some(key_path) = host_config.identity_file {
+            AuthMethod::PrivateKey {
+                path: key_path,
+                passphrase: None,
+            }
+        } else {
+            AuthMethod::None
+        };
+
+        Self {
+            host: host_config.host_name.unwrap_or(host),
+            port: port.or(host_config.port).unwrap_or(22),
+            user: user.or(host_config.user).unwrap_or_else(whoami::username),
+            auth_method,
+            connect_timeout: 30,
+            banner_timeout: DEFAULT_BANNER_TIMEOUT_SECS,
+            proxy_jump: host_config.proxy_jump,
+            compression: false,
+            algorithms: AlgorithmPreferences::default(),
+            address_family: None,
+            known_hosts_files: known_hosts::default_known_hosts_files(),
+            host_key_changed: None,
+        }
+    }
+
+    /// 生成 russh 客户端配置
+    ///
+    /// 算法名称无效时返回 `InvalidRequest`。
+    fn russh_config(&self) -> Result<Config, TerminalError> {
+        let mut config = Config::default();
+        self.algorithms.apply(&mut config.preferred)?;
+        config.preferred.compression = if self.compression {
+            Cow::Borrowed(&[
+                compression::ZLIB_LEGACY,
+                compression::ZLIB,
+                compression::NONE,
+            ])
+        } else {
+            Cow::Borrowed(&[compression::NONE])
+        };
+        Ok(config)
+    }
+}
+
 /// SSH 客户端事件处理器
 pub struct SshClientHandler {
+    /// 连接的主机名，用于在 known_hosts 中查找记录
+    host: String,
+    /// 连接的端口
+    port: u16,
+    /// 校验主机密钥时读取的 known_hosts 文件
+    known_hosts_files: Vec<PathBuf>,
     /// 是否已验证主机密钥
     host_key_verified: bool,
+    /// 主机密钥变更时的确认回调
+    host_key_changed: Option<HostKeyChangedCallback>,
 }
 
 impl SshClientHandler {
-    pub fn new() -> Self {
+    pub fn new(host: String, port: u16, known_hosts_files: Vec<PathBuf>) -> Self {
         Self {
+            host,
+            port,
+            known_hosts_files,
             host_key_verified: false,
+            host_key_changed: None,
         }
     }
-}
 
-impl Default for SshClientHandler {
-    fn default() -> Self {
-        Self::new()
+    /// 设置主机密钥变更时的确认回调
+    pub fn with_host_key_changed(mut self, callback: Option<HostKeyChangedCallback>) -> Self {
+        self.host_key_changed = callback;
+        self
+    }
+
+    /// 主机密钥变更时询问回调，返回是否继续连接
+    ///
+    /// 永久接受时把新密钥写入第一个 known_hosts 文件（通常是用户的 `~/.ssh/known_hosts`），
+    /// 写入失败只记录警告，这次连接仍然继续。
+    async fn confirm_changed_key(&self, server_public_key: &PublicKey, path: PathBuf, line: usize, old_fingerprint: String) -> bool {
+        let Some(callback) = &self.host_key_changed else {
+            return false;
+        };
+
+        let change = HostKeyChange {
+            host: self.host.clone(),
+            port: self.port,
+            key_type: server_public_key.name().to_string(),
+            old_fingerprint,
+            new_fingerprint: known_hosts::fingerprint(server_public_key),
+            known_hosts_path: path,
+            line,
+        };
+        match callback.call(change).await {
+            HostKeyDecision::AcceptOnce => {
+                tracing::warn!("本次连接接受变更的主机密钥: {}", self.host);
+                true
+            }
+            HostKeyDecision::AcceptPermanent => {
+                tracing::warn!("接受变更的主机密钥并写入 known_hosts: {}", self.host);
+                if let Some(file) = self.known_hosts_files.first() {
+                    if let Err(e) = known_hosts::add_host_key(file, &self.host, self.port, server_public_key) {
+                        tracing::warn!("写入 known_hosts 失败: {} - {}", file.display(), e);
+                    }
+                }
+                true
+            }
+            HostKeyDecision::Reject => false,
+        }
     }
 }
 
@@ -67,16 +254,60 @@ impl Handler for SshClientHandler {
     type Error = TerminalError;
 
     /// 检查服务器公钥
-    /// 
-    /// 注意：在生产环境中应该实现 known_hosts 检查
+    ///
+    /// 在 known_hosts 中找到一致的记录时信任；已被吊销时拒绝连接；记录的密钥不同时
+    /// 由确认回调决定，没有回调时拒绝连接。没有记录的主机暂时接受，只记录警告。
     async fn check_server_key(
         &mut self,
-        _server_public_key: &PublicKey,
+        server_public_key: &PublicKey,
     ) -> Result<bool, Self::Error> {
-        // TODO: 实现 known_hosts 检查
-        // 目前接受所有服务器密钥（不安全，仅用于开发）
-        tracing::warn!("接受服务器密钥（未验证 known_hosts）");
-        self.host_key_verified = true;
+        match known_hosts::check_host_key(
+            &self.known_hosts_files,
+            &self.host,
+            self.port,
+            server_public_key,
+        ) {
+            HostKeyStatus::Trusted => {
+                tracing::debug!("主机密钥与 known_hosts 记录一致: {}", self.host);
+                self.host_key_verified = true;
+            }
+            HostKeyStatus::Unknown => {
+                tracing::warn!(
+                    "known_hosts 中没有 {} 的 {} 密钥，接受服务器密钥",
+                    self.host,
+                    server_public_key.name()
+                );
+            }
+            HostKeyStatus::Changed {
+                path,
+                line,
+                fingerprint,
+            } => {
+                if self
+                    .confirm_changed_key(server_public_key, path.clone(), line, fingerprint)
+                    .await
+                {
+                    self.host_key_verified = true;
+                    return Ok(true);
+                }
+                return Err(TerminalError::SshError(format!(
+                    "{} 的 {} 主机密钥与 {}:{} 中的记录不一致，可能存在中间人攻击",
+                    self.host,
+                    server_public_key.name(),
+                    path.display(),
+                    line
+                )));
+            }
+            HostKeyStatus::Revoked { path, line } => {
+                return Err(TerminalError::SshError(format!(
+                    "{} 的 {} 主机密钥已在 {}:{} 中被吊销",
+                    self.host,
+                    server_public_key.name(),
+                    path.display(),
+                    line
+                )));
+            }
+        }
         Ok(true)
     }
 
@@ -131,7 +362,11 @@ pub struct SshClient {
     /// 客户端配置
     config: SshClientConfig,
     /// SSH 会话句柄
-    handle: Option<Handle<SshClientHandler>>,
+    handle: Option<Arc<Handle<SshClientHandler>>>,
+    /// 握手诊断信息
+    diagnostics: Option<SshDiagnostics>,
+    /// 建立 TCP 连接时实际使用的地址
+    resolved_addr: Option<SocketAddr>,
 }
 
 impl SshClient {
@@ -140,10 +375,14 @@ impl SshClient {
         Self {
             config,
             handle: None,
+            diagnostics: None,
+            resolved_addr: None,
         }
     }
 
     /// 从连接参数创建 SSH 客户端
+    ///
+    /// 未显式提供的参数从 `~/.ssh/config` 中与主机别名匹配的配置补全。
     pub fn from_params(
         host: String,
         port: Option<u16>,
@@ -151,30 +390,26 @@ impl SshClient {
         identity_file: Option<String>,
         password: Option<String>,
     ) -> Self {
-        let auth_method = if let Some(key_path) = identity_file {
-            AuthMethod::PrivateKey {
-                path: key_path,
-                passphrase: None,
-            }
-        } else if let Some(pwd) = password {
-            AuthMethod::Password(pwd)
-        } else {
-            AuthMethod::None
-        };
+        let host_config = ssh_config::default_config_path()
+            .map(|path| ssh_config::resolve_host(&path, &host))
+            .unwrap_or_default();
 
-        let config = SshClientConfig {
+        Self::new(SshClientConfig::build(
             host,
-            port: port.unwrap_or(22),
-            user: user.unwrap_or_else(|| whoami::username()),
-            auth_method,
-            connect_timeout: 30,
-        };
-
-        Self::new(config)
+            port,
+            user,
+            identity_file,
+            password,
+            host_config,
+        ))
     }
 
     /// 连接到远程服务器
     pub async fn connect(&mut self) -> Result<(), TerminalError> {
+        if let Some(proxy_jump) = &self.config.proxy_jump {
+            tracing::warn!("暂不支持 ProxyJump，将直接连接: {}", proxy_jump);
+        }
+
         tracing::info!(
             "连接到 SSH 服务器: {}@{}:{}",
             self.config.user,
@@ -182,61 +417,85 @@ impl SshClient {
             self.config.port
         );
 
+        // 创建 SSH 配置，算法配置错误时不必建立连接
+        let ssh_config = Arc::new(self.config.russh_config()?);
+        let preferred = ssh_config.preferred.clone();
+
         // 解析地址
-        let addr = format!("{}:{}", self.config.host, self.config.port)
-            .to_socket_addrs()
-            .map_err(|e| {
-                TerminalError::host_resolution_failed(
-                    &self.config.host,
-                    &e.to_string(),
-                )
-            })?
-            .next()
-            .ok_or_else(|| {
-                TerminalError::host_resolution_failed(
-                    &self.config.host,
-                    "无法解析为有效地址",
-                )
-            })?;
-
-        // 建立 TCP 连接
-        let tcp = TcpStream::connect(addr).await.map_err(|e| {
+        self.resolved_addr = None;
+        let addrs = self.resolve().await?;
+
+        // 与 OpenSSH 一样按顺序尝试每个地址，直到有一个连接成功
+        let (tcp, addr) = connect_first(&addrs, TcpStream::connect).await.map_err(|e| {
             TerminalError::ssh_connection_failed(
                 &self.config.host,
                 self.config.port,
                 &format!("TCP 连接失败: {}", e),
             )
         })?;
-
-        // 创建 SSH 配置
-        let ssh_config = Arc::new(Config::default());
+        tracing::debug!("TCP 连接已建立: {}", addr);
+        self.resolved_addr = Some(addr);
 
         // 创建 SSH 客户端处理器
-        let handler = SshClientHandler::new();
+        let handler = SshClientHandler::new(
+            self.config.host.clone(),
+            self.config.port,
+            self.config.known_hosts_files.clone(),
+        )
+        .with_host_key_changed(self.config.host_key_changed.clone());
 
-        // 建立 SSH 连接
-        let handle = russh::client::connect_stream(ssh_config, tcp, handler)
-            .await
-            .map_err(|e| {
-                TerminalError::ssh_connection_failed(
-                    &self.config.host,
-                    self.config.port,
-                    &format!("SSH 握手失败: {}", e),
-                )
-            })?;
+        // 记录服务器握手数据，用于诊断
+        let (stream, recorded) = HandshakeRecorder::new(tcp);
 
-        self.handle = Some(handle);
+        // 建立 SSH 连接，超时后仍未收到服务器版本行时不再等待
+        let handshake = russh::client::connect_stream(ssh_config, stream, handler);
+        tokio::pin!(handshake);
+        let banner_timeout = Duration::from_secs(self.config.banner_timeout);
+        let result = match tokio::time::timeout(banner_timeout, &mut handshake).await {
+            Ok(result) => result,
+            Err(_) if !diagnostics::has_version_line(&recorded.lock().unwrap()) => {
+                return Err(TerminalError::ConnectionTimeout(format!(
+                    "{}:{} 在 {} 秒内未收到 SSH 版本行（banner）",
+                    self.config.host, self.config.port, self.config.banner_timeout
+                )));
+            }
+            Err(_) => handshake.await,
+        };
+        let handle = result.map_err(|e| {
+            TerminalError::ssh_connection_failed(
+                &self.config.host,
+                self.config.port,
+                &format!("SSH 握手失败: {}", e),
+            )
+        })?;
+
+        self.handle = Some(Arc::new(handle));
 
         // 执行认证
         self.authenticate().await?;
 
+        // 认证成功时密钥交换已经完成
+        self.diagnostics = diagnostics::analyze(&recorded.lock().unwrap(), &preferred);
+        if let Some(diagnostics) = &self.diagnostics {
+            tracing::debug!("SSH 握手信息: {:?}", diagnostics);
+        }
+
         tracing::info!("SSH 连接成功: {}@{}", self.config.user, self.config.host);
         Ok(())
     }
 
+    /// 解析主机地址，按 `address_family` 过滤
+    async fn resolve(&self) -> Result<Vec<SocketAddr>, TerminalError> {
+        let addrs = tokio::net::lookup_host((self.config.host.as_str(), self.config.port))
+            .await
+            .map_err(|e| TerminalError::host_resolution_failed(&self.config.host, &e.to_string()))?;
+        filter_addresses(addrs, self.config.address_family)
+            .map_err(|reason| TerminalError::host_resolution_failed(&self.config.host, reason))
+    }
+
     /// 执行认证
     async fn authenticate(&mut self) -> Result<(), TerminalError> {
-        let handle = self.handle.as_mut().ok_or_else(|| {
+        let handle = self.handle.as_mut().and_then(Arc::get_mut).ok_or_else(|| {
             TerminalError::ssh_connection_failed(
                 &self.config.host,
                 self.config.port,
@@ -248,7 +507,7 @@ impl SshClient {
             AuthMethod::Password(password) => {
                 tracing::debug!("使用密码认证");
                 let auth_result = handle
-                    .authenticate_password(&self.config.user, password)
+                    .authenticate_password(&self.config.user, password.as_str())
                     .await
                     .map_err(|e| {
                         TerminalError::password_auth_failed(&format!(
@@ -267,7 +526,10 @@ impl SshClient {
                 tracing::debug!("使用私钥认证: {}", path);
                 
                 // 加载私钥
-                let key = super::auth::load_private_key(path, passphrase.as_deref())?;
+                let key = super::auth::load_private_key(
+                    path,
+                    passphrase.as_ref().map(|p| p.as_str()),
+                )?;
                 
                 let auth_result = handle
                     .authenticate_publickey(&self.config.user, Arc::new(key))
@@ -312,12 +574,19 @@ impl SshClient {
 
     /// 获取 SSH 会话句柄
     pub fn handle(&self) -> Option<&Handle<SshClientHandler>> {
-        self.handle.as_ref()
+        self.handle.as_deref()
     }
 
     /// 获取可变 SSH 会话句柄
+    ///
+    /// 句柄已通过 [`shared_handle`](Self::shared_handle) 共享时返回 `None`。
     pub fn handle_mut(&mut self) -> Option<&mut Handle<SshClientHandler>> {
-        self.handle.as_mut()
+        self.handle.as_mut().and_then(Arc::get_mut)
+    }
+
+    /// 获取可共享的 SSH 会话句柄，用于在其他任务中打开通道
+    pub fn shared_handle(&self) -> Option<Arc<Handle<SshClientHandler>>> {
+        self.handle.clone()
     }
 
     /// 获取配置
@@ -325,6 +594,23 @@ impl SshClient {
         &self.config
     }
 
+    /// 获取可变配置（仅在连接前修改有效）
+    pub fn config_mut(&mut self) -> &mut SshClientConfig {
+        &mut self.config
+    }
+
+    /// 获取握手诊断信息（连接成功后可用）
+    pub fn diagnostics(&self) -> Option<&SshDiagnostics> {
+        self.diagnostics.as_ref()
+    }
+
+    /// 最近一次连接实际使用的地址
+    ///
+    /// TCP 连接建立后可用，之后的握手或认证失败时仍然保留，便于确认连到了哪台服务器。
+    pub fn resolved_addr(&self) -> Option<SocketAddr> {
+        self.resolved_addr
+    }
+
     /// 检查是否已连接
     pub fn is_connected(&self) -> bool {
         self.handle.is_some()
@@ -345,6 +631,58 @@ impl SshClient {
     }
 }
 
+/// 按地址族过滤解析结果，保持解析顺序
+fn filter_addresses(
+    addrs: impl IntoIterator<Item = SocketAddr>,
+    family: Option<AddrFamily>,
+) -> Result<Vec<SocketAddr>, &'static str> {
+    let mut resolved = false;
+    let addrs: Vec<SocketAddr> = addrs
+        .into_iter()
+        .inspect(|_| resolved = true)
+        .filter(|addr| family.is_none_or(|family| family.matches(addr)))
+        .collect();
+
+    if !addrs.is_empty() {
+        Ok(addrs)
+    } else if !resolved {
+        Err("无法解析为有效地址")
+    } else if family == Some(AddrFamily::Inet6) {
+        Err("没有 IPv6 地址")
+    } else {
+        Err("没有 IPv4 地址")
+    }
+}
+
+/// 按顺序尝试连接每个地址，返回第一个成功的连接及其地址
+///
+/// 全部失败时返回最后一个错误，并附上尝试过的地址。
+async fn connect_first<T, F, Fut>(
+    addrs: &[SocketAddr],
+    mut connect: F,
+) -> Result<(T, SocketAddr), String>
+where
+    F: FnMut(SocketAddr) -> Fut,
+    Fut: Future<Output = std::io::Result<T>>,
+{
+    let mut last_error = None;
+    for &addr in addrs {
+        match connect(addr).await {
+            Ok(stream) => return Ok((stream, addr)),
+            Err(e) => {
+                tracing::debug!("连接 {} 失败: {}", addr, e);
+                last_error = Some(e);
+            }
+        }
+    }
+
+    let tried: Vec<String> = addrs.iter().map(|addr| addr.to_string()).collect();
+    Err(match last_error {
+        Some(e) => format!("{}（尝试的地址: {}）", e, tried.join(", ")),
+        None => "没有可用的地址".to_string(),
+    })
+}
+
 impl Drop for SshClient {
     fn drop(&mut self) {
         if self.handle.is_some() {
@@ -362,10 +700,327 @@ mod tests {
         let config = SshClientConfig::default();
         assert_eq!(config.port, 22);
         assert_eq!(config.connect_timeout, 30);
+        assert_eq!(config.banner_timeout, DEFAULT_BANNER_TIMEOUT_SECS);
+        assert_eq!(config.known_hosts_files, known_hosts::default_known_hosts_files());
         assert!(config.host.is_empty());
         assert!(config.user.is_empty());
     }
 
+    #[test]
+    fn test_ssh_client_config_from_ssh_config() {
+        let dir = std::env::temp_dir().join(format!("ssh-client-config-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("config");
+        std::fs::write(
+            &path,
+            "Host dev\n    HostName dev.example.com\n    Port 2222\n    User alice\n    IdentityFile /keys/dev\n    ProxyJump bastion\n",
+        )
+        .unwrap();
+
+        let config = SshClientConfig::from_ssh_config("dev", &path);
+        assert_eq!(config.host, "dev.example.com");
+        assert_eq!(config.port, 2222);
+        assert_eq!(config.user, "alice");
+        assert_eq!(config.proxy_jump.as_deref(), Some("bastion"));
+        assert!(matches!(
+            &config.auth_method,
+            AuthMethod::PrivateKey { path, .. } if path == "/keys/dev"
+        ));
+
+        // 未匹配的别名保持原样
+        let config = SshClientConfig::from_ssh_config("other.example.com", &path);
+        assert_eq!(config.host, "other.example.com");
+        assert_eq!(config.port, 22);
+        assert!(matches!(config.auth_method, AuthMethod::None));
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_explicit_params_override_ssh_config() {
+        let host_config = SshHostConfig {
+            host_name: Some("dev.example.com".to_string()),
+            port: Some(2222),
+            user: Some("alice".to_string()),
+            identity_file: Some("/keys/dev".to_string()),
+            proxy_jump: None,
+        };
+
+        let config = SshClientConfig::build(
+            "dev".to_string(),
+            Some(22),
+            Some("bob".to_string()),
+            None,
+            Some("secret".to_string()),
+            host_config,
+        );
+        assert_eq!(config.host, "dev.example.com");
+        assert_eq!(config.port, 22);
+        assert_eq!(config.user, "bob");
+        assert!(matches!(config.auth_method, AuthMethod::Password(_)));
+    }
+
+    #[test]
+    fn test_russh_config_compression() {
+        let mut config = SshClientConfig::default();
+        assert_eq!(&*config.russh_config().unwrap().preferred.compression, &[compression::NONE]);
+
+        config.compression = true;
+        let preferred = config.russh_config().unwrap().preferred.compression;
+        assert_eq!(preferred.first(), Some(&compression::ZLIB_LEGACY));
+        assert_eq!(preferred.last(), Some(&compression::NONE));
+    }
+
+    #[test]
+    fn test_russh_config_algorithms() {
+        let mut config = SshClientConfig::default();
+        config.algorithms.ciphers = Some(vec!["aes128-ctr".to_string()]);
+        let preferred = config.russh_config().unwrap().preferred;
+        assert_eq!(&*preferred.cipher, &[russh::cipher::AES_128_CTR]);
+
+        config.algorithms.macs = Some(vec!["hmac-md5".to_string()]);
+        assert!(matches!(config.russh_config(), Err(TerminalError::InvalidRequest(_))));
+    }
+
+    #[tokio::test]
+    async fn test_connect_rejects_unknown_algorithm_before_connecting() {
+        let mut config = SshClientConfig {
+            host: "unresolvable.invalid".to_string(),
+            ..Default::default()
+        };
+        config.algorithms.kex = Some(vec!["sntrup761x25519-sha512@openssh.com".to_string()]);
+        let mut client = SshClient::new(config);
+
+        // 算法错误先于主机解析失败返回
+        match client.connect().await {
+            Err(TerminalError::InvalidRequest(message)) => {
+                assert!(message.contains("sntrup761x25519-sha512@openssh.com"));
+            }
+            other => panic!("unexpected result: {:?}", other.err()),
+        }
+    }
+
+    #[test]
+    fn test_filter_addresses_by_family() {
+        let v4: SocketAddr = "192.0.2.1:22".parse().unwrap();
+        let v6: SocketAddr = "[2001:db8::1]:22".parse().unwrap();
+        let v6_second: SocketAddr = "[2001:db8::2]:22".parse().unwrap();
+        let resolved = vec![v6, v4, v6_second];
+
+        assert_eq!(filter_addresses(resolved.clone(), None).unwrap(), resolved);
+        assert_eq!(filter_addresses(resolved.clone(), Some(AddrFamily::Inet)).unwrap(), vec![v4]);
+        assert_eq!(
+            filter_addresses(resolved, Some(AddrFamily::Inet6)).unwrap(),
+            vec![v6, v6_second]
+        );
+
+        assert!(filter_addresses(vec![v4], Some(AddrFamily::Inet6)).is_err());
+        assert!(filter_addresses(Vec::new(), None).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_connect_first_tries_each_address() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let open = listener.local_addr().unwrap();
+        // 绑定后立即关闭的端口上没有服务，连接被拒绝
+        let closed = {
+            let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+            listener.local_addr().unwrap()
+        };
+
+        let (_stream, addr) = connect_first(&[closed, open], TcpStream::connect).await.unwrap();
+        assert_eq!(addr, open);
+
+        // 模拟的解析结果：只有最后一个地址可以连接
+        let addrs: Vec<SocketAddr> = vec![
+            "[2001:db8::1]:22".parse().unwrap(),
+            "192.0.2.1:22".parse().unwrap(),
+            "192.0.2.2:22".parse().unwrap(),
+        ];
+        let reachable = addrs[2];
+        let mut attempts = Vec::new();
+        let (value, addr) = connect_first(&addrs, |addr| {
+            attempts.push(addr);
+            async move {
+                if addr == reachable {
+                    Ok(addr.port())
+                } else {
+                    Err(std::io::Error::from(std::io::ErrorKind::ConnectionRefused))
+                }
+            }
+        })
+        .await
+        .unwrap();
+        assert_eq!((value, addr), (22, addrs[2]));
+        assert_eq!(attempts, addrs);
+
+        // 全部失败时报告最后的错误和尝试过的地址
+        let message = connect_first(&addrs, |addr| async move {
+            Err::<(), _>(std::io::Error::other(format!("failed {}", addr)))
+        })
+        .await
+        .unwrap_err();
+        assert!(message.contains("failed 192.0.2.2:22"), "{}", message);
+        for addr in &addrs {
+            assert!(message.contains(&addr.to_string()), "{}", message);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_connect_address_family_mismatch() {
+        let mut client = SshClient::new(SshClientConfig {
+            host: "127.0.0.1".to_string(),
+            port: 1,
+            address_family: Some(AddrFamily::Inet6),
+            ..Default::default()
+        });
+
+        match client.connect().await {
+            Err(TerminalError::HostResolutionFailed(message)) => {
+                assert!(message.contains("IPv6"), "{}", message);
+            }
+            other => panic!("unexpected result: {:?}", other.err()),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_connect_banner_timeout() {
+        // 接受 TCP 连接但不发送任何数据的服务器
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = tokio::spawn(async move {
+            let (socket, _) = listener.accept().await.unwrap();
+            tokio::time::sleep(Duration::from_secs(10)).await;
+            drop(socket);
+        });
+
+        let mut client = SshClient::new(SshClientConfig {
+            host: "127.0.0.1".to_string(),
+            port,
+            banner_timeout: 1,
+            ..Default::default()
+        });
+
+        assert_eq!(client.resolved_addr(), None);
+
+        let started = std::time::Instant::now();
+        match client.connect().await {
+            Err(TerminalError::ConnectionTimeout(message)) => {
+                assert!(message.contains("SSH 版本行"), "{}", message);
+            }
+            other => panic!("unexpected result: {:?}", other.err()),
+        }
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert!(!client.is_connected());
+        // TCP 连接已经建立，记录实际连接的地址
+        assert_eq!(
+            client.resolved_addr(),
+            Some(SocketAddr::from(([127, 0, 0, 1], port)))
+        );
+        server.abort();
+    }
+
+    #[tokio::test]
+    async fn test_check_server_key_uses_known_hosts() {
+        use russh::keys::key::KeyPair;
+        use russh::keys::PublicKeyBase64;
+
+        let generate = || KeyPair::generate_ed25519().unwrap().clone_public_key().unwrap();
+        let (recorded, presented) = (generate(), generate());
+
+        let dir = std::env::temp_dir().join(format!("ssh-known-hosts-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("known_hosts");
+        std::fs::write(
+            &path,
+            format!("[example.com]:2222 {} {}\n", recorded.name(), recorded.public_key_base64()),
+        )
+        .unwrap();
+
+        let mut handler = SshClientHandler::new("example.com".to_string(), 2222, vec![path.clone()]);
+        assert!(handler.check_server_key(&recorded).await.unwrap());
+        assert!(handler.host_key_verified);
+
+        // 记录的密钥不同时拒绝连接
+        let mut handler = SshClientHandler::new("example.com".to_string(), 2222, vec![path.clone()]);
+        let err = handler.check_server_key(&presented).await.unwrap_err();
+        assert!(err.to_string().contains(&path.display().to_string()), "{}", err);
+
+        // 没有记录的主机仍然接受
+        let mut handler = SshClientHandler::new("other.example.com".to_string(), 22, vec![path]);
+        assert!(handler.check_server_key(&presented).await.unwrap());
+        assert!(!handler.host_key_verified);
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn test_changed_host_key_asks_client() {
+        use crate::rpc::server::NotificationSender;
+        use crate::ssh::host_key::HostKeyPrompts;
+        use russh::keys::key::KeyPair;
+        use russh::keys::PublicKeyBase64;
+
+        let generate = || KeyPair::generate_ed25519().unwrap().clone_public_key().unwrap();
+        let (recorded, presented) = (generate(), generate());
+
+        let dir = std::env::temp_dir().join(format!("ssh-known-hosts-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("known_hosts");
+        std::fs::write(
+            &path,
+            format!("example.com {} {}\n", recorded.name(), recorded.public_key_base64()),
+        )
+        .unwrap();
+
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let prompts = HostKeyPrompts::new();
+        let callback = prompts.callback("session-1".to_string(), NotificationSender::new_for_test(tx));
+        let handler = || {
+            SshClientHandler::new("example.com".to_string(), 22, vec![path.clone()])
+                .with_host_key_changed(Some(callback.clone()))
+        };
+
+        // 拒绝时连接失败
+        let mut rejected = handler();
+        let check = tokio::spawn({
+            let presented = presented.clone();
+            async move { rejected.check_server_key(&presented).await }
+        });
+        let notification = rx.recv().await.unwrap();
+        assert_eq!(notification.method, "session.host_key_changed");
+        let params = notification.params.unwrap();
+        assert_eq!(params["session_id"], "session-1");
+        assert_eq!(params["host"], "example.com");
+        assert_eq!(params["key_type"], "ssh-ed25519");
+        assert_eq!(params["old_fingerprint"], known_hosts::fingerprint(&recorded));
+        assert_eq!(params["new_fingerprint"], known_hosts::fingerprint(&presented));
+        assert_eq!(params["line"], 1);
+        prompts.resolve("session-1", HostKeyDecision::Reject).unwrap();
+        assert!(check.await.unwrap().is_err());
+
+        // 永久接受时继续连接并写入 known_hosts
+        let mut accepted = handler();
+        let check = tokio::spawn({
+            let presented = presented.clone();
+            async move {
+                let result = accepted.check_server_key(&presented).await;
+                (result, accepted.host_key_verified)
+            }
+        });
+        rx.recv().await.unwrap();
+        prompts.resolve("session-1", HostKeyDecision::AcceptPermanent).unwrap();
+        let (result, verified) = check.await.unwrap();
+        assert!(result.unwrap());
+        assert!(verified);
+        assert_eq!(
+            known_hosts::check_host_key(std::slice::from_ref(&path), "example.com", 22, &presented),
+            HostKeyStatus::Trusted
+        );
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
     #[test]
     fn test_ssh_client_from_params_with_password() {
         let client = SshClient::from_params(
@@ -382,6 +1037,37 @@ mod tests {
         assert!(matches!(client.config.auth_method, AuthMethod::Password(_)));
     }
 
+    #[test]
+    fn test_ssh_client_config_debug_redacts_password() {
+        let client = SshClient::from_params(
+            "example.com".to_string(),
+            None,
+            Some("testuser".to_string()),
+            None,
+            Some("testpass".to_string()),
+        );
+
+        let debug = format!("{:?}", client.config);
+        assert!(!debug.contains("testpass"));
+        assert!(debug.contains("example.com"));
+    }
+
+    #[tokio::test]
+    async fn test_connect_error_does_not_include_password() {
+        // 端口 1 上没有 SSH 服务，连接会立即失败
+        let mut client = SshClient::from_params(
+            "127.0.0.1".to_string(),
+            Some(1),
+            Some("testuser".to_string()),
+            None,
+            Some("testpass".to_string()),
+        );
+
+        let err = client.connect().await.unwrap_err();
+        assert!(!err.to_string().contains("testpass"));
+        assert!(!format!("{:?}", err).contains("testpass"));
+    }
+
     #[test]
     fn test_ssh_client_from_params_with_key() {
         let client = SshClient::from_params(
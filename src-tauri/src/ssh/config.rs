@@ -0,0 +1,314 @@
+//! OpenSSH 配置文件解析
+//!
+//! 从 `~/.ssh/config` 中读取主机别名对应的 `HostName`、`Port`、`User`、
+//! `IdentityFile` 和 `ProxyJump`，用于补全前端未显式提供的连接参数。
+//!
+//! 只实现常用子集：
+//! - `Host` 模式支持 `*`、`?` 通配符和 `!` 取反，`Host *` 可作为全局默认值
+//! - 与 OpenSSH 一致，每个选项以第一次出现的值为准
+//! - `Include` 支持绝对路径、`~/` 路径和相对配置文件所在目录的路径，不展开通配符
+//! - `Match` 块不做求值，其中的选项全部忽略
+
+use std::path::{Path, PathBuf};
+
+use crate::utils::path::expand_tilde;
+
+/// `Include` 最大嵌套深度，防止循环引用
+const MAX_INCLUDE_DEPTH: usize = 16;
+
+/// 从 ssh_config 解析出的主机配置
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SshHostConfig {
+    /// 实际主机名
+    pub host_name: Option<String>,
+    /// 端口
+    pub port: Option<u16>,
+    /// 用户名
+    pub user: Option<String>,
+    /// 私钥文件路径（已展开 `~`）
+    pub identity_file: Option<String>,
+    /// 跳板机
+    pub proxy_jump: Option<String>,
+}
+
+impl SshHostConfig {
+    /// 是否没有任何选项
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// 默认配置文件路径（`~/.ssh/config`）
+pub fn default_config_path() -> Option<PathBuf> {
+    dirs::home_dir().map(|home| home.join(".ssh").join("config"))
+}
+
+/// 在配置文件中查找主机别名对应的配置
+///
+/// 文件不存在或无法读取时返回空配置。
+pub fn resolve_host(path: &Path, alias: &str) -> SshHostConfig {
+    let mut config = SshHostConfig::default();
+    parse_file(path, alias, &mut config, 0);
+
+    // HostName 支持 %h 代表别名本身
+    if let Some(host_name) = &config.host_name {
+        config.host_name = Some(host_name.replace("%h", alias));
+    }
+    config
+}
+
+/// 解析单个配置文件，将匹配的选项合并到 `config`
+fn parse_file(path: &Path, alias: &str, config: &mut SshHostConfig, depth: usize) {
+    if depth > MAX_INCLUDE_DEPTH {
+        tracing::warn!("ssh_config Include 嵌套过深: {}", path.display());
+        return;
+    }
+
+    let content = match std::fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) => {
+            tracing::debug!("无法读取 ssh_config {}: {}", path.display(), e);
+            return;
+        }
+    };
+    let base_dir = path.parent().unwrap_or_else(|| Path::new("."));
+
+    // 文件开头、第一个 Host 之前的选项对所有主机生效
+    let mut active = true;
+
+    for line in content.lines() {
+        let Some((key, value)) = split_line(line) else {
+            continue;
+        };
+
+        match key.to_ascii_lowercase().as_str() {
+            "host" => active = host_matches(value, alias),
+            "match" => active = false,
+            "include" if active => {
+                for include in value.split_whitespace() {
+                    let include_path = resolve_include(base_dir, include);
+                    parse_file(&include_path, alias, config, depth + 1);
+                }
+            }
+            _ if !active => {}
+            "hostname" => set_once(&mut config.host_name, value.to_string()),
+            "user" => set_once(&mut config.user, value.to_string()),
+            "identityfile" => set_once(&mut config.identity_file, expand_tilde(value)),
+            "proxyjump" => set_once(&mut config.proxy_jump, value.to_string()),
+            "port" => match value.parse() {
+                Ok(port) => set_once(&mut config.port, port),
+                Err(_) => tracing::warn!("ssh_config 中的端口无效: {}", value),
+            },
+            _ => {}
+        }
+    }
+}
+
+/// 拆分一行为选项名和值，支持 `Key Value` 与 `Key=Value` 两种写法
+fn split_line(line: &str) -> Option<(&str, &str)> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+        return None;
+    }
+
+    let end = line.find(|c: char| c.is_whitespace() || c == '=')?;
+    let (key, rest) = line.split_at(end);
+    let value = rest
+        .trim_start()
+        .strip_prefix('=')
+        .unwrap_or(rest)
+        .trim();
+    let value = value
+        .strip_prefix('"')
+        .and_then(|v| v.strip_suffix('"'))
+        .unwrap_or(value);
+
+    if value.is_empty() {
+        None
+    } else {
+        Some((key, value))
+    }
+}
+
+/// 仅在尚未设置时写入（第一次出现的值优先）
+fn set_once<T>(slot: &mut Option<T>, value: T) {
+    if slot.is_none() {
+        *slot = Some(value);
+    }
+}
+
+/// 解析 Include 路径
+fn resolve_include(base_dir: &Path, include: &str) -> PathBuf {
+    let expanded = PathBuf::from(expand_tilde(include));
+    if expanded.is_absolute() {
+        expanded
+    } else {
+        base_dir.join(expanded)
+    }
+}
+
+/// 检查 `Host` 行中的模式列表是否匹配别名
+///
+/// 任一取反模式匹配时整体不匹配。
+fn host_matches(patterns: &str, alias: &str) -> bool {
+    let mut matched = false;
+    for pattern in patterns.split_whitespace() {
+        if let Some(negated) = pattern.strip_prefix('!') {
+            if wildcard_match(negated, alias) {
+                return false;
+            }
+        } else if wildcard_match(pattern, alias) {
+            matched = true;
+        }
+    }
+    matched
+}
+
+/// 通配符匹配：`*` 匹配任意字符序列，`?` 匹配单个字符
+pub(super) fn wildcard_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+
+    let (mut p, mut t) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, t));
+            p += 1;
+        } else if let Some((star_p, star_t)) = star {
+            p = star_p + 1;
+            t = star_t + 1;
+            star = Some((star_p, star_t + 1));
+        } else {
+            return false;
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 在临时目录中写入配置文件，返回目录路径
+    fn write_config_dir(files: &[(&str, &str)]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("ssh-config-test-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        for (name, content) in files {
+            std::fs::write(dir.join(name), content).unwrap();
+        }
+        dir
+    }
+
+    #[test]
+    fn test_resolve_alias() {
+        let dir = write_config_dir(&[(
+            "config",
+            "# 开发机\n\
+             Host dev\n\
+             \x20   HostName dev.example.com\n\
+             \x20   Port 2222\n\
+             \x20   User alice\n\
+             \x20   IdentityFile /keys/dev_ed25519\n\
+             \x20   ProxyJump bastion\n\
+             \n\
+             Host other\n\
+             \x20   HostName other.example.com\n",
+        )]);
+
+        let config = resolve_host(&dir.join("config"), "dev");
+        assert_eq!(
+            config,
+            SshHostConfig {
+                host_name: Some("dev.example.com".to_string()),
+                port: Some(2222),
+                user: Some("alice".to_string()),
+                identity_file: Some("/keys/dev_ed25519".to_string()),
+                proxy_jump: Some("bastion".to_string()),
+            }
+        );
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_first_value_wins_and_wildcard_defaults() {
+        let dir = write_config_dir(&[(
+            "config",
+            "Host dev\n\
+             \x20   User alice\n\
+             Host *\n\
+             \x20   User nobody\n\
+             \x20   Port=2200\n",
+        )]);
+        let path = dir.join("config");
+
+        let dev = resolve_host(&path, "dev");
+        assert_eq!(dev.user.as_deref(), Some("alice"));
+        assert_eq!(dev.port, Some(2200));
+
+        let other = resolve_host(&path, "unknown.example.com");
+        assert_eq!(other.user.as_deref(), Some("nobody"));
+        assert!(other.host_name.is_none());
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_include_relative_to_config_dir() {
+        let dir = write_config_dir(&[
+            ("config", "Include hosts.conf\n\nHost *\n    User fallback\n"),
+            ("hosts.conf", "Host db-*\n    HostName %h.internal\n    User dba\n"),
+        ]);
+
+        let config = resolve_host(&dir.join("config"), "db-01");
+        assert_eq!(config.host_name.as_deref(), Some("db-01.internal"));
+        assert_eq!(config.user.as_deref(), Some("dba"));
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_include_cycle_terminates() {
+        let dir = write_config_dir(&[("config", "Include config\nHost *\n    Port 2022\n")]);
+
+        let config = resolve_host(&dir.join("config"), "any");
+        assert_eq!(config.port, Some(2022));
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_negated_pattern_and_match_block() {
+        let dir = write_config_dir(&[(
+            "config",
+            "Host * !private\n    User public\nMatch user root\n    Port 2022\n",
+        )]);
+        let path = dir.join("config");
+
+        assert_eq!(resolve_host(&path, "web").user.as_deref(), Some("public"));
+        assert!(resolve_host(&path, "private").is_empty());
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_missing_file_is_empty() {
+        let config = resolve_host(Path::new("/nonexistent/ssh/config"), "dev");
+        assert!(config.is_empty());
+    }
+
+    #[test]
+    fn test_wildcard_match() {
+        assert!(wildcard_match("*", "anything"));
+        assert!(wildcard_match("*.example.com", "a.example.com"));
+        assert!(wildcard_match("web-?", "web-1"));
+        assert!(!wildcard_match("web-?", "web-10"));
+        assert!(!wildcard_match("dev", "dev2"));
+    }
+}
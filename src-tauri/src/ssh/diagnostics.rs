@@ -0,0 +1,326 @@
+//! SSH 握手诊断
+//!
+//! russh 在握手完成后不会公开协商结果，因此在 TCP 流上记录服务器发来的前几 KB 数据：
+//! 其中的版本行和第一个 KEXINIT 包都是明文。握手完成后解析服务器支持的算法列表，
+//! 再按与 russh 相同的规则（客户端优先级中第一个服务器也支持的算法）得出协商结果。
+
+use std::io;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+
+use russh::Preferred;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+use crate::rpc::types::SshDiagnostics;
+
+/// 最多记录的字节数，足够容纳版本行和 KEXINIT 包
+const MAX_RECORDED_BYTES: usize = 16 * 1024;
+
+/// SSH_MSG_KEXINIT 消息号
+const MSG_KEXINIT: u8 = 20;
+
+/// kex 列表中的扩展标记，不是真正的算法
+const KEX_EXTENSION_MARKERS: &[&str] = &[
+    "ext-info-c",
+    "ext-info-s",
+    "kex-strict-c-v00@openssh.com",
+    "kex-strict-s-v00@openssh.com",
+];
+
+/// 记录读取数据的流包装器
+///
+/// 只记录前 `MAX_RECORDED_BYTES` 字节，之后直接透传。
+pub struct HandshakeRecorder<S> {
+    inner: S,
+    recorded: Arc<Mutex<Vec<u8>>>,
+}
+
+impl<S> HandshakeRecorder<S> {
+    /// 包装流，返回包装器和已记录数据的共享句柄
+    pub fn new(inner: S) -> (Self, Arc<Mutex<Vec<u8>>>) {
+        let recorded = Arc::new(Mutex::new(Vec::new()));
+        (
+            Self {
+                inner,
+                recorded: recorded.clone(),
+            },
+            recorded,
+        )
+    }
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for HandshakeRecorder<S> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let before = buf.filled().len();
+        let result = Pin::new(&mut self.inner).poll_read(cx, buf);
+
+        if let Poll::Ready(Ok(())) = result {
+            let mut recorded = self.recorded.lock().unwrap();
+            let room = MAX_RECORDED_BYTES.saturating_sub(recorded.len());
+            if room > 0 {
+                let new_data = &buf.filled()[before..];
+                recorded.extend_from_slice(&new_data[..new_data.len().min(room)]);
+            }
+        }
+        result
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for HandshakeRecorder<S> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.inner).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
+/// 服务器 KEXINIT 中的算法列表
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct ServerKexInit {
+    kex: Vec<String>,
+    host_key: Vec<String>,
+    cipher: Vec<String>,
+    mac: Vec<String>,
+    compression: Vec<String>,
+}
+
+/// 根据记录的服务器数据和客户端偏好生成诊断信息
+///
+/// 找不到版本行时返回 `None`；KEXINIT 无法解析时只填充版本。
+pub fn analyze(recorded: &[u8], preferred: &Preferred) -> Option<SshDiagnostics> {
+    let (server_version, rest) = split_version_line(recorded)?;
+    let mut diagnostics = SshDiagnostics {
+        server_version,
+        ..Default::default()
+    };
+
+    let Some(server) = parse_kexinit(rest) else {
+        tracing::debug!("无法解析服务器 KEXINIT");
+        return Some(diagnostics);
+    };
+
+    let kex: Vec<&str> = preferred
+        .kex
+        .iter()
+        .map(|n| n.as_ref())
+        .filter(|n| !KEX_EXTENSION_MARKERS.contains(n))
+        .collect();
+    diagnostics.kex = select(&kex, &server.kex);
+    diagnostics.host_key = select(
+        &preferred.key.iter().map(|n| n.as_ref()).collect::<Vec<_>>(),
+        &server.host_key,
+    );
+    diagnostics.cipher = select(
+        &preferred.cipher.iter().map(|n| n.as_ref()).collect::<Vec<_>>(),
+        &server.cipher,
+    );
+    // AEAD 密码自带完整性校验，不使用单独的 MAC
+    if !diagnostics.cipher.as_deref().is_some_and(is_aead) {
+        diagnostics.mac = select(
+            &preferred.mac.iter().map(|n| n.as_ref()).collect::<Vec<_>>(),
+            &server.mac,
+        );
+    }
+    diagnostics.compression = select(
+        &preferred.compression.iter().map(|n| n.as_ref()).collect::<Vec<_>>(),
+        &server.compression,
+    );
+
+    Some(diagnostics)
+}
+
+/// 记录的数据中是否已经包含完整的版本行
+pub fn has_version_line(recorded: &[u8]) -> bool {
+    split_version_line(recorded).is_some()
+}
+
+/// 取出 `SSH-` 开头的版本行，返回版本字符串和之后的数据
+///
+/// RFC 4253 允许服务器在版本行之前发送其他文本行。
+fn split_version_line(data: &[u8]) -> Option<(String, &[u8])> {
+    let mut rest = data;
+    loop {
+        let end = rest.iter().position(|&b| b == b'\n')?;
+        let line = &rest[..end];
+        rest = &rest[end + 1..];
+        if line.starts_with(b"SSH-") {
+            let line = line.strip_suffix(b"\r").unwrap_or(line);
+            return Some((String::from_utf8_lossy(line).into_owned(), rest));
+        }
+    }
+}
+
+/// 解析版本行之后的第一个二进制包（KEXINIT）
+fn parse_kexinit(data: &[u8]) -> Option<ServerKexInit> {
+    let packet_length = u32::from_be_bytes(data.get(..4)?.try_into().ok()?) as usize;
+    let padding_length = *data.get(4)? as usize;
+    let payload = data.get(5..4 + packet_length)?;
+    let payload = payload.get(..payload.len().checked_sub(padding_length)?)?;
+
+    if payload.first() != Some(&MSG_KEXINIT) {
+        return None;
+    }
+
+    // 跳过消息号和 16 字节 cookie
+    let mut reader = payload.get(17..)?;
+    let mut lists = Vec::with_capacity(8);
+    for _ in 0..8 {
+        let len = u32::from_be_bytes(reader.get(..4)?.try_into().ok()?) as usize;
+        let list = reader.get(4..4 + len)?;
+        reader = &reader[4 + len..];
+        lists.push(
+            String::from_utf8_lossy(list)
+                .split(',')
+                .filter(|s| !s.is_empty())
+                .map(str::to_string)
+                .collect::<Vec<_>>(),
+        );
+    }
+
+    // 依次为 kex、主机密钥、密码(c2s, s2c)、MAC(c2s, s2c)、压缩(c2s, s2c)
+    let mut lists = lists.into_iter();
+    let kex = lists.next()?;
+    let host_key = lists.next()?;
+    let cipher = lists.next()?;
+    let _ = lists.next();
+    let mac = lists.next()?;
+    let _ = lists.next();
+    let compression = lists.next()?;
+
+    Some(ServerKexInit {
+        kex,
+        host_key,
+        cipher,
+        mac,
+        compression,
+    })
+}
+
+/// 选出客户端偏好中第一个服务器也支持的算法
+fn select(client: &[&str], server: &[String]) -> Option<String> {
+    client
+        .iter()
+        .find(|c| server.iter().any(|s| s == *c))
+        .map(|c| c.to_string())
+}
+
+/// 是否为 AEAD 密码
+fn is_aead(cipher: &str) -> bool {
+    cipher.contains("poly1305") || cipher.contains("gcm")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    /// 构造服务器的版本行和 KEXINIT 包
+    fn server_handshake(lists: [&str; 8]) -> Vec<u8> {
+        let mut payload = vec![MSG_KEXINIT];
+        payload.extend_from_slice(&[0u8; 16]);
+        for list in lists.iter().chain(["", ""].iter()) {
+            payload.extend_from_slice(&(list.len() as u32).to_be_bytes());
+            payload.extend_from_slice(list.as_bytes());
+        }
+        payload.push(0); // first_kex_packet_follows
+        payload.extend_from_slice(&[0u8; 4]);
+
+        let padding = 8 - (payload.len() + 5) % 8 + 4;
+        let mut data = b"banner line\r\nSSH-2.0-OpenSSH_9.6\r\n".to_vec();
+        data.extend_from_slice(&((payload.len() + padding + 1) as u32).to_be_bytes());
+        data.push(padding as u8);
+        data.extend_from_slice(&payload);
+        data.resize(data.len() + padding, 0);
+        data
+    }
+
+    #[test]
+    fn test_analyze_negotiated_algorithms() {
+        let data = server_handshake([
+            "sntrup761x25519-sha512@openssh.com,curve25519-sha256,ext-info-s,kex-strict-s-v00@openssh.com",
+            "rsa-sha2-512,ssh-ed25519",
+            "aes128-ctr,aes256-ctr",
+            "aes128-ctr,aes256-ctr",
+            "hmac-sha1,hmac-sha2-256",
+            "hmac-sha1,hmac-sha2-256",
+            "none,zlib@openssh.com",
+            "none,zlib@openssh.com",
+        ]);
+
+        let diagnostics = analyze(&data, &Preferred::default()).unwrap();
+        assert_eq!(diagnostics.server_version, "SSH-2.0-OpenSSH_9.6");
+        assert_eq!(diagnostics.kex.as_deref(), Some("curve25519-sha256"));
+        assert_eq!(diagnostics.host_key.as_deref(), Some("ssh-ed25519"));
+        // 客户端偏好顺序优先
+        assert_eq!(diagnostics.cipher.as_deref(), Some("aes256-ctr"));
+        assert_eq!(diagnostics.mac.as_deref(), Some("hmac-sha2-256"));
+        assert_eq!(diagnostics.compression.as_deref(), Some("none"));
+    }
+
+    #[test]
+    fn test_analyze_aead_cipher_has_no_mac() {
+        let data = server_handshake([
+            "curve25519-sha256",
+            "ssh-ed25519",
+            "chacha20-poly1305@openssh.com",
+            "chacha20-poly1305@openssh.com",
+            "hmac-sha2-256",
+            "hmac-sha2-256",
+            "none",
+            "none",
+        ]);
+
+        let diagnostics = analyze(&data, &Preferred::default()).unwrap();
+        assert_eq!(diagnostics.cipher.as_deref(), Some("chacha20-poly1305@openssh.com"));
+        assert!(diagnostics.mac.is_none());
+    }
+
+    #[test]
+    fn test_analyze_version_only() {
+        let diagnostics = analyze(b"SSH-2.0-dropbear\r\n\x00\x00", &Preferred::default()).unwrap();
+        assert_eq!(diagnostics.server_version, "SSH-2.0-dropbear");
+        assert!(diagnostics.kex.is_none());
+
+        assert!(analyze(b"not ssh", &Preferred::default()).is_none());
+    }
+
+    #[test]
+    fn test_has_version_line() {
+        assert!(!has_version_line(b""));
+        assert!(!has_version_line(b"SSH-2.0-partial"));
+        assert!(!has_version_line(b"Welcome\r\n"));
+        assert!(has_version_line(b"Welcome\r\nSSH-2.0-OpenSSH_9.6\r\n"));
+    }
+
+    #[tokio::test]
+    async fn test_recorder_captures_reads() {
+        let (client, mut server) = tokio::io::duplex(64);
+        let (mut recorder, recorded) = HandshakeRecorder::new(client);
+
+        server.write_all(b"SSH-2.0-test\r\n").await.unwrap();
+        let mut buf = [0u8; 14];
+        recorder.read_exact(&mut buf).await.unwrap();
+
+        recorder.write_all(b"SSH-2.0-client\r\n").await.unwrap();
+        let mut echoed = [0u8; 16];
+        server.read_exact(&mut echoed).await.unwrap();
+
+        // 只记录读取方向的数据
+        assert_eq!(recorded.lock().unwrap().as_slice(), b"SSH-2.0-test\r\n");
+    }
+}
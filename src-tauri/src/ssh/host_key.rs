@@ -0,0 +1,210 @@
+//! 主机密钥变更确认
+//!
+//! known_hosts 中记录的密钥与服务器提供的不同时默认拒绝连接。设置了
+//! [`HostKeyChangedCallback`] 时改为暂停握手，等待回调给出决定：只接受这一次、
+//! 接受并写入 known_hosts，或者拒绝。
+//!
+//! [`HostKeyPrompts`] 实现的回调发送 `session.host_key_changed` 通知，等待通过
+//! [`HostKeyPrompts::resolve`] 提交的决定，超时没有决定时按拒绝处理。管理器中的 SSH 会话
+//! 还没有真正连接，所以客户端提交决定的 RPC 方法要等 SSH 会话接入后再注册。
+
+use std::collections::HashMap;
+use std::fmt;
+use std::future::Future;
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use serde::Serialize;
+use tokio::sync::oneshot;
+
+use crate::rpc::server::NotificationSender;
+use crate::rpc::types::HostKeyDecision;
+use crate::utils::error::TerminalError;
+
+/// 等待客户端决定的默认时长
+pub const DEFAULT_DECISION_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// 服务器主机密钥与 known_hosts 记录不一致
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct HostKeyChange {
+    /// 主机名
+    pub host: String,
+    /// 端口
+    pub port: u16,
+    /// 密钥类型，如 `ssh-ed25519`
+    pub key_type: String,
+    /// known_hosts 中记录的密钥指纹（`SHA256:...`）
+    pub old_fingerprint: String,
+    /// 服务器提供的密钥指纹（`SHA256:...`）
+    pub new_fingerprint: String,
+    /// 记录所在的 known_hosts 文件
+    pub known_hosts_path: PathBuf,
+    /// 记录所在的行号（从 1 开始）
+    pub line: usize,
+}
+
+/// 回调返回的决定
+pub type HostKeyDecisionFuture = Pin<Box<dyn Future<Output = HostKeyDecision> + Send>>;
+
+/// 主机密钥变更时调用的回调
+#[derive(Clone)]
+pub struct HostKeyChangedCallback(Arc<dyn Fn(HostKeyChange) -> HostKeyDecisionFuture + Send + Sync>);
+
+impl HostKeyChangedCallback {
+    /// 用异步函数创建回调
+    pub fn new<F>(callback: F) -> Self
+    where
+        F: Fn(HostKeyChange) -> HostKeyDecisionFuture + Send + Sync + 'static,
+    {
+        Self(Arc::new(callback))
+    }
+
+    /// 调用回调并等待决定
+    pub async fn call(&self, change: HostKeyChange) -> HostKeyDecision {
+        (self.0)(change).await
+    }
+}
+
+impl fmt::Debug for HostKeyChangedCallback {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("HostKeyChangedCallback")
+    }
+}
+
+/// 等待客户端决定的主机密钥确认，按会话 ID 索引
+#[derive(Debug, Clone)]
+pub struct HostKeyPrompts {
+    /// 每个会话等待中的决定
+    pending: Arc<Mutex<HashMap<String, oneshot::Sender<HostKeyDecision>>>>,
+    /// 等待决定的最长时间
+    timeout: Duration,
+}
+
+impl HostKeyPrompts {
+    /// 创建确认表，等待时长为 [`DEFAULT_DECISION_TIMEOUT`]
+    pub fn new() -> Self {
+        Self::with_timeout(DEFAULT_DECISION_TIMEOUT)
+    }
+
+    /// 使用指定的等待时长创建确认表
+    pub fn with_timeout(timeout: Duration) -> Self {
+        Self {
+            pending: Arc::new(Mutex::new(HashMap::new())),
+            timeout,
+        }
+    }
+
+    /// 为会话创建回调：发送 `session.host_key_changed` 通知并等待客户端决定
+    ///
+    /// 通知发送失败或超时没有决定时拒绝。
+    pub fn callback(&self, session_id: String, sender: NotificationSender) -> HostKeyChangedCallback {
+        let prompts = self.clone();
+        HostKeyChangedCallback::new(move |change| {
+            let prompts = prompts.clone();
+            let session_id = session_id.clone();
+            let sender = sender.clone();
+            Box::pin(async move { prompts.ask(&session_id, &sender, &change).await })
+        })
+    }
+
+    /// 记录新的确认并等待决定
+    async fn ask(&self, session_id: &str, sender: &NotificationSender, change: &HostKeyChange) -> HostKeyDecision {
+        let (tx, rx) = oneshot::channel();
+        self.lock().insert(session_id.to_string(), tx);
+
+        if let Err(e) = sender.send_host_key_changed(session_id, change) {
+            tracing::error!("发送主机密钥变更通知失败: {}", e);
+            self.lock().remove(session_id);
+            return HostKeyDecision::Reject;
+        }
+
+        match tokio::time::timeout(self.timeout, rx).await {
+            Ok(Ok(decision)) => decision,
+            Ok(Err(_)) => HostKeyDecision::Reject,
+            Err(_) => {
+                tracing::warn!("等待主机密钥确认超时，拒绝连接: {}", session_id);
+                self.lock().remove(session_id);
+                HostKeyDecision::Reject
+            }
+        }
+    }
+
+    /// 提交客户端的决定
+    ///
+    /// 会话没有等待中的确认时返回 `InvalidRequest`。
+    pub fn resolve(&self, session_id: &str, decision: HostKeyDecision) -> Result<(), TerminalError> {
+        let tx = self.lock().remove(session_id).ok_or_else(|| {
+            TerminalError::InvalidRequest(format!("{}: 没有等待确认的主机密钥", session_id))
+        })?;
+        tx.send(decision).map_err(|_| {
+            TerminalError::InvalidRequest(format!("{}: 主机密钥确认已结束", session_id))
+        })
+    }
+
+    /// 会话是否有等待中的确认
+    pub fn is_pending(&self, session_id: &str) -> bool {
+        self.lock().contains_key(session_id)
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, oneshot::Sender<HostKeyDecision>>> {
+        self.pending.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Default for HostKeyPrompts {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::sync::mpsc;
+
+    fn change() -> HostKeyChange {
+        HostKeyChange {
+            host: "example.com".to_string(),
+            port: 22,
+            key_type: "ssh-ed25519".to_string(),
+            old_fingerprint: "SHA256:old".to_string(),
+            new_fingerprint: "SHA256:new".to_string(),
+            known_hosts_path: PathBuf::from("/home/user/.ssh/known_hosts"),
+            line: 3,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_prompt_resolved_by_decision() {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let prompts = HostKeyPrompts::new();
+        let callback = prompts.callback("session-1".to_string(), NotificationSender::new_for_test(tx));
+
+        let task = tokio::spawn(async move { callback.call(change()).await });
+        let notification = rx.recv().await.unwrap();
+        assert_eq!(notification.method, "session.host_key_changed");
+        assert!(prompts.is_pending("session-1"));
+
+        prompts.resolve("session-1", HostKeyDecision::AcceptOnce).unwrap();
+        assert_eq!(task.await.unwrap(), HostKeyDecision::AcceptOnce);
+        assert!(!prompts.is_pending("session-1"));
+
+        // 已经决定过的确认不能再次提交
+        assert!(matches!(
+            prompts.resolve("session-1", HostKeyDecision::Reject),
+            Err(TerminalError::InvalidRequest(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_prompt_times_out_as_reject() {
+        let (tx, _rx) = mpsc::unbounded_channel();
+        let prompts = HostKeyPrompts::with_timeout(Duration::from_millis(20));
+        let callback = prompts.callback("session-1".to_string(), NotificationSender::new_for_test(tx));
+
+        assert_eq!(callback.call(change()).await, HostKeyDecision::Reject);
+        assert!(!prompts.is_pending("session-1"));
+    }
+}
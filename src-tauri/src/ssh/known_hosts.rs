@@ -0,0 +1,378 @@
+//! known_hosts 主机密钥校验
+//!
+//! 按顺序读取 known_hosts 文件（默认 `~/.ssh/known_hosts` 和 `/etc/ssh/ssh_known_hosts`），
+//! 查找与服务器主机名和密钥类型匹配的记录。支持的格式:
+//!
+//! - 逗号分隔的主机列表，`*`、`?` 通配符和 `!` 取反
+//! - 非 22 端口使用 `[host]:port` 的写法
+//! - `HashKnownHosts` 生成的 `|1|<salt>|<hash>` 哈希主机名（HMAC-SHA1）
+//! - `@revoked` 标记的吊销密钥
+//!
+//! `@cert-authority` 行暂不支持，直接跳过。
+
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use hmac::{Hmac, Mac};
+use russh::keys::key::PublicKey;
+use russh::keys::PublicKeyBase64;
+use sha1::Sha1;
+
+use super::config::wildcard_match;
+
+/// 系统级 known_hosts 文件
+#[cfg(unix)]
+const GLOBAL_KNOWN_HOSTS: &str = "/etc/ssh/ssh_known_hosts";
+
+/// 哈希主机名的前缀
+const HASHED_HOST_PREFIX: &str = "|1|";
+
+/// 主机密钥校验结果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HostKeyStatus {
+    /// 有与服务器密钥一致的记录
+    Trusted,
+    /// 没有这个主机、这种密钥类型的记录
+    Unknown,
+    /// 记录的同类型密钥与服务器提供的不同，`fingerprint` 为记录的密钥的指纹
+    Changed {
+        path: PathBuf,
+        line: usize,
+        fingerprint: String,
+    },
+    /// 服务器密钥被 `@revoked` 吊销
+    Revoked { path: PathBuf, line: usize },
+}
+
+/// 默认读取的 known_hosts 文件：用户文件在前，系统文件在后
+pub fn default_known_hosts_files() -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    if let Some(home) = dirs::home_dir() {
+        files.push(home.join(".ssh").join("known_hosts"));
+    }
+    #[cfg(unix)]
+    files.push(PathBuf::from(GLOBAL_KNOWN_HOSTS));
+    files
+}
+
+/// 在 known_hosts 文件中校验服务器密钥
+///
+/// 吊销优先于其他结果；任一记录与服务器密钥一致时信任；只有同类型的不同密钥时视为密钥已变更。
+/// 不存在或无法读取的文件被忽略。
+pub fn check_host_key(files: &[PathBuf], host: &str, port: u16, key: &PublicKey) -> HostKeyStatus {
+    let name = host_lookup_name(host, port);
+    let mut trusted = false;
+    let mut changed = None;
+
+    for path in files {
+        let Ok(content) = std::fs::read_to_string(path) else {
+            continue;
+        };
+
+        for (index, line) in content.lines().enumerate() {
+            let Some(entry) = parse_line(line) else {
+                continue;
+            };
+            if !hosts_match(entry.hosts, &name) {
+                continue;
+            }
+            let Ok(recorded) = russh::keys::parse_public_key_base64(entry.key) else {
+                continue;
+            };
+
+            let line = index + 1;
+            match entry.marker {
+                Some("@revoked") if recorded == *key => {
+                    return HostKeyStatus::Revoked {
+                        path: path.clone(),
+                        line,
+                    };
+                }
+                Some(_) => {}
+                None if recorded == *key => trusted = true,
+                None if recorded.name() == key.name() => {
+                    changed.get_or_insert_with(|| (path.clone(), line, fingerprint(&recorded)));
+                }
+                None => {}
+            }
+        }
+    }
+
+    match (trusted, changed) {
+        (true, _) => HostKeyStatus::Trusted,
+        (false, Some((path, line, fingerprint))) => HostKeyStatus::Changed {
+            path,
+            line,
+            fingerprint,
+        },
+        (false, None) => HostKeyStatus::Unknown,
+    }
+}
+
+/// OpenSSH 格式的密钥指纹（`SHA256:<base64>`）
+pub fn fingerprint(key: &PublicKey) -> String {
+    format!("SHA256:{}", key.fingerprint())
+}
+
+/// 把主机密钥追加到 known_hosts 文件，文件或目录不存在时创建
+///
+/// 同一主机的旧记录保留在原处；有一致的记录时校验结果为可信，旧记录不再起作用。
+pub fn add_host_key(path: &Path, host: &str, port: u16, key: &PublicKey) -> std::io::Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let mut file = std::fs::OpenOptions::new().create(true).append(true).open(path)?;
+    // 文件末尾没有换行时先补上，避免和上一条记录连在一起
+    let needs_newline = std::fs::read(path)
+        .map(|content| content.last().is_some_and(|&b| b != b'\n'))
+        .unwrap_or(false);
+    if needs_newline {
+        file.write_all(b"\n")?;
+    }
+    writeln!(
+        file,
+        "{} {} {}",
+        host_lookup_name(host, port),
+        key.name(),
+        key.public_key_base64()
+    )
+}
+
+/// known_hosts 中记录主机时使用的名称，非 22 端口写作 `[host]:port`
+fn host_lookup_name(host: &str, port: u16) -> String {
+    if port == 22 {
+        host.to_string()
+    } else {
+        format!("[{}]:{}", host, port)
+    }
+}
+
+/// known_hosts 中的一条记录
+struct KnownHostEntry<'a> {
+    /// `@revoked` 或 `@cert-authority`
+    marker: Option<&'a str>,
+    /// 逗号分隔的主机模式
+    hosts: &'a str,
+    /// Base64 编码的公钥
+    key: &'a str,
+}
+
+/// 解析一行记录，注释、空行和字段不全的行返回 `None`
+fn parse_line(line: &str) -> Option<KnownHostEntry<'_>> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+        return None;
+    }
+
+    let mut fields = line.split_whitespace();
+    let mut first = fields.next()?;
+    let marker = if first.starts_with('@') {
+        let marker = first;
+        first = fields.next()?;
+        Some(marker)
+    } else {
+        None
+    };
+    let _key_type = fields.next()?;
+    let key = fields.next()?;
+
+    Some(KnownHostEntry {
+        marker,
+        hosts: first,
+        key,
+    })
+}
+
+/// 检查主机模式列表是否匹配
+///
+/// 任一取反模式匹配时整体不匹配。
+fn hosts_match(patterns: &str, name: &str) -> bool {
+    let mut matched = false;
+    for pattern in patterns.split(',') {
+        if let Some(negated) = pattern.strip_prefix('!') {
+            if wildcard_match(negated, name) {
+                return false;
+            }
+        } else if let Some(hashed) = pattern.strip_prefix(HASHED_HOST_PREFIX) {
+            matched |= hashed_host_matches(hashed, name);
+        } else if wildcard_match(pattern, name) {
+            matched = true;
+        }
+    }
+    matched
+}
+
+/// 检查 `<salt>|<hash>` 形式的哈希主机名，hash 为以 salt 为密钥对主机名计算的 HMAC-SHA1
+fn hashed_host_matches(hashed: &str, name: &str) -> bool {
+    let Some((salt, hash)) = hashed.split_once('|') else {
+        return false;
+    };
+    let (Ok(salt), Ok(hash)) = (BASE64.decode(salt), BASE64.decode(hash)) else {
+        return false;
+    };
+    let Ok(mut mac) = Hmac::<Sha1>::new_from_slice(&salt) else {
+        return false;
+    };
+    mac.update(name.as_bytes());
+    mac.verify_slice(&hash).is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use russh::keys::key::KeyPair;
+
+    fn generate_key() -> PublicKey {
+        KeyPair::generate_ed25519()
+            .unwrap()
+            .clone_public_key()
+            .unwrap()
+    }
+
+    /// 以 OpenSSH `HashKnownHosts` 的格式生成哈希主机名
+    fn hash_host(salt: &[u8], name: &str) -> String {
+        let mut mac = Hmac::<Sha1>::new_from_slice(salt).unwrap();
+        mac.update(name.as_bytes());
+        format!(
+            "|1|{}|{}",
+            BASE64.encode(salt),
+            BASE64.encode(mac.finalize().into_bytes())
+        )
+    }
+
+    /// 在临时目录中写入 known_hosts 文件
+    fn write_known_hosts(content: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("known-hosts-test-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("known_hosts");
+        std::fs::write(&path, content).unwrap();
+        path
+    }
+
+    fn entry(hosts: &str, key: &PublicKey) -> String {
+        format!("{} {} {}\n", hosts, key.name(), key.public_key_base64())
+    }
+
+    #[test]
+    fn test_plaintext_entry() {
+        let key = generate_key();
+        let path = write_known_hosts(&format!(
+            "# comment\n\n{}{}",
+            entry("other.example.com", &generate_key()),
+            entry("alias,example.com", &key)
+        ));
+        let files = vec![path.clone()];
+
+        assert_eq!(check_host_key(&files, "example.com", 22, &key), HostKeyStatus::Trusted);
+        assert_eq!(check_host_key(&files, "alias", 22, &key), HostKeyStatus::Trusted);
+        // 非 22 端口需要 `[host]:port` 记录
+        assert_eq!(check_host_key(&files, "example.com", 2222, &key), HostKeyStatus::Unknown);
+        assert_eq!(check_host_key(&files, "unknown.example.com", 22, &key), HostKeyStatus::Unknown);
+
+        let _ = std::fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn test_hashed_entry() {
+        let key = generate_key();
+        let path = write_known_hosts(&format!(
+            "{}{}",
+            entry(&hash_host(b"0123456789abcdefghij", "example.com"), &key),
+            entry(&hash_host(b"jihgfedcba9876543210", "[example.com]:2222"), &key)
+        ));
+        let files = vec![path.clone()];
+
+        assert_eq!(check_host_key(&files, "example.com", 22, &key), HostKeyStatus::Trusted);
+        assert_eq!(check_host_key(&files, "example.com", 2222, &key), HostKeyStatus::Trusted);
+        assert_eq!(check_host_key(&files, "example.org", 22, &key), HostKeyStatus::Unknown);
+
+        let _ = std::fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn test_mismatch() {
+        let key = generate_key();
+        let (internal, example) = (generate_key(), generate_key());
+        let path = write_known_hosts(&format!(
+            "{}{}",
+            entry("*.internal,!bastion.internal", &internal),
+            entry(&hash_host(b"salt-salt-salt-salt!", "example.com"), &example)
+        ));
+        let files = vec![path.clone()];
+
+        assert_eq!(
+            check_host_key(&files, "db.internal", 22, &key),
+            HostKeyStatus::Changed {
+                path: path.clone(),
+                line: 1,
+                fingerprint: fingerprint(&internal),
+            }
+        );
+        assert_eq!(
+            check_host_key(&files, "example.com", 22, &key),
+            HostKeyStatus::Changed {
+                path: path.clone(),
+                line: 2,
+                fingerprint: fingerprint(&example),
+            }
+        );
+        // 取反模式排除的主机没有记录
+        assert_eq!(check_host_key(&files, "bastion.internal", 22, &key), HostKeyStatus::Unknown);
+
+        let _ = std::fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn test_multiple_files_and_revoked() {
+        let key = generate_key();
+        let user = write_known_hosts(&entry("example.com", &generate_key()));
+        let global = write_known_hosts(&entry("example.com", &key));
+        let missing = PathBuf::from("/nonexistent/known_hosts");
+
+        // 任一文件中有一致的记录即可信任
+        let files = vec![missing, user.clone(), global.clone()];
+        assert_eq!(check_host_key(&files, "example.com", 22, &key), HostKeyStatus::Trusted);
+
+        let revoked = write_known_hosts(&format!("@revoked * {} {}\n", key.name(), key.public_key_base64()));
+        let files = vec![global.clone(), revoked.clone()];
+        assert_eq!(
+            check_host_key(&files, "example.com", 22, &key),
+            HostKeyStatus::Revoked {
+                path: revoked.clone(),
+                line: 1
+            }
+        );
+
+        for path in [user, global, revoked] {
+            let _ = std::fs::remove_dir_all(path.parent().unwrap());
+        }
+    }
+
+    #[test]
+    fn test_add_host_key() {
+        let (old, new) = (generate_key(), generate_key());
+        // 最后一行没有换行
+        let path = write_known_hosts(entry("[example.com]:2222", &old).trim_end());
+        let files = vec![path.clone()];
+        assert!(matches!(
+            check_host_key(&files, "example.com", 2222, &new),
+            HostKeyStatus::Changed { .. }
+        ));
+
+        add_host_key(&path, "example.com", 2222, &new).unwrap();
+        assert_eq!(check_host_key(&files, "example.com", 2222, &new), HostKeyStatus::Trusted);
+        assert_eq!(check_host_key(&files, "example.com", 2222, &old), HostKeyStatus::Trusted);
+
+        // 文件不存在时创建
+        let created = path.parent().unwrap().join("new").join("known_hosts");
+        add_host_key(&created, "example.com", 22, &new).unwrap();
+        assert_eq!(
+            check_host_key(&[created], "example.com", 22, &new),
+            HostKeyStatus::Trusted
+        );
+
+        let _ = std::fs::remove_dir_all(path.parent().unwrap());
+    }
+}
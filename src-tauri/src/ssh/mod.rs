@@ -2,7 +2,13 @@
 //!
 //! 负责 SSH 远程连接的建立和管理。
 
+pub mod algorithms;
 pub mod client;
+pub mod config;
+pub mod diagnostics;
+pub mod host_key;
+pub mod known_hosts;
+pub mod retry;
 pub mod session;
 pub mod auth;
 
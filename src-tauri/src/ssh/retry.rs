@@ -0,0 +1,237 @@
+//! SSH 连接重试
+//!
+//! 连接超时、主机解析失败等可恢复错误往往是暂时的，按指数退避重试。
+//! 认证失败虽然被标记为可恢复（用户可以重新输入凭据），但自动重试没有意义，会立即失败。
+//!
+//! 重新打开意外关闭的通道时在退避间隔上加入随机抖动，避免多个会话同时重试。
+
+use std::future::Future;
+use std::pin::Pin;
+use std::time::Duration;
+
+use crate::utils::error::TerminalError;
+
+/// 默认重试基础间隔（毫秒）
+pub const DEFAULT_RETRY_BASE_DELAY_MS: u64 = 1000;
+
+/// 单次重试的最大间隔
+pub const MAX_RETRY_DELAY: Duration = Duration::from_secs(30);
+
+/// 重试策略
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// 最大重试次数（不含首次尝试），0 表示不重试
+    pub max_retries: u32,
+    /// 第一次重试前的等待时间，之后每次翻倍
+    pub base_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 0,
+            base_delay: Duration::from_millis(DEFAULT_RETRY_BASE_DELAY_MS),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// 创建重试策略
+    pub fn new(max_retries: u32, base_delay: Duration) -> Self {
+        Self {
+            max_retries,
+            base_delay,
+        }
+    }
+
+    /// 第 `retry` 次重试（从 0 开始）前的等待时间
+    pub fn delay_for(&self, retry: u32) -> Duration {
+        self.base_delay
+            .checked_mul(2u32.saturating_pow(retry))
+            .unwrap_or(MAX_RETRY_DELAY)
+            .min(MAX_RETRY_DELAY)
+    }
+
+    /// 第 `retry` 次重试前带随机抖动的等待时间，取值范围为 `[delay / 2, delay]`
+    pub fn jittered_delay_for(&self, retry: u32) -> Duration {
+        apply_jitter(self.delay_for(retry), random_fraction())
+    }
+
+    /// 错误是否值得重试
+    pub fn should_retry(error: &TerminalError) -> bool {
+        error.is_recoverable() && !error.is_auth_error()
+    }
+}
+
+/// 按 `fraction`（`[0, 1)`）在 `[delay / 2, delay]` 之间取值
+fn apply_jitter(delay: Duration, fraction: f64) -> Duration {
+    let half = delay / 2;
+    half + half.mul_f64(fraction.clamp(0.0, 1.0))
+}
+
+/// `[0, 1)` 之间的随机数
+///
+/// 抖动不需要密码学强度的随机数，使用标准库的随机哈希种子，避免额外依赖。
+fn random_fraction() -> f64 {
+    use std::hash::{BuildHasher, Hasher};
+
+    let mut hasher = std::collections::hash_map::RandomState::new().build_hasher();
+    if let Ok(now) = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH) {
+        hasher.write_u128(now.as_nanos());
+    }
+    (hasher.finish() >> 11) as f64 / (1u64 << 53) as f64
+}
+
+/// 单次连接尝试
+pub type AttemptFuture<'a, T> = Pin<Box<dyn Future<Output = Result<T, TerminalError>> + Send + 'a>>;
+
+/// 按重试策略执行连接
+///
+/// 每次重试前调用 `on_retry(重试序号, 等待时间, 上次错误)`，重试序号从 1 开始。
+pub async fn retry_with_backoff<C, T>(
+    policy: &RetryPolicy,
+    target: &mut C,
+    mut attempt: impl for<'a> FnMut(&'a mut C) -> AttemptFuture<'a, T>,
+    mut on_retry: impl FnMut(u32, Duration, &TerminalError),
+) -> Result<T, TerminalError> {
+    let mut retry = 0;
+    loop {
+        match attempt(target).await {
+            Ok(value) => return Ok(value),
+            Err(e) if retry < policy.max_retries && RetryPolicy::should_retry(&e) => {
+                let delay = policy.delay_for(retry);
+                retry += 1;
+                on_retry(retry, delay, &e);
+                tokio::time::sleep(delay).await;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 前 `failures` 次返回给定错误的模拟连接
+    struct MockConnector {
+        failures: u32,
+        attempts: u32,
+        error: fn() -> TerminalError,
+    }
+
+    impl MockConnector {
+        fn new(failures: u32, error: fn() -> TerminalError) -> Self {
+            Self {
+                failures,
+                attempts: 0,
+                error,
+            }
+        }
+
+        async fn connect(&mut self) -> Result<u32, TerminalError> {
+            self.attempts += 1;
+            if self.attempts <= self.failures {
+                Err((self.error)())
+            } else {
+                Ok(self.attempts)
+            }
+        }
+    }
+
+    fn timeout() -> TerminalError {
+        TerminalError::ConnectionTimeout("example.com:22".to_string())
+    }
+
+    fn auth_failure() -> TerminalError {
+        TerminalError::AuthenticationFailed("密码被服务器拒绝".to_string())
+    }
+
+    #[test]
+    fn test_delay_doubles_and_caps() {
+        let policy = RetryPolicy::new(10, Duration::from_millis(500));
+        assert_eq!(policy.delay_for(0), Duration::from_millis(500));
+        assert_eq!(policy.delay_for(1), Duration::from_secs(1));
+        assert_eq!(policy.delay_for(2), Duration::from_secs(2));
+        assert_eq!(policy.delay_for(10), MAX_RETRY_DELAY);
+        assert_eq!(policy.delay_for(u32::MAX), MAX_RETRY_DELAY);
+    }
+
+    #[test]
+    fn test_jitter_stays_within_bounds() {
+        let delay = Duration::from_millis(400);
+        assert_eq!(apply_jitter(delay, 0.0), Duration::from_millis(200));
+        assert_eq!(apply_jitter(delay, 0.5), Duration::from_millis(300));
+        assert!(apply_jitter(delay, 0.999) <= delay);
+
+        let policy = RetryPolicy::new(3, delay);
+        for retry in 0..3 {
+            let jittered = policy.jittered_delay_for(retry);
+            assert!(jittered >= policy.delay_for(retry) / 2);
+            assert!(jittered <= policy.delay_for(retry));
+        }
+
+        for _ in 0..100 {
+            let fraction = random_fraction();
+            assert!((0.0..1.0).contains(&fraction));
+        }
+    }
+
+    #[test]
+    fn test_should_retry() {
+        assert!(RetryPolicy::should_retry(&timeout()));
+        assert!(RetryPolicy::should_retry(&TerminalError::HostResolutionFailed(
+            "example.com".to_string()
+        )));
+        assert!(!RetryPolicy::should_retry(&auth_failure()));
+        assert!(!RetryPolicy::should_retry(&TerminalError::ChannelError(
+            "请求 shell 失败".to_string()
+        )));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_retry_succeeds_after_two_failures() {
+        let policy = RetryPolicy::new(3, Duration::from_millis(100));
+        let mut connector = MockConnector::new(2, timeout);
+        let mut retries = Vec::new();
+
+        let result = retry_with_backoff(
+            &policy,
+            &mut connector,
+            |c| Box::pin(c.connect()),
+            |retry, delay, _| retries.push((retry, delay)),
+        )
+        .await;
+
+        assert_eq!(result.unwrap(), 3);
+        assert_eq!(
+            retries,
+            vec![
+                (1, Duration::from_millis(100)),
+                (2, Duration::from_millis(200)),
+            ]
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_retry_gives_up_after_max_retries() {
+        let policy = RetryPolicy::new(2, Duration::from_millis(100));
+        let mut connector = MockConnector::new(5, timeout);
+
+        let result = retry_with_backoff(&policy, &mut connector, |c| Box::pin(c.connect()), |_, _, _| {}).await;
+
+        assert!(matches!(result, Err(TerminalError::ConnectionTimeout(_))));
+        assert_eq!(connector.attempts, 3);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_auth_failure_is_not_retried() {
+        let policy = RetryPolicy::new(3, Duration::from_millis(100));
+        let mut connector = MockConnector::new(1, auth_failure);
+
+        let result = retry_with_backoff(&policy, &mut connector, |c| Box::pin(c.connect()), |_, _, _| {}).await;
+
+        assert!(matches!(result, Err(TerminalError::AuthenticationFailed(_))));
+        assert_eq!(connector.attempts, 1);
+    }
+}
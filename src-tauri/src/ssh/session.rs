@@ -2,18 +2,23 @@
 //!
 //! 管理 SSH PTY 通道，处理输入/输出。
 
+use std::collections::HashMap;
 use std::sync::Arc;
-use std::time::{SystemTime, UNIX_EPOCH};
 
 use russh::client::Msg;
 use russh::ChannelMsg;
 use tokio::sync::{mpsc, Mutex, RwLock};
 
+use crate::pty::local::{FORCE_COLOR_ENV, LOCALE_ENV};
 use crate::rpc::server::NotificationSender;
 use crate::rpc::types::{ConnectionType, SessionInfo, SessionStatus, TermSize};
+use crate::utils::codec;
 use crate::utils::error::TerminalError;
+use crate::utils::time::{system_clock, SharedClock};
 
-use super::client::SshClient;
+use super::client::{SshClient, SshClientHandler};
+use super::host_key::HostKeyPrompts;
+use super::retry::{retry_with_backoff, RetryPolicy, DEFAULT_RETRY_BASE_DELAY_MS};
 
 /// SSH 通道包装器
 ///
@@ -36,9 +41,14 @@ impl ChannelWrapper {
     }
 
     /// 调整 PTY 大小
-    async fn resize(&self, cols: u32, rows: u32) -> Result<(), TerminalError> {
+    async fn resize(&self, term_size: &TermSize) -> Result<(), TerminalError> {
         self.inner
-            .window_change(cols, rows, 0, 0)
+            .window_change(
+                term_size.cols as u32,
+                term_size.rows as u32,
+                term_size.pixel_width as u32,
+                term_size.pixel_height as u32,
+            )
             .await
             .map_err(|e| {
                 TerminalError::ChannelError(format!("调整大小失败: {}", e))
@@ -65,6 +75,358 @@ impl ChannelWrapper {
     }
 }
 
+/// 通道消息来源
+///
+/// 输出读取器通过它等待通道消息，并在通道意外关闭时重新打开通道。
+#[async_trait::async_trait]
+trait ChannelSource: Send {
+    /// 等待下一条通道消息，通道已关闭时返回 `None`
+    async fn wait(&mut self) -> Option<ChannelMsg>;
+
+    /// 底层 SSH 连接是否仍然存活
+    fn is_alive(&self) -> bool;
+
+    /// 在同一连接上打开新通道并重新请求 PTY 和 shell
+    async fn reopen(&mut self) -> Result<(), TerminalError>;
+}
+
+/// 基于 SSH 连接的通道消息来源
+struct SshChannelSource {
+    /// 与会话共享的通道，重新打开后输入和调整大小也使用新通道
+    channel: Arc<Mutex<ChannelWrapper>>,
+    /// SSH 会话句柄
+    handle: Option<Arc<russh::client::Handle<SshClientHandler>>>,
+    /// 当前终端大小，重新请求 PTY 时使用
+    term_size: Arc<RwLock<TermSize>>,
+    /// 重新打开通道时再次发送的环境变量
+    env: Vec<(String, String)>,
+}
+
+#[async_trait::async_trait]
+impl ChannelSource for SshChannelSource {
+    async fn wait(&mut self) -> Option<ChannelMsg> {
+        self.channel.lock().await.wait().await
+    }
+
+    fn is_alive(&self) -> bool {
+        self.handle.as_ref().is_some_and(|handle| !handle.is_closed())
+    }
+
+    async fn reopen(&mut self) -> Result<(), TerminalError> {
+        let handle = self.handle.as_ref().ok_or_else(|| {
+            TerminalError::channel_error("打开会话", "无法获取 SSH 会话句柄")
+        })?;
+        let term_size = self.term_size.read().await.clone();
+        let channel = request_shell_channel(handle, &term_size, &self.env).await?;
+        *self.channel.lock().await = ChannelWrapper::new(channel);
+        Ok(())
+    }
+}
+
+/// 可以发送 env 请求的通道
+#[async_trait::async_trait]
+trait EnvRequester: Sync {
+    /// 发送一个不要求回复的 env 请求
+    async fn set_env(&self, name: &str, value: &str) -> Result<(), russh::Error>;
+}
+
+#[async_trait::async_trait]
+impl EnvRequester for russh::Channel<Msg> {
+    async fn set_env(&self, name: &str, value: &str) -> Result<(), russh::Error> {
+        russh::Channel::set_env(self, false, name, value).await
+    }
+}
+
+/// 按顺序发送 env 请求，单个变量失败时记录日志并继续
+async fn send_env_requests<C: EnvRequester>(channel: &C, env: &[(String, String)]) {
+    for (name, value) in env {
+        if let Err(e) = channel.set_env(name, value).await {
+            tracing::warn!("发送环境变量失败: {} - {}", name, e);
+        }
+    }
+}
+
+/// 打开会话通道，发送 env 请求，然后请求 PTY 和 shell
+///
+/// env 请求不要求回复，服务器不允许（`AcceptEnv` 之外）的变量被忽略。
+async fn request_shell_channel(
+    handle: &russh::client::Handle<SshClientHandler>,
+    term_size: &TermSize,
+    env: &[(String, String)],
+) -> Result<russh::Channel<Msg>, TerminalError> {
+    // 打开会话通道
+    let channel = handle.channel_open_session().await.map_err(|e| {
+        TerminalError::channel_error("打开会话通道", &e.to_string())
+    })?;
+
+    // 请求 PTY
+    channel
+        .request_pty(
+            false,                    // want_reply
+            "xterm-256color",         // term
+            term_size.cols as u32,    // col_width
+            term_size.rows as u32,    // row_height
+            term_size.pixel_width as u32,  // pix_width
+            term_size.pixel_height as u32, // pix_height
+            &[],                      // terminal_modes
+        )
+        .await
+        .map_err(|e| {
+            TerminalError::channel_error("请求 PTY", &e.to_string())
+        })?;
+
+    send_env_requests(&channel, env).await;
+
+    // 请求 shell
+    channel.request_shell(false).await.map_err(|e| {
+        TerminalError::channel_error("请求 shell", &e.to_string())
+    })?;
+
+    Ok(channel)
+}
+
+/// 读取通道输出并发送通知，直到通道结束或收到停止信号
+///
+/// `notify_ready` 为 true 时在第一次收到 stdout 数据后发送 `session.ready` 通知。
+/// 会话结束时按 `clock` 记录结束时间。
+#[allow(clippy::too_many_arguments)]
+async fn run_output_reader<S: ChannelSource>(
+    session_id: String,
+    mut source: S,
+    info: Arc<RwLock<SessionInfo>>,
+    notification_sender: NotificationSender,
+    reopen_policy: RetryPolicy,
+    notify_ready: bool,
+    clock: SharedClock,
+    mut stop_rx: mpsc::Receiver<()>,
+) {
+    tracing::info!("SSH 输出读取器启动: {}", session_id);
+
+    // 输出分块序号
+    let mut seq: u64 = 0;
+    // 还需要发送 `session.ready` 通知
+    let mut ready_pending = notify_ready;
+
+    loop {
+        // 同时监听停止信号和通道消息
+        let attached = notification_sender.is_attached();
+        let msg = tokio::select! {
+            biased;
+
+            // 检查停止信号（优先级更高）
+            _ = stop_rx.recv() => {
+                tracing::info!("SSH 输出读取器收到停止信号: {}", session_id);
+                break;
+            }
+
+            // 没有客户端接收通知时不读取通道，由通道窗口对远端施加背压
+            _ = notification_sender.wait_attached(), if !attached => continue,
+
+            // 读取通道消息
+            msg = source.wait(), if attached => msg,
+        };
+
+        match msg {
+            Some(ChannelMsg::Data { data }) => {
+                // 发送输出通知（base64 编码）
+                let encoded = codec::encode_output(&data);
+                if let Err(e) = notification_sender.send_output(&session_id, &encoded, seq, true) {
+                    tracing::error!("发送输出通知失败: {}", e);
+                    break;
+                }
+                seq += 1;
+
+                if ready_pending {
+                    ready_pending = false;
+                    tracing::debug!("SSH 会话就绪: {}", session_id);
+                    if let Err(e) = notification_sender.send_ready(&session_id) {
+                        tracing::error!("发送就绪通知失败: {}", e);
+                    }
+                }
+            }
+            Some(ChannelMsg::ExtendedData { data, ext }) => {
+                // stderr 数据 (ext == 1)
+                tracing::debug!("SSH stderr (ext={}): {} bytes", ext, data.len());
+                let encoded = codec::encode_output(&data);
+                if let Err(e) = notification_sender.send_output(&session_id, &encoded, seq, true) {
+                    tracing::error!("发送 stderr 通知失败: {}", e);
+                    break;
+                }
+                seq += 1;
+            }
+            Some(ChannelMsg::ExitStatus { exit_status }) => {
+                tracing::info!("SSH 进程退出: {} (code={})", session_id, exit_status);
+
+                // 更新会话信息
+                let timing = {
+                    let mut info_guard = info.write().await;
+                    info_guard.mark_ended(SessionStatus::Done, clock.unix_secs());
+                    info_guard.exit_code = Some(exit_status as i32);
+                    info_guard.timing()
+                };
+
+                if let Err(e) = notification_sender.send_status_with_reason(
+                    &session_id,
+                    "done",
+                    Some(exit_status as i32),
+                    None,
+                    timing,
+                ) {
+                    tracing::error!("发送状态通知失败: {}", e);
+                }
+                break;
+            }
+            Some(ChannelMsg::ExitSignal {
+                signal_name,
+                core_dumped,
+                error_message,
+                lang_tag: _,
+            }) => {
+                let signal = signal_name_str(&signal_name);
+                tracing::info!(
+                    "SSH 进程被信号终止: {} (signal={}, core_dumped={})",
+                    session_id,
+                    signal,
+                    core_dumped
+                );
+
+                let timing = {
+                    let mut info_guard = info.write().await;
+                    info_guard.mark_ended(SessionStatus::Done, clock.unix_secs());
+                    info_guard.timing()
+                };
+
+                let error_message = Some(error_message.as_str()).filter(|m| !m.is_empty());
+                if let Err(e) = notification_sender.send_signal_status(
+                    &session_id,
+                    &signal,
+                    core_dumped,
+                    error_message,
+                    timing,
+                ) {
+                    tracing::error!("发送状态通知失败: {}", e);
+                }
+                break;
+            }
+            Some(ChannelMsg::Eof) => {
+                tracing::info!("SSH 通道 EOF: {}", session_id);
+                break;
+            }
+            // 未收到退出状态就关闭，连接仍然存活时尝试重新打开通道
+            Some(ChannelMsg::Close) | None
+                if reopen_policy.max_retries > 0 && source.is_alive() =>
+            {
+                tracing::warn!("SSH 通道意外关闭，尝试重新打开: {}", session_id);
+                match reopen_with_backoff(&session_id, &mut source, &reopen_policy, &mut stop_rx).await {
+                    Some(Ok(())) => {
+                        tracing::info!("SSH 通道已重新打开: {}", session_id);
+                        ready_pending = notify_ready;
+                        info.write().await.status = SessionStatus::Running;
+                        if let Err(e) = notification_sender.send_status(&session_id, "running", None) {
+                            tracing::error!("发送状态通知失败: {}", e);
+                        }
+                    }
+                    Some(Err(e)) => {
+                        tracing::error!("SSH 通道重新打开失败: {} - {}", session_id, e);
+                        info.write().await.mark_ended(SessionStatus::Error, clock.unix_secs());
+                        if let Err(send_err) = notification_sender.send_error_status(&session_id, &e) {
+                            tracing::error!("发送错误状态通知失败: {}", send_err);
+                        }
+                        break;
+                    }
+                    None => {
+                        tracing::info!("SSH 输出读取器在重新打开通道时收到停止信号: {}", session_id);
+                        break;
+                    }
+                }
+            }
+            Some(ChannelMsg::Close) => {
+                tracing::info!("SSH 通道关闭: {}", session_id);
+                break;
+            }
+            Some(other) => {
+                tracing::debug!("SSH 通道消息: {:?}", other);
+            }
+            None => {
+                // 未收到退出状态或 EOF 就断开，视为连接异常
+                tracing::warn!("SSH 通道已断开: {}", session_id);
+                info.write().await.mark_ended(SessionStatus::Error, clock.unix_secs());
+
+                let error = TerminalError::SessionClosed(format!("SSH 连接意外断开: {}", session_id));
+                if let Err(e) = notification_sender.send_error_status(&session_id, &error) {
+                    tracing::error!("发送错误状态通知失败: {}", e);
+                }
+                break;
+            }
+        }
+    }
+
+    tracing::info!("SSH 输出读取器结束: {}", session_id);
+}
+
+/// 按退避策略重新打开通道，每次尝试前等待带抖动的退避时间
+///
+/// 返回 `None` 表示等待期间收到了停止信号；连接断开后不再继续尝试。
+async fn reopen_with_backoff<S: ChannelSource>(
+    session_id: &str,
+    source: &mut S,
+    policy: &RetryPolicy,
+    stop_rx: &mut mpsc::Receiver<()>,
+) -> Option<Result<(), TerminalError>> {
+    let mut last_error = None;
+
+    for retry in 0..policy.max_retries {
+        let delay = policy.jittered_delay_for(retry);
+        tokio::select! {
+            biased;
+            _ = stop_rx.recv() => return None,
+            _ = tokio::time::sleep(delay) => {}
+        }
+
+        match source.reopen().await {
+            Ok(()) => return Some(Ok(())),
+            Err(e) => {
+                tracing::warn!(
+                    "第 {} 次重新打开 SSH 通道失败: {} - {}",
+                    retry + 1,
+                    session_id,
+                    e
+                );
+                last_error = Some(e);
+                if !source.is_alive() {
+                    break;
+                }
+            }
+        }
+    }
+
+    Some(Err(match last_error {
+        Some(e) => TerminalError::SessionClosed(format!("SSH 通道无法重新打开: {}", e)),
+        None => TerminalError::SessionClosed(format!("SSH 连接意外断开: {}", session_id)),
+    }))
+}
+
+/// 信号名（RFC 4254 格式，不带 `SIG` 前缀）
+fn signal_name_str(signal: &russh::Sig) -> String {
+    use russh::Sig;
+    match signal {
+        Sig::ABRT => "ABRT",
+        Sig::ALRM => "ALRM",
+        Sig::FPE => "FPE",
+        Sig::HUP => "HUP",
+        Sig::ILL => "ILL",
+        Sig::INT => "INT",
+        Sig::KILL => "KILL",
+        Sig::PIPE => "PIPE",
+        Sig::QUIT => "QUIT",
+        Sig::SEGV => "SEGV",
+        Sig::TERM => "TERM",
+        Sig::USR1 => "USR1",
+        Sig::Custom(name) => name,
+    }
+    .to_string()
+}
+
 /// SSH 会话
 ///
 /// 封装 SSH 连接和 PTY 通道，提供终端交互功能。
@@ -81,6 +443,26 @@ pub struct SshSession {
     output_task: Option<tokio::task::JoinHandle<()>>,
     /// 停止信号发送器
     stop_tx: Option<mpsc::Sender<()>>,
+    /// 连接重试策略
+    retry_policy: RetryPolicy,
+    /// 通道意外关闭时重新打开的最大次数，0 表示不重新打开
+    channel_retries: u32,
+    /// 第一次收到输出时是否发送 `session.ready` 通知
+    notify_ready: bool,
+    /// 当前终端大小（与输出读取器共享）
+    term_size: Arc<RwLock<TermSize>>,
+    /// 是否已发送 EOF
+    input_closed: bool,
+    /// shell 打开后写入一次的初始输入
+    initial_input: Option<Vec<u8>>,
+    /// 通过 env 请求发送的环境变量
+    env: HashMap<String, String>,
+    /// 通过 env 请求设置到 `LANG` 和 `LC_ALL` 的 locale
+    locale: Option<String>,
+    /// 是否通过 env 请求设置彩色输出的环境变量
+    force_color: bool,
+    /// 时间来源
+    clock: SharedClock,
 }
 
 impl SshSession {
@@ -93,33 +475,59 @@ impl SshSession {
         identity_file: Option<String>,
         password: Option<String>,
     ) -> Self {
-        let created_at = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_secs();
+        Self::with_clock(session_id, host, port, user, identity_file, password, system_clock())
+    }
+
+    /// 使用指定的时间来源创建 SSH 会话
+    pub fn with_clock(
+        session_id: String,
+        host: String,
+        port: Option<u16>,
+        user: Option<String>,
+        identity_file: Option<String>,
+        password: Option<String>,
+        clock: SharedClock,
+    ) -> Self {
+        let created_at = clock.unix_secs();
 
         let client = SshClient::from_params(
             host.clone(),
             port,
             user.clone(),
             identity_file.clone(),
-            password.clone(),
+            password,
         );
 
+        // 记录补全默认值（及 ssh_config）后的端口和用户名
         let info = SessionInfo {
             id: session_id.clone(),
             connection_type: ConnectionType::Ssh {
                 host,
-                port,
-                user,
+                port: Some(client.config().port),
+                user: Some(client.config().user.clone()),
                 identity_file,
-                password,
+                // 密码只保存在客户端的认证配置中，释放时清零
+                password: None,
+                compression: false,
+                max_retries: 0,
+                retry_delay_ms: DEFAULT_RETRY_BASE_DELAY_MS,
+                channel_retries: 0,
+                notify_ready: false,
+                env: None,
             },
             status: SessionStatus::Init,
             title: None,
+            name: None,
             cwd: None,
             exit_code: None,
             created_at,
+            modes: Default::default(),
+            ssh: None,
+            exit_reason: None,
+            ended_at: None,
+            duration_secs: None,
+            pid: None,
+            resolved_addr: None,
         };
 
         Self {
@@ -129,67 +537,239 @@ impl SshSession {
             info: Arc::new(RwLock::new(info)),
             output_task: None,
             stop_tx: None,
+            retry_policy: RetryPolicy::default(),
+            channel_retries: 0,
+            notify_ready: false,
+            term_size: Arc::new(RwLock::new(TermSize::default())),
+            input_closed: false,
+            initial_input: None,
+            env: HashMap::new(),
+            locale: None,
+            force_color: false,
+            clock,
+        }
+    }
+
+    /// 设置是否启用压缩，需在 `connect` 之前调用
+    pub async fn set_compression(&mut self, enabled: bool) {
+        self.client.config_mut().compression = enabled;
+        if let ConnectionType::Ssh { compression, .. } = &mut self.info.write().await.connection_type {
+            *compression = enabled;
         }
     }
 
+    /// 设置连接重试策略，需在 `connect` 之前调用
+    pub async fn set_retry_policy(&mut self, policy: RetryPolicy) {
+        self.retry_policy = policy;
+        if let ConnectionType::Ssh {
+            max_retries,
+            retry_delay_ms,
+            ..
+        } = &mut self.info.write().await.connection_type
+        {
+            *max_retries = policy.max_retries;
+            *retry_delay_ms = policy.base_delay.as_millis() as u64;
+        }
+    }
+
+    /// 设置通道意外关闭时重新打开的最大次数，需在 `start_output_reader` 之前调用
+    ///
+    /// 退避间隔使用连接重试策略的基础间隔。
+    pub async fn set_channel_retries(&mut self, retries: u32) {
+        self.channel_retries = retries;
+        if let ConnectionType::Ssh { channel_retries, .. } = &mut self.info.write().await.connection_type {
+            *channel_retries = retries;
+        }
+    }
+
+    /// 设置是否在第一次收到 shell 输出时发送 `session.ready` 通知，需在
+    /// `start_output_reader` 之前调用
+    ///
+    /// `request_shell` 返回后状态立即变为 `running`，此时 shell 可能还没有输出提示符；
+    /// `session.ready` 表示终端已经可以交互。通道重新打开后会再次发送。
+    pub async fn set_notify_ready(&mut self, enabled: bool) {
+        self.notify_ready = enabled;
+        if let ConnectionType::Ssh { notify_ready, .. } = &mut self.info.write().await.connection_type {
+            *notify_ready = enabled;
+        }
+    }
+
+    /// 主机密钥变更时通过 `session.host_key_changed` 通知询问客户端，需在 `connect` 之前调用
+    ///
+    /// 决定通过 [`HostKeyPrompts::resolve`] 提交。
+    pub fn set_host_key_prompts(&mut self, prompts: &HostKeyPrompts, sender: &NotificationSender) {
+        self.client.config_mut().host_key_changed =
+            Some(prompts.callback(self.session_id.clone(), sender.clone()));
+    }
+
+    /// 设置连接成功、shell 打开后写入的初始输入，需在 `connect` 之前调用
+    ///
+    /// 只写入一次，重试连接或重新打开通道时不会再次写入。
+    pub fn set_initial_input(&mut self, data: Vec<u8>) {
+        self.initial_input = Some(data);
+    }
+
+    /// 设置打开 shell 前通过 env 请求发送的环境变量，需在 `connect` 之前调用
+    ///
+    /// 变量按名称排序发送，服务器拒绝的变量被忽略。
+    pub async fn set_env(&mut self, env: HashMap<String, String>) {
+        self.env = env.clone();
+        if let ConnectionType::Ssh { env: recorded, .. } = &mut self.info.write().await.connection_type {
+            *recorded = (!env.is_empty()).then_some(env);
+        }
+    }
+
+    /// 设置打开 shell 前通过 env 请求发送的 locale（`LANG` 和 `LC_ALL`），需在 `connect` 之前调用
+    pub fn set_locale(&mut self, locale: Option<String>) {
+        self.locale = locale;
+    }
+
+    /// 设置是否在打开 shell 前通过 env 请求发送 [`FORCE_COLOR_ENV`]，需在 `connect` 之前调用
+    ///
+    /// 服务器通常只接受 `AcceptEnv` 中列出的变量，不接受时静默忽略。
+    pub fn set_force_color(&mut self, enabled: bool) {
+        self.force_color = enabled;
+    }
+
+    /// 实际发送的环境变量：按名称排序的 `env`，之后是 `env` 中没有给出的 locale 变量
+    /// 和 [`FORCE_COLOR_ENV`]
+    fn requested_env(&self) -> Vec<(String, String)> {
+        let mut env: Vec<_> = self
+            .env
+            .iter()
+            .map(|(name, value)| (name.clone(), value.clone()))
+            .collect();
+        env.sort();
+
+        let locale = self
+            .locale
+            .iter()
+            .flat_map(|locale| LOCALE_ENV.iter().map(move |name| (*name, locale.as_str())));
+        let color = FORCE_COLOR_ENV.iter().copied().filter(|_| self.force_color);
+        for (name, value) in locale.chain(color) {
+            if !self.env.contains_key(name) {
+                env.push((name.to_string(), value.to_string()));
+            }
+        }
+        env
+    }
+
     /// 连接并打开 PTY 通道
+    ///
+    /// 可恢复错误按重试策略重试，最终失败时会话状态置为 `Error`。
     pub async fn connect(&mut self, term_size: TermSize) -> Result<(), TerminalError> {
+        self.connect_inner(term_size, None).await
+    }
+
+    /// 连接并打开 PTY 通道，失败时发送带错误详情的 `session.status` 通知
+    ///
+    /// 重试期间会话保持 `connecting` 状态，每次重试前重新发送一次该状态。
+    pub async fn connect_with_notifier(
+        &mut self,
+        term_size: TermSize,
+        notification_sender: &NotificationSender,
+    ) -> Result<(), TerminalError> {
+        let result = self.connect_inner(term_size, Some(notification_sender)).await;
+        if let Err(e) = &result {
+            if let Err(send_err) = notification_sender.send_error_status(&self.session_id, e) {
+                tracing::error!("发送错误状态通知失败: {}", send_err);
+            }
+        }
+        result
+    }
+
+    /// 连接流程的公共实现
+    async fn connect_inner(
+        &mut self,
+        term_size: TermSize,
+        notification_sender: Option<&NotificationSender>,
+    ) -> Result<(), TerminalError> {
         // 更新状态为连接中
         {
             let mut info = self.info.write().await;
             info.status = SessionStatus::Connecting;
         }
 
-        // 建立 SSH 连接
-        self.client.connect().await?;
-
-        // 获取会话句柄
-        let handle = self.client.handle_mut().ok_or_else(|| {
-            TerminalError::channel_error("打开会话", "无法获取 SSH 会话句柄")
-        })?;
-
-        // 打开会话通道
-        let channel = handle.channel_open_session().await.map_err(|e| {
-            TerminalError::channel_error("打开会话通道", &e.to_string())
-        })?;
-
-        // 请求 PTY
-        channel
-            .request_pty(
-                false,                    // want_reply
-                "xterm-256color",         // term
-                term_size.cols as u32,    // col_width
-                term_size.rows as u32,    // row_height
-                0,                        // pix_width
-                0,                        // pix_height
-                &[],                      // terminal_modes
-            )
-            .await
-            .map_err(|e| {
-                TerminalError::channel_error("请求 PTY", &e.to_string())
-            })?;
+        let policy = self.retry_policy;
+        let session_id = self.session_id.clone();
+        let result = retry_with_backoff(
+            &policy,
+            self,
+            |session| Box::pin(session.open_channel(term_size.clone())),
+            |retry, delay, e| {
+                tracing::warn!(
+                    "SSH 连接失败，{:?} 后第 {} 次重试: {} - {}",
+                    delay,
+                    retry,
+                    session_id,
+                    e
+                );
+                if let Some(sender) = notification_sender {
+                    if let Err(send_err) = sender.send_status(&session_id, "connecting", None) {
+                        tracing::error!("发送状态通知失败: {}", send_err);
+                    }
+                }
+            },
+        )
+        .await;
 
-        // 请求 shell
-        channel.request_shell(false).await.map_err(|e| {
-            TerminalError::channel_error("请求 shell", &e.to_string())
-        })?;
+        // 握手失败时也记录，便于确认连到了哪台服务器
+        self.info.write().await.resolved_addr = self.client.resolved_addr().map(|addr| addr.to_string());
 
-        // 包装通道
-        self.channel = Some(Arc::new(Mutex::new(ChannelWrapper::new(channel))));
+        if let Err(e) = result {
+            tracing::error!("SSH 会话建立失败: {} - {}", self.session_id, e);
+            self.info
+                .write()
+                .await
+                .mark_ended(SessionStatus::Error, self.clock.unix_secs());
+            return Err(e);
+        }
 
         // 更新状态为运行中
         {
             let mut info = self.info.write().await;
             info.status = SessionStatus::Running;
+            info.ssh = self.client.diagnostics().cloned();
         }
 
         tracing::info!("SSH 会话已建立: {}", self.session_id);
+
+        if let Some(data) = self.initial_input.take() {
+            if let Err(e) = self.send_input(&data).await {
+                tracing::warn!("写入初始输入失败: {} - {}", self.session_id, e);
+            }
+        }
+        Ok(())
+    }
+
+    /// 建立 SSH 连接并请求 PTY 和 shell
+    async fn open_channel(&mut self, term_size: TermSize) -> Result<(), TerminalError> {
+        // 重试时先断开上一次尝试留下的连接
+        if self.client.is_connected() {
+            let _ = self.client.disconnect().await;
+        }
+
+        // 建立 SSH 连接
+        self.client.connect().await?;
+
+        // 获取会话句柄
+        let handle = self.client.handle().ok_or_else(|| {
+            TerminalError::channel_error("打开会话", "无法获取 SSH 会话句柄")
+        })?;
+
+        let channel = request_shell_channel(handle, &term_size, &self.requested_env()).await?;
+        *self.term_size.write().await = term_size;
+
+        // 包装通道
+        self.channel = Some(Arc::new(Mutex::new(ChannelWrapper::new(channel))));
+        self.input_closed = false;
         Ok(())
     }
 
     /// 启动输出读取器
     ///
     /// 开始异步读取 SSH 通道输出并通过通知发送到前端。
+    /// 设置了通道重开次数时，通道意外关闭而连接仍然存活会重新打开通道。
     pub async fn start_output_reader(
         &mut self,
         notification_sender: NotificationSender,
@@ -198,95 +778,26 @@ impl SshSession {
             TerminalError::ChannelError("通道未打开".to_string())
         })?;
 
-        let session_id = self.session_id.clone();
-        let info = self.info.clone();
-        let (stop_tx, mut stop_rx) = mpsc::channel::<()>(1);
+        let source = SshChannelSource {
+            channel,
+            handle: self.client.shared_handle(),
+            term_size: self.term_size.clone(),
+            env: self.requested_env(),
+        };
+        let reopen_policy = RetryPolicy::new(self.channel_retries, self.retry_policy.base_delay);
+        let (stop_tx, stop_rx) = mpsc::channel::<()>(1);
 
         // 启动输出读取任务
-        let task = tokio::spawn(async move {
-            tracing::info!("SSH 输出读取器启动: {}", session_id);
-
-            loop {
-                // 使用 select 来同时监听停止信号和通道消息
-                tokio::select! {
-                    biased;
-                    
-                    // 检查停止信号（优先级更高）
-                    _ = stop_rx.recv() => {
-                        tracing::info!("SSH 输出读取器收到停止信号: {}", session_id);
-                        break;
-                    }
-                    
-                    // 读取通道消息
-                    msg = async {
-                        let mut channel_guard = channel.lock().await;
-                        channel_guard.wait().await
-                    } => {
-                        match msg {
-                            Some(ChannelMsg::Data { data }) => {
-                                // 发送输出通知（base64 编码）
-                                let encoded = base64::Engine::encode(
-                                    &base64::engine::general_purpose::STANDARD,
-                                    &data,
-                                );
-                                if let Err(e) = notification_sender.send_output(&session_id, &encoded) {
-                                    tracing::error!("发送输出通知失败: {}", e);
-                                    break;
-                                }
-                            }
-                            Some(ChannelMsg::ExtendedData { data, ext }) => {
-                                // stderr 数据 (ext == 1)
-                                tracing::debug!("SSH stderr (ext={}): {} bytes", ext, data.len());
-                                let encoded = base64::Engine::encode(
-                                    &base64::engine::general_purpose::STANDARD,
-                                    &data,
-                                );
-                                if let Err(e) = notification_sender.send_output(&session_id, &encoded) {
-                                    tracing::error!("发送 stderr 通知失败: {}", e);
-                                    break;
-                                }
-                            }
-                            Some(ChannelMsg::ExitStatus { exit_status }) => {
-                                tracing::info!("SSH 进程退出: {} (code={})", session_id, exit_status);
-                                
-                                // 更新会话信息
-                                {
-                                    let mut info_guard = info.write().await;
-                                    info_guard.status = SessionStatus::Done;
-                                    info_guard.exit_code = Some(exit_status as i32);
-                                }
-                                
-                                if let Err(e) = notification_sender.send_status(
-                                    &session_id,
-                                    "done",
-                                    Some(exit_status as i32),
-                                ) {
-                                    tracing::error!("发送状态通知失败: {}", e);
-                                }
-                                break;
-                            }
-                            Some(ChannelMsg::Eof) => {
-                                tracing::info!("SSH 通道 EOF: {}", session_id);
-                                break;
-                            }
-                            Some(ChannelMsg::Close) => {
-                                tracing::info!("SSH 通道关闭: {}", session_id);
-                                break;
-                            }
-                            Some(other) => {
-                                tracing::debug!("SSH 通道消息: {:?}", other);
-                            }
-                            None => {
-                                tracing::info!("SSH 通道已断开: {}", session_id);
-                                break;
-                            }
-                        }
-                    }
-                }
-            }
-
-            tracing::info!("SSH 输出读取器结束: {}", session_id);
-        });
+        let task = tokio::spawn(run_output_reader(
+            self.session_id.clone(),
+            source,
+            self.info.clone(),
+            notification_sender,
+            reopen_policy,
+            self.notify_ready,
+            self.clock.clone(),
+            stop_rx,
+        ));
 
         self.output_task = Some(task);
         self.stop_tx = Some(stop_tx);
@@ -295,10 +806,18 @@ impl SshSession {
     }
 
     /// 发送输入到 SSH 通道
+    ///
+    /// 已经通过 [`send_eof`](Self::send_eof) 关闭输入时返回 `SessionClosed`。
     pub async fn send_input(&self, data: &[u8]) -> Result<(), TerminalError> {
         let channel = self.channel.as_ref().ok_or_else(|| {
             TerminalError::ChannelError("通道未打开".to_string())
         })?;
+        if self.input_closed {
+            return Err(TerminalError::SessionClosed(format!(
+                "{}: 输入已关闭",
+                self.session_id
+            )));
+        }
 
         let channel_guard = channel.lock().await;
         channel_guard.send_data(data).await?;
@@ -307,14 +826,61 @@ impl SshSession {
         Ok(())
     }
 
-    /// 调整 PTY 大小
-    pub async fn resize(&self, term_size: TermSize) -> Result<(), TerminalError> {
+    /// 向 SSH 通道发送 EOF，远程进程读到输入结束
+    ///
+    /// 通道保持打开，仍然可以接收输出。
+    pub async fn send_eof(&mut self) -> Result<(), TerminalError> {
         let channel = self.channel.as_ref().ok_or_else(|| {
             TerminalError::ChannelError("通道未打开".to_string())
         })?;
 
-        let channel_guard = channel.lock().await;
-        channel_guard.resize(term_size.cols as u32, term_size.rows as u32).await?;
+        channel.lock().await.eof().await?;
+        self.input_closed = true;
+
+        tracing::debug!("发送 SSH EOF: {}", self.session_id);
+        Ok(())
+    }
+
+    /// 调整 PTY 大小
+    ///
+    /// 会话已结束或通道已关闭时返回 `SessionClosed`，客户端应停止发送调整请求。
+    /// 发送失败时根据连接是否仍然存活把会话置为 `Done` 或 `Error`。
+    pub async fn resize(&self, term_size: TermSize) -> Result<(), TerminalError> {
+        let Some(channel) = self.channel.as_ref() else {
+            let status = self.info.read().await.status;
+            if matches!(status, SessionStatus::Done | SessionStatus::Error) {
+                return Err(TerminalError::SessionClosed(format!(
+                    "{}: 会话已结束",
+                    self.session_id
+                )));
+            }
+            return Err(TerminalError::ChannelError("通道未打开".to_string()));
+        };
+
+        let result = channel
+            .lock()
+            .await
+            .resize(&term_size)
+            .await;
+        if let Err(e) = result {
+            let connected = self
+                .client
+                .shared_handle()
+                .is_some_and(|handle| !handle.is_closed());
+            let status = if connected {
+                SessionStatus::Done
+            } else {
+                SessionStatus::Error
+            };
+            tracing::warn!("调整 SSH PTY 大小失败，通道已关闭: {} - {}", self.session_id, e);
+
+            let mut info = self.info.write().await;
+            if matches!(info.status, SessionStatus::Connecting | SessionStatus::Running) {
+                info.mark_ended(status, self.clock.unix_secs());
+            }
+            return Err(TerminalError::SessionClosed(format!("{}: {}", self.session_id, e)));
+        }
+        *self.term_size.write().await = term_size.clone();
 
         tracing::debug!(
             "调整 SSH PTY 大小: {}x{}",
@@ -353,10 +919,10 @@ impl SshSession {
         self.client.disconnect().await?;
 
         // 更新状态
-        {
-            let mut info = self.info.write().await;
-            info.status = SessionStatus::Done;
-        }
+        self.info
+            .write()
+            .await
+            .mark_ended(SessionStatus::Done, self.clock.unix_secs());
 
         Ok(())
     }
@@ -401,6 +967,22 @@ impl Drop for SshSession {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_ssh_session_with_clock() {
+        use crate::utils::time::FakeClock;
+
+        let session = SshSession::with_clock(
+            "test-session".to_string(),
+            "example.com".to_string(),
+            None,
+            None,
+            None,
+            None,
+            Arc::new(FakeClock::new(1_700_000_000)),
+        );
+        assert_eq!(session.info.try_read().unwrap().created_at, 1_700_000_000);
+    }
+
     #[test]
     fn test_ssh_session_new() {
         let session = SshSession::new(
@@ -415,6 +997,24 @@ mod tests {
         assert_eq!(session.id(), "test-session-id");
     }
 
+    #[tokio::test]
+    async fn test_ssh_session_info_does_not_keep_password() {
+        let session = SshSession::new(
+            "test-id".to_string(),
+            "host.example.com".to_string(),
+            None,
+            Some("user".to_string()),
+            None,
+            Some("hunter2".to_string()),
+        );
+
+        let info = session.info().await;
+        assert!(matches!(
+            info.connection_type,
+            ConnectionType::Ssh { password: None, .. }
+        ));
+    }
+
     #[tokio::test]
     async fn test_ssh_session_info() {
         let session = SshSession::new(
@@ -430,17 +1030,468 @@ mod tests {
         assert_eq!(info.id, "test-id");
         assert_eq!(info.status, SessionStatus::Init);
         
-        if let ConnectionType::Ssh { host, port, user, identity_file, password } = &info.connection_type {
+        if let ConnectionType::Ssh { host, port, user, identity_file, password, compression, max_retries, .. } = &info.connection_type {
             assert_eq!(host, "host.example.com");
             assert_eq!(*port, Some(2222));
             assert_eq!(*user, Some("user".to_string()));
             assert_eq!(*identity_file, Some("/path/to/key".to_string()));
             assert!(password.is_none());
+            assert!(!compression);
+            assert_eq!(*max_retries, 0);
         } else {
             panic!("Expected SSH connection type");
         }
     }
 
+    #[tokio::test]
+    async fn test_ssh_session_info_resolves_defaults() {
+        let session = SshSession::new(
+            "test-id".to_string(),
+            "host.example.com".to_string(),
+            None,
+            None,
+            None,
+            None,
+        );
+
+        let info = session.info().await;
+        match info.connection_type {
+            ConnectionType::Ssh { port, user, .. } => {
+                assert_eq!(port, Some(session.client.config().port));
+                assert_eq!(user.as_deref(), Some(session.client.config().user.as_str()));
+                assert!(!user.unwrap().is_empty());
+            }
+            _ => panic!("Expected SSH connection type"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_ssh_connect_failure_sends_error_status() {
+        // 端口 1 上没有 SSH 服务，连接会立即失败
+        let mut session = SshSession::new(
+            "test-id".to_string(),
+            "127.0.0.1".to_string(),
+            Some(1),
+            Some("user".to_string()),
+            None,
+            Some("secret".to_string()),
+        );
+
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let sender = NotificationSender::new_for_test(tx);
+        let result = session
+            .connect_with_notifier(TermSize::default(), &sender)
+            .await;
+        let err = result.unwrap_err();
+        assert_eq!(session.info().await.status, SessionStatus::Error);
+
+        let notification = rx.try_recv().unwrap();
+        assert_eq!(notification.method, "session.status");
+        let params = notification.params.unwrap();
+        assert_eq!(params["session_id"], "test-id");
+        assert_eq!(params["status"], "error");
+        assert_eq!(params["error"]["message"], err.to_string());
+        assert_eq!(params["error"]["error_type"], err.error_type());
+        assert_eq!(params["error"]["code"], err.code());
+    }
+
+    #[tokio::test]
+    async fn test_ssh_session_set_compression() {
+        let mut session = SshSession::new(
+            "test-id".to_string(),
+            "example.com".to_string(),
+            None,
+            None,
+            None,
+            None,
+        );
+
+        session.set_compression(true).await;
+        assert!(session.client.config().compression);
+        assert!(matches!(
+            session.info().await.connection_type,
+            ConnectionType::Ssh { compression: true, .. }
+        ));
+    }
+
+    /// 记录 env 请求的通道，名称以 `DENIED_` 开头的请求失败
+    #[derive(Default)]
+    struct RecordingEnvChannel {
+        requests: std::sync::Mutex<Vec<(String, String)>>,
+    }
+
+    #[async_trait::async_trait]
+    impl EnvRequester for RecordingEnvChannel {
+        async fn set_env(&self, name: &str, value: &str) -> Result<(), russh::Error> {
+            self.requests
+                .lock()
+                .unwrap()
+                .push((name.to_string(), value.to_string()));
+            if name.starts_with("DENIED_") {
+                return Err(russh::Error::RequestDenied);
+            }
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_env_requests_sent_in_order() {
+        let mut session = SshSession::new(
+            "test-id".to_string(),
+            "example.com".to_string(),
+            None,
+            None,
+            None,
+            None,
+        );
+        let env = HashMap::from([
+            ("LANG".to_string(), "en_US.UTF-8".to_string()),
+            ("DENIED_VAR".to_string(), "x".to_string()),
+            ("COLORTERM".to_string(), "24bit".to_string()),
+        ]);
+        session.set_env(env.clone()).await;
+        session.set_locale(Some("zh_CN.UTF-8".to_string()));
+        session.set_force_color(true);
+        assert!(matches!(
+            session.info().await.connection_type,
+            ConnectionType::Ssh { env: Some(recorded), .. } if recorded == env
+        ));
+
+        let channel = RecordingEnvChannel::default();
+        send_env_requests(&channel, &session.requested_env()).await;
+
+        // 单个变量失败不影响后面的请求；显式给出的 COLORTERM 和 LANG 优先
+        let pair = |name: &str, value: &str| (name.to_string(), value.to_string());
+        assert_eq!(
+            *channel.requests.lock().unwrap(),
+            vec![
+                pair("COLORTERM", "24bit"),
+                pair("DENIED_VAR", "x"),
+                pair("LANG", "en_US.UTF-8"),
+                pair("LC_ALL", "zh_CN.UTF-8"),
+                pair("FORCE_COLOR", "1"),
+                pair("CLICOLOR_FORCE", "1"),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_ssh_session_set_retry_policy() {
+        let mut session = SshSession::new(
+            "test-id".to_string(),
+            "example.com".to_string(),
+            None,
+            None,
+            None,
+            None,
+        );
+
+        let policy = RetryPolicy::new(3, std::time::Duration::from_millis(250));
+        session.set_retry_policy(policy).await;
+        assert_eq!(session.retry_policy, policy);
+        assert!(matches!(
+            session.info().await.connection_type,
+            ConnectionType::Ssh { max_retries: 3, retry_delay_ms: 250, .. }
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_ssh_session_set_notify_ready() {
+        let mut session = SshSession::new(
+            "test-id".to_string(),
+            "example.com".to_string(),
+            None,
+            None,
+            None,
+            None,
+        );
+        assert!(!session.notify_ready);
+
+        session.set_notify_ready(true).await;
+        assert!(session.notify_ready);
+        assert!(matches!(
+            session.info().await.connection_type,
+            ConnectionType::Ssh { notify_ready: true, .. }
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_ssh_session_set_channel_retries() {
+        let mut session = SshSession::new(
+            "test-id".to_string(),
+            "example.com".to_string(),
+            None,
+            None,
+            None,
+            None,
+        );
+
+        session.set_channel_retries(2).await;
+        assert_eq!(session.channel_retries, 2);
+        assert!(matches!(
+            session.info().await.connection_type,
+            ConnectionType::Ssh { channel_retries: 2, .. }
+        ));
+    }
+
+    /// 按脚本返回通道消息的模拟来源，每个通道的消息用完后返回 `None`
+    struct MockChannelSource {
+        /// 每次打开的通道依次产生的消息
+        channels: std::collections::VecDeque<Vec<ChannelMsg>>,
+        /// 当前通道剩余的消息
+        current: std::collections::VecDeque<ChannelMsg>,
+        /// 前 `failures` 次重新打开失败
+        failures: u32,
+        reopens: Arc<std::sync::atomic::AtomicU32>,
+    }
+
+    impl MockChannelSource {
+        fn new(channels: Vec<Vec<ChannelMsg>>, failures: u32) -> Self {
+            let mut channels: std::collections::VecDeque<_> = channels.into();
+            let current = channels.pop_front().unwrap_or_default().into();
+            Self {
+                channels,
+                current,
+                failures,
+                reopens: Arc::new(std::sync::atomic::AtomicU32::new(0)),
+            }
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl ChannelSource for MockChannelSource {
+        async fn wait(&mut self) -> Option<ChannelMsg> {
+            self.current.pop_front()
+        }
+
+        fn is_alive(&self) -> bool {
+            true
+        }
+
+        async fn reopen(&mut self) -> Result<(), TerminalError> {
+            let attempt = self.reopens.fetch_add(1, std::sync::atomic::Ordering::SeqCst) + 1;
+            if attempt <= self.failures {
+                return Err(TerminalError::channel_error("打开会话通道", "拒绝"));
+            }
+            self.current = self.channels.pop_front().unwrap_or_default().into();
+            Ok(())
+        }
+    }
+
+    fn data(bytes: &[u8]) -> ChannelMsg {
+        ChannelMsg::Data {
+            data: russh::CryptoVec::from_slice(bytes),
+        }
+    }
+
+    fn ssh_info() -> Arc<RwLock<SessionInfo>> {
+        let session = SshSession::new(
+            "test-id".to_string(),
+            "example.com".to_string(),
+            None,
+            None,
+            None,
+            None,
+        );
+        session.info_ref()
+    }
+
+    /// 运行输出读取器直到结束，返回 (方法, 参数) 列表
+    async fn run_mock_reader(
+        source: MockChannelSource,
+        info: Arc<RwLock<SessionInfo>>,
+        max_retries: u32,
+    ) -> Vec<(String, serde_json::Value)> {
+        run_mock_reader_with_ready(source, info, max_retries, false).await
+    }
+
+    async fn run_mock_reader_with_ready(
+        source: MockChannelSource,
+        info: Arc<RwLock<SessionInfo>>,
+        max_retries: u32,
+        notify_ready: bool,
+    ) -> Vec<(String, serde_json::Value)> {
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let (_stop_tx, stop_rx) = mpsc::channel(1);
+        run_output_reader(
+            "test-id".to_string(),
+            source,
+            info,
+            NotificationSender::new_for_test(tx),
+            RetryPolicy::new(max_retries, std::time::Duration::from_millis(100)),
+            notify_ready,
+            system_clock(),
+            stop_rx,
+        )
+        .await;
+
+        let mut notifications = Vec::new();
+        while let Ok(n) = rx.try_recv() {
+            notifications.push((n.method, n.params.unwrap()));
+        }
+        notifications
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_output_reader_reopens_closed_channel() {
+        let source = MockChannelSource::new(
+            vec![
+                vec![data(b"before"), ChannelMsg::Close],
+                vec![data(b"after"), ChannelMsg::ExitStatus { exit_status: 0 }],
+            ],
+            1,
+        );
+        let reopens = source.reopens.clone();
+        let info = ssh_info();
+
+        let notifications = run_mock_reader(source, info.clone(), 3).await;
+
+        // 第一次重新打开失败，第二次成功
+        assert_eq!(reopens.load(std::sync::atomic::Ordering::SeqCst), 2);
+        let methods: Vec<_> = notifications.iter().map(|(m, _)| m.as_str()).collect();
+        assert_eq!(
+            methods,
+            vec!["terminal.output", "session.status", "terminal.output", "session.status"]
+        );
+        assert_eq!(notifications[1].1["status"], "running");
+        assert_eq!(notifications[1].1["session_id"], "test-id");
+        assert_eq!(notifications[3].1["status"], "done");
+        // 重新打开后输出序号继续递增
+        assert_eq!(notifications[2].1["seq"], 1);
+        assert_eq!(info.read().await.status, SessionStatus::Done);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_output_reader_ready_on_first_data() {
+        let source = MockChannelSource::new(
+            vec![
+                vec![
+                    ChannelMsg::ExtendedData {
+                        data: russh::CryptoVec::from_slice(b"motd warning"),
+                        ext: 1,
+                    },
+                    data(b"$ "),
+                    data(b"more"),
+                    ChannelMsg::Close,
+                ],
+                vec![data(b"$ "), ChannelMsg::ExitStatus { exit_status: 0 }],
+            ],
+            0,
+        );
+        let info = ssh_info();
+
+        let notifications = run_mock_reader_with_ready(source, info, 1, true).await;
+
+        // stderr 不算就绪，第一次 stdout 输出后发送一次；通道重新打开后再发送一次
+        let methods: Vec<_> = notifications.iter().map(|(m, _)| m.as_str()).collect();
+        assert_eq!(
+            methods,
+            vec![
+                "terminal.output",
+                "terminal.output",
+                "session.ready",
+                "terminal.output",
+                "session.status",
+                "terminal.output",
+                "session.ready",
+                "session.status",
+            ]
+        );
+        assert_eq!(notifications[2].1["session_id"], "test-id");
+    }
+
+    #[tokio::test]
+    async fn test_output_reader_exit_signal() {
+        let source = MockChannelSource::new(
+            vec![vec![
+                data(b"x"),
+                ChannelMsg::ExitSignal {
+                    signal_name: russh::Sig::KILL,
+                    core_dumped: false,
+                    error_message: String::new(),
+                    lang_tag: String::new(),
+                },
+                // 退出信号之后的消息不再处理
+                data(b"ignored"),
+            ]],
+            0,
+        );
+        let info = ssh_info();
+
+        let notifications = run_mock_reader(source, info.clone(), 0).await;
+
+        assert_eq!(notifications.len(), 2);
+        assert_eq!(notifications[1].0, "session.status");
+        assert_eq!(notifications[1].1["status"], "done");
+        assert_eq!(notifications[1].1["signal"], "KILL");
+        assert_eq!(notifications[1].1["core_dumped"], false);
+        assert!(notifications[1].1.get("error_message").is_none());
+        let info = info.read().await;
+        assert_eq!(info.status, SessionStatus::Done);
+        assert_eq!(info.exit_code, None);
+        assert!(info.ended_at.unwrap() >= info.created_at);
+        assert_eq!(notifications[1].1["ended_at"], info.ended_at.unwrap());
+    }
+
+    #[test]
+    fn test_signal_name_str() {
+        assert_eq!(signal_name_str(&russh::Sig::SEGV), "SEGV");
+        assert_eq!(signal_name_str(&russh::Sig::Custom("WINCH".to_string())), "WINCH");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_output_reader_reopen_exhausted() {
+        let source = MockChannelSource::new(vec![vec![ChannelMsg::Close]], u32::MAX);
+        let reopens = source.reopens.clone();
+        let info = ssh_info();
+
+        let notifications = run_mock_reader(source, info.clone(), 2).await;
+
+        assert_eq!(reopens.load(std::sync::atomic::Ordering::SeqCst), 2);
+        assert_eq!(notifications.len(), 1);
+        assert_eq!(notifications[0].0, "session.status");
+        assert_eq!(notifications[0].1["status"], "error");
+        assert_eq!(notifications[0].1["error"]["error_type"], "session_closed");
+        assert_eq!(info.read().await.status, SessionStatus::Error);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_output_reader_does_not_reopen_by_default() {
+        let source = MockChannelSource::new(vec![vec![ChannelMsg::Close], vec![data(b"x")]], 0);
+        let reopens = source.reopens.clone();
+
+        let notifications = run_mock_reader(source, ssh_info(), 0).await;
+
+        assert_eq!(reopens.load(std::sync::atomic::Ordering::SeqCst), 0);
+        assert!(notifications.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_ssh_session_records_resolved_addr() {
+        // 接受 TCP 连接后立即关闭的服务器，握手失败但 TCP 连接已经建立
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = tokio::spawn(async move {
+            while let Ok((socket, _)) = listener.accept().await {
+                drop(socket);
+            }
+        });
+
+        let mut session = SshSession::new(
+            "test-id".to_string(),
+            "127.0.0.1".to_string(),
+            Some(addr.port()),
+            Some("user".to_string()),
+            None,
+            None,
+        );
+        assert_eq!(session.info().await.resolved_addr, None);
+
+        assert!(session.connect(TermSize::default()).await.is_err());
+        assert_eq!(session.info().await.resolved_addr, Some(addr.to_string()));
+        server.abort();
+    }
+
     #[tokio::test]
     async fn test_ssh_session_not_connected_initially() {
         let session = SshSession::new(
@@ -470,6 +1521,20 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[tokio::test]
+    async fn test_ssh_session_eof_without_channel() {
+        let mut session = SshSession::new(
+            "test-id".to_string(),
+            "example.com".to_string(),
+            None,
+            None,
+            None,
+            None,
+        );
+
+        assert!(matches!(session.send_eof().await, Err(TerminalError::ChannelError(_))));
+    }
+
     #[tokio::test]
     async fn test_ssh_session_resize_without_channel() {
         let session = SshSession::new(
@@ -481,7 +1546,28 @@ mod tests {
             None,
         );
 
-        let result = session.resize(TermSize { rows: 24, cols: 80 }).await;
-        assert!(result.is_err());
+        let result = session.resize(TermSize::default()).await;
+        assert!(matches!(result, Err(TerminalError::ChannelError(_))));
+    }
+
+    #[tokio::test]
+    async fn test_ssh_session_resize_after_close() {
+        let mut session = SshSession::new(
+            "test-id".to_string(),
+            "example.com".to_string(),
+            None,
+            None,
+            None,
+            None,
+        );
+        session.close().await.unwrap();
+
+        let err = session
+            .resize(TermSize::default())
+            .await
+            .unwrap_err();
+        assert!(matches!(err, TerminalError::SessionClosed(_)), "unexpected error: {:?}", err);
+        assert_eq!(crate::rpc::types::JsonRpcError::from(err).code, -32002);
+        assert_eq!(session.info().await.status, SessionStatus::Done);
     }
 }
@@ -0,0 +1,87 @@
+//! 输入/输出编码
+//!
+//! `session.input` 的数据和 `terminal.output` 通知中的数据都使用 base64 编码传输，
+//! 所有编码和解码都经过这里，保证两个方向使用同一种 base64 变体。
+//! `session.write_raw` 的十六进制输入也在这里解码。
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+
+use super::error::TerminalError;
+
+/// 编码发送给前端的输出数据
+pub fn encode_output(data: &[u8]) -> String {
+    STANDARD.encode(data)
+}
+
+/// 解码前端发送的输入数据
+///
+/// 数据不是有效的 base64 时返回 `InvalidRequest`。
+pub fn decode_input(data: &str) -> Result<Vec<u8>, TerminalError> {
+    STANDARD
+        .decode(data)
+        .map_err(|e| TerminalError::InvalidRequest(format!("Invalid base64 data: {}", e)))
+}
+
+/// 解码十六进制输入（如 `1b5b41`），大小写均可
+///
+/// 长度为奇数或包含非十六进制字符时返回 `InvalidRequest`。
+pub fn decode_hex(data: &str) -> Result<Vec<u8>, TerminalError> {
+    if !data.len().is_multiple_of(2) {
+        return Err(TerminalError::InvalidRequest(format!(
+            "Invalid hex data: odd length {}",
+            data.len()
+        )));
+    }
+    let digit = |c: u8| char::from(c).to_digit(16);
+    data.as_bytes()
+        .chunks(2)
+        .map(|pair| match (digit(pair[0]), digit(pair[1])) {
+            (Some(high), Some(low)) => Ok((high << 4 | low) as u8),
+            _ => Err(TerminalError::InvalidRequest(format!(
+                "Invalid hex data: {:?}",
+                String::from_utf8_lossy(pair)
+            ))),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        for data in [&b""[..], b"a", b"ab", b"abc", b"echo hi\n", &[0, 0xff, 0x1b, 0x80]] {
+            assert_eq!(decode_input(&encode_output(data)).unwrap(), data);
+        }
+        assert_eq!(encode_output(b"hello"), "aGVsbG8=");
+        assert_eq!(decode_input("aGVsbG8=").unwrap(), b"hello");
+    }
+
+    #[test]
+    fn test_decode_invalid() {
+        for data in ["not base64!", "aGVsbG8", "a"] {
+            match decode_input(data) {
+                Err(TerminalError::InvalidRequest(message)) => {
+                    assert!(message.contains("Invalid base64 data"));
+                }
+                other => panic!("unexpected result for {:?}: {:?}", data, other),
+            }
+        }
+    }
+
+    #[test]
+    fn test_decode_hex() {
+        assert_eq!(decode_hex("1b5b41").unwrap(), b"\x1b[A");
+        assert_eq!(decode_hex("FF00").unwrap(), [0xff, 0x00]);
+        assert_eq!(decode_hex("").unwrap(), b"");
+        for data in ["1b5", "zz", "+1", "1b 5b"] {
+            assert!(
+                matches!(decode_hex(data), Err(TerminalError::InvalidRequest(_))),
+                "{:?}",
+                data
+            );
+        }
+    }
+}
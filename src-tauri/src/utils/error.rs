@@ -12,8 +12,9 @@
 //! - 需求 10.1: PTY 创建失败时返回描述性错误消息
 //! - 需求 10.2: SSH 连接失败时返回连接错误详情
 
+use serde::Serialize;
 use thiserror::Error;
-use crate::rpc::types::JsonRpcError;
+use crate::rpc::types::{JsonRpcError, SessionError};
 
 /// 终端错误类型
 #[derive(Debug, Error)]
@@ -69,6 +70,10 @@ pub enum TerminalError {
     /// 私钥加载失败
     #[error("私钥加载失败: {0}")]
     PrivateKeyLoadFailed(String),
+
+    /// 当前环境不支持伪终端（如没有 /dev/ptmx 的容器或 CI）
+    #[error("当前环境不支持终端: {0}")]
+    PtyUnsupported(String),
 }
 
 impl From<russh::Error> for TerminalError {
@@ -89,13 +94,40 @@ impl From<russh::Error> for TerminalError {
 
 impl From<TerminalError> for JsonRpcError {
     fn from(err: TerminalError) -> Self {
+        JsonRpcError {
+            code: err.rpc_code(),
+            message: err.to_string(),
+            data: Some(serde_json::json!({
+                "error_type": err.error_type(),
+                "error_code": err.code(),
+                "recoverable": err.is_recoverable(),
+            })),
+        }
+    }
+}
+
+impl From<&TerminalError> for SessionError {
+    fn from(err: &TerminalError) -> Self {
+        SessionError {
+            message: err.to_string(),
+            error_type: err.error_type().to_string(),
+            code: err.code(),
+            recoverable: err.is_recoverable(),
+        }
+    }
+}
+
+impl TerminalError {
+    /// 获取 JSON-RPC 错误码
+    pub fn rpc_code(&self) -> i32 {
         // 根据错误类型映射到适当的 JSON-RPC 错误码
         // 使用应用特定的错误码范围 (-32000 到 -32099)
-        let code = match &err {
+        match self {
             TerminalError::SessionNotFound(_) => -32001,
             TerminalError::InvalidRequest(_) => -32602, // 使用标准的无效参数错误码
             TerminalError::SerializationError(_) => -32700, // 使用标准的解析错误码
             TerminalError::PtyCreationFailed(_) => -32010,
+            TerminalError::PtyUnsupported(_) => -32011,
             TerminalError::SshConnectionFailed(_) => -32020,
             TerminalError::AuthenticationFailed(_) => -32021,
             TerminalError::ConnectionTimeout(_) => -32022,
@@ -105,21 +137,9 @@ impl From<TerminalError> for JsonRpcError {
             TerminalError::ChannelError(_) => -32026,
             TerminalError::SessionClosed(_) => -32002,
             TerminalError::IoError(_) => -32603, // 使用标准的内部错误码
-        };
-
-        JsonRpcError {
-            code,
-            message: err.to_string(),
-            data: Some(serde_json::json!({
-                "error_type": err.error_type(),
-                "error_code": err.code(),
-                "recoverable": err.is_recoverable(),
-            })),
         }
     }
-}
 
-impl TerminalError {
     /// 获取错误码
     pub fn code(&self) -> i32 {
         match self {
@@ -136,6 +156,7 @@ impl TerminalError {
             TerminalError::ChannelError(_) => 1011,
             TerminalError::HostResolutionFailed(_) => 1012,
             TerminalError::PrivateKeyLoadFailed(_) => 1013,
+            TerminalError::PtyUnsupported(_) => 1014,
         }
     }
 
@@ -155,6 +176,7 @@ impl TerminalError {
             TerminalError::ChannelError(_) => "channel_error",
             TerminalError::HostResolutionFailed(_) => "host_resolution_failed",
             TerminalError::PrivateKeyLoadFailed(_) => "private_key_load_failed",
+            TerminalError::PtyUnsupported(_) => "pty_unsupported",
         }
     }
 
@@ -250,6 +272,18 @@ impl TerminalError {
         ))
     }
 
+    /// 按打开 PTY 时的 IO 错误创建错误
+    ///
+    /// 环境不提供伪终端时（见 [`is_pty_unsupported`]）返回 `PtyUnsupported`，
+    /// 否则返回 `PtyCreationFailed`。
+    pub fn pty_open_failed(err: &std::io::Error) -> Self {
+        if is_pty_unsupported(err) {
+            TerminalError::PtyUnsupported(format!("无法打开伪终端设备: {}", err))
+        } else {
+            TerminalError::PtyCreationFailed(err.to_string())
+        }
+    }
+
     /// 创建会话关闭错误
     pub fn session_closed(session_id: &str, reason: &str) -> Self {
         TerminalError::SessionClosed(format!(
@@ -259,6 +293,76 @@ impl TerminalError {
     }
 }
 
+/// 打开 PTY 时的 IO 错误是否表示当前环境不支持伪终端
+///
+/// 没有 `/dev/ptmx`（`ENOENT`）、设备不可用（`ENXIO`、`ENODEV`）或不是终端设备
+/// （`ENOTTY`）时为 true；权限不足、文件描述符耗尽等其他错误不算。
+pub fn is_pty_unsupported(err: &std::io::Error) -> bool {
+    if err.kind() == std::io::ErrorKind::Unsupported {
+        return true;
+    }
+    #[cfg(unix)]
+    {
+        matches!(
+            err.raw_os_error(),
+            Some(libc::ENOENT | libc::ENXIO | libc::ENODEV | libc::ENOTTY)
+        )
+    }
+    #[cfg(not(unix))]
+    {
+        false
+    }
+}
+
+/// 错误目录条目
+///
+/// 描述一种 [`TerminalError`] 的错误码和分类，客户端据此把错误码映射为用户可读的提示。
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ErrorDescriptor {
+    /// JSON-RPC 错误码
+    pub rpc_code: i32,
+    /// 内部错误码
+    pub code: i32,
+    /// 错误类型名称
+    pub error_type: &'static str,
+    /// 是否为可恢复错误
+    pub recoverable: bool,
+}
+
+impl From<&TerminalError> for ErrorDescriptor {
+    fn from(err: &TerminalError) -> Self {
+        ErrorDescriptor {
+            rpc_code: err.rpc_code(),
+            code: err.code(),
+            error_type: err.error_type(),
+            recoverable: err.is_recoverable(),
+        }
+    }
+}
+
+/// 列出所有错误类型
+///
+/// 每个 [`TerminalError`] 变体对应一个条目，按内部错误码排序。
+pub fn error_catalog() -> Vec<ErrorDescriptor> {
+    let examples = [
+        TerminalError::PtyCreationFailed(String::new()),
+        TerminalError::SshConnectionFailed(String::new()),
+        TerminalError::SessionNotFound(String::new()),
+        TerminalError::InvalidRequest(String::new()),
+        TerminalError::IoError(std::io::Error::other("")),
+        TerminalError::SerializationError(serde::de::Error::custom("")),
+        TerminalError::AuthenticationFailed(String::new()),
+        TerminalError::ConnectionTimeout(String::new()),
+        TerminalError::SessionClosed(String::new()),
+        TerminalError::SshError(String::new()),
+        TerminalError::ChannelError(String::new()),
+        TerminalError::HostResolutionFailed(String::new()),
+        TerminalError::PrivateKeyLoadFailed(String::new()),
+        TerminalError::PtyUnsupported(String::new()),
+    ];
+    examples.iter().map(ErrorDescriptor::from).collect()
+}
+
 /// SSH 错误详情
 /// 
 /// 提供更详细的 SSH 错误信息，用于日志和调试。
@@ -489,6 +593,87 @@ mod tests {
         assert_eq!(data["recoverable"], true);
     }
 
+    /// 变体总数，新增变体时这里无法编译，提醒同步更新 [`error_catalog`]
+    fn variant_count(err: &TerminalError) -> usize {
+        match err {
+            TerminalError::PtyCreationFailed(_)
+            | TerminalError::SshConnectionFailed(_)
+            | TerminalError::SessionNotFound(_)
+            | TerminalError::InvalidRequest(_)
+            | TerminalError::IoError(_)
+            | TerminalError::SerializationError(_)
+            | TerminalError::AuthenticationFailed(_)
+            | TerminalError::ConnectionTimeout(_)
+            | TerminalError::SessionClosed(_)
+            | TerminalError::SshError(_)
+            | TerminalError::ChannelError(_)
+            | TerminalError::HostResolutionFailed(_)
+            | TerminalError::PrivateKeyLoadFailed(_)
+            | TerminalError::PtyUnsupported(_) => 14,
+        }
+    }
+
+    #[test]
+    fn test_error_catalog_lists_every_variant_once() {
+        let catalog = error_catalog();
+        assert_eq!(
+            catalog.len(),
+            variant_count(&TerminalError::InvalidRequest(String::new()))
+        );
+
+        for (i, entry) in catalog.iter().enumerate() {
+            for other in &catalog[i + 1..] {
+                assert_ne!(entry.error_type, other.error_type);
+                assert_ne!(entry.code, other.code);
+                assert_ne!(entry.rpc_code, other.rpc_code);
+            }
+        }
+
+        // 条目与错误转换为 JSON-RPC 错误时的值一致
+        let rpc_err: JsonRpcError = TerminalError::SessionClosed("s".to_string()).into();
+        let entry = catalog.iter().find(|e| e.error_type == "session_closed").unwrap();
+        assert_eq!(entry.rpc_code, rpc_err.code);
+        assert_eq!(rpc_err.data.unwrap()["error_code"], entry.code);
+        assert_eq!(entry.code, 1009);
+        assert!(catalog.windows(2).all(|w| w[0].code < w[1].code));
+    }
+
+    #[test]
+    fn test_pty_unsupported_classification() {
+        use std::io::{Error, ErrorKind};
+
+        assert!(is_pty_unsupported(&Error::from(ErrorKind::Unsupported)));
+        assert!(!is_pty_unsupported(&Error::from(ErrorKind::PermissionDenied)));
+        assert!(!is_pty_unsupported(&Error::other("boom")));
+
+        #[cfg(unix)]
+        {
+            for errno in [libc::ENOENT, libc::ENXIO, libc::ENODEV, libc::ENOTTY] {
+                let err = Error::from_raw_os_error(errno);
+                assert!(is_pty_unsupported(&err), "{}", err);
+                assert!(matches!(
+                    TerminalError::pty_open_failed(&err),
+                    TerminalError::PtyUnsupported(_)
+                ));
+            }
+            for errno in [libc::EACCES, libc::EMFILE] {
+                let err = Error::from_raw_os_error(errno);
+                assert!(!is_pty_unsupported(&err), "{}", err);
+                assert!(matches!(
+                    TerminalError::pty_open_failed(&err),
+                    TerminalError::PtyCreationFailed(_)
+                ));
+            }
+        }
+
+        let err = TerminalError::PtyUnsupported("no /dev/ptmx".to_string());
+        assert_eq!(err.error_type(), "pty_unsupported");
+        assert_eq!(err.code(), 1014);
+        assert!(!err.is_recoverable());
+        let rpc_err: JsonRpcError = err.into();
+        assert_eq!(rpc_err.code, -32011);
+    }
+
     #[test]
     fn test_json_rpc_error_data_contains_metadata() {
         let err = TerminalError::ConnectionTimeout("10s".to_string());
@@ -2,8 +2,15 @@
 //!
 //! 提供错误类型、状态管理和通用工具函数。
 
+pub mod codec;
 pub mod error;
+pub mod path;
 pub mod state;
+pub mod time;
 
 pub use error::TerminalError;
+pub use path::resolve_program;
 pub use state::{SessionStateManager, StateTransitionResult};
+
+/// TCP 认证令牌的环境变量，子进程不会继承
+pub const AUTH_TOKEN_ENV: &str = "TERMINAL_PLUGIN_AUTH_TOKEN";
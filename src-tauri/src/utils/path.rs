@@ -0,0 +1,232 @@
+//! 路径处理
+//!
+//! 展开客户端传入路径中的 `~` 和环境变量引用，以及在 PATH 中查找程序。
+
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+
+/// 展开路径开头的 `~` 为用户主目录
+///
+/// 只处理 `~` 和 `~/...`，`~user` 形式保持不变。
+pub fn expand_tilde(path: &str) -> String {
+    if path == "~" || path.starts_with("~/") {
+        if let Some(home) = dirs::home_dir() {
+            return path.replacen('~', &home.to_string_lossy(), 1);
+        }
+    }
+    path.to_string()
+}
+
+/// 展开路径中的 `$VAR` 和 `${VAR}` 环境变量引用
+///
+/// 未定义的变量和不完整的引用保持原样。
+pub fn expand_env_vars(path: &str) -> String {
+    let mut result = String::with_capacity(path.len());
+    let mut rest = path;
+
+    while let Some(pos) = rest.find('$') {
+        result.push_str(&rest[..pos]);
+        let after = &rest[pos + 1..];
+
+        let (name, consumed) = if let Some(braced) = after.strip_prefix('{') {
+            match braced.find('}') {
+                Some(end) => (&braced[..end], end + 2),
+                None => ("", 0),
+            }
+        } else {
+            let end = after
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                .unwrap_or(after.len());
+            (&after[..end], end)
+        };
+
+        match std::env::var(name) {
+            Ok(value) if !name.is_empty() => {
+                result.push_str(&value);
+                rest = &after[consumed..];
+            }
+            _ => {
+                result.push('$');
+                rest = after;
+            }
+        }
+    }
+
+    result.push_str(rest);
+    result
+}
+
+/// 依次展开环境变量引用和 `~`
+pub fn expand_path(path: &str) -> String {
+    expand_tilde(&expand_env_vars(path))
+}
+
+/// 在 PATH 中查找程序，返回绝对路径
+///
+/// 名称包含路径分隔符时不搜索 PATH，只检查该文件是否存在且可执行。
+/// 不解析符号链接，shell 可能依赖 argv[0] 判断自身类型。
+pub fn resolve_program(name: &str) -> Option<PathBuf> {
+    resolve_program_in(name, std::env::var_os("PATH").as_deref())
+}
+
+/// 在给定的 PATH 值中查找程序
+///
+/// 用于子进程使用自定义 PATH 的情况，`path_var` 为 `None` 时只能解析带路径的名称。
+pub fn resolve_program_in(name: &str, path_var: Option<&OsStr>) -> Option<PathBuf> {
+    if name.is_empty() {
+        return None;
+    }
+
+    let program = Path::new(name);
+    if program.is_absolute() || program.components().count() > 1 {
+        return executable_candidates(program)
+            .into_iter()
+            .find(|candidate| is_executable(candidate))
+            .map(absolute);
+    }
+
+    std::env::split_paths(path_var?)
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .flat_map(|dir| executable_candidates(&dir.join(name)))
+        .find(|candidate| is_executable(candidate))
+        .map(absolute)
+}
+
+/// 转换为绝对路径（相对于当前目录），不解析符号链接
+fn absolute(path: PathBuf) -> PathBuf {
+    if path.is_absolute() {
+        return path;
+    }
+    match std::env::current_dir() {
+        Ok(dir) => dir.join(path),
+        Err(_) => path,
+    }
+}
+
+/// 可能的可执行文件路径
+#[cfg(unix)]
+fn executable_candidates(path: &Path) -> Vec<PathBuf> {
+    vec![path.to_path_buf()]
+}
+
+/// 可能的可执行文件路径
+///
+/// 没有扩展名时依次尝试 PATHEXT 中的扩展名。
+#[cfg(windows)]
+fn executable_candidates(path: &Path) -> Vec<PathBuf> {
+    if path.extension().is_some() {
+        return vec![path.to_path_buf()];
+    }
+    let pathext = std::env::var("PATHEXT").unwrap_or_else(|_| ".COM;.EXE;.BAT;.CMD".to_string());
+    pathext
+        .split(';')
+        .filter(|ext| !ext.is_empty())
+        .map(|ext| {
+            let mut candidate = path.as_os_str().to_os_string();
+            candidate.push(ext);
+            PathBuf::from(candidate)
+        })
+        .collect()
+}
+
+/// 是否为可执行的普通文件
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    std::fs::metadata(path)
+        .map(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+        .unwrap_or(false)
+}
+
+/// 是否为可执行的普通文件
+#[cfg(windows)]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expand_tilde() {
+        let expanded = expand_tilde("~/test/path");
+        assert!(!expanded.starts_with("~/"));
+
+        let no_tilde = expand_tilde("/absolute/path");
+        assert_eq!(no_tilde, "/absolute/path");
+    }
+
+    #[test]
+    fn test_expand_tilde_home_and_user_forms() {
+        if let Some(home) = dirs::home_dir() {
+            let home = home.to_string_lossy().to_string();
+            assert_eq!(expand_tilde("~"), home);
+            assert_eq!(expand_tilde("~/x"), format!("{}/x", home));
+        }
+        assert_eq!(expand_tilde("~other/x"), "~other/x");
+        assert_eq!(expand_tilde("relative/~/x"), "relative/~/x");
+    }
+
+    #[test]
+    fn test_expand_env_vars() {
+        std::env::set_var("TERMINAL_PLUGIN_PATH_TEST", "/opt/test");
+        assert_eq!(expand_env_vars("$TERMINAL_PLUGIN_PATH_TEST/bin"), "/opt/test/bin");
+        assert_eq!(expand_env_vars("${TERMINAL_PLUGIN_PATH_TEST}x"), "/opt/testx");
+
+        // 未定义的变量和不完整的引用保持原样
+        assert_eq!(
+            expand_env_vars("$TERMINAL_PLUGIN_PATH_UNDEFINED/bin"),
+            "$TERMINAL_PLUGIN_PATH_UNDEFINED/bin"
+        );
+        assert_eq!(expand_env_vars("${TERMINAL_PLUGIN_PATH_TEST"), "${TERMINAL_PLUGIN_PATH_TEST");
+        assert_eq!(expand_env_vars("cost$"), "cost$");
+        assert_eq!(expand_env_vars("/absolute/path"), "/absolute/path");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_resolve_program_found() {
+        let sh = resolve_program("sh").expect("sh should be in PATH");
+        assert!(sh.is_absolute());
+        assert!(sh.ends_with("sh"));
+
+        // 带路径的名称直接检查
+        assert_eq!(resolve_program("/bin/sh"), Some(PathBuf::from("/bin/sh")));
+    }
+
+    #[test]
+    fn test_resolve_program_not_found() {
+        assert!(resolve_program("terminal-plugin-no-such-program").is_none());
+        assert!(resolve_program("/nonexistent/dir/program").is_none());
+        assert!(resolve_program("").is_none());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_resolve_program_uses_given_path() {
+        let dir = std::env::temp_dir().join(format!("resolve-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let script = dir.join("myshell");
+        std::fs::write(&script, b"#!/bin/sh\n").unwrap();
+
+        // 没有执行权限的文件不算
+        assert!(resolve_program_in("myshell", Some(dir.as_os_str())).is_none());
+
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+        assert_eq!(resolve_program_in("myshell", Some(dir.as_os_str())), Some(script));
+        assert!(resolve_program_in("myshell", None).is_none());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_expand_path() {
+        std::env::set_var("TERMINAL_PLUGIN_PATH_DIR", "project");
+        let expanded = expand_path("~/$TERMINAL_PLUGIN_PATH_DIR");
+        assert!(!expanded.starts_with('~'));
+        assert!(expanded.ends_with("/project"));
+        assert_eq!(expand_path("/usr/bin/fish"), "/usr/bin/fish");
+    }
+}
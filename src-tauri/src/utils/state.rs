@@ -635,7 +635,7 @@ mod proptests {
         /// **验证: 需求 10.4**
         #[test]
         fn prop_any_state_can_transition_to_error(
-            session_id in session_id_strategy(),
+            _session_id in session_id_strategy(),
             initial_status in session_status_strategy()
         ) {
             // 验证从任何状态都可以转换到 Error
@@ -0,0 +1,124 @@
+//! 时间来源
+//!
+//! 会话的创建时间和空闲超时等与时间有关的逻辑通过 [`Clock`] 读取时间，
+//! 默认使用系统时钟；测试中可以换成 [`FakeClock`]，手动推进时间而不必真的等待。
+
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// 时间来源
+pub trait Clock: Send + Sync + fmt::Debug {
+    /// 当前的系统时间
+    fn now(&self) -> SystemTime;
+
+    /// 单调时间，从时钟创建起经过的时长，用于计算时间间隔
+    fn monotonic(&self) -> Duration;
+
+    /// 当前的 Unix 时间戳（秒）
+    fn unix_secs(&self) -> u64 {
+        self.now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_secs())
+            .unwrap_or(0)
+    }
+}
+
+/// 共享的时间来源
+pub type SharedClock = Arc<dyn Clock>;
+
+/// 创建系统时钟
+pub fn system_clock() -> SharedClock {
+    Arc::new(SystemClock::new())
+}
+
+/// 系统时钟
+#[derive(Debug)]
+pub struct SystemClock {
+    /// 单调时间的起点
+    origin: Instant,
+}
+
+impl SystemClock {
+    /// 创建系统时钟
+    pub fn new() -> Self {
+        Self {
+            origin: Instant::now(),
+        }
+    }
+}
+
+impl Default for SystemClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for SystemClock {
+    fn now(&self) -> SystemTime {
+        SystemTime::now()
+    }
+
+    fn monotonic(&self) -> Duration {
+        self.origin.elapsed()
+    }
+}
+
+/// 手动推进的时钟，用于测试
+///
+/// 只有调用 [`advance`](Self::advance) 时时间才会前进，系统时间和单调时间同步推进。
+#[derive(Debug)]
+pub struct FakeClock {
+    /// 当前的系统时间和单调时间
+    state: Mutex<(SystemTime, Duration)>,
+}
+
+impl FakeClock {
+    /// 创建从指定 Unix 时间戳（秒）开始的时钟
+    pub fn new(unix_secs: u64) -> Self {
+        Self {
+            state: Mutex::new((UNIX_EPOCH + Duration::from_secs(unix_secs), Duration::ZERO)),
+        }
+    }
+
+    /// 推进时间
+    pub fn advance(&self, duration: Duration) {
+        let mut state = self.state.lock().unwrap();
+        state.0 += duration;
+        state.1 += duration;
+    }
+}
+
+impl Clock for FakeClock {
+    fn now(&self) -> SystemTime {
+        self.state.lock().unwrap().0
+    }
+
+    fn monotonic(&self) -> Duration {
+        self.state.lock().unwrap().1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fake_clock_advance() {
+        let clock = FakeClock::new(1_700_000_000);
+        assert_eq!(clock.unix_secs(), 1_700_000_000);
+        assert_eq!(clock.monotonic(), Duration::ZERO);
+
+        clock.advance(Duration::from_secs(90));
+        assert_eq!(clock.unix_secs(), 1_700_000_090);
+        assert_eq!(clock.monotonic(), Duration::from_secs(90));
+    }
+
+    #[test]
+    fn test_system_clock() {
+        let clock = SystemClock::new();
+        let before = clock.monotonic();
+        assert!(clock.unix_secs() > 0);
+        assert!(clock.monotonic() >= before);
+    }
+}
@@ -10,6 +10,10 @@
 export interface TermSize {
   rows: number;
   cols: number;
+  /** 窗口宽度（像素），供图像协议使用 */
+  pixel_width?: number;
+  /** 窗口高度（像素），供图像协议使用 */
+  pixel_height?: number;
 }
 
 /**